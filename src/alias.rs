@@ -77,7 +77,6 @@ pub(crate) fn get_default_aliases() -> Aliases {
             ("mv", "!git -C \"$GIT_PREFIX\" mv"),
             ("resolved", "!git -C \"$GIT_PREFIX\" add"),
             ("rm", "!git -C \"$GIT_PREFIX\" rm"),
-            ("status", "!git status -s"),
         ]
         .map(|(name, command)| (name.into(), Alias::new(name, command))),
     );
@@ -37,6 +37,26 @@ pub(crate) fn merged_arg() -> Arg {
         .action(clap::ArgAction::SetTrue)
 }
 
+/// The `--delete-merged` option deleting already-merged patches instead of pushing.
+pub(crate) fn delete_merged_arg() -> Arg {
+    Arg::new("delete-merged")
+        .long("delete-merged")
+        .help("Delete patches merged upstream")
+        .long_help(
+            "Delete patches that have been merged upstream instead of pushing them \
+             back onto the stack.\n\
+             \n\
+             A patch is considered merged if the upstream changes include a commit \
+             with the same patch-id, the same \"Change-Id\" trailer, or the same \
+             \"Link\" trailer and subject as the patch, or if the patch's changes \
+             already exist in the new stack base. This catches patches that landed \
+             upstream in modified form. Matching by subject and author is also done \
+             when \"stgit.merged.match-subject\" is enabled. The upstream commit each \
+             deleted patch was merged as is recorded in the stack log.",
+        )
+        .action(clap::ArgAction::SetTrue)
+}
+
 /// The --conflicts option determining how push-time conflicts are handled.
 pub(crate) fn push_conflicts_arg() -> clap::Arg {
     clap::Arg::new("conflicts")
@@ -66,6 +86,20 @@ pub(crate) fn push_conflicts_arg() -> clap::Arg {
         .action(clap::ArgAction::Set)
 }
 
+/// The `--cascade` option for also rebasing branches stacked on the current branch.
+pub(crate) fn cascade_arg() -> Arg {
+    Arg::new("cascade")
+        .long("cascade")
+        .help("Also rebase branches stacked on this branch")
+        .long_help(
+            "After the patches are pushed back, also rebase the stacks of any \
+             branches stacked on this branch, and of branches stacked on those \
+             branches, onto their updated parent branches. See the `--stacked` \
+             option of `stg branch --create`.",
+        )
+        .action(clap::ArgAction::SetTrue)
+}
+
 pub(crate) fn committer_date_is_author_date_arg() -> clap::Arg {
     Arg::new("committer-date-is-author-date")
         .long("committer-date-is-author-date")
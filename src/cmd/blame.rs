@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg blame` implementation.
+
+use std::{collections::HashMap, io::Write, path::PathBuf};
+
+use anyhow::Result;
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    ext::RepositoryExtended,
+    patch::PatchName,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "blame",
+    category: super::CommandCategory::StackInspection,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Show which patch introduced each line of a file")
+        .long_about(
+            "Annotate each line of a file with the name of the applied patch that \
+             introduced it. Lines that originate from the stack base or earlier are \
+             annotated with \"base\". The file's contents are taken from the topmost \
+             applied patch; changes in the work tree that have not been refreshed \
+             are not shown.\n\
+             \n\
+             With '--porcelain', each line is output as tab-separated fields for \
+             use by scripts and editor plugins:\n\
+             \n    \
+             <patch> <commit> <orig-line> <final-line> <content>\n\
+             \n\
+             where <patch> is the patch name or \"base\", <commit> is the commit \
+             that introduced the line, <orig-line> is the line's number in that \
+             commit, and <final-line> is its number in the annotated file.",
+        )
+        .arg(
+            Arg::new("path")
+                .help("File to annotate")
+                .value_name("path")
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("line-range")
+                .short('L')
+                .help("Annotate only the given line range")
+                .long_help(
+                    "Annotate only the given line range. The range may be given as \
+                     '<start>,<end>', '<start>,+<count>', or ':<funcname>', as with \
+                     git-blame(1). This option may be repeated.",
+                )
+                .value_name("range")
+                .num_args(1)
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("porcelain")
+                .long("porcelain")
+                .help("Output in a format for scripts and editor plugins")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+    let path = matches
+        .get_one::<PathBuf>("path")
+        .expect("required argument");
+    let line_ranges: Vec<String> = matches
+        .get_many::<String>("line-range")
+        .map(|ranges| ranges.cloned().collect())
+        .unwrap_or_default();
+
+    let patch_commit_ids: HashMap<gix::ObjectId, &PatchName> = stack
+        .applied()
+        .iter()
+        .map(|pn| (stack.get_patch_commit_id(pn), pn))
+        .collect();
+
+    let blame_lines = repo
+        .stupid()
+        .blame(stack.base().id, stack.top().id, path, &line_ranges)?;
+
+    let base_name = "base";
+    let name_width = blame_lines
+        .iter()
+        .map(|line| {
+            patch_commit_ids
+                .get(&line.commit_id)
+                .map_or(base_name.len(), |pn| pn.len())
+        })
+        .max()
+        .unwrap_or_default();
+    let line_num_width = blame_lines
+        .iter()
+        .map(|line| line.final_line.to_string().len())
+        .max()
+        .unwrap_or_default();
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    let use_porcelain = matches.get_flag("porcelain");
+
+    for line in &blame_lines {
+        let name: &str = patch_commit_ids
+            .get(&line.commit_id)
+            .map_or(base_name, |pn| AsRef::<str>::as_ref(*pn));
+        if use_porcelain {
+            write!(
+                stdout,
+                "{name}\t{}\t{}\t{}\t",
+                line.commit_id, line.orig_line, line.final_line
+            )?;
+        } else {
+            write!(
+                stdout,
+                "{name:name_width$} {:>line_num_width$}) ",
+                line.final_line
+            )?;
+        }
+        stdout.write_all(&line.content)?;
+        stdout.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg branch --all` implementation.
+
+use std::ffi::OsString;
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgGroup, ArgMatches};
+
+use crate::{
+    argset,
+    ext::RepositoryExtended,
+    print_info_message, print_warning_message,
+    stack::{InitializationPolicy, Stack, TransactionError},
+    stupid::Stupid,
+    wrap::{Branch, PartialRefName},
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("--all")
+        .override_usage(super::super::make_usage(
+            "stg branch --all",
+            &["{--pull | --rebase <upstream>}"],
+        ))
+        .about("Pull or rebase every branch with a StGit stack")
+        .long_about(
+            "Update the stacks of all branches with StGit stacks in one command, \
+             either by pulling each branch, as with `stg pull`, or by rebasing each \
+             branch onto <upstream>, as with `stg rebase <upstream>`.\n\
+             \n\
+             Each branch is checked out in turn. Protected branches are skipped. \
+             Branches stacked on another branch are not pulled or rebased on their own; \
+             they are rebased onto their parent branch after the parent branch is \
+             updated, as with `--cascade`.\n\
+             \n\
+             Branches that fail to update for reasons other than conflicts are \
+             reported and the remaining branches are still updated. If updating a \
+             branch results in conflicts, no further branches are updated and the \
+             conflicted branch is left checked out so the conflicts may be resolved. \
+             Otherwise, the originally checked-out branch is checked out again.\n\
+             \n\
+             A summary of the outcome for each branch is output at the end.",
+        )
+        .arg(
+            Arg::new("pull")
+                .long("pull")
+                .help("Pull each branch")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("rebase")
+                .long("rebase")
+                .help("Rebase each branch onto <upstream>")
+                .value_name("upstream")
+                .num_args(1),
+        )
+        .group(
+            ArgGroup::new("operation")
+                .args(["pull", "rebase"])
+                .required(true),
+        )
+        .arg(argset::push_conflicts_arg())
+}
+
+/// Outcome of updating one branch.
+enum Outcome {
+    Updated,
+    Skipped(&'static str),
+    Failed,
+    Conflicts(anyhow::Error),
+    NotUpdated,
+}
+
+pub(super) fn dispatch(repo: &gix::Repository, matches: &ArgMatches) -> Result<()> {
+    let stupid = repo.stupid();
+    repo.check_repository_state()?;
+    stupid.statuses(None)?.check_index_and_worktree_clean()?;
+
+    let mut branchnames = Vec::new();
+    for local_branch in repo.references()?.local_branches()?.filter_map(Result::ok) {
+        let local_branch = Branch::wrap(local_branch);
+        if let Ok(branchname) = local_branch.get_branch_partial_name() {
+            branchnames.push(branchname);
+        }
+    }
+    branchnames.sort();
+
+    let config = repo.config_snapshot();
+
+    // Stacked branches are rebased by their root branch's `--cascade`.
+    let roots: Vec<(&PartialRefName, bool)> = super::stacked::stacked_order(&config, &branchnames)
+        .into_iter()
+        .filter(|(_, depth)| *depth == 0)
+        .filter_map(|(branchname, _)| {
+            Stack::from_branch_name(repo, branchname, InitializationPolicy::RequireInitialized)
+                .ok()
+                .map(|stack| (branchname, stack.is_protected(&config)))
+        })
+        .collect();
+
+    if roots.is_empty() {
+        return Err(anyhow!("no branches with StGit stacks"));
+    }
+
+    let original_branchname = repo.get_current_branch()?.get_branch_partial_name()?;
+
+    let (stgit_command, mut args): (&super::super::StGitCommand, Vec<OsString>) =
+        if let Some(upstream) = matches.get_one::<String>("rebase") {
+            (
+                &super::super::rebase::STGIT_COMMAND,
+                vec!["rebase".into(), upstream.into()],
+            )
+        } else {
+            (&super::super::pull::STGIT_COMMAND, vec!["pull".into()])
+        };
+    args.push("--cascade".into());
+    if let Some(policy) = matches.get_one::<String>("conflicts") {
+        args.push(format!("--conflicts={policy}").into());
+    }
+    if let Some(color) = matches.get_one::<String>("color") {
+        args.push(format!("--color={color}").into());
+    }
+
+    let mut outcomes: Vec<(&PartialRefName, Outcome)> = Vec::with_capacity(roots.len());
+    let mut halted = false;
+
+    for (branchname, is_protected) in roots {
+        let outcome = if halted {
+            Outcome::NotUpdated
+        } else if is_protected {
+            Outcome::Skipped("protected")
+        } else {
+            print_info_message(matches, &format!("Updating branch `{branchname}`"));
+            let result = stupid.checkout(branchname.as_ref()).and_then(|_| {
+                let command_matches = (stgit_command.make)()
+                    .arg(crate::color::get_color_arg())
+                    .try_get_matches_from(&args)?;
+                (stgit_command.run)(&command_matches)
+            });
+            match result {
+                Ok(()) => Outcome::Updated,
+                Err(e) if is_conflict(&e) => {
+                    halted = true;
+                    Outcome::Conflicts(e)
+                }
+                Err(e) => {
+                    print_warning_message(
+                        matches,
+                        &format!("Failed to update branch `{branchname}`: {e:#}"),
+                    );
+                    Outcome::Failed
+                }
+            }
+        };
+        outcomes.push((branchname, outcome));
+    }
+
+    let width = outcomes
+        .iter()
+        .map(|(branchname, _)| branchname.as_ref().len())
+        .max()
+        .unwrap_or_default();
+    println!("Summary:");
+    for (branchname, outcome) in &outcomes {
+        let status = match outcome {
+            Outcome::Updated => "updated".to_string(),
+            Outcome::Skipped(reason) => format!("skipped ({reason})"),
+            Outcome::Failed => "failed".to_string(),
+            Outcome::Conflicts(_) => "conflicts".to_string(),
+            Outcome::NotUpdated => "not updated".to_string(),
+        };
+        println!("  {branchname:width$}  {status}");
+    }
+
+    let num_failed = outcomes
+        .iter()
+        .filter(|(_, outcome)| matches!(outcome, Outcome::Failed))
+        .count();
+
+    if let Some((_, Outcome::Conflicts(e))) = outcomes
+        .iter()
+        .find(|(_, outcome)| matches!(outcome, Outcome::Conflicts(_)))
+    {
+        let conflicted_branchname = repo
+            .get_current_branch()
+            .and_then(|branch| branch.get_branch_partial_name())
+            .map_or_else(|_| "HEAD".to_string(), |branchname| branchname.to_string());
+        Err(super::super::Error::CausedConflicts(format!(
+            "{e:#}; resolve the conflicts on branch `{conflicted_branchname}`"
+        ))
+        .into())
+    } else {
+        stupid.checkout(original_branchname.as_ref())?;
+        if num_failed == 0 {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{num_failed} of {} branches failed to update",
+                outcomes.len()
+            ))
+        }
+    }
+}
+
+/// Determine whether an error left conflicts or a partially updated stack behind.
+fn is_conflict(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<TransactionError>().is_some()
+        || matches!(
+            e.downcast_ref::<super::super::Error>(),
+            Some(super::super::Error::CausedConflicts(_))
+        )
+}
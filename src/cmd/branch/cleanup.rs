@@ -2,11 +2,14 @@
 
 //! `stg branch --cleanup` implementation.
 
+use std::str::FromStr;
+
 use anyhow::{anyhow, Result};
 
 use crate::{
     branchloc::BranchLocator,
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    wrap::PartialRefName,
 };
 
 pub(super) fn command() -> clap::Command {
@@ -23,7 +26,8 @@ pub(super) fn command() -> clap::Command {
              A protected branch will not be cleaned up; it must be unprotected \
              first.\n\
              \n\
-             A cleaned up branch may be reinitialized using 'stg init'.",
+             A cleaned up branch may be reinitialized using 'stg init'. The clean up \
+             may also be undone, restoring the stack, with `stg undo --branch-op`.",
         )
         .arg(
             clap::Arg::new("branch")
@@ -52,6 +56,13 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
             "clean up not permitted: the series still contains patches (override with --force)"
         ));
     }
+    let branchname = PartialRefName::from_str(stack.get_branch_name())?;
+    let record = super::oplog::BranchOpRecord::with_snapshot(
+        repo,
+        super::oplog::BranchOperation::Cleanup,
+        &branchname,
+        None,
+    )?;
     stack.deinitialize()?;
-    Ok(())
+    super::oplog::append(repo, &record)
 }
@@ -85,7 +85,14 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &ArgMatches) -> Result<(
         Stack::from_branch_name(repo, new_branchname, InitializationPolicy::MustInitialize)?;
     };
 
-    super::set_stgit_parent(repo, new_branchname, Some(&current_branchname))?;
+    super::set_stgit_parent(
+        repo,
+        new_branchname,
+        Some(&super::StGitParent {
+            branchname: current_branchname.clone(),
+            stacked: false,
+        }),
+    )?;
 
     super::set_description(
         repo,
@@ -94,5 +101,15 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &ArgMatches) -> Result<(
     )?;
 
     let new_branch = repo.get_branch(new_branchname)?;
-    stupid.checkout(new_branch.get_branch_name().unwrap())
+    stupid.checkout(new_branch.get_branch_name().unwrap())?;
+
+    super::oplog::append(
+        repo,
+        &super::oplog::BranchOpRecord::with_snapshot(
+            repo,
+            super::oplog::BranchOperation::Clone,
+            &current_branchname,
+            Some(new_branchname),
+        )?,
+    )
 }
@@ -21,7 +21,7 @@ pub(super) fn command() -> clap::Command {
         .short_flag('c')
         .override_usage(super::super::make_usage(
             "stg branch --create",
-            &["<new-branch> [committish]"],
+            &["[--stacked] <new-branch> [committish]"],
         ))
         .about("Create and switch to a new branch")
         .long_about(
@@ -32,7 +32,12 @@ pub(super) fn command() -> clap::Command {
              StGit attempts to detect the branch from which the new branch forked, as \
              well as the remote repository of that parent branch such that 'stg pull' \
              will pull from the correct remote branch. A warning will be printed if \
-             the parent branch cannot be determined.",
+             the parent branch cannot be determined.\n\
+             \n\
+             With '--stacked', the new branch's stack is recorded as being stacked \
+             on top of the parent branch's stack. The parent branch must be a local \
+             branch with a StGit stack. Stacked branches may then be rebased along \
+             with their parent using `stg rebase --cascade` or `stg pull --cascade`.",
         )
         .arg(
             clap::Arg::new("new-branch")
@@ -45,6 +50,12 @@ pub(super) fn command() -> clap::Command {
                 .help("Base commit for new branch")
                 .value_parser(clap::value_parser!(SingleRevisionSpec)),
         )
+        .arg(
+            clap::Arg::new("stacked")
+                .long("stacked")
+                .help("Stack the new branch on top of the parent branch's stack")
+                .action(clap::ArgAction::SetTrue),
+        )
 }
 
 pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Result<()> {
@@ -106,6 +117,32 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
         None
     };
 
+    let stacked = matches.get_flag("stacked");
+    if stacked {
+        let parent_branchname = parent_branch
+            .as_ref()
+            .filter(|branch| {
+                matches!(
+                    branch.get_reference_name().category(),
+                    Some(gix::refs::Category::LocalBranch)
+                )
+            })
+            .map(Branch::get_branch_partial_name)
+            .transpose()?
+            .ok_or_else(|| anyhow!("`--stacked` requires a local parent branch"))?;
+        if Stack::from_branch_name(
+            repo,
+            &parent_branchname,
+            InitializationPolicy::RequireInitialized,
+        )
+        .is_err()
+        {
+            return Err(anyhow!(
+                "parent branch `{parent_branchname}` does not have a StGit stack"
+            ));
+        }
+    }
+
     let (target_commit, target_name) = if let Some(parent_branch) = parent_branch.as_ref() {
         (
             Rc::new(parent_branch.get_commit()?),
@@ -149,8 +186,14 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
     };
 
     if let Some(parent_branch) = parent_branch.as_ref() {
-        let parent_branchname = parent_branch.get_branch_partial_name().ok();
-        super::set_stgit_parent(repo, new_branchname, parent_branchname.as_ref())?;
+        let parent = parent_branch
+            .get_branch_partial_name()
+            .ok()
+            .map(|branchname| super::StGitParent {
+                branchname,
+                stacked,
+            });
+        super::set_stgit_parent(repo, new_branchname, parent.as_ref())?;
         if let Some(upstream_name) = set_upstream(parent_branch, &new_branch, repo)? {
             print_info_message(
                 matches,
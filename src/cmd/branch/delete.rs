@@ -8,6 +8,7 @@ use crate::{
     branchloc::BranchLocator,
     ext::RepositoryExtended,
     stack::{InitializationPolicy, Stack, StackStateAccess},
+    stupid::Stupid,
 };
 
 pub(super) fn command() -> clap::Command {
@@ -23,7 +24,11 @@ pub(super) fn command() -> clap::Command {
              The branch will not be deleted if there are any patches remaining unless \
              the '--force' option is provided.\n\
              \n\
-             A protected branch may not be deleted; it must be unprotected first.",
+             A protected branch may not be deleted; it must be unprotected first.\n\
+             \n\
+             The deletion is recorded in the branch operation log such that the \
+             branch, its stack, and its configuration may be restored with `stg \
+             branch --undelete` or `stg undo --branch-op`.",
         )
         .arg(
             clap::Arg::new("branch-any")
@@ -50,10 +55,17 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
     let current_branchname = current_branch
         .as_ref()
         .and_then(|branch| branch.get_branch_partial_name().ok());
-    if Some(target_branchname) == current_branchname {
+    if Some(&target_branchname) == current_branchname.as_ref() {
         return Err(anyhow!("cannot delete the current branch"));
     }
 
+    let record = super::oplog::BranchOpRecord::with_snapshot(
+        repo,
+        super::oplog::BranchOperation::Delete,
+        &target_branchname,
+        None,
+    )?;
+
     if let Ok(stack) = Stack::from_branch(
         repo,
         target_branch.clone(),
@@ -70,5 +82,10 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
     }
 
     target_branch.delete()?;
-    Ok(())
+    // As with `git branch -D`, the branch's config section goes with the branch. It is
+    // ok if the section does not exist.
+    repo.stupid()
+        .config_remove_section(&format!("branch.{target_branchname}"))
+        .ok();
+    super::oplog::append(repo, &record)
 }
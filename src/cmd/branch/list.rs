@@ -6,25 +6,85 @@ use std::io::Write;
 
 use anyhow::Result;
 use bstr::ByteSlice;
+use clap::Arg;
 use termcolor::WriteColor;
 
 use crate::{
     ext::RepositoryExtended,
-    stack::{InitializationPolicy, Stack},
-    wrap::Branch,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::{Stupid, StupidContext},
+    wrap::{Branch, PartialRefName},
 };
 
 pub(super) fn command() -> clap::Command {
     clap::Command::new("--list")
         .short_flag('l')
-        .override_usage(super::super::make_usage("stg branch --list", &[""]))
+        .override_usage(super::super::make_usage(
+            "stg branch --list",
+            &["[--verbose | --json]"],
+        ))
         .about("List branches in this repository")
         .long_about(
             "List each branch in the current repository along with its description, if \
              any. The current branch is prefixed with '>'. Branches initialized with \
              StGit stacks are prefixed with 's'. Protected branches are prefixed with \
-             'p'.",
+             'p'.\n\
+             \n\
+             Branches stacked on another branch, using `stg branch --create --stacked` \
+             or `stg branch --stack-on`, are listed beneath their parent branch and \
+             indented to show the stacking hierarchy.\n\
+             \n\
+             With '--verbose', each branch with a StGit stack is followed by a line \
+             showing the number of applied, unapplied, and hidden patches; the topmost \
+             applied patch; the number of commits the branch is ahead and behind of its \
+             upstream branch; whether the stack base is stale; and the last stack \
+             operation and its time. The stack base is stale if the branch's parent \
+             branch, or its upstream branch if there is no parent branch, has commits \
+             that are not in the stack base.\n\
+             \n\
+             With '--json', the same information is output as a JSON array with one \
+             object per branch.",
         )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help("Show stack details for each branch")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("verbose"),
+        )
+}
+
+#[derive(serde::Serialize)]
+struct BranchSummary<'a> {
+    name: &'a str,
+    current: bool,
+    stgit: bool,
+    protected: bool,
+    parent: Option<String>,
+    stacked: bool,
+    description: Option<String>,
+    stack: Option<StackSummary>,
+}
+
+#[derive(serde::Serialize)]
+struct StackSummary {
+    applied: usize,
+    unapplied: usize,
+    hidden: usize,
+    top: Option<String>,
+    upstream: Option<String>,
+    ahead: Option<usize>,
+    behind: Option<usize>,
+    stale_base: Option<bool>,
+    last_operation: String,
+    last_operation_time: String,
 }
 
 pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Result<()> {
@@ -39,20 +99,69 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
     }
 
     branchnames.sort();
-    let branchname_width = branchnames.iter().map(|name| name.as_ref().len()).max();
+
+    let config = repo.config_snapshot();
+    let entries = super::stacked::stacked_order(&config, &branchnames);
+    let branchname_width = entries
+        .iter()
+        .map(|(name, depth)| 2 * depth + name.as_ref().len())
+        .max();
 
     let current_branch = repo.get_current_branch().ok();
     let current_branchname = current_branch
         .as_ref()
         .and_then(|branch| branch.get_branch_partial_name().ok());
 
-    let config = repo.config_snapshot();
+    let show_stack = matches.get_flag("verbose") || matches.get_flag("json");
+    let stupid = repo.stupid();
+
+    let mut summaries = Vec::with_capacity(entries.len());
+    for &(branchname, _) in &entries {
+        let parent = super::get_stgit_parent(&config, branchname);
+        let description = config
+            .plumbing()
+            .string("branch", Some(branchname.into()), "description")
+            .map(|description| description.to_str_lossy().to_string())
+            .filter(|description| !description.is_empty());
+        let stack =
+            Stack::from_branch_name(repo, branchname, InitializationPolicy::RequireInitialized)
+                .ok();
+        let stack_summary = match stack.as_ref() {
+            Some(stack) if show_stack => Some(summarize_stack(
+                &stupid,
+                stack,
+                branchname,
+                parent.as_ref(),
+            )?),
+            _ => None,
+        };
+        summaries.push(BranchSummary {
+            name: branchname.as_ref(),
+            current: Some(branchname) == current_branchname.as_ref(),
+            stgit: stack.is_some(),
+            protected: stack
+                .as_ref()
+                .map_or(false, |stack| stack.is_protected(&config)),
+            parent: parent.as_ref().map(|parent| parent.branchname.to_string()),
+            stacked: parent.as_ref().map_or(false, |parent| parent.stacked),
+            description,
+            stack: stack_summary,
+        });
+    }
+
+    if matches.get_flag("json") {
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        serde_json::to_writer_pretty(&mut stdout, &summaries)?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
 
     let mut stdout = crate::color::get_color_stdout(matches);
     let mut color_spec = termcolor::ColorSpec::new();
 
-    for branchname in &branchnames {
-        let is_current = Some(branchname) == current_branchname.as_ref();
+    for (summary, (branchname, depth)) in summaries.iter().zip(entries) {
+        let is_current = summary.current;
 
         if is_current {
             stdout.set_color(color_spec.set_intense(true))?;
@@ -63,15 +172,13 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
             write!(stdout, "  ")?;
         };
 
-        if let Ok(stack) =
-            Stack::from_branch_name(repo, branchname, InitializationPolicy::RequireInitialized)
-        {
+        if summary.stgit {
             color_spec.set_fg(Some(termcolor::Color::Cyan));
             stdout.set_color(&color_spec)?;
             write!(stdout, "s")?;
             color_spec.clear();
             stdout.set_color(&color_spec)?;
-            if stack.is_protected(&config) {
+            if summary.protected {
                 color_spec.set_fg(Some(termcolor::Color::Yellow));
                 stdout.set_color(&color_spec)?;
                 write!(stdout, "p\t")?;
@@ -88,7 +195,13 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
             stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Green)))?;
         }
         let branchname_width = branchname_width.expect("max is Some when !branchnames.is_empty()");
-        write!(stdout, "{branchname:branchname_width$}")?;
+        let indent = 2 * depth;
+        write!(
+            stdout,
+            "{:indent$}{branchname:width$}",
+            "",
+            width = branchname_width - indent
+        )?;
         if is_current {
             color_spec.clear();
             stdout.set_color(&color_spec)?;
@@ -100,18 +213,110 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
         color_spec.clear();
         stdout.set_color(&color_spec)?;
 
-        let description = config
-            .plumbing()
-            .string("branch", Some(branchname.into()), "description")
-            .unwrap_or_default();
-        if description.is_empty() {
-            writeln!(stdout)?;
-        } else {
-            write!(stdout, " ")?;
-            stdout.write_all(description.as_bstr())?;
+        if let Some(description) = summary.description.as_ref() {
+            write!(stdout, " {description}")?;
+        }
+        writeln!(stdout)?;
+
+        if let Some(stack_summary) = summary.stack.as_ref() {
+            color_spec.set_dimmed(true);
+            stdout.set_color(&color_spec)?;
+            write!(
+                stdout,
+                "    \t{:indent$}  {}",
+                "",
+                format_stack_summary(stack_summary)
+            )?;
+            color_spec.clear();
+            stdout.set_color(&color_spec)?;
             writeln!(stdout)?;
         }
     }
 
     Ok(())
 }
+
+/// Gather the stack details shown with `--verbose` and `--json`.
+fn summarize_stack(
+    stupid: &StupidContext,
+    stack: &Stack,
+    branchname: &PartialRefName,
+    parent: Option<&super::StGitParent>,
+) -> Result<StackSummary> {
+    let upstream = stupid.branch_upstream(branchname.as_ref())?;
+    let ahead_behind = upstream
+        .as_ref()
+        .and_then(|upstream| stupid.ahead_behind(branchname.as_ref(), upstream).ok());
+
+    // The stack base is compared with the parent branch if there is one since the
+    // parent branch is what `stg pull` and `stg rebase --cascade` rebase onto.
+    let base_id = stack.base().id.to_string();
+    let stale_base = parent
+        .map(|parent| parent.branchname.to_string())
+        .or_else(|| upstream.clone())
+        .and_then(|reference| stupid.ahead_behind(&base_id, &reference).ok())
+        .map(|base_ahead_behind| base_ahead_behind.behind > 0);
+
+    let state_commit = stack
+        .repo
+        .find_reference(stack.get_stack_refname())?
+        .into_fully_peeled_id()?
+        .object()?
+        .try_into_commit()?;
+    let last_operation = state_commit
+        .message_raw()?
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_str_lossy()
+        .to_string();
+    let last_operation_time = state_commit
+        .decode()?
+        .time()
+        .format(gix::date::time::format::ISO8601);
+
+    Ok(StackSummary {
+        applied: stack.applied().len(),
+        unapplied: stack.unapplied().len(),
+        hidden: stack.hidden().len(),
+        top: stack.applied().last().map(ToString::to_string),
+        upstream: upstream.map(|upstream| {
+            upstream
+                .strip_prefix("refs/remotes/")
+                .or_else(|| upstream.strip_prefix("refs/heads/"))
+                .unwrap_or(&upstream)
+                .to_string()
+        }),
+        ahead: ahead_behind.as_ref().map(|ahead_behind| ahead_behind.ahead),
+        behind: ahead_behind
+            .as_ref()
+            .map(|ahead_behind| ahead_behind.behind),
+        stale_base,
+        last_operation,
+        last_operation_time,
+    })
+}
+
+/// Format the stack details line shown with `--verbose`.
+fn format_stack_summary(summary: &StackSummary) -> String {
+    let mut parts = vec![format!(
+        "{} applied, {} unapplied, {} hidden",
+        summary.applied, summary.unapplied, summary.hidden
+    )];
+    if let Some(top) = summary.top.as_ref() {
+        parts.push(format!("top: {top}"));
+    }
+    if let (Some(upstream), Some(ahead), Some(behind)) =
+        (summary.upstream.as_ref(), summary.ahead, summary.behind)
+    {
+        parts.push(format!("{upstream}: +{ahead} -{behind}"));
+    }
+    if summary.stale_base == Some(true) {
+        parts.push("stale base".to_string());
+    }
+    parts.push(format!(
+        "last: {} ({})",
+        summary.last_operation, summary.last_operation_time
+    ));
+    parts.join("; ")
+}
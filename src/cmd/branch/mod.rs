@@ -2,16 +2,23 @@
 
 //! `stg branch` implementation.
 
+mod all;
 mod cleanup;
 mod clone;
 mod create;
 mod delete;
 mod describe;
 mod list;
+mod oplog;
 mod protect;
 mod rename;
+mod stack_on;
+mod stacked;
+mod undelete;
 mod unprotect;
 
+use std::str::FromStr;
+
 use anyhow::Result;
 use bstr::ByteSlice;
 
@@ -19,6 +26,11 @@ use crate::{
     branchloc::BranchLocator, ext::RepositoryExtended, stupid::Stupid, wrap::PartialRefName,
 };
 
+pub(super) use self::{
+    oplog::undo_branch_op,
+    stacked::{cascade_rebase, hint_stacked_children},
+};
+
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "branch",
     category: super::CommandCategory::StackManipulation,
@@ -49,15 +61,18 @@ fn make() -> clap::Command {
             &[
                 "",
                 "[--merge] <branch>",
-                "{--list,-l}",
-                "{--create,-c} <new-branch> [committish]",
+                "{--list,-l} [--verbose | --json]",
+                "{--create,-c} [--stacked] <new-branch> [committish]",
                 "--clone [new-branch]",
                 "{--rename,-r} [old-name] <new-name>",
                 "{--protect,-p} [branch]",
                 "{--unprotect,-u} [branch]",
                 "--delete [--force] <branch>",
+                "--undelete <branch>",
                 "--cleanup [--force] [branch]",
                 "{--describe,-d} <description> [branch]",
+                "--stack-on <parent-branch> [branch]",
+                "--all {--pull | --rebase <upstream>}",
             ],
         ))
         .subcommand(self::list::command())
@@ -67,8 +82,11 @@ fn make() -> clap::Command {
         .subcommand(self::protect::command())
         .subcommand(self::unprotect::command())
         .subcommand(self::delete::command())
+        .subcommand(self::undelete::command())
         .subcommand(self::cleanup::command())
         .subcommand(self::describe::command())
+        .subcommand(self::stack_on::command())
+        .subcommand(self::all::command())
         .arg(
             clap::Arg::new("merge")
                 .long("merge")
@@ -95,8 +113,11 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
             "--protect" => self::protect::dispatch(&repo, submatches),
             "--unprotect" => self::unprotect::dispatch(&repo, submatches),
             "--delete" => self::delete::dispatch(&repo, submatches),
+            "--undelete" => self::undelete::dispatch(&repo, submatches),
             "--cleanup" => self::cleanup::dispatch(&repo, submatches),
             "--describe" => self::describe::dispatch(&repo, submatches),
+            "--stack-on" => self::stack_on::dispatch(&repo, submatches),
+            "--all" => self::all::dispatch(&repo, submatches),
             s => panic!("unhandled branch subcommand {s}"),
         }
     } else if let Some(target_branch_loc) = matches.get_one::<BranchLocator>("branch-any") {
@@ -168,39 +189,77 @@ fn set_description(
     Ok(())
 }
 
-fn get_stgit_parent(config: &gix::config::Snapshot, branchname: &PartialRefName) -> Option<String> {
-    config
+/// Parent branch of a StGit branch as recorded in the config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct StGitParent {
+    /// Name of the parent branch.
+    pub(super) branchname: PartialRefName,
+
+    /// Whether the branch's stack is stacked on top of the parent branch's stack.
+    pub(super) stacked: bool,
+}
+
+/// Get the parent branch recorded in `branch.<name>.stgit.parentbranch`.
+///
+/// The parent is stacked if `branch.<name>.stgit.stacked` is true.
+pub(super) fn get_stgit_parent(
+    config: &gix::config::Snapshot,
+    branchname: &PartialRefName,
+) -> Option<StGitParent> {
+    let subsection = format!("{branchname}.stgit");
+    let parent_branchname = config
         .plumbing()
-        .string(
-            "branch",
-            Some(format!("{branchname}.stgit").as_str().into()),
-            "parentbranch",
-        )
-        .and_then(|bs| bs.to_str().ok().map(str::to_string))
+        .string("branch", Some(subsection.as_str().into()), "parentbranch")
+        .and_then(|bs| {
+            bs.to_str()
+                .ok()
+                .and_then(|s| PartialRefName::from_str(s).ok())
+        })?;
+    let stacked = config
+        .plumbing()
+        .boolean("branch", Some(subsection.as_str().into()), "stacked")
+        .and_then(Result::ok)
+        .unwrap_or(false);
+    Some(StGitParent {
+        branchname: parent_branchname,
+        stacked,
+    })
 }
 
 fn set_stgit_parent(
     repo: &gix::Repository,
     branchname: &PartialRefName,
-    parent_branchname: Option<&PartialRefName>,
+    parent: Option<&StGitParent>,
 ) -> Result<()> {
     let subsection = format!("{branchname}.stgit");
     let mut local_config_file = repo.local_config_file()?;
-    if let Some(parent_branchname) = parent_branchname {
+    let remove_value = |config_file: &mut gix::config::File<'static>, key: &str| {
+        if let Ok(mut value) =
+            config_file.raw_value_mut("branch", Some(subsection.as_str().into()), key)
+        {
+            value.delete();
+        }
+    };
+    if let Some(parent) = parent {
         local_config_file.set_raw_value(
             "branch",
             Some(subsection.as_str().into()),
             "parentbranch",
-            parent_branchname.as_ref(),
+            parent.branchname.as_ref(),
         )?;
-    } else {
-        if let Ok(mut value) = local_config_file.raw_value_mut(
-            "branch",
-            Some(subsection.as_str().into()),
-            "parentbranch",
-        ) {
-            value.delete();
+        if parent.stacked {
+            local_config_file.set_raw_value(
+                "branch",
+                Some(subsection.as_str().into()),
+                "stacked",
+                "true",
+            )?;
+        } else {
+            remove_value(&mut local_config_file, "stacked");
         }
+    } else {
+        remove_value(&mut local_config_file, "parentbranch");
+        remove_value(&mut local_config_file, "stacked");
         if let Ok(section) = local_config_file.section("branch", Some(subsection.as_str().into())) {
             if section.num_values() == 0 {
                 local_config_file.remove_section_by_id(section.id());
@@ -0,0 +1,477 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Log of branch-level operations.
+//!
+//! Deleting, renaming, cleaning up, and cloning branches happens outside of any one
+//! branch's stack log. So that these operations may be undone, each one is recorded in
+//! the repository-wide `refs/stgit/branch-ops` reference. Each record commit's tree
+//! contains a single `operation.json` blob describing the operation along with the
+//! references and config entries needed to undo it.
+//!
+//! The first parent of a record commit is the previous record commit, if any. The
+//! remaining parents are the targets of the recorded branch head, stack state, email
+//! history, and quilt sync references, which keeps them reachable. Patch commits are
+//! kept reachable by the stack state commit.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+
+use crate::{
+    ext::{CommitOptions, RepositoryExtended},
+    print_info_message,
+    stack::{
+        email_history_refname_from_branch_name, quilt_sync_refname_from_branch_name,
+        state_refname_from_branch_name, InitializationPolicy, Stack,
+    },
+    stupid::Stupid,
+    wrap::{Message, PartialRefName},
+};
+
+/// Name of the reference with the branch operation log.
+const OPLOG_REFNAME: &str = "refs/stgit/branch-ops";
+
+/// Name of the blob containing the operation record.
+const OPERATION_JSON: &str = "operation.json";
+
+/// Kind of branch-level operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum BranchOperation {
+    Delete,
+    Rename,
+    Cleanup,
+    Clone,
+}
+
+impl BranchOperation {
+    fn as_str(self) -> &'static str {
+        match self {
+            BranchOperation::Delete => "delete",
+            BranchOperation::Rename => "rename",
+            BranchOperation::Cleanup => "cleanup",
+            BranchOperation::Clone => "clone",
+        }
+    }
+}
+
+/// Record of a branch-level operation.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct BranchOpRecord {
+    pub(super) operation: BranchOperation,
+
+    /// The deleted, renamed, cleaned up, or cloned branch.
+    pub(super) branch: String,
+
+    /// The new name of a renamed branch or the name of a clone.
+    pub(super) new_branch: Option<String>,
+
+    /// References of the deleted or cleaned up branch, or of the clone.
+    pub(super) refs: Vec<RefRecord>,
+
+    /// Config entries of the deleted or cleaned up branch.
+    pub(super) config: Vec<ConfigRecord>,
+}
+
+/// Record of one reference.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct RefRecord {
+    pub(super) name: String,
+    pub(super) target: String,
+}
+
+/// Record of one config entry.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct ConfigRecord {
+    pub(super) key: String,
+    pub(super) value: String,
+}
+
+impl BranchOpRecord {
+    /// Record an operation on a branch along with the branch's references and config.
+    pub(super) fn with_snapshot(
+        repo: &gix::Repository,
+        operation: BranchOperation,
+        branchname: &PartialRefName,
+        new_branchname: Option<&PartialRefName>,
+    ) -> Result<Self> {
+        let snapshot_branchname = if operation == BranchOperation::Clone {
+            new_branchname.expect("clone has a new branch name")
+        } else {
+            branchname
+        };
+        Ok(Self {
+            operation,
+            branch: branchname.to_string(),
+            new_branch: new_branchname.map(ToString::to_string),
+            refs: branch_refs(repo, snapshot_branchname)?,
+            config: branch_config(repo, snapshot_branchname)?,
+        })
+    }
+
+    /// Record a branch rename.
+    pub(super) fn rename(branchname: &PartialRefName, new_branchname: &PartialRefName) -> Self {
+        Self {
+            operation: BranchOperation::Rename,
+            branch: branchname.to_string(),
+            new_branch: Some(new_branchname.to_string()),
+            refs: Vec::new(),
+            config: Vec::new(),
+        }
+    }
+
+    /// Get a one-line summary of the operation.
+    pub(super) fn summary(&self) -> String {
+        let operation = self.operation.as_str();
+        if let Some(new_branch) = self.new_branch.as_ref() {
+            format!("{operation} {} to {new_branch}", self.branch)
+        } else {
+            format!("{operation} {}", self.branch)
+        }
+    }
+
+    /// Get the ids of the commits kept reachable by the record commit.
+    fn kept_ids(&self) -> Result<Vec<gix::ObjectId>> {
+        let mut ids = Vec::new();
+        for ref_record in &self.refs {
+            if ref_record.name.starts_with("refs/patches/") {
+                continue;
+            }
+            let id = gix::ObjectId::from_hex(ref_record.target.as_bytes())
+                .map_err(|_| anyhow!("invalid object id `{}`", ref_record.target))?;
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    fn get_ref(&self, refname: &str) -> Option<&RefRecord> {
+        self.refs
+            .iter()
+            .find(|ref_record| ref_record.name == refname)
+    }
+}
+
+/// Get the references belonging to a branch.
+fn branch_refs(repo: &gix::Repository, branchname: &PartialRefName) -> Result<Vec<RefRecord>> {
+    let branchname = branchname.as_ref();
+    let mut refnames = vec![
+        format!("refs/heads/{branchname}"),
+        state_refname_from_branch_name(branchname),
+        email_history_refname_from_branch_name(branchname),
+        quilt_sync_refname_from_branch_name(branchname),
+    ];
+    let patch_ref_prefix = format!("refs/patches/{branchname}/");
+    for reference in repo.references()?.all()?.filter_map(Result::ok) {
+        if reference
+            .name()
+            .as_bstr()
+            .starts_with(patch_ref_prefix.as_bytes())
+        {
+            refnames.push(reference.name().as_bstr().to_string());
+        }
+    }
+
+    let mut refs = Vec::with_capacity(refnames.len());
+    for refname in refnames {
+        if let Some(reference) = repo.try_find_reference(refname.as_str())? {
+            refs.push(RefRecord {
+                name: refname,
+                target: reference.into_fully_peeled_id()?.to_string(),
+            });
+        }
+    }
+    Ok(refs)
+}
+
+/// Get the `branch.<name>` and `branch.<name>.stgit` config entries of a branch.
+fn branch_config(repo: &gix::Repository, branchname: &PartialRefName) -> Result<Vec<ConfigRecord>> {
+    let mut escaped = String::new();
+    for c in branchname.as_ref().chars() {
+        if "\\.^$*+?()[]{}|".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    Ok(repo
+        .stupid()
+        .config_get_regexp(&format!("^branch\\.{escaped}(\\.stgit)?\\.[^.]+$"))?
+        .into_iter()
+        .map(|(key, value)| ConfigRecord { key, value })
+        .collect())
+}
+
+/// Read the branch operation log, most recent record first.
+///
+/// Each record is paired with the id of its record commit.
+fn read(repo: &gix::Repository) -> Result<Vec<(BranchOpRecord, gix::ObjectId)>> {
+    let mut records = Vec::new();
+    let mut next_commit = if let Some(oplog_ref) = repo.try_find_reference(OPLOG_REFNAME)? {
+        Some(
+            oplog_ref
+                .into_fully_peeled_id()?
+                .object()?
+                .try_into_commit()?,
+        )
+    } else {
+        None
+    };
+
+    while let Some(commit) = next_commit {
+        let operation_json = commit
+            .tree()?
+            .lookup_entry_by_path(OPERATION_JSON)?
+            .ok_or_else(|| {
+                anyhow!(
+                    "`{OPERATION_JSON}` not found in `{OPLOG_REFNAME}` ({})",
+                    commit.id
+                )
+            })?;
+        let operation_json_blob = operation_json
+            .object()?
+            .peel_to_kind(gix::objs::Kind::Blob)?;
+        let record: BranchOpRecord = serde_json::from_slice(&operation_json_blob.data)
+            .context("deserializing branch operation record")?;
+
+        let parent_ids: Vec<gix::ObjectId> = commit.parent_ids().map(|id| id.detach()).collect();
+        next_commit = if parent_ids.len() > record.kept_ids()?.len() {
+            Some(repo.find_commit(parent_ids[0])?)
+        } else {
+            None
+        };
+        records.push((record, commit.id));
+    }
+
+    Ok(records)
+}
+
+/// Commit a record on top of the given previous record commit.
+fn commit_record(
+    repo: &gix::Repository,
+    prev_id: Option<gix::ObjectId>,
+    record: &BranchOpRecord,
+) -> Result<gix::ObjectId> {
+    let operation_json_id = repo.write_blob(serde_json::to_string_pretty(record)?.as_bytes())?;
+    let tree = gix::objs::Tree {
+        entries: vec![gix::objs::tree::Entry {
+            mode: gix::objs::tree::EntryMode::Blob,
+            filename: OPERATION_JSON.into(),
+            oid: operation_json_id.detach(),
+        }],
+    };
+    let tree_id = repo.write_object(tree)?.detach();
+
+    repo.commit_with_options(
+        repo.get_author()?,
+        repo.get_committer()?,
+        &Message::from(record.summary().as_str()),
+        tree_id,
+        prev_id.into_iter().chain(record.kept_ids()?),
+        &CommitOptions {
+            commit_encoding: None,
+            gpgsign: false,
+        },
+    )
+}
+
+/// Point the log reference at the given record commit, or delete it if empty.
+fn update_log(repo: &gix::Repository, top_id: Option<gix::ObjectId>, message: &str) -> Result<()> {
+    if let Some(top_id) = top_id {
+        repo.reference(
+            OPLOG_REFNAME,
+            top_id,
+            gix::refs::transaction::PreviousValue::Any,
+            message,
+        )?;
+    } else if let Some(oplog_ref) = repo.try_find_reference(OPLOG_REFNAME)? {
+        oplog_ref.delete()?;
+    }
+    Ok(())
+}
+
+/// Append a record to the branch operation log.
+pub(super) fn append(repo: &gix::Repository, record: &BranchOpRecord) -> Result<()> {
+    let prev_id = if let Some(oplog_ref) = repo.try_find_reference(OPLOG_REFNAME)? {
+        Some(oplog_ref.into_fully_peeled_id()?.detach())
+    } else {
+        None
+    };
+    let commit_id = commit_record(repo, prev_id, record)?;
+    update_log(repo, Some(commit_id), &record.summary())
+}
+
+/// Remove the record at the given position, counting from the most recent record.
+///
+/// The more recent records are recommitted on top of the removed record's previous
+/// record.
+fn remove(
+    repo: &gix::Repository,
+    records: &[(BranchOpRecord, gix::ObjectId)],
+    index: usize,
+) -> Result<()> {
+    let mut top_id = records.get(index + 1).map(|(_, commit_id)| *commit_id);
+    for (record, _) in records[..index].iter().rev() {
+        top_id = Some(commit_record(repo, top_id, record)?);
+    }
+    update_log(
+        repo,
+        top_id,
+        &format!("undo {}", records[index].0.summary()),
+    )
+}
+
+/// Undo the most recent branch-level operation.
+pub(in super::super) fn undo_branch_op(repo: &gix::Repository, matches: &ArgMatches) -> Result<()> {
+    let records = read(repo)?;
+    let (record, _) = records
+        .first()
+        .ok_or_else(|| anyhow!("no branch operations to undo"))?;
+    undo(repo, record)?;
+    remove(repo, &records, 0)?;
+    print_info_message(
+        matches,
+        &format!("Undid branch operation `{}`", record.summary()),
+    );
+    Ok(())
+}
+
+/// Restore the most recently deleted branch with the given name.
+pub(super) fn undelete(repo: &gix::Repository, branchname: &PartialRefName) -> Result<()> {
+    let records = read(repo)?;
+    let index = records
+        .iter()
+        .position(|(record, _)| {
+            record.operation == BranchOperation::Delete && record.branch == branchname.as_ref()
+        })
+        .ok_or_else(|| anyhow!("no deleted branch `{branchname}` in the branch operation log"))?;
+    undo(repo, &records[index].0)?;
+    remove(repo, &records, index)
+}
+
+fn undo(repo: &gix::Repository, record: &BranchOpRecord) -> Result<()> {
+    let branchname = PartialRefName::from_str(&record.branch)?;
+    let new_branchname = record
+        .new_branch
+        .as_deref()
+        .map(PartialRefName::from_str)
+        .transpose()?;
+    let head_refname = format!("refs/heads/{branchname}");
+    let state_refname = state_refname_from_branch_name(branchname.as_ref());
+
+    match (record.operation, new_branchname) {
+        (BranchOperation::Delete, _) => {
+            if repo.try_find_reference(head_refname.as_str())?.is_some() {
+                return Err(anyhow!("branch `{branchname}` already exists"));
+            }
+            restore(repo, record, &branchname, true)
+        }
+
+        (BranchOperation::Cleanup, _) => {
+            if repo.try_find_reference(head_refname.as_str())?.is_none() {
+                return Err(anyhow!("branch `{branchname}` no longer exists"));
+            } else if repo.try_find_reference(state_refname.as_str())?.is_some() {
+                return Err(anyhow!(
+                    "StGit stack already initialized for branch `{branchname}`"
+                ));
+            }
+            restore(repo, record, &branchname, false)
+        }
+
+        (BranchOperation::Rename, Some(new_branchname)) => {
+            if repo.try_find_reference(head_refname.as_str())?.is_some() {
+                return Err(anyhow!("branch `{branchname}` already exists"));
+            }
+            super::rename::rename(repo, &new_branchname, &branchname)
+        }
+
+        (BranchOperation::Clone, Some(new_branchname)) => {
+            for ref_record in &record.refs {
+                let current_target = repo
+                    .try_find_reference(ref_record.name.as_str())?
+                    .map(|reference| reference.into_fully_peeled_id().map(|id| id.to_string()))
+                    .transpose()?;
+                if current_target.as_deref() != Some(ref_record.target.as_str()) {
+                    return Err(anyhow!(
+                        "branch `{new_branchname}` has changed since it was cloned"
+                    ));
+                }
+            }
+
+            let stupid = repo.stupid();
+            let current_branchname = repo
+                .get_current_branch()
+                .and_then(|branch| branch.get_branch_partial_name())
+                .ok();
+            if current_branchname.as_ref() == Some(&new_branchname) {
+                stupid.statuses(None)?.check_worktree_clean()?;
+                stupid.checkout(branchname.as_ref())?;
+            }
+
+            if let Ok(stack) = Stack::from_branch_name(
+                repo,
+                &new_branchname,
+                InitializationPolicy::RequireInitialized,
+            ) {
+                stack.deinitialize()?;
+            }
+            repo.get_branch(&new_branchname)?.delete()?;
+            stupid
+                .config_remove_section(&format!("branch.{new_branchname}"))
+                .ok();
+            Ok(())
+        }
+
+        (operation, None) => Err(anyhow!(
+            "invalid `{}` branch operation record without new branch name",
+            operation.as_str()
+        )),
+    }
+}
+
+/// Recreate a branch's recorded references and config entries.
+fn restore(
+    repo: &gix::Repository,
+    record: &BranchOpRecord,
+    branchname: &PartialRefName,
+    restore_head: bool,
+) -> Result<()> {
+    let head_refname = format!("refs/heads/{branchname}");
+    let message = format!("undo {}", record.summary());
+    for ref_record in &record.refs {
+        if !restore_head && ref_record.name == head_refname {
+            continue;
+        }
+        let target_id = gix::ObjectId::from_hex(ref_record.target.as_bytes())
+            .map_err(|_| anyhow!("invalid object id `{}`", ref_record.target))?;
+        repo.reference(
+            ref_record.name.as_str(),
+            target_id,
+            gix::refs::transaction::PreviousValue::Any,
+            message.as_str(),
+        )?;
+    }
+
+    // The recorded entries replace any config entries recreated since.
+    let stupid = repo.stupid();
+    stupid
+        .config_remove_section(&format!("branch.{branchname}.stgit"))
+        .ok();
+    stupid
+        .config_remove_section(&format!("branch.{branchname}"))
+        .ok();
+    for config_record in &record.config {
+        stupid.config_add(&config_record.key, &config_record.value)?;
+    }
+
+    // Instantiating the stack verifies the restored stack state and its patch refs.
+    if record
+        .get_ref(&state_refname_from_branch_name(branchname.as_ref()))
+        .is_some()
+    {
+        Stack::from_branch_name(repo, branchname, InitializationPolicy::RequireInitialized)?;
+    }
+
+    Ok(())
+}
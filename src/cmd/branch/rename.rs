@@ -2,13 +2,14 @@
 
 //! `stg branch --rename` implementation.
 
-use std::str::FromStr;
-
 use anyhow::Result;
 
 use crate::{
     ext::RepositoryExtended,
-    stack::{state_refname_from_branch_name, InitializationPolicy, Stack, StackAccess},
+    stack::{
+        email_history_refname_from_branch_name, quilt_sync_refname_from_branch_name,
+        state_refname_from_branch_name, InitializationPolicy, Stack, StackAccess,
+    },
     stupid::Stupid,
     wrap::PartialRefName,
 };
@@ -45,11 +46,23 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
         (&current_branch_name, names[0])
     };
 
+    rename(repo, old_branchname, new_branchname)?;
+    super::oplog::append(
+        repo,
+        &super::oplog::BranchOpRecord::rename(old_branchname, new_branchname),
+    )
+}
+
+/// Rename a branch along with its stack, if any.
+pub(super) fn rename(
+    repo: &gix::Repository,
+    old_branchname: &PartialRefName,
+    new_branchname: &PartialRefName,
+) -> Result<()> {
     let stupid = repo.stupid();
-    let parent_branchname = super::get_stgit_parent(&repo.config_snapshot(), old_branchname);
-    let parent_branchname = parent_branchname
-        .map(|name| PartialRefName::from_str(name.as_str()))
-        .transpose()?;
+    let config = repo.config_snapshot();
+    let parent = super::get_stgit_parent(&config, old_branchname);
+    let stacked_children = super::stacked::stacked_children(repo, &config, old_branchname)?;
 
     if let Ok(stack) = Stack::from_branch_name(
         repo,
@@ -77,6 +90,22 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
             ))?,
             deref: false,
         })?;
+        for refname_from_branch_name in [
+            email_history_refname_from_branch_name,
+            quilt_sync_refname_from_branch_name,
+        ] {
+            if let Some(reference) =
+                repo.try_find_reference(refname_from_branch_name(old_branchname.as_ref()).as_str())?
+            {
+                let target_id = reference.into_fully_peeled_id()?.detach();
+                repo.reference(
+                    refname_from_branch_name(new_branchname.as_ref()).as_str(),
+                    target_id,
+                    gix::refs::transaction::PreviousValue::MustNotExist,
+                    format!("rename {old_branchname} to {new_branchname}"),
+                )?;
+            }
+        }
         stupid
             .config_rename_section(
                 &format!("branch.{old_branchname}.stgit"),
@@ -88,6 +117,16 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
     } else {
         stupid.branch_move(Some(old_branchname.as_ref()), new_branchname.as_ref())?;
     }
-    super::set_stgit_parent(repo, new_branchname, parent_branchname.as_ref())?;
+    super::set_stgit_parent(repo, new_branchname, parent.as_ref())?;
+    for child_branchname in &stacked_children {
+        super::set_stgit_parent(
+            repo,
+            child_branchname,
+            Some(&super::StGitParent {
+                branchname: new_branchname.clone(),
+                stacked: true,
+            }),
+        )?;
+    }
     Ok(())
 }
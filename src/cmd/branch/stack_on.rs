@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg branch --stack-on` implementation.
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    ext::RepositoryExtended,
+    stack::{InitializationPolicy, Stack},
+    wrap::PartialRefName,
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("--stack-on")
+        .override_usage(super::super::make_usage(
+            "stg branch --stack-on",
+            &["<parent-branch> [branch]"],
+        ))
+        .about("Record a branch's stack as stacked on another branch's stack")
+        .long_about(
+            "Record the stack of the current branch, or the specified branch, as being \
+             stacked on top of the stack of <parent-branch>.\n\
+             \n\
+             Stacked branches are rebased along with their parent branch with `stg \
+             rebase --cascade` or `stg pull --cascade`, and are listed beneath their \
+             parent branch by `stg branch --list`.\n\
+             \n\
+             The branch's patches are not rebased by this command. Use `stg rebase \
+             <parent-branch>` to move the branch's stack on top of the parent branch's \
+             stack if it is not already there.",
+        )
+        .arg(
+            Arg::new("parent-branch")
+                .help("Branch whose stack to stack on")
+                .required(true)
+                .value_parser(clap::value_parser!(PartialRefName)),
+        )
+        .arg(
+            Arg::new("branch")
+                .help("Branch to stack")
+                .value_parser(clap::value_parser!(PartialRefName)),
+        )
+}
+
+pub(super) fn dispatch(repo: &gix::Repository, matches: &ArgMatches) -> Result<()> {
+    let parent_branchname = matches
+        .get_one::<PartialRefName>("parent-branch")
+        .expect("required argument");
+    let current_branchname;
+    let branchname = if let Some(branchname) = matches.get_one::<PartialRefName>("branch") {
+        branchname
+    } else {
+        current_branchname = repo.get_current_branch()?.get_branch_partial_name()?;
+        &current_branchname
+    };
+
+    if branchname == parent_branchname {
+        return Err(anyhow!("cannot stack branch `{branchname}` on itself"));
+    }
+
+    Stack::from_branch_name(repo, branchname, InitializationPolicy::RequireInitialized)?;
+    Stack::from_branch_name(
+        repo,
+        parent_branchname,
+        InitializationPolicy::RequireInitialized,
+    )?;
+
+    let config = repo.config_snapshot();
+    let mut ancestor = super::get_stgit_parent(&config, parent_branchname);
+    let mut visited = vec![parent_branchname.clone()];
+    while let Some(parent) = ancestor.filter(|parent| parent.stacked) {
+        if &parent.branchname == branchname {
+            return Err(anyhow!(
+                "cannot stack `{branchname}` on `{parent_branchname}` since \
+                 `{parent_branchname}` is stacked on `{branchname}`"
+            ));
+        } else if visited.contains(&parent.branchname) {
+            break;
+        }
+        ancestor = super::get_stgit_parent(&config, &parent.branchname);
+        visited.push(parent.branchname);
+    }
+
+    super::set_stgit_parent(
+        repo,
+        branchname,
+        Some(&super::StGitParent {
+            branchname: parent_branchname.clone(),
+            stacked: true,
+        }),
+    )
+}
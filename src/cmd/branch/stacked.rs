@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Support for stacked branches, i.e. branches whose stacks are built on top of the
+//! stack of another branch.
+
+use anyhow::Result;
+use bstr::ByteSlice;
+use clap::ArgMatches;
+
+use crate::{
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    print_info_message, print_warning_message,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+    wrap::{Branch, PartialRefName},
+};
+
+/// Get the branches whose stacks are directly stacked on the given branch's stack.
+pub(in super::super) fn stacked_children(
+    repo: &gix::Repository,
+    config: &gix::config::Snapshot,
+    branchname: &PartialRefName,
+) -> Result<Vec<PartialRefName>> {
+    let mut children = Vec::new();
+    for local_branch in repo.references()?.local_branches()?.filter_map(Result::ok) {
+        let local_branch = Branch::wrap(local_branch);
+        if let Ok(child_branchname) = local_branch.get_branch_partial_name() {
+            if super::get_stgit_parent(config, &child_branchname).map_or(false, |parent| {
+                parent.stacked && &parent.branchname == branchname
+            }) {
+                children.push(child_branchname);
+            }
+        }
+    }
+    children.sort();
+    Ok(children)
+}
+
+/// Get the branches stacked directly or indirectly on the given branch's stack.
+///
+/// Each branch is paired with its parent branch. Parents precede their children.
+fn stacked_descendants(
+    repo: &gix::Repository,
+    config: &gix::config::Snapshot,
+    branchname: &PartialRefName,
+) -> Result<Vec<(PartialRefName, PartialRefName)>> {
+    fn visit(
+        repo: &gix::Repository,
+        config: &gix::config::Snapshot,
+        root: &PartialRefName,
+        parent: &PartialRefName,
+        descendants: &mut Vec<(PartialRefName, PartialRefName)>,
+    ) -> Result<()> {
+        for child in stacked_children(repo, config, parent)? {
+            // Guard against cycles in the recorded stacked relationships.
+            if &child == root || descendants.iter().any(|(existing, _)| existing == &child) {
+                continue;
+            }
+            descendants.push((child.clone(), parent.clone()));
+            visit(repo, config, root, &child, descendants)?;
+        }
+        Ok(())
+    }
+
+    let mut descendants = Vec::new();
+    visit(repo, config, branchname, branchname, &mut descendants)?;
+    Ok(descendants)
+}
+
+/// Order branches such that stacked branches follow their parent branch.
+///
+/// Each branch is paired with its depth in the stacking hierarchy. Branches that are
+/// not stacked on any of the listed branches have depth zero.
+pub(super) fn stacked_order<'a>(
+    config: &gix::config::Snapshot,
+    branchnames: &'a [PartialRefName],
+) -> Vec<(&'a PartialRefName, usize)> {
+    let parents: Vec<Option<&PartialRefName>> = branchnames
+        .iter()
+        .map(|branchname| {
+            super::get_stgit_parent(config, branchname)
+                .filter(|parent| parent.stacked)
+                .and_then(|parent| branchnames.iter().find(|name| **name == parent.branchname))
+        })
+        .collect();
+
+    fn visit<'a>(
+        branchnames: &'a [PartialRefName],
+        parents: &[Option<&PartialRefName>],
+        index: usize,
+        depth: usize,
+        entries: &mut Vec<(&'a PartialRefName, usize)>,
+    ) {
+        if entries.iter().any(|(name, _)| *name == &branchnames[index]) {
+            return;
+        }
+        entries.push((&branchnames[index], depth));
+        for (child_index, parent) in parents.iter().enumerate() {
+            if *parent == Some(&branchnames[index]) {
+                visit(branchnames, parents, child_index, depth + 1, entries);
+            }
+        }
+    }
+
+    let mut entries = Vec::with_capacity(branchnames.len());
+    for (index, parent) in parents.iter().enumerate() {
+        if parent.is_none() {
+            visit(branchnames, &parents, index, 0, &mut entries);
+        }
+    }
+    // Branches in a stacking cycle have no root; list them unindented.
+    for index in 0..branchnames.len() {
+        visit(branchnames, &parents, index, 0, &mut entries);
+    }
+    entries
+}
+
+/// Print a hint about branches stacked on the given branch which may need rebasing.
+pub(in super::super) fn hint_stacked_children(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    branchname: &PartialRefName,
+) -> Result<()> {
+    let children = stacked_children(repo, &repo.config_snapshot(), branchname)?;
+    if !children.is_empty() {
+        let children: Vec<String> = children.iter().map(|name| format!("`{name}`")).collect();
+        print_info_message(
+            matches,
+            &format!(
+                "Stacked branches may need to be rebased: {}; use `--cascade` to rebase them",
+                children.join(", ")
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// Rebase the stacks of branches stacked on the given branch onto their parents.
+///
+/// Each stacked branch is checked out in turn, its applied patches popped, its base
+/// moved to its parent branch's head, and its patches pushed back. Branches stacked on
+/// those branches are subsequently rebased in the same way. The given branch is checked
+/// out again once all stacked branches are rebased.
+///
+/// If pushing back a stacked branch's patches results in conflicts, the cascade stops
+/// with that branch checked out.
+pub(in super::super) fn cascade_rebase(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    branchname: &PartialRefName,
+    allow_push_conflicts: bool,
+) -> Result<()> {
+    let config = repo.config_snapshot();
+    let descendants = stacked_descendants(repo, &config, branchname)?;
+    if descendants.is_empty() {
+        return Ok(());
+    }
+
+    let stupid = repo.stupid();
+    stupid.statuses(None)?.check_index_and_worktree_clean()?;
+
+    let mut skipped: Vec<&PartialRefName> = Vec::new();
+
+    for (i, (child, parent)) in descendants.iter().enumerate() {
+        if skipped.contains(&parent) {
+            skipped.push(child);
+            continue;
+        }
+
+        let stack = Stack::from_branch_name(repo, child, InitializationPolicy::RequireInitialized)?;
+        if stack.is_protected(&config) {
+            print_warning_message(
+                matches,
+                &format!("Not rebasing protected stacked branch `{child}`"),
+            );
+            skipped.push(child);
+            continue;
+        }
+
+        let parent_head_id = repo.get_branch(parent)?.get_commit()?.id;
+        if stack.base().id == parent_head_id {
+            continue;
+        }
+        stack.check_head_top_mismatch()?;
+
+        print_info_message(
+            matches,
+            &format!("Rebasing stacked branch `{child}` onto `{parent}`"),
+        );
+        if let Err(e) = rebase_stacked_branch(
+            repo,
+            &config,
+            matches,
+            child,
+            parent_head_id,
+            allow_push_conflicts,
+        ) {
+            let remaining: Vec<String> = descendants[i + 1..]
+                .iter()
+                .map(|(name, _)| format!("`{name}`"))
+                .collect();
+            if !remaining.is_empty() {
+                print_warning_message(
+                    matches,
+                    &format!("Stacked branches not rebased: {}", remaining.join(", ")),
+                );
+            }
+            return Err(e);
+        }
+    }
+
+    stupid.checkout(branchname.as_ref())
+}
+
+/// Check out a stacked branch and rebase its stack onto the target commit.
+fn rebase_stacked_branch(
+    repo: &gix::Repository,
+    config: &gix::config::Snapshot,
+    matches: &ArgMatches,
+    branchname: &PartialRefName,
+    target_id: gix::ObjectId,
+    allow_push_conflicts: bool,
+) -> Result<()> {
+    let stupid = repo.stupid();
+    stupid.checkout(branchname.as_ref())?;
+
+    let stack =
+        Stack::from_branch_name(repo, branchname, InitializationPolicy::RequireInitialized)?;
+    let applied = stack.applied().to_vec();
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            trans.pop_patches(|pn| applied.contains(pn))?;
+            Ok(())
+        })
+        .execute("rebase (pop)")?;
+
+    let rebase_cmd = config
+        .plumbing()
+        .string(
+            "branch",
+            Some(format!("{branchname}.stgit").as_str().into()),
+            "rebasecmd",
+        )
+        .or_else(|| config.string("stgit.rebasecmd"))
+        .and_then(|bs| bs.to_str().map(str::to_string).ok())
+        .unwrap_or_else(|| "git reset --hard".to_string());
+    stupid.user_rebase(&rebase_cmd, target_id)?;
+
+    let stack =
+        Stack::from_branch_name(repo, branchname, InitializationPolicy::RequireInitialized)?;
+    let stack = if stack.is_head_top() {
+        stack
+    } else {
+        stack.log_external_mods(Some("rebase"))?
+    };
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .allow_push_conflicts(allow_push_conflicts)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| trans.push_patches(&applied, false))
+        .execute("rebase (reapply)")?;
+
+    Ok(())
+}
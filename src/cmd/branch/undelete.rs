@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg branch --undelete` implementation.
+
+use anyhow::Result;
+
+use crate::wrap::PartialRefName;
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("--undelete")
+        .override_usage(super::super::make_usage(
+            "stg branch --undelete",
+            &["<branch>"],
+        ))
+        .about("Restore a deleted branch")
+        .long_about(
+            "Restore a branch deleted with `stg branch --delete`, including its StGit \
+             stack, patch references, and configuration.\n\
+             \n\
+             The most recent deletion of the named branch recorded in the branch \
+             operation log is restored and removed from the log. The branch must not \
+             exist.",
+        )
+        .arg(
+            clap::Arg::new("branch-any")
+                .help("Deleted branch to restore")
+                .value_name("branch")
+                .required(true)
+                .value_parser(clap::value_parser!(PartialRefName)),
+        )
+}
+
+pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Result<()> {
+    let branchname = matches
+        .get_one::<PartialRefName>("branch-any")
+        .expect("required argument");
+    super::oplog::undelete(repo, branchname)
+}
@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg bundle create` implementation.
+
+use std::{io::Write, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Arg;
+
+use crate::{
+    ext::RepositoryExtended,
+    stack::{InitializationPolicy, Stack, StackAccess, StackState, StackStateAccess},
+    stupid::Stupid,
+    wrap::PartialRefName,
+};
+
+/// First line of a version 2 git bundle file.
+const BUNDLE_SIGNATURE: &[u8] = b"# v2 git bundle\n";
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("create")
+        .about("Create a bundle file containing stacks")
+        .long_about(
+            "Create a git bundle file containing the stacks of the current branch or \
+             of the named branches.\n\
+             \n\
+             For each stack, the bundle contains the branch, the stack state \
+             reference, and the patch references, along with the complete history \
+             of the branch and every patch commit, including unapplied and hidden \
+             patches.\n\
+             \n\
+             By default, the stack state is recorded without its log such that \
+             `stg log` only shows a single entry after the bundle is imported. Use \
+             '--log' to include the complete stack log in the bundle.",
+        )
+        .arg(
+            Arg::new("file")
+                .help("Bundle file to create")
+                .value_name("file")
+                .required(true)
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("branches")
+                .help("Branches whose stacks are bundled")
+                .value_name("branch")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PartialRefName)),
+        )
+        .arg(
+            Arg::new("log")
+                .long("log")
+                .help("Include the stack log in the bundle")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let path = matches.get_one::<PathBuf>("file").unwrap();
+    let include_log = matches.get_flag("log");
+
+    let stacks = if let Some(branchnames) = matches.get_many::<PartialRefName>("branches") {
+        branchnames
+            .map(|branchname| {
+                Stack::from_branch_name(&repo, branchname, InitializationPolicy::RequireInitialized)
+            })
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        vec![Stack::current(
+            &repo,
+            InitializationPolicy::RequireInitialized,
+        )?]
+    };
+
+    let mut heads: Vec<(gix::ObjectId, String)> = Vec::new();
+
+    for stack in &stacks {
+        stack
+            .check_head_top_mismatch()
+            .with_context(|| format!("bundling branch `{}`", stack.get_branch_name()))?;
+
+        heads.push((
+            stack.get_branch_head().id,
+            stack.get_branch_refname().as_bstr().to_string(),
+        ));
+
+        let state_commit = repo
+            .find_reference(stack.get_stack_refname())?
+            .into_fully_peeled_id()?
+            .object()?
+            .try_into_commit()?;
+        let state_commit_id = if include_log {
+            state_commit.id
+        } else {
+            let mut state = StackState::from_commit(&repo, &state_commit)?;
+            state.prev = None;
+            state.commit(&repo, None, "bundle create")?
+        };
+        heads.push((state_commit_id, stack.get_stack_refname().to_string()));
+
+        for patchname in stack.all_patches() {
+            heads.push((
+                stack.get_patch_commit_id(patchname),
+                stack.patch_revspec(patchname.as_ref()),
+            ));
+        }
+    }
+
+    let pack = repo
+        .stupid()
+        .pack_objects(heads.iter().map(|(commit_id, _)| *commit_id))?;
+
+    let mut bundle = BUNDLE_SIGNATURE.to_vec();
+    for (commit_id, refname) in &heads {
+        writeln!(bundle, "{commit_id} {refname}")?;
+    }
+    bundle.push(b'\n');
+    bundle.extend(pack);
+
+    std::fs::write(path, bundle)
+        .with_context(|| format!("writing bundle file `{}`", path.display()))?;
+
+    Ok(())
+}
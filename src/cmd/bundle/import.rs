@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg bundle import` implementation.
+
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use clap::Arg;
+
+use crate::{
+    ext::RepositoryExtended,
+    stack::{state_refname_from_branch_name, InitializationPolicy, Stack},
+    stupid::Stupid,
+    wrap::PartialRefName,
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("import")
+        .about("Import stacks from a bundle file")
+        .long_about(
+            "Import the stacks contained in a bundle file created with \
+             `stg bundle create`.\n\
+             \n\
+             The branch, stack state, and patch references of each stack in the \
+             bundle are recreated in the current repository. If named branches are \
+             specified, only those branches' stacks are imported.\n\
+             \n\
+             Importing a stack whose branch or stack already exists in the \
+             repository is refused unless '--force' is used, in which case the \
+             existing branch and stack are replaced. The current branch may not be \
+             replaced.",
+        )
+        .arg(
+            Arg::new("file")
+                .help("Bundle file to import")
+                .value_name("file")
+                .required(true)
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("branches")
+                .help("Only import the stacks of these branches")
+                .value_name("branch")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PartialRefName)),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("Replace existing branches and stacks")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stupid = repo.stupid();
+    let path = matches.get_one::<PathBuf>("file").unwrap();
+    let force = matches.get_flag("force");
+
+    stupid.bundle_verify(path)?;
+    let heads = stupid.bundle_list_heads(path)?;
+
+    let has_head = |refname: &str| {
+        heads
+            .iter()
+            .any(|(_, head_refname)| head_refname == refname)
+    };
+    let bundled_branchnames: Vec<&str> = heads
+        .iter()
+        .filter_map(|(_, refname)| refname.strip_prefix("refs/stacks/"))
+        .filter(|branchname| has_head(&format!("refs/heads/{branchname}")))
+        .collect();
+
+    if bundled_branchnames.is_empty() {
+        return Err(anyhow!(
+            "bundle `{}` does not contain any StGit stacks",
+            path.display()
+        ));
+    }
+
+    let branchnames: Vec<&str> =
+        if let Some(requested) = matches.get_many::<PartialRefName>("branches") {
+            requested
+                .map(|branchname| {
+                    bundled_branchnames
+                        .iter()
+                        .copied()
+                        .find(|&bundled| bundled == branchname.as_ref())
+                        .ok_or_else(|| anyhow!("no stack for branch `{branchname}` in bundle"))
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            bundled_branchnames
+        };
+
+    let current_branch = repo.get_current_branch().ok();
+    let current_branchname = current_branch
+        .as_ref()
+        .and_then(|branch| branch.get_branch_name().ok());
+
+    for &branchname in &branchnames {
+        if current_branchname == Some(branchname) {
+            return Err(anyhow!(
+                "cannot import stack over the current branch `{branchname}`"
+            ));
+        } else if force {
+            continue;
+        }
+        let branch_refname = format!("refs/heads/{branchname}");
+        if repo
+            .try_find_reference(state_refname_from_branch_name(branchname).as_str())?
+            .is_some()
+        {
+            return Err(anyhow!(
+                "StGit stack already initialized for branch `{branchname}`; \
+                 use `--force` to replace it"
+            ));
+        } else if repo.try_find_reference(branch_refname.as_str())?.is_some() {
+            return Err(anyhow!(
+                "branch `{branchname}` already exists; use `--force` to replace it"
+            ));
+        }
+    }
+
+    let refspecs: Vec<String> = branchnames
+        .iter()
+        .flat_map(|branchname| {
+            [
+                format!("+refs/heads/{branchname}:refs/heads/{branchname}"),
+                format!("+refs/stacks/{branchname}:refs/stacks/{branchname}"),
+            ]
+        })
+        .collect();
+    stupid.fetch_bundle(path, refspecs.iter().map(String::as_str))?;
+
+    // Instantiating each stack recreates its patch refs.
+    for branchname in branchnames {
+        let branchname = PartialRefName::from_str(branchname)?;
+        Stack::from_branch_name(&repo, &branchname, InitializationPolicy::RequireInitialized)?;
+    }
+
+    Ok(())
+}
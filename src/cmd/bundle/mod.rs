@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg bundle` implementation.
+
+mod create;
+mod import;
+
+use anyhow::Result;
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "bundle",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Transfer stacks using git bundle files")
+        .long_about(
+            "Transfer stacks between repositories using git bundle files.\n\
+             \n\
+             A bundle created with `stg bundle create` contains each stack's branch, \
+             its stack state reference, and its patch references, along with all the \
+             commits they refer to. The bundle file may be carried to another \
+             repository, e.g. on an air-gapped machine, where `stg bundle import` \
+             recreates the branches and their stacks.\n\
+             \n\
+             Bundles created by `stg bundle create` are regular git bundles which may \
+             also be inspected with git-bundle(1).",
+        )
+        .subcommand_required(true)
+        .subcommand(create::command())
+        .subcommand(import::command())
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("create", sub_matches)) => create::dispatch(sub_matches),
+        Some(("import", sub_matches)) => import::dispatch(sub_matches),
+        _ => panic!("valid subcommand is expected"),
+    }
+}
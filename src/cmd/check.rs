@@ -0,0 +1,489 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg check` implementation.
+//!
+//! The lint rules are also used to gate `stg commit`, `stg refresh`, `stg email
+//! format`, and `stg email send` when enabled with `--check` or the `stgit.check.gate`
+//! configuration value.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::{patchedit, patchrange, PatchName, PatchRange, RangeConstraint},
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "check",
+    category: super::CommandCategory::PatchInspection,
+    make,
+    run,
+};
+
+/// Default maximum subject length, in characters.
+const DEFAULT_SUBJECT_LIMIT: usize = 72;
+
+/// Default maximum size of added files, in bytes.
+const DEFAULT_FILE_SIZE_LIMIT: usize = 1024 * 1024;
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Check patches for common problems")
+        .long_about(
+            "Check patches for common problems before they are sent or committed. By \
+             default, all applied patches are checked. Each problem found is shown \
+             as '<patch>: <rule>: <description>' and the command fails if any problem \
+             is found.\n\
+             \n\
+             The rules are:\n\
+             \n  \
+             - signoff: the message has no Signed-off-by trailer from the patch \
+             author;\n  \
+             - subject-length: the subject is longer than `stgit.check.subjectLimit` \
+             characters (default 72);\n  \
+             - subject-prefix: the subject does not start with an 'area: ' prefix, or \
+             starts with a bracketed prefix such as '[PATCH]'. This rule is disabled \
+             by default;\n  \
+             - trailing-whitespace: an added line ends with whitespace;\n  \
+             - conflict-markers: an added line is a leftover conflict marker;\n  \
+             - large-file: an added file is larger than `stgit.check.fileSizeLimit` \
+             bytes (default 1m);\n  \
+             - message-encoding: the message is not UTF-8;\n  \
+             - author-from: the author differs from the committer and \
+             `format.from` is not set, so emails for the patch would not have an \
+             in-body From: header crediting the author. This rule is disabled by \
+             default.\n\
+             \n\
+             Each rule may be enabled or disabled with the `stgit.check.<rule>` \
+             boolean configuration value.\n\
+             \n\
+             Checking may also be required before `stg commit`, `stg refresh`, `stg \
+             email format`, and `stg email send` by using their '--check' option or \
+             by listing 'commit', 'refresh', 'email-format', or 'email-send' in the \
+             comma-separated `stgit.check.gate` configuration value. For `stg \
+             refresh`, only the rules concerning the refreshed changes are checked.",
+        )
+        .arg(
+            Arg::new("patchranges")
+                .help("Patches to check")
+                .value_name("patch")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PatchRange)),
+        )
+        .arg(argset::branch_arg())
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+
+    let patches: Vec<PatchName> =
+        if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges") {
+            patchrange::resolve_names(&stack, range_specs, RangeConstraint::All)?
+        } else {
+            stack.applied().to_vec()
+        };
+
+    let checker = Checker::new(&repo, false)?;
+    let mut problems = Vec::new();
+    for patchname in &patches {
+        let commit = stack.get_patch_commit(patchname);
+        problems.extend(checker.check(patchname.as_ref(), commit)?);
+    }
+
+    report(&problems)
+}
+
+/// Arguments for `--check` and `--no-check` for commands that may be gated.
+pub(super) fn gate_args() -> [Arg; 2] {
+    [
+        Arg::new("check")
+            .long("check")
+            .help("Check the patches with `stg check` first")
+            .long_help(
+                "Check the patches with the rules of `stg check` and stop if any \
+                 problem is found. This is the default when the command is listed in \
+                 the `stgit.check.gate` configuration value.",
+            )
+            .action(clap::ArgAction::SetTrue)
+            .overrides_with("no-check"),
+        Arg::new("no-check")
+            .long("no-check")
+            .help("Do not check the patches with `stg check`")
+            .action(clap::ArgAction::SetTrue)
+            .overrides_with("check"),
+    ]
+}
+
+/// Determine whether checking is enabled for the command.
+pub(super) fn is_gated(repo: &gix::Repository, matches: &ArgMatches, gate_name: &str) -> bool {
+    if matches.get_flag("check") {
+        true
+    } else if matches.get_flag("no-check") {
+        false
+    } else {
+        repo.config_snapshot()
+            .string("stgit.check.gate")
+            .map_or(false, |value| {
+                value
+                    .to_str_lossy()
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .any(|name| name == gate_name)
+            })
+    }
+}
+
+/// Check the patches if gating is enabled for the command, failing on any problem.
+///
+/// The patches are given as pairs of a patch name and a commit id. When `changes_only`
+/// is true, only the rules concerning the patches' diffs are checked.
+pub(super) fn gate(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    gate_name: &str,
+    patches: &[(String, gix::ObjectId)],
+    changes_only: bool,
+) -> Result<()> {
+    if !is_gated(repo, matches, gate_name) {
+        return Ok(());
+    }
+
+    let checker = Checker::new(repo, changes_only)?;
+    let mut problems = Vec::new();
+    for (name, commit_id) in patches {
+        problems.extend(checker.check(name, &repo.find_commit(*commit_id)?)?);
+    }
+    report(&problems).map_err(|e| anyhow!("{e}; use `--no-check` to skip checking"))
+}
+
+/// Print problems and fail if there are any.
+fn report(problems: &[Problem]) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for problem in problems {
+        writeln!(
+            stdout,
+            "{}: {}: {}",
+            problem.patch,
+            problem.rule.name(),
+            problem.description
+        )?;
+    }
+    match problems.len() {
+        0 => Ok(()),
+        1 => Err(anyhow!("found 1 problem")),
+        n => Err(anyhow!("found {n} problems")),
+    }
+}
+
+/// A lint rule.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    Signoff,
+    SubjectLength,
+    SubjectPrefix,
+    TrailingWhitespace,
+    ConflictMarkers,
+    LargeFile,
+    MessageEncoding,
+    AuthorFrom,
+}
+
+impl Rule {
+    const ALL: [Rule; 8] = [
+        Rule::Signoff,
+        Rule::SubjectLength,
+        Rule::SubjectPrefix,
+        Rule::TrailingWhitespace,
+        Rule::ConflictMarkers,
+        Rule::LargeFile,
+        Rule::MessageEncoding,
+        Rule::AuthorFrom,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Rule::Signoff => "signoff",
+            Rule::SubjectLength => "subject-length",
+            Rule::SubjectPrefix => "subject-prefix",
+            Rule::TrailingWhitespace => "trailing-whitespace",
+            Rule::ConflictMarkers => "conflict-markers",
+            Rule::LargeFile => "large-file",
+            Rule::MessageEncoding => "message-encoding",
+            Rule::AuthorFrom => "author-from",
+        }
+    }
+
+    fn is_enabled_by_default(self) -> bool {
+        !matches!(self, Rule::SubjectPrefix | Rule::AuthorFrom)
+    }
+
+    /// Whether the rule concerns a patch's changes rather than its metadata.
+    fn is_change_rule(self) -> bool {
+        matches!(
+            self,
+            Rule::TrailingWhitespace | Rule::ConflictMarkers | Rule::LargeFile
+        )
+    }
+}
+
+/// A problem found with a patch.
+struct Problem {
+    patch: String,
+    rule: Rule,
+    description: String,
+}
+
+/// Checks patches against the configured rules.
+struct Checker<'repo> {
+    repo: &'repo gix::Repository,
+    rules: Vec<Rule>,
+    subject_limit: usize,
+    file_size_limit: usize,
+    has_format_from: bool,
+}
+
+impl<'repo> Checker<'repo> {
+    fn new(repo: &'repo gix::Repository, changes_only: bool) -> Result<Self> {
+        let config = repo.config_snapshot();
+        let rules = Rule::ALL
+            .into_iter()
+            .filter(|rule| {
+                config
+                    .boolean(format!("stgit.check.{}", rule.name()).as_str())
+                    .unwrap_or_else(|| rule.is_enabled_by_default())
+            })
+            .filter(|rule| !changes_only || rule.is_change_rule())
+            .collect();
+        let get_limit = |key: &str, default: usize| -> Result<usize> {
+            if let Some(value) = config.integer(key) {
+                usize::try_from(value).map_err(|_| anyhow!("invalid `{key}` value `{value}`"))
+            } else {
+                Ok(default)
+            }
+        };
+        Ok(Self {
+            repo,
+            rules,
+            subject_limit: get_limit("stgit.check.subjectLimit", DEFAULT_SUBJECT_LIMIT)?,
+            file_size_limit: get_limit("stgit.check.fileSizeLimit", DEFAULT_FILE_SIZE_LIMIT)?,
+            has_format_from: config.string("format.from").map_or(false, |value| {
+                !value.is_empty() && !value.eq_ignore_ascii_case(b"false")
+            }),
+        })
+    }
+
+    fn check(&self, name: &str, commit: &gix::Commit) -> Result<Vec<Problem>> {
+        let mut problems = Vec::new();
+        let mut add = |rule: Rule, description: String| {
+            problems.push(Problem {
+                patch: name.to_string(),
+                rule,
+                description,
+            });
+        };
+        let is_enabled = |rule: Rule| self.rules.contains(&rule);
+
+        let commit_ref = commit.decode()?;
+        let author = commit.author_strict()?;
+
+        if is_enabled(Rule::MessageEncoding) {
+            if let Some(encoding) = commit_ref.encoding.filter(|encoding| {
+                !encoding.eq_ignore_ascii_case(b"utf-8") && !encoding.eq_ignore_ascii_case(b"utf8")
+            }) {
+                add(
+                    Rule::MessageEncoding,
+                    format!("message encoding is `{encoding}`, not UTF-8"),
+                );
+            } else if commit_ref.message.to_str().is_err() {
+                add(
+                    Rule::MessageEncoding,
+                    "message is not valid UTF-8".to_string(),
+                );
+            }
+        }
+
+        let message = commit.message_ex().decode()?.to_string();
+        let subject = message.lines().next().unwrap_or_default();
+
+        if is_enabled(Rule::Signoff)
+            && !patchedit::parse_trailers(&message)
+                .iter()
+                .any(|(token, value)| {
+                    token.eq_ignore_ascii_case("Signed-off-by")
+                        && value
+                            .rsplit_once('<')
+                            .and_then(|(_, rest)| rest.split_once('>'))
+                            .map_or(false, |(email, _)| {
+                                email.as_bytes().eq_ignore_ascii_case(&author.email)
+                            })
+                })
+        {
+            add(
+                Rule::Signoff,
+                format!(
+                    "missing Signed-off-by from author `{} <{}>`",
+                    author.name, author.email
+                ),
+            );
+        }
+
+        if is_enabled(Rule::SubjectLength) {
+            let length = subject.chars().count();
+            if length > self.subject_limit {
+                add(
+                    Rule::SubjectLength,
+                    format!(
+                        "subject is {length} characters long; the limit is {}",
+                        self.subject_limit
+                    ),
+                );
+            }
+        }
+
+        if is_enabled(Rule::SubjectPrefix) {
+            if subject.starts_with('[') {
+                add(
+                    Rule::SubjectPrefix,
+                    "subject starts with a bracketed prefix".to_string(),
+                );
+            } else if !subject.split_once(": ").map_or(false, |(area, _)| {
+                !area.is_empty() && !area.starts_with(char::is_whitespace)
+            }) {
+                add(
+                    Rule::SubjectPrefix,
+                    "subject has no `area: ` prefix".to_string(),
+                );
+            }
+        }
+
+        if is_enabled(Rule::AuthorFrom) && !self.has_format_from {
+            let committer = commit.committer_strict()?;
+            if author.name != committer.name || author.email != committer.email {
+                add(
+                    Rule::AuthorFrom,
+                    format!(
+                        "author `{} <{}>` differs from committer `{} <{}>` and \
+                         `format.from` is not set",
+                        author.name, author.email, committer.name, committer.email
+                    ),
+                );
+            }
+        }
+
+        if self.rules.iter().any(|rule| rule.is_change_rule()) {
+            let parent = commit.get_parent_commit()?;
+            let parent_tree_id = parent.tree_id()?.detach();
+            let tree_id = commit.tree_id()?.detach();
+
+            if is_enabled(Rule::TrailingWhitespace) || is_enabled(Rule::ConflictMarkers) {
+                let diff = self.repo.stupid().diff_tree_patch(
+                    parent_tree_id,
+                    tree_id,
+                    None::<Vec<&str>>,
+                    false,
+                    ["-U0", "--no-ext-diff"],
+                )?;
+                for (path, line_num, line) in added_lines(&diff) {
+                    if is_enabled(Rule::TrailingWhitespace) && line.ends_with([' ', '\t']) {
+                        add(
+                            Rule::TrailingWhitespace,
+                            format!("{path}:{line_num}: trailing whitespace"),
+                        );
+                    }
+                    if is_enabled(Rule::ConflictMarkers) && is_conflict_marker(&line) {
+                        add(
+                            Rule::ConflictMarkers,
+                            format!("{path}:{line_num}: conflict marker `{line}`"),
+                        );
+                    }
+                }
+            }
+
+            if is_enabled(Rule::LargeFile) {
+                let diff_files = self
+                    .repo
+                    .stupid()
+                    .diff_tree_files(parent_tree_id, tree_id)?;
+                for path in diff_files.iter() {
+                    if parent.tree()?.lookup_entry_by_path(path)?.is_some() {
+                        continue;
+                    }
+                    if let Some(entry) = commit.tree()?.lookup_entry_by_path(path)? {
+                        let object = entry.object()?;
+                        if object.kind == gix::objs::Kind::Blob
+                            && object.data.len() > self.file_size_limit
+                        {
+                            add(
+                                Rule::LargeFile,
+                                format!(
+                                    "{}: added file is {} bytes; the limit is {}",
+                                    path.display(),
+                                    object.data.len(),
+                                    self.file_size_limit
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(problems)
+    }
+}
+
+/// Get the added lines of a zero-context diff with their paths and line numbers.
+fn added_lines(diff: &[u8]) -> Vec<(String, usize, String)> {
+    let mut added = Vec::new();
+    let mut in_header = false;
+    let mut path: Option<String> = None;
+    let mut line_num = 0;
+    for line in diff.lines() {
+        if line.starts_with(b"diff --git ") {
+            in_header = true;
+            path = None;
+        } else if in_header && line.starts_with(b"+++ ") {
+            path = line
+                .strip_prefix(b"+++ b/")
+                .map(|path| path.to_str_lossy().to_string());
+        } else if let Some(rest) = line.strip_prefix(b"@@ -") {
+            in_header = false;
+            line_num = rest
+                .split_str(" +")
+                .nth(1)
+                .and_then(|range| range.split(|&b| b == b',' || b == b' ').next())
+                .and_then(|start| start.to_str().ok())
+                .and_then(|start| start.parse::<usize>().ok())
+                .unwrap_or_default();
+        } else if !in_header {
+            if let (Some(content), Some(path)) = (line.strip_prefix(b"+"), path.as_ref()) {
+                added.push((path.clone(), line_num, content.to_str_lossy().to_string()));
+                line_num += 1;
+            }
+        }
+    }
+    added
+}
+
+/// Determine whether a line is a conflict marker left by a merge.
+fn is_conflict_marker(line: &str) -> bool {
+    ["<<<<<<<", "|||||||", "=======", ">>>>>>>"]
+        .iter()
+        .any(|marker| {
+            line.strip_prefix(marker)
+                .map_or(false, |rest| rest.is_empty() || rest.starts_with(' '))
+        })
+}
@@ -2,14 +2,17 @@
 
 //! `stg clean` implementation.
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches};
 
 use crate::{
     color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended},
     patch::PatchName,
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{
+        find_merged_in_commits, InitializationPolicy, MergedPatch, Stack, StackAccess,
+        StackStateAccess,
+    },
     stupid::Stupid,
 };
 
@@ -26,7 +29,17 @@ fn make() -> clap::Command {
         .long_about(
             "Delete the empty patches from the entire series by default, \
              or only empty patches from the applied or unapplied patches. \
-             A patch is considered empty if its tree is the same as its parent.",
+             A patch is considered empty if its tree is the same as its parent.\n\
+             \n\
+             With --merged, patches that have been merged upstream are also deleted. \
+             A patch is considered merged if a commit on the branch's upstream that \
+             is not yet in the stack base, made since the patch was authored, has the \
+             same patch-id, the same \"Change-Id\" trailer, or the same \"Link\" \
+             trailer and subject as the patch. A \"Link\" trailer alone is not enough \
+             since several patches may link to the same bug report or series. \
+             Matching by subject and author is also done when \
+             \"stgit.merged.match-subject\" is enabled. The upstream commit each \
+             deleted patch was merged as is recorded in the stack log.",
         )
         .arg(
             Arg::new("applied")
@@ -42,6 +55,13 @@ fn make() -> clap::Command {
                 .help("Delete empty unapplied patches")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("merged")
+                .long("merged")
+                .short('m')
+                .help("Also delete patches merged upstream")
+                .action(clap::ArgAction::SetTrue),
+        )
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -84,19 +104,80 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     }
 
-    if !to_delete.is_empty() {
+    let merged = if matches.get_flag("merged") {
+        let mut candidates: Vec<&PatchName> = Vec::new();
+        if clean_applied {
+            candidates.extend(stack.applied());
+        }
+        if clean_unapplied {
+            candidates.extend(stack.unapplied());
+        }
+        candidates.retain(|pn| !to_delete.contains(*pn));
+        find_merged_upstream(&stack, &candidates)?
+    } else {
+        Vec::new()
+    };
+
+    if !to_delete.is_empty() || !merged.is_empty() {
         stack
             .setup_transaction()
             .allow_conflicts(true)
             .use_index_and_worktree(false)
             .with_output_stream(get_color_stdout(matches))
             .transact(|trans| {
-                let to_push = trans.delete_patches(|pn| to_delete.contains(pn))?;
+                for merged_patch in &merged {
+                    trans.add_log_detail(merged_patch.to_string());
+                }
+                let to_push = trans.delete_patches(|pn| {
+                    to_delete.contains(pn)
+                        || merged
+                            .iter()
+                            .any(|merged_patch| &merged_patch.patchname == pn)
+                })?;
                 trans.push_patches(&to_push, false)?;
                 Ok(())
             })
-            .execute("delete")?;
+            .execute(if merged.is_empty() {
+                "delete"
+            } else {
+                "clean --merged"
+            })?;
     }
 
     Ok(())
 }
+
+/// Find candidate patches that have been merged into the branch's upstream.
+///
+/// Only upstream commits that are not yet in the stack base and were made since the
+/// oldest candidate patch was authored are considered.
+fn find_merged_upstream(stack: &Stack, candidates: &[&PatchName]) -> Result<Vec<MergedPatch>> {
+    let branchname = stack.get_branch_name();
+    let upstream = stack
+        .repo
+        .stupid()
+        .branch_upstream(branchname)?
+        .ok_or_else(|| {
+            anyhow!("branch `{branchname}` has no upstream to check for merged patches")
+        })?;
+    let upstream_id = stack.repo.rev_parse_single(upstream.as_str())?.detach();
+
+    let mut since: Option<gix::actor::Time> = None;
+    for patchname in candidates {
+        let author_time = stack.get_patch_commit(patchname).author_strict()?.time;
+        if since.map_or(true, |since| author_time.seconds() < since.seconds()) {
+            since = Some(author_time);
+        }
+    }
+
+    if let Some(since) = since {
+        let upstream_ids =
+            stack
+                .repo
+                .stupid()
+                .rev_list_since(stack.base().id, upstream_id, since)?;
+        find_merged_in_commits(stack.repo, stack, candidates, &upstream_ids)
+    } else {
+        Ok(Vec::new())
+    }
+}
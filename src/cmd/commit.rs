@@ -74,6 +74,7 @@ fn make() -> clap::Command {
                 .help("Allow empty patches to be committed")
                 .action(clap::ArgAction::SetTrue),
         )
+        .args(super::check::gate_args())
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -135,6 +136,12 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     }
 
+    let patch_commits: Vec<(String, gix::ObjectId)> = patches
+        .iter()
+        .map(|pn| (pn.to_string(), stack.get_patch_commit_id(pn)))
+        .collect();
+    super::check::gate(&repo, matches, "commit", &patch_commits, false)?;
+
     stack.check_head_top_mismatch()?;
 
     stack
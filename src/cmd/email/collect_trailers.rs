@@ -0,0 +1,314 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg email collect-trailers` implementation.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use bstr::ByteSlice;
+use clap::{Arg, ValueHint};
+
+use super::history::{self, is_cover_subject, Headers};
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    color::get_color_stdout,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::{patchedit, PatchName},
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+};
+
+/// Trailers collected from review replies.
+const REVIEW_TRAILERS: [&str; 3] = ["Acked-by", "Reviewed-by", "Tested-by"];
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("collect-trailers")
+        .about("Add review trailers from email replies to patches")
+        .long_about(
+            "Collect 'Acked-by', 'Reviewed-by', and 'Tested-by' trailers from replies \
+             to a sent patch series and add them to the corresponding patches' \
+             messages.\n\
+             \n\
+             The replies are read from mbox files or maildirs, such as those saved \
+             from a mail client or downloaded from a mailing list archive. The \
+             original patch emails may also be included.\n\
+             \n\
+             Each reply is matched to a patch by the Message-IDs recorded when the \
+             series was formatted or sent with `stg email`, or by the Message-IDs of \
+             the original patch emails found in the mailbox. The reply's \
+             'In-Reply-To' header is tried first, followed by its 'References' \
+             header, so that replies to replies are also matched. Otherwise, the \
+             reply is matched by its subject with the 'Re:' and '[PATCH vN m/n]' \
+             prefixes removed.\n\
+             \n\
+             Trailers from replies to the cover letter apply to every patch of the \
+             series. Quoted lines are ignored, as are trailers already present in \
+             a patch's message.",
+        )
+        .arg(
+            Arg::new("mailboxes")
+                .help("Mbox files or maildirs with replies")
+                .value_name("mbox|maildir")
+                .num_args(1..)
+                .required(true)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::AnyPath),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .short('n')
+                .help("Show the trailers that would be added without adding them")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// The target of a reply.
+#[derive(Clone)]
+enum Target {
+    Patch(PatchName),
+    Cover(Vec<PatchName>),
+}
+
+/// An email read from a mailbox.
+struct Mail {
+    message_id: Option<String>,
+    in_reply_to: Vec<String>,
+    references: Vec<String>,
+    is_reply: bool,
+    is_cover: bool,
+    subject: String,
+    trailers: Vec<(&'static str, String)>,
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+
+    let mut mails = Vec::new();
+    for path in matches
+        .get_many::<PathBuf>("mailboxes")
+        .expect("required argument")
+    {
+        read_mails(&repo, path, &mut mails)?;
+    }
+
+    let series: Vec<&PatchName> = stack.applied().iter().chain(stack.unapplied()).collect();
+    let mut by_subject: HashMap<String, &PatchName> = HashMap::new();
+    for &patchname in &series {
+        let message = stack.get_patch_commit(patchname).message_ex();
+        let message = message.decode()?;
+        if let Some(subject) = message.lines().next() {
+            by_subject.insert(subject.trim().to_string(), patchname);
+        }
+    }
+
+    // Message-IDs of the patch emails and cover letters, as recorded when the series
+    // was formatted or sent, or as found in the mailboxes.
+    let mut targets: HashMap<String, Target> = HashMap::new();
+    for record in history::read(&repo, stack.get_branch_name())? {
+        let record_patches: Vec<PatchName> = record
+            .patches
+            .iter()
+            .map(|patch_record| patch_record.name.clone())
+            .filter(|patchname| series.contains(&patchname))
+            .collect();
+        if let Some(message_id) = record.cover_message_id {
+            targets.insert(message_id, Target::Cover(record_patches));
+        }
+        for patch_record in record.patches {
+            if let Some(message_id) = patch_record.message_id {
+                if series.contains(&&patch_record.name) {
+                    targets.insert(message_id, Target::Patch(patch_record.name));
+                }
+            }
+        }
+    }
+
+    let mut mailbox_patches = Vec::new();
+    for mail in mails.iter().filter(|mail| !mail.is_reply && !mail.is_cover) {
+        if let Some(&patchname) = by_subject.get(&mail.subject) {
+            mailbox_patches.push(patchname.clone());
+            if let Some(message_id) = mail.message_id.as_ref() {
+                targets
+                    .entry(message_id.clone())
+                    .or_insert_with(|| Target::Patch(patchname.clone()));
+            }
+        }
+    }
+    for mail in mails.iter().filter(|mail| !mail.is_reply && mail.is_cover) {
+        if let Some(message_id) = mail.message_id.as_ref() {
+            targets
+                .entry(message_id.clone())
+                .or_insert_with(|| Target::Cover(mailbox_patches.clone()));
+        }
+    }
+
+    let mut collected: HashMap<&PatchName, Vec<(&'static str, String)>> = HashMap::new();
+    for mail in &mails {
+        if !mail.is_reply || mail.trailers.is_empty() {
+            continue;
+        }
+        let target = mail
+            .in_reply_to
+            .iter()
+            .chain(mail.references.iter().rev())
+            .find_map(|message_id| targets.get(message_id).cloned())
+            .or_else(|| {
+                by_subject
+                    .get(&mail.subject)
+                    .map(|&patchname| Target::Patch(patchname.clone()))
+            });
+        let patchnames = match target {
+            Some(Target::Patch(patchname)) => vec![patchname],
+            Some(Target::Cover(patchnames)) => patchnames,
+            None => continue,
+        };
+        for patchname in patchnames {
+            let patchname: &PatchName = series
+                .iter()
+                .find(|&&pn| pn == &patchname)
+                .expect("targets are in the series");
+            let patch_trailers = collected.entry(patchname).or_default();
+            for trailer in &mail.trailers {
+                if !patch_trailers.contains(trailer) {
+                    patch_trailers.push(trailer.clone());
+                }
+            }
+        }
+    }
+
+    let mut updates: Vec<(PatchName, gix::ObjectId)> = Vec::new();
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for &patchname in &series {
+        let new_trailers = if let Some(new_trailers) = collected.get(patchname) {
+            new_trailers
+        } else {
+            continue;
+        };
+        let patch_commit = stack.get_patch_commit(patchname);
+        let message = patch_commit.message_ex();
+        let existing: HashSet<(String, String)> = patchedit::parse_trailers(&message.decode()?)
+            .into_iter()
+            .map(|(token, value)| (token.to_ascii_lowercase(), value))
+            .collect();
+        let new_trailers: Vec<(&str, &str)> = new_trailers
+            .iter()
+            .filter(|(token, value)| {
+                !existing.contains(&(token.to_ascii_lowercase(), value.clone()))
+            })
+            .map(|(token, value)| (*token, value.as_str()))
+            .collect();
+        if new_trailers.is_empty() {
+            continue;
+        }
+        for (token, value) in &new_trailers {
+            writeln!(stdout, "{patchname}: {token}: {value}")?;
+        }
+        if !matches.get_flag("dry-run") {
+            let message = patchedit::append_trailers(&repo, message, new_trailers)?;
+            let commit_id = repo.commit_ex(
+                &patch_commit.author_strict()?,
+                repo.get_committer()?,
+                &message,
+                patch_commit.tree_id()?.detach(),
+                patch_commit.parent_ids().map(|id| id.detach()),
+            )?;
+            updates.push((patchname.clone(), commit_id));
+        }
+    }
+    drop(stdout);
+
+    if updates.is_empty() {
+        return Ok(());
+    }
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(false)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            let first_applied = trans
+                .applied()
+                .iter()
+                .position(|pn| updates.iter().any(|(patchname, _)| patchname == pn));
+            let popped = if let Some(pos) = first_applied {
+                let to_pop = trans.applied()[pos..].to_vec();
+                let popped_extra = trans.pop_patches(|pn| to_pop.contains(pn))?;
+                assert!(popped_extra.is_empty());
+                to_pop
+            } else {
+                vec![]
+            };
+            for (patchname, commit_id) in &updates {
+                trans.update_patch(patchname, *commit_id)?;
+            }
+            trans.push_tree_patches(&popped)
+        })
+        .execute("email collect-trailers")?;
+
+    Ok(())
+}
+
+/// Read the emails from an mbox file or maildir.
+fn read_mails(repo: &gix::Repository, path: &Path, mails: &mut Vec<Mail>) -> Result<()> {
+    let stupid = repo.stupid();
+    let out_dir = tempfile::tempdir()?;
+    let num_mails = stupid.mailsplit(Some(path), out_dir.path(), false, true)?;
+    for i in 1..=num_mails {
+        let mail_path = out_dir.path().join(format!("{i:04}"));
+        let content = std::fs::read(&mail_path)?;
+        let headers = Headers::parse(content.lines());
+        let raw_subject = headers.get("Subject").unwrap_or_default();
+        let (mailinfo, body, _diff) =
+            stupid.mailinfo(Some(std::fs::File::open(&mail_path)?), false, false)?;
+
+        // `git mailinfo` decodes the subject and strips its "Re:" and "[PATCH]"
+        // prefixes.
+        let subject = mailinfo
+            .lines()
+            .find_map(|line| line.strip_prefix(b"Subject: "))
+            .map(|subject| subject.to_str_lossy().trim().to_string())
+            .unwrap_or_default();
+
+        let mut trailers = Vec::new();
+        for line in body.lines() {
+            let line = line.to_str_lossy();
+            if let Some((token, value)) = line.split_once(':') {
+                if let Some(&token) = REVIEW_TRAILERS
+                    .iter()
+                    .find(|trailer| trailer.eq_ignore_ascii_case(token.trim()))
+                {
+                    let value = value.trim();
+                    if !value.is_empty() {
+                        trailers.push((token, value.to_string()));
+                    }
+                }
+            }
+        }
+
+        mails.push(Mail {
+            message_id: headers.message_ids("Message-Id").into_iter().next(),
+            in_reply_to: headers.message_ids("In-Reply-To"),
+            references: headers.message_ids("References"),
+            is_reply: raw_subject
+                .get(..3)
+                .map_or(false, |prefix| prefix.eq_ignore_ascii_case("re:")),
+            is_cover: is_cover_subject(raw_subject),
+            subject,
+            trailers,
+        });
+    }
+    Ok(())
+}
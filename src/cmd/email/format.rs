@@ -2,16 +2,28 @@
 
 //! `stg email format` implementation.
 
-use anyhow::{anyhow, Result};
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
 use clap::Arg;
 
+use super::{
+    super::range_diff::{self, SeriesPatch},
+    history, recipients,
+};
 use crate::{
     argset,
     branchloc::BranchLocator,
     ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
+    templates,
 };
 
 pub(super) fn command() -> clap::Command {
@@ -83,6 +95,85 @@ pub(super) fn command() -> clap::Command {
                 .action(clap::ArgAction::Append)
                 .value_name("option"),
         )
+        .arg(
+            Arg::new("stack-range-diff")
+                .long("stack-range-diff")
+                .help("Show changes against a previous stack state in cover letter")
+                .long_help(
+                    "As a reviewer aid, insert the output of `stg range-diff` into the \
+                     cover letter, or as commentary of the lone patch of a \
+                     single-patch series, comparing the applied patches of <state> with \
+                     the patches being formatted. <state> may be a branch name or a \
+                     stack state committish as accepted by `stg range-diff`. Unlike \
+                     '--range-diff', patches are paired by name even when the previous \
+                     and current series are not related by a common base.",
+                )
+                .num_args(1)
+                .value_name("state")
+                .value_parser(clap::builder::NonEmptyStringValueParser::new())
+                .conflicts_with("range-diff"),
+        )
+        .arg(
+            Arg::new("reroll")
+                .long("reroll")
+                .help("Format the next version of a previously sent series")
+                .long_help(
+                    "Format the next version of a series previously sent with `stg \
+                     email send`. StGit records each series formatted or sent from the \
+                     branch, and the most recently sent version is used to fill in \
+                     options that would otherwise be worked out by hand:\n\
+                     \n  \
+                     - the first email is made a reply to the previous version's cover \
+                     letter, or to its first patch, unless '--in-reply-to' is given. \
+                     This requires the previous version to have been formatted with \
+                     threading enabled, since `git format-patch` only adds Message-Id \
+                     headers when threading;\n  \
+                     - threading is enabled unless '--thread', '--no-thread', or the \
+                     `format.thread` configuration is set;\n  \
+                     - the changes since the previous version are shown in the cover \
+                     letter, or as commentary of a lone patch, unless '--range-diff', \
+                     '--interdiff', or '--stack-range-diff' is given.\n\
+                     \n\
+                     The changes are shown as a range-diff, pairing patches by name as \
+                     with '--stack-range-diff', by default. Use '--reroll=interdiff' to \
+                     show an interdiff instead, or '--reroll=none' to not show the \
+                     changes.\n\
+                     \n\
+                     If no version of the series has been sent, the series is formatted \
+                     as usual.",
+                )
+                .value_name("changes")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("range-diff")
+                .value_parser(["range-diff", "interdiff", "none"]),
+        )
+        .arg(
+            Arg::new("cover-template")
+                .long("cover-template")
+                .help("Fill in the cover letter using template <file>")
+                .long_help(
+                    "Generate a cover letter and fill in its subject and description \
+                     by rendering template <file>. The first line of the rendered \
+                     template becomes the cover letter's subject and the remaining \
+                     lines become its description. Without this option, the \
+                     \"covermail.tmpl\" template is used, if found, whenever a cover \
+                     letter is generated.\n\
+                     \n\
+                     In addition to the \"branch\", \"version\", and \"total\" \
+                     variables, and the branch's \"branch_description\", the template \
+                     may loop over the \"patches\" list, where each patch has the \
+                     same variables as in `stg export` templates. As with `stg export` \
+                     templates, the template language is only used if the template \
+                     starts with a '{# ... #}' comment tag.",
+                )
+                .value_name("file")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(recipients::auto_cc_arg())
+        .arg(recipients::explain_arg())
+        .args(super::super::check::gate_args())
         .next_help_heading("Format Options")
         .args(format_options())
         .next_help_heading("Message Options")
@@ -143,7 +234,11 @@ fn format_options() -> Vec<Arg> {
                  '--reroll-count=4.4', or '--reroll-count=4rev2' are allowed), but the \
                  downside of using such a reroll-count is that the \
                  range-diff/interdiff with the previous version does not state exactly \
-                 which version the new iteration is compared against.",
+                 which version the new iteration is compared against.\n\
+                 \n\
+                 Once a version of the series has been sent with `stg email send`, \
+                 the reroll count defaults to the next version number. Give \
+                 '--reroll-count' to resend or renumber a version.",
             )
             .value_name("n")
             .num_args(1),
@@ -473,6 +568,11 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
         }
     }
 
+    let series_commits: Vec<(String, gix::ObjectId)> = patches
+        .iter()
+        .map(|patchname| (patchname.to_string(), stack.get_patch_commit_id(patchname)))
+        .collect();
+
     let mut format_args: Vec<(usize, String)> = Vec::new();
 
     // This dummy command is constructed with just the Args that are to be
@@ -515,16 +615,276 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
         format_args.extend(values.cloned());
     }
 
+    let base = stack
+        .get_patch_commit(&patches[0])
+        .parent_ids()
+        .next()
+        .unwrap()
+        .detach();
+
+    let mut version = matches
+        .get_one::<String>("reroll-count")
+        .and_then(|count| history::parse_reroll_count(count));
+    let mut old_series = None;
+
+    if let Some(state_spec) = argset::get_one_str(matches, "stack-range-diff") {
+        let old_state = range_diff::resolve_state(&repo, state_spec)?;
+        old_series = Some(range_diff::state_series(&old_state, false));
+    }
+
+    let records = history::read(&repo, stack.get_branch_name())?;
+    let last_sent = records.iter().find(|record| record.sent);
+
+    if version.is_none() {
+        if let Some(last_sent) = last_sent {
+            let next_version = last_sent.version + 1;
+            format_args.push(format!("--reroll-count={next_version}"));
+            version = Some(next_version);
+        }
+    }
+
+    if let Some(changes) = matches.get_one::<String>("reroll") {
+        if let Some(last_sent) = last_sent {
+            let is_given = |arg_id: &str| {
+                matches!(
+                    matches.value_source(arg_id),
+                    Some(clap::parser::ValueSource::CommandLine)
+                )
+            };
+            if !is_given("in-reply-to") {
+                if let Some(message_id) = last_sent.thread_message_id() {
+                    format_args.push(format!("--in-reply-to={message_id}"));
+                }
+            }
+            if !is_given("thread")
+                && !is_given("no-thread")
+                && repo.config_snapshot().string("format.thread").is_none()
+            {
+                format_args.push("--thread".to_string());
+            }
+            if !is_given("range-diff") && !is_given("interdiff") && old_series.is_none() {
+                match changes.as_str() {
+                    "range-diff" => old_series = Some(last_sent.series(&repo)?),
+                    "interdiff" => format_args.push(format!("--interdiff={}", last_sent.top())),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let given: Vec<String> = ["to", "cc"]
+        .into_iter()
+        .flat_map(|arg_id| matches.get_many::<String>(arg_id).into_iter().flatten())
+        .cloned()
+        .collect();
+    if let Some(found) = recipients::auto_cc(&repo, matches, &series_commits, &given)? {
+        if matches.get_flag("explain") {
+            return recipients::explain(&found);
+        }
+        for recipient in found {
+            format_args.push(format!("--cc={}", recipient.address));
+        }
+    }
+
+    super::super::check::gate(&repo, matches, "email-format", &series_commits, false)?;
+
+    let cover_template = if let Some(template_path) = matches.get_one::<PathBuf>("cover-template") {
+        if !format_args.iter().any(|arg| arg == "--cover-letter") {
+            format_args.push("--cover-letter".to_string());
+        }
+        Some(
+            std::fs::read_to_string(template_path)
+                .with_context(|| format!("reading {template_path:?}"))?,
+        )
+    } else {
+        templates::get_template(&repo, "covermail.tmpl")?
+    };
+
+    let last = stack.get_patch_commit_id(patches.last().unwrap());
+    format_args.push(format!("{base}..{last}"));
+
+    if format_args.iter().any(|arg| arg == "--stdout") {
+        if old_series.is_some() {
+            return Err(anyhow!(
+                "cannot show changes to the series when formatting to `--stdout`"
+            ));
+        }
+        if matches.contains_id("cover-template") {
+            return Err(anyhow!(
+                "cannot fill in the cover letter when formatting to `--stdout`"
+            ));
+        }
+        return repo.stupid().format_patch(format_args);
+    }
+
+    let quiet = matches.get_flag("quiet");
+    format_args.retain(|arg| arg != "--quiet");
+    let paths = repo.stupid().format_patch_files(format_args)?;
+
+    if let Some(cover_template) = cover_template.as_ref() {
+        if paths.len() == patches.len() + 1 {
+            fill_cover_letter(&repo, &stack, &patches, &paths[0], cover_template, version)?;
+        }
+    }
+
+    if let Some(old_series) = old_series {
+        let new_series: Vec<SeriesPatch> = patches
+            .iter()
+            .map(|patchname| SeriesPatch {
+                name: patchname.clone(),
+                commit: stack.get_patch_commit(patchname).clone(),
+            })
+            .collect();
+        let output = range_diff::range_diff(&repo, &old_series, &new_series, false)?;
+        let heading = range_diff_heading(version);
+        embed_range_diff(&paths, patches.len(), &heading, &output)?;
+    }
+
+    if !quiet {
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        for path in &paths {
+            writeln!(stdout, "{}", path.display())?;
+        }
+    }
+
+    let mut message_ids = HashMap::new();
+    let mut cover_message_id = None;
+    for path in &paths {
+        let email_file = history::EmailFile::read(path)?;
+        if let Some(commit_id) = email_file.commit_id {
+            message_ids.insert(commit_id, email_file.message_id);
+        } else {
+            cover_message_id = email_file.message_id;
+        }
+    }
+    let record = history::SeriesRecord {
+        version: version.unwrap_or(1),
+        sent: false,
+        base,
+        cover_message_id,
+        patches: patches
+            .iter()
+            .map(|patchname| {
+                let commit = stack.get_patch_commit_id(patchname);
+                history::PatchRecord {
+                    name: patchname.clone(),
+                    commit,
+                    message_id: message_ids.remove(&commit).flatten(),
+                }
+            })
+            .collect(),
+    };
+    history::append(&repo, stack.get_branch_name(), &record)
+}
+
+/// Fill in the cover letter's subject and description by rendering the template.
+///
+/// The first line of the rendered template replaces the "*** SUBJECT HERE ***"
+/// placeholder of the cover letter generated by `git format-patch` and the remaining
+/// lines replace the "*** BLURB HERE ***" placeholder.
+fn fill_cover_letter(
+    repo: &gix::Repository,
+    stack: &Stack,
+    patches: &[PatchName],
+    path: &Path,
+    template: &str,
+    version: Option<usize>,
+) -> Result<()> {
+    let branch_name = stack.get_branch_name();
+    let mut context = templates::Context::default();
+    context.insert("branch", branch_name);
+    if let Some(description) = repo
+        .config_snapshot()
+        .string(format!("branch.{branch_name}.description").as_str())
     {
-        let base = stack
-            .get_patch_commit(&patches[0])
-            .parent_ids()
-            .next()
-            .unwrap()
-            .detach();
-        let last = stack.get_patch_commit_id(patches.last().unwrap());
-        format_args.push(format!("{base}..{last}"));
+        context.insert("branch_description", description.as_bstr());
+    }
+    context.insert("version", version.unwrap_or(1));
+    context.insert("total", patches.len());
+    let mut patch_values = Vec::with_capacity(patches.len());
+    for (i, patchname) in patches.iter().enumerate() {
+        let mut vars = templates::patch_vars(
+            repo,
+            Some(patchname),
+            stack.get_patch_commit(patchname),
+            None,
+        )?;
+        vars.insert("index".to_string(), templates::Value::from(i + 1));
+        patch_values.push(templates::Value::from(vars));
+    }
+    context.insert("patches", patch_values);
+
+    let rendered =
+        templates::render(repo, template, &context).context("rendering cover letter template")?;
+    let (subject, blurb) = rendered
+        .split_once_str("\n")
+        .unwrap_or((rendered.as_slice(), &b""[..]));
+    let blurb = blurb.trim_start_with(|c| c == '\n').trim_end();
+
+    let content = std::fs::read(path)?
+        .replace("*** SUBJECT HERE ***", subject.trim())
+        .replace("*** BLURB HERE ***", blurb);
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Get heading line preceding an embedded range-diff.
+///
+/// Follows `git format-patch` in naming the previous version when the reroll count is
+/// known.
+fn range_diff_heading(version: Option<usize>) -> String {
+    match version {
+        Some(version) if version > 1 => format!("Range-diff against v{}:", version - 1),
+        _ => "Range-diff:".to_string(),
     }
+}
+
+/// Insert range-diff output into the cover letter or lone patch's commentary.
+fn embed_range_diff(
+    paths: &[PathBuf],
+    num_patches: usize,
+    heading: &str,
+    range_diff: &[u8],
+) -> Result<()> {
+    let (path, is_cover_letter) = if paths.len() == num_patches + 1 {
+        (&paths[0], true)
+    } else if num_patches == 1 && paths.len() == 1 {
+        (&paths[0], false)
+    } else {
+        return Err(anyhow!(
+            "showing changes to the series requires `--cover-letter` or a single patch"
+        ));
+    };
+
+    let content = std::fs::read(path)?;
+    let mut embedded = Vec::with_capacity(heading.len() + range_diff.len() + 2);
+    embedded.extend_from_slice(heading.as_bytes());
+    embedded.push(b'\n');
+    embedded.extend_from_slice(range_diff);
+
+    let insert_pos = if is_cover_letter {
+        // Insert before the signature, if any, otherwise at the end.
+        match content.rfind(b"\n-- \n") {
+            Some(pos) => pos + 1,
+            None => {
+                embedded.insert(0, b'\n');
+                content.len()
+            }
+        }
+    } else {
+        // Insert after the "---" line separating the message from the diff.
+        embedded.push(b'\n');
+        content
+            .find(b"\n---\n")
+            .map(|pos| pos + 5)
+            .ok_or_else(|| anyhow!("patch separator not found in `{}`", path.display()))?
+    };
 
-    repo.stupid().format_patch(format_args)
+    let mut updated = Vec::with_capacity(content.len() + embedded.len());
+    updated.extend_from_slice(&content[..insert_pos]);
+    updated.extend_from_slice(&embedded);
+    updated.extend_from_slice(&content[insert_pos..]);
+    std::fs::write(path, updated)?;
+    Ok(())
 }
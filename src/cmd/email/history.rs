@@ -0,0 +1,338 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! History of formatted and sent patch series.
+//!
+//! Each time a series is formatted with `stg email format` or sent with `stg email
+//! send`, a record of the series is committed to the branch's
+//! `refs/stgit/email/<branch>` reference. Each record commit's tree contains a single
+//! `series.json` blob with the series version, the patches' names and commit ids, and
+//! the Message-IDs of the emails.
+//!
+//! The first parent of a record commit is the previous record commit, if any. The last
+//! parent is the top commit of the recorded series, which keeps the recorded patch
+//! commits reachable so that later versions of the series may be compared with them.
+
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
+
+use super::super::range_diff::SeriesPatch;
+use crate::{
+    ext::{CommitOptions, RepositoryExtended},
+    patch::PatchName,
+    stack::email_history_refname_from_branch_name,
+    wrap::Message,
+};
+
+/// Name of the blob containing the series record.
+const SERIES_JSON: &str = "series.json";
+
+/// Record of a formatted or sent patch series.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct SeriesRecord {
+    /// Version of the series, i.e. the reroll count.
+    pub(super) version: usize,
+
+    /// Whether the series was sent, as opposed to only formatted.
+    pub(super) sent: bool,
+
+    /// Base commit of the series.
+    #[serde(with = "hex_oid")]
+    pub(super) base: gix::ObjectId,
+
+    /// Message-ID of the cover letter, if any.
+    pub(super) cover_message_id: Option<String>,
+
+    /// The patches of the series, in order.
+    pub(super) patches: Vec<PatchRecord>,
+}
+
+/// Record of one patch of a series.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct PatchRecord {
+    pub(super) name: PatchName,
+
+    #[serde(with = "hex_oid")]
+    pub(super) commit: gix::ObjectId,
+
+    pub(super) message_id: Option<String>,
+}
+
+impl SeriesRecord {
+    /// Get the commit id of the topmost patch of the series.
+    pub(super) fn top(&self) -> gix::ObjectId {
+        self.patches
+            .last()
+            .map_or(self.base, |patch_record| patch_record.commit)
+    }
+
+    /// Get the Message-ID that the next version of the series should reply to.
+    ///
+    /// This is the cover letter's Message-ID, or the first patch's Message-ID when
+    /// the series was sent without a cover letter.
+    pub(super) fn thread_message_id(&self) -> Option<&str> {
+        self.cover_message_id.as_deref().or_else(|| {
+            self.patches
+                .first()
+                .and_then(|patch_record| patch_record.message_id.as_deref())
+        })
+    }
+
+    /// Get the recorded patches as a series suitable for range-diffing.
+    pub(super) fn series<'repo>(
+        &self,
+        repo: &'repo gix::Repository,
+    ) -> Result<Vec<SeriesPatch<'repo>>> {
+        self.patches
+            .iter()
+            .map(|patch_record| {
+                Ok(SeriesPatch {
+                    name: patch_record.name.clone(),
+                    commit: std::rc::Rc::new(repo.find_commit(patch_record.commit)?),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Read the series history of a branch, most recent record first.
+pub(super) fn read(repo: &gix::Repository, branch_name: &str) -> Result<Vec<SeriesRecord>> {
+    let refname = email_history_refname_from_branch_name(branch_name);
+    let mut records = Vec::new();
+    let mut next_commit = if let Some(history_ref) = repo.try_find_reference(refname.as_str())? {
+        Some(
+            history_ref
+                .into_fully_peeled_id()?
+                .object()?
+                .try_into_commit()?,
+        )
+    } else {
+        None
+    };
+
+    while let Some(commit) = next_commit {
+        let series_json = commit
+            .tree()?
+            .lookup_entry_by_path(SERIES_JSON)?
+            .ok_or_else(|| anyhow!("`{SERIES_JSON}` not found in `{refname}` ({})", commit.id))?;
+        let series_json_blob = series_json.object()?.peel_to_kind(gix::objs::Kind::Blob)?;
+        let record: SeriesRecord = serde_json::from_slice(&series_json_blob.data)
+            .context("deserializing email series record")?;
+        records.push(record);
+
+        let parent_ids: Vec<gix::ObjectId> = commit.parent_ids().map(|id| id.detach()).collect();
+        next_commit = if parent_ids.len() > 1 {
+            Some(repo.find_commit(parent_ids[0])?)
+        } else {
+            None
+        };
+    }
+
+    Ok(records)
+}
+
+/// Append a record to the series history of a branch.
+pub(super) fn append(
+    repo: &gix::Repository,
+    branch_name: &str,
+    record: &SeriesRecord,
+) -> Result<()> {
+    let refname = email_history_refname_from_branch_name(branch_name);
+    let prev_id = if let Some(history_ref) = repo.try_find_reference(refname.as_str())? {
+        Some(history_ref.into_fully_peeled_id()?.detach())
+    } else {
+        None
+    };
+
+    let series_json_id = repo.write_blob(serde_json::to_string_pretty(record)?.as_bytes())?;
+    let tree = gix::objs::Tree {
+        entries: vec![gix::objs::tree::Entry {
+            mode: gix::objs::tree::EntryMode::Blob,
+            filename: SERIES_JSON.into(),
+            oid: series_json_id.detach(),
+        }],
+    };
+    let tree_id = repo.write_object(tree)?.detach();
+
+    let message = format!(
+        "{} v{}",
+        if record.sent { "send" } else { "format" },
+        record.version
+    );
+    let message = Message::from(message.as_str());
+    let commit_id = repo.commit_with_options(
+        repo.get_author()?,
+        repo.get_committer()?,
+        &message,
+        tree_id,
+        prev_id.into_iter().chain(std::iter::once(record.top())),
+        &CommitOptions {
+            commit_encoding: None,
+            gpgsign: false,
+        },
+    )?;
+
+    repo.reference(
+        refname.as_str(),
+        commit_id,
+        gix::refs::transaction::PreviousValue::Any,
+        message.raw_bytes(),
+    )?;
+
+    Ok(())
+}
+
+/// Information from an email file generated by `git format-patch`.
+pub(super) struct EmailFile {
+    /// Commit id from the mbox "From" line. This is `None` for a cover letter.
+    ///
+    /// `git format-patch` puts the series' top commit id in the cover letter's "From"
+    /// line, so cover letters are instead recognized by their "0/N" subject prefix.
+    pub(super) commit_id: Option<gix::ObjectId>,
+
+    /// The value of the Message-Id header, without angle brackets.
+    pub(super) message_id: Option<String>,
+
+    /// The unfolded value of the Subject header.
+    pub(super) subject: Option<String>,
+}
+
+impl EmailFile {
+    /// Read the mbox "From" line and headers of an email file.
+    pub(super) fn read(path: &Path) -> Result<Self> {
+        let content =
+            std::fs::read(path).with_context(|| format!("reading `{}`", path.display()))?;
+        let mut lines = content.lines();
+
+        let commit_id = lines
+            .next()
+            .and_then(|line| line.strip_prefix(b"From "))
+            .and_then(|rest| rest.split_str(" ").next())
+            .and_then(|hex| gix::ObjectId::from_hex(hex).ok())
+            .ok_or_else(|| anyhow!("`{}` is not a formatted patch email", path.display()))?;
+        let headers = Headers::parse(lines);
+        let subject = headers.get("Subject").map(ToString::to_string);
+
+        let commit_id = if commit_id.is_null() || subject.as_deref().map_or(false, is_cover_subject)
+        {
+            None
+        } else {
+            Some(commit_id)
+        };
+
+        Ok(Self {
+            commit_id,
+            message_id: headers.message_ids("Message-Id").into_iter().next(),
+            subject,
+        })
+    }
+
+    /// Get the series version from a subject prefix such as "[PATCH v2 1/3]".
+    pub(super) fn subject_version(&self) -> Option<usize> {
+        let subject = self.subject.as_deref()?;
+        let prefix = subject.strip_prefix('[')?.split(']').next()?;
+        prefix.split_whitespace().find_map(|word| {
+            word.strip_prefix('v')
+                .and_then(|count| count.parse::<usize>().ok())
+        })
+    }
+}
+
+/// Unfolded email headers.
+pub(super) struct Headers(Vec<(String, String)>);
+
+impl Headers {
+    /// Parse the headers from the lines preceding the first empty line.
+    ///
+    /// A leading mbox "From" line is skipped.
+    pub(super) fn parse<'a>(lines: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for (i, line) in lines.into_iter().enumerate() {
+            if line.is_empty() {
+                break;
+            } else if i == 0 && line.starts_with(b"From ") {
+                continue;
+            } else if line.starts_with(b" ") || line.starts_with(b"\t") {
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim().to_str_lossy().as_ref());
+                }
+            } else if let Some((name, value)) = line.split_once_str(":") {
+                headers.push((
+                    name.to_str_lossy().to_string(),
+                    value.trim().to_str_lossy().to_string(),
+                ));
+            }
+        }
+        Self(headers)
+    }
+
+    /// Get the value of the first header with the given name, ignoring case.
+    pub(super) fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Get the message ids, without angle brackets, from a header such as Message-Id,
+    /// In-Reply-To, or References.
+    pub(super) fn message_ids(&self, name: &str) -> Vec<String> {
+        let mut message_ids = Vec::new();
+        if let Some(value) = self.get(name) {
+            let mut rest = value;
+            while let Some(start) = rest.find('<') {
+                if let Some(len) = rest[start..].find('>') {
+                    message_ids.push(rest[start + 1..start + len].to_string());
+                    rest = &rest[start + len + 1..];
+                } else {
+                    break;
+                }
+            }
+        }
+        message_ids
+    }
+}
+
+/// Determine whether a subject has a cover letter prefix such as "[PATCH v2 0/3]".
+pub(super) fn is_cover_subject(subject: &str) -> bool {
+    subject
+        .strip_prefix('[')
+        .and_then(|rest| rest.split(']').next())
+        .map_or(false, |prefix| {
+            prefix.split_whitespace().any(|word| {
+                word.split_once('/').map_or(false, |(index, _)| {
+                    !index.is_empty() && index.chars().all(|c| c == '0')
+                })
+            })
+        })
+}
+
+/// Parse the leading integer of a reroll count such as "3" or "3rev2".
+pub(super) fn parse_reroll_count(reroll_count: &str) -> Option<usize> {
+    let end = reroll_count
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(reroll_count.len());
+    reroll_count[..end].parse().ok()
+}
+
+/// Serialize object ids as hex strings.
+mod hex_oid {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        oid: &gix::ObjectId,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(oid)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<gix::ObjectId, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        gix::ObjectId::from_hex(hex.as_bytes())
+            .map_err(|_| D::Error::custom(format!("invalid object id `{hex}`")))
+    }
+}
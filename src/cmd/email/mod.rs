@@ -2,7 +2,11 @@
 
 //! `stg email` implementation.
 
+mod collect_trailers;
 mod format;
+mod history;
+mod native;
+mod recipients;
 mod send;
 
 use anyhow::Result;
@@ -24,7 +28,9 @@ fn make() -> clap::Command {
              with an optional cover letter using `stg email format`. Then, after \
              checking the email files' contents, sending the emails using `stg email \
              send`. This workflow may be condensed to one step by specifying patch \
-             names to `stg email send` instead of email files.\n\
+             names to `stg email send` instead of email files. Review trailers from \
+             replies to the sent emails may then be added to the patches with `stg \
+             email collect-trailers`.\n\
              \n\
              The `format` and `send` subcommands are thin wrappers over `git \
              format-patch` and `git send-email`, respectively. Refer to the \
@@ -32,12 +38,14 @@ fn make() -> clap::Command {
              configuration and options.",
         )
         .subcommand_required(true)
+        .subcommand(collect_trailers::command())
         .subcommand(format::command())
         .subcommand(send::command())
 }
 
 fn run(matches: &clap::ArgMatches) -> Result<()> {
     match matches.subcommand() {
+        Some(("collect-trailers", sub_matches)) => collect_trailers::dispatch(sub_matches),
         Some(("format", sub_matches)) => format::dispatch(sub_matches),
         Some(("send", sub_matches)) => send::dispatch(sub_matches),
         _ => panic!("valid subcommand is expected"),
@@ -0,0 +1,637 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Send emails without `git send-email`.
+//!
+//! Emails generated by `git format-patch` are prepared for sending in the same way as
+//! `git send-email` prepares them: the sender, recipients, date, and threading headers
+//! are set. The prepared emails are then either piped to a sendmail-compatible command
+//! or, when StGit is built with the `email-smtp` feature, sent using the built-in SMTP
+//! client.
+
+use std::{io::Write, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
+
+use super::history::{is_cover_subject, EmailFile, Headers};
+use crate::{ext::RepositoryExtended, stupid::Stupid};
+
+/// Options for addressing and threading emails.
+pub(super) struct SendOptions {
+    pub(super) from: Option<String>,
+    pub(super) to: Vec<String>,
+    pub(super) cc: Vec<String>,
+    pub(super) bcc: Vec<String>,
+    pub(super) reply_to: Option<String>,
+    pub(super) in_reply_to: Option<String>,
+    pub(super) thread: bool,
+    pub(super) dry_run: bool,
+    pub(super) quiet: bool,
+}
+
+/// How emails are delivered.
+enum Delivery {
+    /// Pipe to a sendmail-compatible shell command.
+    Sendmail(String),
+
+    /// Send to an SMTP server.
+    Smtp(SmtpConfig),
+}
+
+/// Encryption used for the SMTP connection.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Encryption {
+    None,
+    StartTls,
+    Tls,
+}
+
+/// SMTP server configuration from `sendemail.*` configuration values.
+#[cfg_attr(not(feature = "email-smtp"), allow(dead_code))]
+struct SmtpConfig {
+    server: String,
+    port: u16,
+    encryption: Encryption,
+    user: Option<String>,
+    password: Option<String>,
+    domain: Option<String>,
+}
+
+impl Delivery {
+    fn from_config(config: &gix::config::Snapshot) -> Result<Self> {
+        let get_string = |key: &str| {
+            config
+                .string(key)
+                .map(|value| value.to_str_lossy().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        if let Some(sendmail_cmd) = get_string("sendemail.sendmailCmd") {
+            return Ok(Self::Sendmail(sendmail_cmd));
+        }
+
+        let server = get_string("sendemail.smtpServer").unwrap_or_else(|| "localhost".into());
+        if server.starts_with('/') {
+            return Ok(Self::Sendmail(format!(
+                "'{}'",
+                server.replace('\'', "'\\''")
+            )));
+        }
+
+        let encryption = match get_string("sendemail.smtpEncryption")
+            .map(|value| value.to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("none") => Encryption::None,
+            Some("tls") => Encryption::StartTls,
+            Some("ssl") => Encryption::Tls,
+            Some(value) => {
+                return Err(anyhow!(
+                    "invalid `sendemail.smtpEncryption` value `{value}`"
+                ))
+            }
+        };
+
+        let port = if let Some(port) = config.integer("sendemail.smtpServerPort") {
+            u16::try_from(port)
+                .map_err(|_| anyhow!("invalid `sendemail.smtpServerPort` value `{port}`"))?
+        } else if encryption == Encryption::Tls {
+            465
+        } else {
+            25
+        };
+
+        Ok(Self::Smtp(SmtpConfig {
+            server,
+            port,
+            encryption,
+            user: get_string("sendemail.smtpUser"),
+            password: get_string("sendemail.smtpPass"),
+            domain: get_string("sendemail.smtpDomain"),
+        }))
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Sendmail(sendmail_cmd) => format!("Sendmail: {sendmail_cmd}"),
+            Self::Smtp(smtp) => format!("Server: {}:{}", smtp.server, smtp.port),
+        }
+    }
+}
+
+/// An email prepared for sending.
+struct PreparedEmail {
+    envelope_sender: String,
+    recipients: Vec<String>,
+    content: Vec<u8>,
+    info: EmailFile,
+}
+
+/// Send email files.
+///
+/// Returns information about each sent email, including the Message-ID it was sent
+/// with.
+pub(super) fn send(
+    repo: &gix::Repository,
+    paths: &[PathBuf],
+    opts: &SendOptions,
+) -> Result<Vec<EmailFile>> {
+    let config = repo.config_snapshot();
+    let delivery = Delivery::from_config(&config)?;
+
+    let sender = if let Some(from) = opts.from.as_ref() {
+        from.clone()
+    } else if let Some(from) = config.string("sendemail.from") {
+        from.to_str_lossy().to_string()
+    } else {
+        let committer = repo.get_committer()?;
+        format!("{} <{}>", committer.name, committer.email)
+    };
+    let envelope_sender = config
+        .string("sendemail.envelopeSender")
+        .map(|value| value.to_str_lossy().to_string())
+        .filter(|value| value != "auto")
+        .unwrap_or_else(|| bare_address(&sender).to_string());
+
+    let now = gix::actor::Time::now_local_or_utc();
+    let mut first_message_id: Option<String> = None;
+    let mut emails = Vec::with_capacity(paths.len());
+    for (i, path) in paths.iter().enumerate() {
+        let content =
+            std::fs::read(path).with_context(|| format!("reading `{}`", path.display()))?;
+        let date = gix::actor::Time::new(
+            now.seconds_since_unix_epoch + u32::try_from(i)?,
+            now.offset_in_seconds,
+        );
+        let default_message_id = format!(
+            "{}.{}-{}-{}",
+            now.format(time::macros::format_description!(
+                "[year][month][day][hour][minute][second]"
+            )),
+            std::process::id(),
+            i + 1,
+            envelope_sender,
+        );
+        let email = prepare_email(
+            &content,
+            &sender,
+            &envelope_sender,
+            opts,
+            &date.format(gix::date::time::format::RFC2822),
+            &default_message_id,
+            first_message_id.as_deref(),
+        )
+        .with_context(|| format!("preparing `{}`", path.display()))?;
+        if first_message_id.is_none() {
+            first_message_id = email.info.message_id.clone();
+        }
+        emails.push(email);
+    }
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    let mut sent = Vec::with_capacity(emails.len());
+    for email in emails {
+        if !opts.dry_run {
+            match &delivery {
+                Delivery::Sendmail(sendmail_cmd) => {
+                    repo.stupid()
+                        .sendmail(sendmail_cmd, &email.recipients, &email.content)?
+                }
+                Delivery::Smtp(smtp) => send_smtp(repo, smtp, &email)?,
+            }
+        }
+
+        if opts.quiet {
+            let subject = email.info.subject.as_deref().unwrap_or_default();
+            if opts.dry_run {
+                writeln!(stdout, "Dry-Sent {subject}")?;
+            } else {
+                writeln!(stdout, "Sent {subject}")?;
+            }
+        } else {
+            if opts.dry_run {
+                writeln!(stdout, "Dry-OK. Log says:")?;
+            } else {
+                writeln!(stdout, "OK. Log says:")?;
+            }
+            writeln!(stdout, "{}", delivery.describe())?;
+            writeln!(stdout, "MAIL FROM:<{}>", email.envelope_sender)?;
+            for recipient in &email.recipients {
+                writeln!(stdout, "RCPT TO:<{recipient}>")?;
+            }
+            let headers = Headers::parse(email.content.lines());
+            for name in [
+                "From",
+                "To",
+                "Cc",
+                "Subject",
+                "Date",
+                "Message-Id",
+                "In-Reply-To",
+                "References",
+            ] {
+                if let Some(value) = headers.get(name) {
+                    writeln!(stdout, "{name}: {value}")?;
+                }
+            }
+            writeln!(stdout)?;
+            writeln!(stdout, "Result: OK")?;
+        }
+
+        sent.push(email.info);
+    }
+
+    Ok(sent)
+}
+
+/// Prepare an email generated by `git format-patch` for sending.
+fn prepare_email(
+    content: &[u8],
+    sender: &str,
+    envelope_sender: &str,
+    opts: &SendOptions,
+    date: &str,
+    default_message_id: &str,
+    first_message_id: Option<&str>,
+) -> Result<PreparedEmail> {
+    let (header_block, body) = if let Some(pos) = content.find(b"\n\n") {
+        (&content[..pos + 1], &content[pos + 2..])
+    } else {
+        (content, &b""[..])
+    };
+
+    let mut commit_id = None;
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for (i, line) in header_block.lines().enumerate() {
+        if i == 0 && line.starts_with(b"From ") {
+            commit_id = line[5..]
+                .split_str(" ")
+                .next()
+                .and_then(|hex| gix::ObjectId::from_hex(hex).ok())
+                .filter(|commit_id| !commit_id.is_null());
+        } else if line.starts_with(b" ") || line.starts_with(b"\t") {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push_str(&line.to_str_lossy());
+            }
+        } else if let Some((name, value)) = line.split_once_str(":") {
+            headers.push((
+                name.to_str_lossy().to_string(),
+                value.trim().to_str_lossy().to_string(),
+            ));
+        } else {
+            return Err(anyhow!("invalid email header `{}`", line.to_str_lossy()));
+        }
+    }
+
+    let get = |headers: &[(String, String)], name: &str| {
+        headers
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone())
+    };
+    let remove = |headers: &mut Vec<(String, String)>, name: &str| {
+        headers.retain(|(header_name, _)| !header_name.eq_ignore_ascii_case(name));
+    };
+
+    // As with `git send-email`, the original author is retained in the body when the
+    // sender is someone else.
+    let author = get(&headers, "From").map(|author| decode_header(&author));
+    let body_prefix = match author.as_deref() {
+        Some(author) if author != sender => format!("From: {author}\n\n"),
+        _ => String::new(),
+    };
+    remove(&mut headers, "From");
+    headers.insert(0, ("From".into(), encode_address(sender)));
+    if !body_prefix.is_ascii() && get(&headers, "Content-Type").is_none() {
+        if get(&headers, "MIME-Version").is_none() {
+            headers.push(("MIME-Version".into(), "1.0".into()));
+        }
+        headers.push(("Content-Type".into(), "text/plain; charset=UTF-8".into()));
+        headers.push(("Content-Transfer-Encoding".into(), "8bit".into()));
+    }
+
+    remove(&mut headers, "Date");
+    headers.push(("Date".into(), date.into()));
+
+    let mut to = split_addresses(&get(&headers, "To").unwrap_or_default());
+    to.extend(opts.to.iter().cloned());
+    let mut cc = split_addresses(&get(&headers, "Cc").unwrap_or_default());
+    cc.extend(opts.cc.iter().cloned());
+    remove(&mut headers, "To");
+    remove(&mut headers, "Cc");
+    if !to.is_empty() {
+        headers.push(("To".into(), to.join(", ")));
+    }
+    if !cc.is_empty() {
+        headers.push(("Cc".into(), cc.join(", ")));
+    }
+
+    if let Some(reply_to) = opts.reply_to.as_ref() {
+        remove(&mut headers, "Reply-To");
+        headers.push(("Reply-To".into(), reply_to.clone()));
+    }
+
+    let message_id = if let Some(message_id) = get(&headers, "Message-Id") {
+        message_id
+    } else {
+        let message_id = format!("<{default_message_id}>");
+        headers.push(("Message-Id".into(), message_id.clone()));
+        message_id
+    };
+
+    if get(&headers, "In-Reply-To").is_none() {
+        let in_reply_to = opts.in_reply_to.as_deref().map(angle_bracketed);
+        let mut references: Vec<String> = in_reply_to.iter().cloned().collect();
+        let parent = match first_message_id {
+            Some(first_message_id) if opts.thread => {
+                let first_message_id = angle_bracketed(first_message_id);
+                references.push(first_message_id.clone());
+                Some(first_message_id)
+            }
+            _ => in_reply_to,
+        };
+        if let Some(parent) = parent {
+            headers.push(("In-Reply-To".into(), parent));
+            headers.push(("References".into(), references.join(" ")));
+        }
+    }
+
+    let mut recipients: Vec<String> = Vec::new();
+    for address in to.iter().chain(cc.iter()).chain(opts.bcc.iter()) {
+        let address = bare_address(address).to_string();
+        if !address.is_empty() && !recipients.contains(&address) {
+            recipients.push(address);
+        }
+    }
+    if recipients.is_empty() {
+        return Err(anyhow!("no recipients; use `--to`, `--cc`, or `--bcc`"));
+    }
+
+    let mut prepared = Vec::with_capacity(content.len() + 256);
+    for (name, value) in &headers {
+        writeln!(prepared, "{name}: {value}")?;
+    }
+    prepared.push(b'\n');
+    prepared.extend_from_slice(body_prefix.as_bytes());
+    prepared.extend_from_slice(body);
+
+    let subject = get(&headers, "Subject");
+    // The cover letter's "From" line has the top patch's commit id.
+    let commit_id = commit_id.filter(|_| !subject.as_deref().map_or(false, is_cover_subject));
+    Ok(PreparedEmail {
+        envelope_sender: envelope_sender.to_string(),
+        recipients,
+        content: prepared,
+        info: EmailFile {
+            commit_id,
+            message_id: Some(
+                message_id
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string(),
+            ),
+            subject,
+        },
+    })
+}
+
+/// Split a comma-separated list of addresses, respecting quoted display names.
+pub(super) fn split_addresses(value: &str) -> Vec<String> {
+    let mut addresses = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut in_angle = false;
+    for c in value.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_angle = true,
+            '>' if !in_quotes => in_angle = false,
+            ',' if !in_quotes && !in_angle => {
+                if !current.trim().is_empty() {
+                    addresses.push(current.trim().to_string());
+                }
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        addresses.push(current.trim().to_string());
+    }
+    addresses
+}
+
+/// Get the bare email address from an address such as "Name <name@example.com>".
+pub(super) fn bare_address(address: &str) -> &str {
+    if let (Some(start), Some(end)) = (address.rfind('<'), address.rfind('>')) {
+        if start < end {
+            return address[start + 1..end].trim();
+        }
+    }
+    address.trim()
+}
+
+/// Ensure a message id is surrounded by angle brackets.
+fn angle_bracketed(message_id: &str) -> String {
+    let message_id = message_id.trim();
+    if message_id.starts_with('<') {
+        message_id.to_string()
+    } else {
+        format!("<{message_id}>")
+    }
+}
+
+/// Encode a non-ASCII display name in an address as an RFC 2047 encoded-word.
+///
+/// The encoding matches that used by `git format-patch`.
+fn encode_address(address: &str) -> String {
+    let (name, addr_spec) = if let Some(pos) = address.rfind('<') {
+        (address[..pos].trim().trim_matches('"'), &address[pos..])
+    } else {
+        return address.to_string();
+    };
+    if name.is_ascii() {
+        return address.to_string();
+    }
+    let mut encoded = String::from("=?UTF-8?q?");
+    for b in name.bytes() {
+        if b.is_ascii_alphanumeric() || b"!*+-/".contains(&b) {
+            encoded.push(char::from(b));
+        } else {
+            encoded.push_str(&format!("={b:02X}"));
+        }
+    }
+    encoded.push_str("?= ");
+    encoded.push_str(addr_spec);
+    encoded
+}
+
+/// Decode RFC 2047 encoded-words in a header value.
+///
+/// Whitespace between adjacent encoded-words is dropped. Malformed encoded-words and
+/// words in unknown charsets are kept as-is.
+fn decode_header(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    let mut after_encoded_word = false;
+    while let Some(start) = rest.find("=?") {
+        if let Some((word, len)) = decode_encoded_word(&rest[start..]) {
+            let between = &rest[..start];
+            if !after_encoded_word || !between.trim().is_empty() {
+                decoded.push_str(between);
+            }
+            decoded.push_str(&word);
+            rest = &rest[start + len..];
+            after_encoded_word = true;
+        } else {
+            decoded.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            after_encoded_word = false;
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Decode the encoded-word at the start of `s`.
+///
+/// Returns the decoded text and the length of the encoded-word.
+fn decode_encoded_word(s: &str) -> Option<(String, usize)> {
+    let inner = s.strip_prefix("=?")?;
+    let (charset, inner) = inner.split_once('?')?;
+    let (encoding, inner) = inner.split_once('?')?;
+    let end = inner.find("?=")?;
+    let text = &inner[..end];
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+    let bytes = match encoding {
+        "q" | "Q" => decode_q(text)?,
+        "b" | "B" => decode_base64(text)?,
+        _ => return None,
+    };
+    // The charset may have an RFC 2231 language suffix.
+    let charset = charset.split('*').next().unwrap_or_default();
+    let encoding = encoding_rs::Encoding::for_label(charset.as_bytes())?;
+    let (word, _) = encoding.decode_without_bom_handling(&bytes);
+    Some((word.into_owned(), s.len() - inner[end + 2..].len()))
+}
+
+fn decode_q(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut iter = text.bytes();
+    while let Some(b) = iter.next() {
+        match b {
+            b'_' => bytes.push(b' '),
+            b'=' => {
+                let hex = [iter.next()?, iter.next()?];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+            }
+            b => bytes.push(b),
+        }
+    }
+    Some(bytes)
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let sextet = |b: u8| -> Option<u32> {
+        match b {
+            b'A'..=b'Z' => Some(u32::from(b - b'A')),
+            b'a'..=b'z' => Some(u32::from(b - b'a') + 26),
+            b'0'..=b'9' => Some(u32::from(b - b'0') + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    };
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        let mut acc = 0u32;
+        for (i, &b) in chunk.iter().enumerate() {
+            acc |= sextet(b)? << (18 - 6 * i);
+        }
+        let acc = acc.to_be_bytes();
+        match chunk.len() {
+            4 => bytes.extend_from_slice(&acc[1..4]),
+            3 => bytes.extend_from_slice(&acc[1..3]),
+            2 => bytes.push(acc[1]),
+            _ => return None,
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(feature = "email-smtp")]
+fn send_smtp(repo: &gix::Repository, smtp: &SmtpConfig, email: &PreparedEmail) -> Result<()> {
+    use lettre::{
+        address::Envelope,
+        transport::smtp::{
+            authentication::Credentials,
+            client::{Tls, TlsParameters},
+            extension::ClientId,
+        },
+        Address, SmtpTransport, Transport,
+    };
+
+    let parse_address = |address: &str| {
+        address
+            .parse::<Address>()
+            .map_err(|_| anyhow!("invalid email address `{address}`"))
+    };
+    let envelope = Envelope::new(
+        Some(parse_address(&email.envelope_sender)?),
+        email
+            .recipients
+            .iter()
+            .map(|recipient| parse_address(recipient))
+            .collect::<Result<Vec<_>>>()?,
+    )?;
+
+    let tls = match smtp.encryption {
+        Encryption::None => Tls::None,
+        Encryption::StartTls => Tls::Required(TlsParameters::new(smtp.server.clone())?),
+        Encryption::Tls => Tls::Wrapper(TlsParameters::new(smtp.server.clone())?),
+    };
+    let mut builder = SmtpTransport::builder_dangerous(smtp.server.as_str())
+        .port(smtp.port)
+        .tls(tls);
+    if let Some(domain) = smtp.domain.as_ref() {
+        builder = builder.hello_name(ClientId::Domain(domain.clone()));
+    }
+    if let Some(user) = smtp.user.as_ref() {
+        let password = if let Some(password) = smtp.password.as_ref() {
+            password.clone()
+        } else {
+            repo.stupid()
+                .credential_fill(&format!("smtp://{user}@{}:{}", smtp.server, smtp.port))?
+                .ok_or_else(|| anyhow!("no SMTP password for `{user}`"))?
+        };
+        builder = builder.credentials(Credentials::new(user.clone(), password));
+    }
+
+    // SMTP requires CRLF line endings.
+    let mut content = Vec::with_capacity(email.content.len() + email.content.len() / 32);
+    for line in email.content.lines_with_terminator() {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        content.extend_from_slice(line);
+        content.extend_from_slice(b"\r\n");
+    }
+
+    builder
+        .build()
+        .send_raw(&envelope, &content)
+        .with_context(|| format!("sending to {}:{}", smtp.server, smtp.port))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "email-smtp"))]
+fn send_smtp(_repo: &gix::Repository, _smtp: &SmtpConfig, _email: &PreparedEmail) -> Result<()> {
+    Err(anyhow!(
+        "StGit not built with support for SMTP; set `sendemail.sendmailCmd` instead"
+    ))
+}
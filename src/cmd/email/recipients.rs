@@ -0,0 +1,515 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Automatic discovery of email recipients.
+//!
+//! Recipients for a patch series may be found in the trailers of the patches'
+//! messages, in a MAINTAINERS file that maps paths to the people responsible for them,
+//! and from the authors of the existing lines that the patches change.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+use clap::Arg;
+
+use super::native::{bare_address, split_addresses};
+use crate::{
+    ext::{CommitExtended, RepositoryExtended},
+    patch::patchedit,
+    stupid::Stupid,
+};
+
+/// Trailers whose values are added as recipients.
+const TRAILERS: [&str; 7] = [
+    "Signed-off-by",
+    "Acked-by",
+    "Reviewed-by",
+    "Tested-by",
+    "Reported-by",
+    "Suggested-by",
+    "Cc",
+];
+
+/// Default path of the maintainers file, relative to the top of the tree.
+const DEFAULT_MAINTAINERS_PATH: &str = "MAINTAINERS";
+
+pub(super) fn auto_cc_arg() -> Arg {
+    Arg::new("auto-cc")
+        .long("auto-cc")
+        .help("Cc people found in trailers, MAINTAINERS, or blame")
+        .long_help(
+            "Add Cc recipients found from the patches. The <sources> to search are \
+             given as a comma-separated list of:\n\
+             \n  \
+             - 'trailers': the addresses in each patch's Signed-off-by, Acked-by, \
+             Reviewed-by, Tested-by, Reported-by, Suggested-by, and Cc trailers;\n  \
+             - 'maintainers': the maintainers, reviewers, and mailing lists of the \
+             files changed by each patch, as listed in the MAINTAINERS file at the \
+             top of the tree;\n  \
+             - 'blame': the authors of the existing lines that each patch changes or \
+             removes.\n\
+             \n\
+             Without a value, 'trailers' and 'maintainers' are searched. Each address \
+             is added once for the whole series, and addresses given with '--to' or \
+             '--cc' are not repeated. Use '--explain' to see why each address is \
+             added.\n\
+             \n\
+             The MAINTAINERS file follows the format used by the Linux kernel: \
+             sections separated by blank lines, each starting with a title line \
+             followed by 'M:' (maintainer), 'R:' (reviewer), and 'L:' (mailing list) \
+             lines with addresses and 'F:' and 'X:' lines with path patterns to \
+             include and exclude. A pattern ending with '/' or without wildcards \
+             matches everything below that directory. The '*' and '?' wildcards do \
+             not match '/'. The file is read from the tree of the last patch being \
+             sent. A different path may be set with the `stgit.email.maintainers` \
+             configuration option.",
+        )
+        .value_name("sources")
+        .num_args(0..)
+        .require_equals(true)
+        .value_delimiter(',')
+        .default_missing_values(["trailers", "maintainers"])
+        .value_parser(["trailers", "maintainers", "blame"])
+        .action(clap::ArgAction::Append)
+}
+
+pub(super) fn explain_arg() -> Arg {
+    Arg::new("explain")
+        .long("explain")
+        .help("Show why each '--auto-cc' recipient is added, then stop")
+        .long_help(
+            "Show the recipients found with '--auto-cc', each followed by the \
+             reasons it was found, without formatting or sending any emails.",
+        )
+        .requires("auto-cc")
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// A recipient found from the patches.
+pub(super) struct Recipient {
+    /// The address, possibly including a display name.
+    pub(super) address: String,
+
+    /// Why the recipient was added.
+    pub(super) reasons: Vec<String>,
+}
+
+/// Recipients found so far, excluding addresses that are already recipients.
+struct Recipients {
+    excluded: Vec<String>,
+    found: Vec<Recipient>,
+}
+
+impl Recipients {
+    fn add(&mut self, address: &str, reason: String) {
+        let address = address.trim();
+        let bare = bare_address(address);
+        if !bare.contains('@')
+            || self
+                .excluded
+                .iter()
+                .any(|excluded| excluded.eq_ignore_ascii_case(bare))
+        {
+            return;
+        }
+        if let Some(recipient) = self
+            .found
+            .iter_mut()
+            .find(|recipient| bare_address(&recipient.address).eq_ignore_ascii_case(bare))
+        {
+            if !recipient.reasons.contains(&reason) {
+                recipient.reasons.push(reason);
+            }
+        } else {
+            self.found.push(Recipient {
+                address: address.to_string(),
+                reasons: vec![reason],
+            });
+        }
+    }
+}
+
+/// Find the recipients requested with `--auto-cc`.
+///
+/// The patches are given as pairs of a name used in explanations and a commit id.
+/// Addresses in `given` are already recipients and are not returned. Returns `None`
+/// when `--auto-cc` is not used.
+pub(super) fn auto_cc(
+    repo: &gix::Repository,
+    matches: &clap::ArgMatches,
+    patches: &[(String, gix::ObjectId)],
+    given: &[String],
+) -> Result<Option<Vec<Recipient>>> {
+    let sources: Vec<&str> = if let Some(sources) = matches.get_many::<String>("auto-cc") {
+        sources.map(String::as_str).collect()
+    } else {
+        return Ok(None);
+    };
+
+    let mut recipients = Recipients {
+        excluded: given
+            .iter()
+            .flat_map(|addresses| split_addresses(addresses))
+            .map(|address| bare_address(&address).to_string())
+            .collect(),
+        found: Vec::new(),
+    };
+
+    let commits = patches
+        .iter()
+        .map(|(name, commit_id)| Ok((name.as_str(), repo.find_commit(*commit_id)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    if sources.contains(&"trailers") {
+        for (name, commit) in &commits {
+            let message = commit.message_ex();
+            for (token, value) in patchedit::parse_trailers(&message.decode()?) {
+                if let Some(token) = TRAILERS
+                    .iter()
+                    .find(|trailer| trailer.eq_ignore_ascii_case(&token))
+                {
+                    recipients.add(&value, format!("{token} trailer in {name}"));
+                }
+            }
+        }
+    }
+
+    if sources.contains(&"maintainers") {
+        if let Some((_, top)) = commits.last() {
+            let sections = read_maintainers(repo, top)?;
+            for (name, commit) in &commits {
+                let parent_tree_id = repo
+                    .find_commit(commit.parent_ids().next().unwrap().detach())?
+                    .tree_id()?
+                    .detach();
+                let diff_files = repo
+                    .stupid()
+                    .diff_tree_files(parent_tree_id, commit.tree_id()?.detach())?;
+                for path in diff_files.iter() {
+                    let path = path.to_string_lossy();
+                    for section in sections.iter().filter(|section| section.matches(&path)) {
+                        for (role, address) in &section.people {
+                            let reason = if section.title.is_empty() {
+                                format!("{role} of {path} changed in {name}")
+                            } else {
+                                format!("{role} of {path} changed in {name} ({})", section.title)
+                            };
+                            recipients.add(address, reason);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if sources.contains(&"blame") {
+        let mut authors: HashMap<gix::ObjectId, String> = HashMap::new();
+        for (name, commit) in &commits {
+            let parent_id = commit.parent_ids().next().unwrap().detach();
+            let parent_tree_id = repo.find_commit(parent_id)?.tree_id()?.detach();
+            let diff = repo.stupid().diff_tree_patch(
+                parent_tree_id,
+                commit.tree_id()?.detach(),
+                None::<Vec<&str>>,
+                false,
+                ["-U0", "--no-ext-diff"],
+            )?;
+            for (path, line_ranges) in changed_line_ranges(&diff) {
+                let blame_lines =
+                    repo.stupid()
+                        .blame_commit(parent_id, Path::new(&path), &line_ranges)?;
+                for blame_line in blame_lines {
+                    if let Entry::Vacant(entry) = authors.entry(blame_line.commit_id) {
+                        let author = repo.find_commit(blame_line.commit_id)?.author_strict()?;
+                        entry.insert(format!("{} <{}>", author.name, author.email));
+                    }
+                    recipients.add(
+                        &authors[&blame_line.commit_id],
+                        format!("author of lines in {path} changed by {name}"),
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(Some(recipients.found))
+}
+
+/// Print each recipient followed by the reasons it was found.
+pub(super) fn explain(recipients: &[Recipient]) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for recipient in recipients {
+        writeln!(stdout, "{}", recipient.address)?;
+        for reason in &recipient.reasons {
+            writeln!(stdout, "    {reason}")?;
+        }
+    }
+    Ok(())
+}
+
+/// A section of a MAINTAINERS file.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Section {
+    title: String,
+    people: Vec<(&'static str, String)>,
+    files: Vec<String>,
+    excludes: Vec<String>,
+}
+
+impl Section {
+    /// Determine whether a path is covered by this section.
+    fn matches(&self, path: &str) -> bool {
+        self.files
+            .iter()
+            .any(|pattern| pattern_matches(pattern, path))
+            && !self
+                .excludes
+                .iter()
+                .any(|pattern| pattern_matches(pattern, path))
+    }
+}
+
+/// Read the MAINTAINERS file from the tree of a commit.
+///
+/// A missing file is only an error when its path is configured.
+fn read_maintainers(repo: &gix::Repository, commit: &gix::Commit) -> Result<Vec<Section>> {
+    let configured_path = repo
+        .config_snapshot()
+        .string("stgit.email.maintainers")
+        .map(|path| path.to_str_lossy().to_string());
+    let path = configured_path
+        .as_deref()
+        .unwrap_or(DEFAULT_MAINTAINERS_PATH);
+    if let Some(entry) = commit.tree()?.lookup_entry_by_path(path)? {
+        let blob = entry.object()?.peel_to_kind(gix::objs::Kind::Blob)?;
+        Ok(parse_maintainers(&blob.data.to_str_lossy()))
+    } else if configured_path.is_some() {
+        Err(anyhow!(
+            "maintainers file `{path}` not found in {}",
+            commit.id
+        ))
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Parse the sections of a MAINTAINERS file.
+fn parse_maintainers(content: &str) -> Vec<Section> {
+    let mut sections = Vec::new();
+    let mut current: Option<Section> = None;
+    for line in content.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            if let Some(section) = current.take() {
+                sections.push(section);
+            }
+            continue;
+        }
+
+        let mut chars = line.chars();
+        let tag = chars.next().filter(char::is_ascii_uppercase);
+        let value = if tag.is_some() && chars.next() == Some(':') {
+            Some(chars.as_str().trim())
+        } else {
+            None
+        };
+
+        if let (Some(tag), Some(value)) = (tag, value) {
+            let section = current.get_or_insert_with(Section::default);
+            match tag {
+                'M' => section.people.push(("maintainer", value.to_string())),
+                'R' => section.people.push(("reviewer", value.to_string())),
+                'L' => {
+                    // Mailing list entries may be followed by a parenthesized comment.
+                    let list = value.split(" (").next().unwrap_or(value);
+                    section.people.push(("mailing list", list.to_string()));
+                }
+                'F' => section.files.push(value.to_string()),
+                'X' => section.excludes.push(value.to_string()),
+                _ => {}
+            }
+        } else if current.is_none() {
+            current = Some(Section {
+                title: line.trim().to_string(),
+                ..Default::default()
+            });
+        }
+    }
+    if let Some(section) = current {
+        sections.push(section);
+    }
+    sections
+}
+
+/// Determine whether a MAINTAINERS path pattern matches a path.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    if pattern.ends_with('/') {
+        path.starts_with(pattern)
+    } else if !pattern.contains(['*', '?']) {
+        path == pattern
+            || path
+                .strip_prefix(pattern)
+                .map_or(false, |rest| rest.starts_with('/'))
+    } else {
+        glob_matches(pattern.as_bytes(), path.as_bytes())
+    }
+}
+
+/// Match a glob pattern where '*' and '?' do not match '/'.
+fn glob_matches(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((b'*', pattern_rest)) => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != b'/')
+            .any(|i| glob_matches(pattern_rest, &path[i..])),
+        Some((b'?', pattern_rest)) => matches!(
+            path.split_first(),
+            Some((c, path_rest)) if *c != b'/' && glob_matches(pattern_rest, path_rest)
+        ),
+        Some((pattern_c, pattern_rest)) => matches!(
+            path.split_first(),
+            Some((c, path_rest)) if c == pattern_c && glob_matches(pattern_rest, path_rest)
+        ),
+    }
+}
+
+/// Get the ranges of old lines changed in each file of a zero-context diff.
+///
+/// The ranges use the `<start>,+<count>` syntax of `git blame -L`. Files added by the
+/// diff and hunks that only add lines are skipped.
+fn changed_line_ranges(diff: &[u8]) -> Vec<(String, Vec<String>)> {
+    let mut files: Vec<(String, Vec<String>)> = Vec::new();
+    let mut in_header = false;
+    let mut old_path: Option<String> = None;
+    for line in diff.lines() {
+        if line.starts_with(b"diff --git ") {
+            in_header = true;
+            old_path = None;
+        } else if in_header && line.starts_with(b"--- ") {
+            // Quoted paths with unusual characters are not handled.
+            old_path = line
+                .strip_prefix(b"--- a/")
+                .map(|path| path.to_str_lossy().to_string());
+        } else if let Some(rest) = line.strip_prefix(b"@@ -") {
+            in_header = false;
+            let path = if let Some(path) = old_path.as_ref() {
+                path
+            } else {
+                continue;
+            };
+            let range = rest
+                .split_str(" ")
+                .next()
+                .unwrap_or_default()
+                .to_str_lossy();
+            let (start, count) = range.split_once(',').unwrap_or((&range, "1"));
+            if let (Ok(start), Ok(count)) = (start.parse::<usize>(), count.parse::<usize>()) {
+                if count > 0 {
+                    let line_range = format!("{start},+{count}");
+                    if let Some((_, ranges)) = files.iter_mut().find(|(p, _)| p == path) {
+                        ranges.push(line_range);
+                    } else {
+                        files.push((path.clone(), vec![line_range]));
+                    }
+                }
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maintainers_sections() {
+        let sections = parse_maintainers(
+            "Preamble text\n\
+             \tM: not a tag\n\
+             \n\
+             FOO DRIVER\n\
+             M:\tJane Doe <jane@example.com>\n\
+             R: Rev Iewer <rev@example.com>\n\
+             L: foo@lists.example.com (moderated for non-subscribers)\n\
+             S: Maintained\n\
+             F: drivers/foo/\n\
+             X: drivers/foo/generated/\n\
+             \n\
+             BAR\n\
+             M: bar@example.com\n\
+             F: include/bar*.h\n",
+        );
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[0].title, "Preamble text");
+        assert!(sections[0].people.is_empty());
+        assert_eq!(
+            sections[1],
+            Section {
+                title: "FOO DRIVER".into(),
+                people: vec![
+                    ("maintainer", "Jane Doe <jane@example.com>".into()),
+                    ("reviewer", "Rev Iewer <rev@example.com>".into()),
+                    ("mailing list", "foo@lists.example.com".into()),
+                ],
+                files: vec!["drivers/foo/".into()],
+                excludes: vec!["drivers/foo/generated/".into()],
+            }
+        );
+        assert!(sections[1].matches("drivers/foo/core.c"));
+        assert!(!sections[1].matches("drivers/foo/generated/regs.c"));
+        assert!(!sections[1].matches("drivers/foobar.c"));
+        assert!(sections[2].matches("include/bar.h"));
+        assert!(sections[2].matches("include/bar-ops.h"));
+        assert!(!sections[2].matches("include/bar/ops.h"));
+    }
+
+    #[test]
+    fn path_patterns() {
+        assert!(pattern_matches("src", "src/main.rs"));
+        assert!(pattern_matches("src/main.rs", "src/main.rs"));
+        assert!(!pattern_matches("src", "srcs/main.rs"));
+        assert!(pattern_matches("src/*.rs", "src/main.rs"));
+        assert!(!pattern_matches("src/*.rs", "src/cmd/mod.rs"));
+        assert!(pattern_matches("src/*/mod.rs", "src/cmd/mod.rs"));
+        assert!(pattern_matches("src/ma?n.rs", "src/main.rs"));
+        assert!(!pattern_matches("src?main.rs", "src/main.rs"));
+    }
+
+    #[test]
+    fn diff_line_ranges() {
+        let diff = b"diff --git a/a.txt b/a.txt\n\
+                     index 1111111..2222222 100644\n\
+                     --- a/a.txt\n\
+                     +++ b/a.txt\n\
+                     @@ -2 +2 @@ line\n\
+                     -old\n\
+                     +new\n\
+                     @@ -5,0 +6,2 @@ line\n\
+                     +added\n\
+                     +added\n\
+                     @@ -9,3 +11,0 @@ line\n\
+                     --- a/removed\n\
+                     -gone\n\
+                     -gone\n\
+                     diff --git a/new.txt b/new.txt\n\
+                     new file mode 100644\n\
+                     index 0000000..3333333\n\
+                     --- /dev/null\n\
+                     +++ b/new.txt\n\
+                     @@ -0,0 +1 @@\n\
+                     +new\n";
+        assert_eq!(
+            changed_line_ranges(diff),
+            vec![(
+                "a.txt".to_string(),
+                vec!["2,+1".to_string(), "9,+3".to_string()]
+            )]
+        );
+    }
+}
@@ -2,16 +2,21 @@
 
 //! `stg email send` implementation.
 
-use std::{path::Path, str::FromStr};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use anyhow::{anyhow, Result};
 use clap::Arg;
 
+use super::{history, native, recipients};
 use crate::{
     argset,
     branchloc::BranchLocator,
     ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchRange, RangeConstraint},
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
@@ -38,7 +43,10 @@ pub(super) fn command() -> clap::Command {
              configuration options. In particular, it is recommended to statically \
              configure SMTP details such as `sendemail.smtpServer`, \
              `sendemail.smtpUser`, etc. Refer to git-config(1) and git-send-email(1) \
-             man pages for more detail on all the available configuration options.",
+             man pages for more detail on all the available configuration options.\n\
+             \n\
+             With `--native`, the emails are sent without `git send-email`, either by \
+             a sendmail-like command or by StGit's own SMTP client.",
         )
         .override_usage(super::super::make_usage(
             "stg email send",
@@ -93,6 +101,32 @@ pub(super) fn command() -> clap::Command {
                 .action(clap::ArgAction::Append)
                 .value_name("option"),
         )
+        .arg(
+            Arg::new("native")
+                .long("native")
+                .help("Send the emails without `git send-email`")
+                .long_help(
+                    "Send the emails without using `git send-email`.\n\n\
+                     The emails are delivered with the command from the \
+                     `sendemail.sendmailCmd` configuration option or, when \
+                     `sendemail.smtpServer` is an absolute path, with that sendmail-like \
+                     program. Otherwise, the emails are delivered to the \
+                     `sendemail.smtpServer` SMTP server using StGit's own SMTP client, \
+                     which honors the `sendemail.smtpServerPort`, \
+                     `sendemail.smtpEncryption`, `sendemail.smtpUser`, \
+                     `sendemail.smtpPass`, and `sendemail.smtpDomain` configuration \
+                     options. The SMTP password, if not configured, is requested with \
+                     `git credential`.\n\n\
+                     Native sending may also be enabled with the `stgit.email.native` \
+                     configuration option. The `--compose`, `--annotate`, `--identity`, \
+                     `--confirm`, and `--git-opt` options require `git send-email` and \
+                     may not be used when sending natively.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(recipients::auto_cc_arg())
+        .arg(recipients::explain_arg())
+        .args(super::super::check::gate_args())
         .next_help_heading("Compose Options")
         .args(compose_options())
         .next_help_heading("Send Options")
@@ -353,6 +387,11 @@ fn format_options() -> Vec<Arg> {
             .long("reroll-count")
             .short('v')
             .help("Mark the series as the <n>th reroll")
+            .long_help(
+                "Mark the series as the <n>-th iteration of the topic. When sending \
+                 patches by name, the reroll count defaults to the next version \
+                 number once a version of the series has been sent.",
+            )
             .value_name("n")
             .num_args(1),
         Arg::new("rfc")
@@ -381,6 +420,7 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     )?;
 
     let source_args = matches.get_many::<String>("patchranges-or-paths");
+    let mut sent_patches: Option<Vec<PatchName>> = None;
     let sources = if let Some(patchranges_or_paths) = source_args {
         let patchranges_or_paths = patchranges_or_paths.collect::<Vec<_>>();
         if patchranges_or_paths.iter().all(|s| Path::new(s).is_dir())
@@ -422,6 +462,7 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
                 .unwrap()
                 .detach();
             let last = stack.get_patch_commit_id(patches.last().unwrap());
+            sent_patches = Some(patches);
             vec![format!("{base}..{last}")]
         }
     } else if matches.get_flag("all") {
@@ -437,18 +478,179 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
         }
         let base = stack.base().id;
         let last = stack.get_patch_commit_id(applied.last().unwrap());
+        sent_patches = Some(applied.to_vec());
         vec![format!("{base}..{last}")]
     } else {
         panic!("expect either patchranges or -a/--all")
     };
 
-    let mut send_args = Vec::new();
+    let dry_run = matches.get_flag("dry-run");
+    let mut reroll_count = matches
+        .get_one::<String>("reroll-count")
+        .and_then(|count| history::parse_reroll_count(count));
+    let config = repo.config_snapshot();
 
-    let mut dummy_command = clap::Command::new("dummy")
-        .args(compose_options())
-        .args(automate_options())
-        .args(administer_options())
-        .args(format_options());
+    let use_native =
+        matches.get_flag("native") || config.boolean("stgit.email.native").unwrap_or(false);
+
+    // The sent patches' commits, if they are known. Emails formatted elsewhere may be
+    // for commits not in this repository.
+    let series_commits: Vec<(String, gix::ObjectId)> = if let Some(patches) = &sent_patches {
+        patches
+            .iter()
+            .map(|patchname| (patchname.to_string(), stack.get_patch_commit_id(patchname)))
+            .collect()
+    } else {
+        let mut series_commits = Vec::new();
+        for path in source_paths(&sources)? {
+            if let Ok(history::EmailFile {
+                commit_id: Some(commit_id),
+                ..
+            }) = history::EmailFile::read(&path)
+            {
+                if repo.find_commit(commit_id).is_ok() {
+                    let name = stack
+                        .all_patches()
+                        .find(|patchname| stack.get_patch_commit_id(patchname) == commit_id)
+                        .map_or_else(|| path.display().to_string(), ToString::to_string);
+                    series_commits.push((name, commit_id));
+                }
+            }
+        }
+        series_commits
+    };
+
+    let given: Vec<String> = ["to", "cc", "bcc"]
+        .into_iter()
+        .flat_map(|arg_id| matches.get_many::<String>(arg_id).into_iter().flatten())
+        .cloned()
+        .collect();
+    let mut auto_cc = Vec::new();
+    if let Some(found) = recipients::auto_cc(&repo, matches, &series_commits, &given)? {
+        if matches.get_flag("explain") {
+            return recipients::explain(&found);
+        }
+        auto_cc = found
+            .into_iter()
+            .map(|recipient| recipient.address)
+            .collect();
+    }
+
+    super::super::check::gate(&repo, matches, "email-send", &series_commits, false)?;
+
+    if use_native {
+        for (arg_id, long) in [
+            ("compose", "compose"),
+            ("annotate", "annotate"),
+            ("identity", "identity"),
+            ("confirm", "confirm"),
+            ("git-send-email-opt", "git-opt"),
+        ] {
+            if matches!(
+                matches.value_source(arg_id),
+                Some(clap::parser::ValueSource::CommandLine)
+            ) {
+                return Err(anyhow!(
+                    "`--{long}` requires `git send-email` and cannot be used with native sending"
+                ));
+            }
+        }
+    }
+
+    let format_dir = tempfile::tempdir()?;
+    let paths = if sent_patches.is_some() {
+        let mut format_args: Vec<OsString> = passthrough_args(matches, format_options())
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+        if reroll_count.is_none() {
+            let records = history::read(&repo, stack.get_branch_name())?;
+            if let Some(last_sent) = records.iter().find(|record| record.sent) {
+                let next_version = last_sent.version + 1;
+                format_args.push(format!("--reroll-count={next_version}").into());
+                reroll_count = Some(next_version);
+            }
+        }
+        format_args.push("--output-directory".into());
+        format_args.push(format_dir.path().into());
+        format_args.extend(sources.iter().map(OsString::from));
+        repo.stupid().format_patch_files(format_args)?
+    } else {
+        source_paths(&sources)?
+    };
+
+    let record = if use_native {
+        let get_values = |arg_id: &str| {
+            matches
+                .get_many::<String>(arg_id)
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default()
+        };
+        let opts = native::SendOptions {
+            from: matches.get_one::<String>("from").cloned(),
+            to: get_values("to"),
+            cc: get_values("cc").into_iter().chain(auto_cc).collect(),
+            bcc: get_values("bcc"),
+            reply_to: matches.get_one::<String>("reply-to").cloned(),
+            in_reply_to: matches.get_one::<String>("in-reply-to").cloned(),
+            thread: !matches.get_flag("no-thread")
+                && config.boolean("sendemail.thread").unwrap_or(true),
+            dry_run,
+            quiet: matches.get_flag("quiet"),
+        };
+        let sent = native::send(&repo, &paths, &opts)?;
+        if dry_run {
+            None
+        } else {
+            sent_files_record(&repo, &stack, sent, reroll_count)?
+        }
+    } else {
+        if sent_patches.is_some() {
+            add_message_ids(&repo, &paths)?;
+        }
+
+        let mut send_args: Vec<OsString> = passthrough_args(
+            matches,
+            [compose_options(), automate_options(), administer_options()].concat(),
+        )
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+        send_args.extend(
+            auto_cc
+                .iter()
+                .map(|address| OsString::from(format!("--cc={address}"))),
+        );
+        if let Some(values) = matches.get_many::<String>("git-send-email-opt") {
+            send_args.extend(values.map(OsString::from));
+        }
+        send_args.extend(paths.iter().map(OsString::from));
+
+        repo.stupid().send_email(send_args)?;
+
+        if dry_run {
+            None
+        } else {
+            let email_files = paths
+                .iter()
+                .filter_map(|path| history::EmailFile::read(path).ok())
+                .collect();
+            sent_files_record(&repo, &stack, email_files, reroll_count)?
+        }
+    };
+
+    if let Some(record) = record {
+        history::append(&repo, stack.get_branch_name(), &record)?;
+    }
+
+    Ok(())
+}
+
+/// Get the options given on the command line that are passed through to git.
+fn passthrough_args(matches: &clap::ArgMatches, args: Vec<Arg>) -> Vec<String> {
+    let mut passthrough_args = Vec::new();
+
+    let mut dummy_command = clap::Command::new("dummy").args(args);
     dummy_command.build();
 
     for arg in dummy_command.get_arguments() {
@@ -464,24 +666,156 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
                 let values = matches.get_many::<String>(arg_id).unwrap();
                 assert!(indices.len() == values.len());
                 indices.into_iter().zip(values).for_each(|(index, value)| {
-                    send_args.push((index, format!("--{long}={value}")));
+                    passthrough_args.push((index, format!("--{long}={value}")));
                 });
             } else {
-                indices.for_each(|index| send_args.push((index, format!("--{long}"))));
+                indices.for_each(|index| passthrough_args.push((index, format!("--{long}"))));
             }
         }
     }
 
-    send_args.sort_by_key(|(index, _)| *index);
+    passthrough_args.sort_by_key(|(index, _)| *index);
+    passthrough_args.into_iter().map(|(_, s)| s).collect()
+}
 
-    let mut send_args = send_args.drain(..).map(|(_, s)| s).collect::<Vec<_>>();
+/// Get the email file paths from file and directory sources.
+///
+/// All files in a source directory are used, in name order.
+fn source_paths(sources: &[String]) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for source in sources {
+        let source = Path::new(source);
+        if source.is_dir() {
+            let mut dir_paths = Vec::new();
+            for entry in source.read_dir()? {
+                let path = entry?.path();
+                if path.is_file() {
+                    dir_paths.push(path);
+                }
+            }
+            dir_paths.sort();
+            paths.append(&mut dir_paths);
+        } else {
+            paths.push(source.to_path_buf());
+        }
+    }
+    Ok(paths)
+}
+
+/// Add a Message-Id header to each formatted email file that does not have one.
+///
+/// `git send-email` keeps the Message-Id of an email file, so giving each email an id
+/// up front lets the ids of the sent emails be recorded in the series history. The ids
+/// take the form `git format-patch --thread` would give them.
+fn add_message_ids(repo: &gix::Repository, paths: &[PathBuf]) -> Result<()> {
+    let committer = repo.get_committer()?;
+    let now = gix::actor::Time::now_local_or_utc().seconds_since_unix_epoch;
+    for path in paths {
+        let email_file = history::EmailFile::read(path)?;
+        if email_file.message_id.is_some() {
+            continue;
+        }
+        let unique = email_file
+            .commit_id
+            .map_or_else(|| "cover".to_string(), |commit_id| commit_id.to_string());
+        let header = format!("Message-Id: <{unique}.{now}.git.{}>\n", committer.email);
+        let mut content = std::fs::read(path)?;
+        let headers_start = content
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(content.len(), |pos| pos + 1);
+        content.splice(headers_start..headers_start, header.bytes());
+        std::fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+/// Make a history record for a series sent as email files.
+///
+/// The patch names are taken from the record of the series being formatted, if any,
+/// or otherwise from the stack. No record is made if none of the sent files are emails
+/// for known patches.
+fn sent_files_record(
+    repo: &gix::Repository,
+    stack: &Stack,
+    email_files: Vec<history::EmailFile>,
+    reroll_count: Option<usize>,
+) -> Result<Option<history::SeriesRecord>> {
+    let records = history::read(repo, stack.get_branch_name())?;
+    let format_record = records.iter().find(|record| {
+        !record.sent
+            && email_files
+                .iter()
+                .filter_map(|email_file| email_file.commit_id)
+                .all(|commit_id| {
+                    record
+                        .patches
+                        .iter()
+                        .any(|patch_record| patch_record.commit == commit_id)
+                })
+    });
+
+    let mut version = reroll_count.or_else(|| format_record.map(|record| record.version));
+    let mut cover_message_id = None;
+    let mut patches = Vec::new();
+    for email_file in email_files {
+        if version.is_none() {
+            version = email_file.subject_version();
+        }
+        if let Some(commit_id) = email_file.commit_id {
+            let patchname = format_record
+                .and_then(|record| {
+                    record
+                        .patches
+                        .iter()
+                        .find(|patch_record| patch_record.commit == commit_id)
+                        .map(|patch_record| patch_record.name.clone())
+                })
+                .or_else(|| {
+                    stack
+                        .all_patches()
+                        .find(|patchname| stack.get_patch_commit_id(patchname) == commit_id)
+                        .cloned()
+                });
+            if let Some(name) = patchname {
+                patches.push(history::PatchRecord {
+                    name,
+                    commit: commit_id,
+                    message_id: email_file.message_id,
+                });
+            }
+        } else {
+            cover_message_id = email_file.message_id;
+        }
+    }
 
-    if let Some(values) = matches.get_many::<String>("git-send-email-opt") {
-        send_args.extend(values.cloned());
+    if patches.is_empty() {
+        return Ok(None);
     }
 
-    let mut sources = sources;
-    send_args.append(&mut sources);
+    let base = match format_record {
+        Some(record)
+            if record
+                .patches
+                .first()
+                .map(|patch_record| patch_record.commit)
+                == Some(patches[0].commit) =>
+        {
+            record.base
+        }
+        _ => repo
+            .find_commit(patches[0].commit)?
+            .parent_ids()
+            .next()
+            .ok_or_else(|| anyhow!("sent patch `{}` has no parent", patches[0].name))?
+            .detach(),
+    };
 
-    repo.stupid().send_email(send_args)
+    Ok(Some(history::SeriesRecord {
+        version: version.unwrap_or(1),
+        sent: true,
+        base,
+        cover_message_id,
+        patches,
+    }))
 }
@@ -4,23 +4,22 @@
 
 use std::{
     borrow::Cow,
-    collections::HashMap,
     ffi::OsString,
     io::Write,
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
-use bstr::BStr;
+use anyhow::{anyhow, Context, Result};
 use clap::Arg;
 
 use crate::{
     argset,
     branchloc::BranchLocator,
     ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchRange, RangeConstraint},
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
+    templates,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -40,19 +39,54 @@ fn make() -> clap::Command {
              Patches are exported to 'patches-<branch>' by default. The '--dir' option \
              may be used to specify a different output directory.\n\
              \n\
+             Alternatively, the patches may be exported to a single file. With \
+             '--mbox', the patches are written as email messages to an mbox file \
+             suitable for `git am` or `stg import --mbox`. With '--archive', the \
+             series file and patch files are packaged in a tar archive, compressed \
+             according to the archive file name's \".tar.gz\", \".tgz\", or \
+             \".tar.bz2\" extension, suitable for `stg import --series`.\n\
+             \n\
              The patch file output may be customized via a template file found at \
              \"$GIT_DIR/patchexport.tmpl\", \"~/.stgit/templates/patchexport.tmpl\", \
-             or \"$(prefix)/share/stgit/templates\". The following variables are \
-             supported in the template file:\n\
-             \n    %(description)s - patch description\
-             \n    %(shortdescr)s  - the first line of the patch description\
-             \n    %(longdescr)s   - the rest of the patch description, after the first line\
-             \n    %(diffstat)s    - the diff statistics\
-             \n    %(authname)s    - author name\
-             \n    %(authemail)s   - author email\
-             \n    %(authdate)s    - patch creation date (ISO-8601 format)\
-             \n    %(commname)s    - committer name\
-             \n    %(commemail)s   - committer email",
+             or \"$(prefix)/share/stgit/templates\". The patch's diff follows the \
+             rendered template in each patch file.\n\
+             \n\
+             A template that starts with a '{# ... #}' comment tag uses the template \
+             language described below. Other templates, as written for older StGit \
+             versions, only have their '%(name)s' specifiers replaced by variables \
+             and are otherwise output verbatim.\n\
+             \n\
+             Template variables are output with '{{ name }}' or, as with older \
+             templates, '%(name)s'. Filters may be applied as in \
+             '{{ authdate | date(\"rfc2822\") }}' and '{{ longdescr | wrap(72) }}'. \
+             The supported filters are 'date' (with \"iso\", \"iso-strict\", \
+             \"rfc2822\", \"short\", \"raw\", or \"unix\" format), 'default', \
+             'indent', 'join', 'length', 'lower', 'trim', 'upper', and 'wrap'. \
+             Conditionals use '{% if name %}', '{% elif name %}', '{% else %}', and \
+             '{% endif %}'; lists are looped over with '{% for item in list %}' and \
+             '{% endfor %}'; and other named templates are included with \
+             '{% include \"name\" %}'. The following variables are supported in the \
+             template file:\n\
+             \n    description - patch description\
+             \n    shortdescr  - the first line of the patch description\
+             \n    longdescr   - the rest of the patch description, after the first line\
+             \n    diffstat    - the diff statistics\
+             \n    authname    - author name\
+             \n    authemail   - author email\
+             \n    authdate    - patch creation date (ISO-8601 format)\
+             \n    commname    - committer name\
+             \n    commemail   - committer email\
+             \n    commdate    - commit date (ISO-8601 format)\
+             \n    name        - patch name\
+             \n    index       - position of the patch in the exported series\
+             \n    total       - number of exported patches\
+             \n    branch      - branch name\
+             \n    commit      - patch commit id\
+             \n    parent      - parent commit id\
+             \n    notes       - the patch commit's notes\
+             \n    trailers    - list of trailers, each with 'key' and 'value'\
+             \n    files       - list of changed files, each with 'path', 'added', \
+             'deleted', and 'binary'",
         )
         .arg(
             Arg::new("patchranges")
@@ -118,6 +152,29 @@ fn make() -> clap::Command {
                 .conflicts_with("dir")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("mbox")
+                .long("mbox")
+                .help("Export patches as emails to mbox <file>")
+                .value_name("file")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["dir", "stdout", "archive"]),
+        )
+        .arg(
+            Arg::new("archive")
+                .long("archive")
+                .help("Export series to tar archive <file>")
+                .long_help(
+                    "Export the series file and patch files to tar archive <file>. The \
+                     archive is compressed with gzip or bzip2 when the file name ends \
+                     with \".tar.gz\" or \".tgz\", or \".tar.bz2\", respectively.",
+                )
+                .value_name("file")
+                .value_hint(clap::ValueHint::FilePath)
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with_all(["dir", "stdout"]),
+        )
         .arg(argset::diff_opts_arg())
 }
 
@@ -126,7 +183,6 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     let opt_branch = matches.get_one::<BranchLocator>("branch");
     let stack =
         Stack::from_branch_locator(&repo, opt_branch, InitializationPolicy::AllowUninitialized)?;
-    let stupid = repo.stupid();
 
     if opt_branch.is_none()
         && repo
@@ -178,25 +234,21 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
 
     let diff_opts = argset::get_diff_opts(matches, &repo.config_snapshot(), false, true);
 
-    let template = if let Some(template_file) = matches.get_one::<PathBuf>("template") {
-        Cow::Owned(std::fs::read_to_string(template_file)?)
-    } else {
-        match crate::templates::get_template(&repo, "patchexport.tmpl") {
-            Ok(Some(template)) => Cow::Owned(template),
-            Ok(None) => Cow::Borrowed(crate::templates::PATCHEXPORT_TMPL),
-            Err(e) => return Err(e),
-        }
-    };
+    if let Some(mbox_path) = matches.get_one::<PathBuf>("mbox") {
+        return write_mbox(&repo, &stack, &patches, mbox_path, &diff_opts);
+    }
 
-    let need_diffstat = template.contains("%(diffstat)");
+    let template = get_template(&repo, matches.get_one::<PathBuf>("template"))?;
 
     let stdout_flag = matches.get_flag("stdout");
+    let archive_path = matches.get_one::<PathBuf>("archive");
+    let mut archive_files: Vec<(String, Vec<u8>)> = Vec::new();
     let mut series = format!(
         "# This series applies on Git commit {}\n",
         stack.base().id()
     );
 
-    if !stdout_flag {
+    if !stdout_flag && archive_path.is_none() {
         std::fs::create_dir_all(output_dir).with_context(|| format!("creating {output_dir:?}"))?;
     }
 
@@ -212,63 +264,19 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         series.push('\n');
 
         let patch_commit = stack.get_patch_commit(patchname);
-        let parent_commit = patch_commit.get_parent_commit()?;
-
-        let mut replacements: HashMap<&str, Cow<'_, BStr>> = HashMap::new();
-        let message = patch_commit.message_ex();
-        let description = message.decode()?;
-        let description = description.as_ref();
-        let (shortdescr, longdescr) = if let Some((shortdescr, rest)) = description.split_once('\n')
-        {
-            let longdescr = rest.trim_start_matches('\n').trim_end();
-            (shortdescr, longdescr)
-        } else {
-            (description, "")
-        };
-        replacements.insert("description", Cow::Borrowed(description.into()));
-        replacements.insert("shortdescr", Cow::Borrowed(shortdescr.into()));
-        replacements.insert("longdescr", Cow::Borrowed(longdescr.into()));
-        let author = patch_commit.author()?;
-        replacements.insert("authname", Cow::Borrowed(author.name));
-        replacements.insert("authemail", Cow::Borrowed(author.email));
-        replacements.insert(
-            "authdate",
-            Cow::Owned(author.time.format(gix::date::time::format::ISO8601).into()),
-        );
-        let committer = patch_commit.committer()?;
-        replacements.insert("commname", Cow::Borrowed(committer.name));
-        replacements.insert("commemail", Cow::Borrowed(committer.email));
-        replacements.insert(
-            "commdate",
-            Cow::Owned(
-                committer
-                    .time
-                    .format(gix::date::time::format::ISO8601)
-                    .into(),
-            ),
-        );
-
-        let diff = stupid.diff_tree_patch(
-            parent_commit.tree_id()?.detach(),
-            patch_commit.tree_id()?.detach(),
-            <Option<Vec<OsString>>>::None,
-            false,
-            diff_opts.iter(),
+        let mut context = templates::Context::default();
+        context.insert("branch", stack.get_branch_name());
+        context.insert("index", i + 1);
+        context.insert("total", patches.len());
+        let content = render_patch(
+            &repo,
+            patchname,
+            patch_commit,
+            context,
+            &template,
+            &diff_opts,
         )?;
 
-        if need_diffstat {
-            replacements.insert(
-                "diffstat",
-                if parent_commit.tree_id()? == patch_commit.tree_id()? {
-                    Cow::Borrowed("".into())
-                } else {
-                    Cow::Owned(stupid.diffstat(diff.as_ref())?)
-                },
-            );
-        }
-
-        let specialized = crate::templates::specialize_template(&template, &replacements);
-
         if stdout_flag {
             let stdout = std::io::stdout();
             let mut stdout = stdout.lock();
@@ -281,20 +289,23 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
                     '-'
                 )?;
             }
-            stdout.write_all(&specialized)?;
-            stdout.write_all(&diff)?;
+            stdout.write_all(&content)?;
+        } else if archive_path.is_some() {
+            archive_files.push((patchfile_name, content));
         } else {
             let mut file = std::fs::File::options()
                 .write(true)
                 .create(true)
                 .open(output_dir.join(&patchfile_name))
                 .with_context(|| format!("opening {patchfile_name}"))?;
-            file.write_all(&specialized)?;
-            file.write_all(&diff)?;
+            file.write_all(&content)?;
         }
     }
 
-    if !stdout_flag {
+    if let Some(archive_path) = archive_path {
+        archive_files.insert(0, (String::from("series"), series.into_bytes()));
+        write_archive(archive_path, output_dir, &archive_files)?;
+    } else if !stdout_flag {
         let series_path = output_dir.join("series");
         std::fs::write(&series_path, series.as_str())
             .with_context(|| format!("writing {series_path:?}"))?;
@@ -302,3 +313,137 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
 
     Ok(())
 }
+
+/// Write the patches as `git format-patch` email messages to an mbox file.
+///
+/// The messages are numbered "[PATCH n/m]" when there is more than one patch.
+fn write_mbox(
+    repo: &gix::Repository,
+    stack: &Stack,
+    patches: &[PatchName],
+    mbox_path: &Path,
+    diff_opts: &[String],
+) -> Result<()> {
+    let stupid = repo.stupid();
+    let mut mbox = Vec::new();
+    for (i, patchname) in patches.iter().enumerate() {
+        let subject_prefix = if patches.len() > 1 {
+            format!("--subject-prefix=PATCH {}/{}", i + 1, patches.len())
+        } else {
+            String::from("--subject-prefix=PATCH")
+        };
+        let commit_id = stack.get_patch_commit(patchname).id.to_string();
+        let message = stupid.format_patch_stdout(
+            [
+                "--no-numbered",
+                "--no-cover-letter",
+                subject_prefix.as_str(),
+            ]
+            .into_iter()
+            .chain(diff_opts.iter().map(String::as_str))
+            .chain(["-1", commit_id.as_str()]),
+        )?;
+        mbox.extend_from_slice(&message);
+    }
+    std::fs::write(mbox_path, mbox).with_context(|| format!("writing {mbox_path:?}"))
+}
+
+/// Write the series and patch files to a tar archive in the given directory.
+///
+/// The archive is compressed according to the archive file name's extension.
+#[cfg(feature = "import-compressed")]
+fn write_archive(archive_path: &Path, dir: &Path, files: &[(String, Vec<u8>)]) -> Result<()> {
+    let file_name = archive_path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let create = || {
+        std::fs::File::create(archive_path).with_context(|| format!("creating {archive_path:?}"))
+    };
+    if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+        let encoder = flate2::write::GzEncoder::new(create()?, flate2::Compression::default());
+        build_tar(encoder, dir, files)?.finish()?;
+    } else if file_name.ends_with(".tar.bz2") {
+        let encoder = bzip2::write::BzEncoder::new(create()?, bzip2::Compression::default());
+        build_tar(encoder, dir, files)?.finish()?;
+    } else if file_name.ends_with(".tar") {
+        build_tar(create()?, dir, files)?.flush()?;
+    } else {
+        return Err(anyhow!(
+            "archive `{}` does not have a .tar, .tar.gz, .tgz, or .tar.bz2 extension",
+            archive_path.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "import-compressed"))]
+fn write_archive(_: &Path, _: &Path, _: &[(String, Vec<u8>)]) -> Result<()> {
+    Err(anyhow!("StGit not built with support for series archives"))
+}
+
+#[cfg(feature = "import-compressed")]
+fn build_tar<W: Write>(writer: W, dir: &Path, files: &[(String, Vec<u8>)]) -> Result<W> {
+    let mtime = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let mut builder = tar::Builder::new(writer);
+    for (file_name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        builder.append_data(&mut header, dir.join(file_name), content.as_slice())?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+/// Get the patch export template.
+///
+/// The template is read from `template_file` if provided, otherwise the
+/// "patchexport.tmpl" template is looked up with a fallback to the builtin template.
+pub(super) fn get_template(
+    repo: &gix::Repository,
+    template_file: Option<&PathBuf>,
+) -> Result<Cow<'static, str>> {
+    if let Some(template_file) = template_file {
+        Ok(Cow::Owned(std::fs::read_to_string(template_file)?))
+    } else {
+        let template = templates::get_template(repo, "patchexport.tmpl")?;
+        Ok(template.map_or(Cow::Borrowed(templates::PATCHEXPORT_TMPL), Cow::Owned))
+    }
+}
+
+/// Render the exported patch file content for a patch commit using the template.
+///
+/// The patch's variables are added to the provided `context`, which may define
+/// additional variables such as the patch's index in the exported series.
+pub(super) fn render_patch(
+    repo: &gix::Repository,
+    patchname: &PatchName,
+    patch_commit: &gix::Commit<'_>,
+    mut context: templates::Context,
+    template: &str,
+    diff_opts: &[String],
+) -> Result<Vec<u8>> {
+    let parent_commit = patch_commit.get_parent_commit()?;
+    let diff = repo.stupid().diff_tree_patch(
+        parent_commit.tree_id()?.detach(),
+        patch_commit.tree_id()?.detach(),
+        <Option<Vec<OsString>>>::None,
+        false,
+        diff_opts.iter(),
+    )?;
+
+    context.extend(templates::patch_vars(
+        repo,
+        Some(patchname),
+        patch_commit,
+        Some(diff.as_ref()),
+    )?);
+    let mut content =
+        templates::render(repo, template, &context).context("rendering export template")?;
+    content.extend_from_slice(&diff);
+    Ok(content)
+}
@@ -128,6 +128,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
             diff.as_ref(),
             reject_flag,
             false,
+            false,
             strip_level,
             None,
             context_lines,
@@ -147,6 +148,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
             diff.as_ref(),
             reject_flag,
             false,
+            false,
             strip_level,
             None,
             context_lines,
@@ -3,8 +3,10 @@
 //! `stg import` implementation.
 
 use std::{
+    collections::BTreeMap,
     io::Read,
     path::{Path, PathBuf},
+    rc::Rc,
 };
 
 use anyhow::{anyhow, Context, Result};
@@ -12,11 +14,13 @@ use bstr::{BStr, BString, ByteSlice, ByteVec};
 use clap::{Arg, ArgGroup};
 
 use crate::{
+    branchloc::BranchLocator,
     color::get_color_stdout,
-    ext::{RepositoryExtended, TimeExtended},
-    patch::{patchedit, PatchName},
+    ext::{CommitExtended, RepositoryExtended, TimeExtended},
+    patch::{name::EmailSubject, patchedit, PatchName},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
+    wrap::Message,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -37,13 +41,38 @@ fn make() -> clap::Command {
              can be overridden with '--name'. The patch can either be a normal file \
              with the description at the top, or it can have standard mail format. The \
              \"Subject\", \"From\", and \"Date\" headers will be used for the imported \
-             patch's author details.\n\
+             patch's author details. Likewise, the \"# User\" and \"# Date\" header \
+             lines of patches created by 'hg export' are used.\n\
              \n\
              Patches may also be imported from a mail file (-m/--mail), an mbox \
              (-M/--mbox), or a series (-S/--series). Furthermore, the -u/--url option \
              allows the patches source to be fetched from a url instead of from a \
              local file.\n\
              \n\
+             Patch files compressed with gzip, bzip2, xz, or zstd, or contained in a \
+             zip archive, are decompressed before being imported. The compression \
+             format is determined by the file name's extension or, failing that, by \
+             the file's content. Support for xz, zstd, and zip requires StGit to be \
+             built with the \"import-xz\", \"import-zstd\", and \"import-zip\" \
+             features, respectively.\n\
+             \n\
+             A series file lists one patch file name per line, as with quilt. Each \
+             name may be followed by a '-p<n>' strip level and a '-R' option to apply \
+             the patch in reverse. When the series file is in a quilt \"patches\" \
+             directory with a \".pc/applied-patches\" status file next to it, only \
+             the patches listed in the status file are left applied.\n\
+             \n\
+             Stacks managed with Guilt or TopGit may be migrated to StGit with \
+             '--from-guilt' or '--from-topgit'. See also 'stg quilt sync' for \
+             keeping a quilt patches directory and the stack in step.\n\
+             \n\
+             When an mbox contains patch emails with numbered subjects such as \
+             \"[PATCH v2 1/3]\", as found in a mailing list thread, only one revision \
+             of the series is imported. By default, the newest complete revision is \
+             selected; use '--version' to select a specific revision. The patches are \
+             imported in the order of their numbers, while the cover letter and any \
+             replies are skipped.\n\
+             \n\
              If a patch does not apply cleanly, the failed diff is written to a \
              .stgit-failed.patch file and an empty patch is added to the stack.\n\
              \n\
@@ -57,6 +86,8 @@ fn make() -> clap::Command {
                     "[OPTIONS] -m [<mail-path>|<Maildir-path>]",
                     "[OPTIONS] -M [<mbox-path>]",
                     "[OPTIONS] -S [<series-path>]",
+                    "[OPTIONS] --from-guilt [<branch>]",
+                    "[OPTIONS] --from-topgit",
                     "[OPTIONS] -u <diff-url>",
                     "[OPTIONS] -u -m <mail-url>",
                     "[OPTIONS] -u -M <mbox-url>",
@@ -68,6 +99,8 @@ fn make() -> clap::Command {
                     "[OPTIONS] -m [<mail-path>|<Maildir-path>]",
                     "[OPTIONS] -M [<mbox-path>]",
                     "[OPTIONS] -S [<series-path>]",
+                    "[OPTIONS] --from-guilt [<branch>]",
+                    "[OPTIONS] --from-topgit",
                 ]
             },
         ))
@@ -97,15 +130,73 @@ fn make() -> clap::Command {
                 .help("Import patch series from an mbox file")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("version")
+                .long("version")
+                .help("Import revision <N> of a patch series from an mbox")
+                .long_help(
+                    "Import revision <N> of a patch series from an mbox containing \
+                     emails with numbered subjects such as \"[PATCH v2 1/3]\". Emails \
+                     without a version in their subject belong to revision 1.",
+                )
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .requires("mbox"),
+        )
         .arg(
             Arg::new("series")
                 .long("series")
                 .short('S')
                 .help("Import patch series")
-                .long_help("Import patch series from a series file are tar archive.")
+                .long_help(
+                    "Import patch series from a series file or from a tar or zip \
+                     archive containing a series file. Tar archives and series files \
+                     may be compressed with gzip, bzip2, xz, or zstd.",
+                )
                 .action(clap::ArgAction::SetTrue),
         )
-        .group(ArgGroup::new("whence").args(["mail", "mbox", "series"]));
+        .arg(
+            Arg::new("from-guilt")
+                .long("from-guilt")
+                .help("Migrate a Guilt patch stack to StGit")
+                .long_help(
+                    "Migrate the Guilt patch stack of <branch>, or of the current \
+                     branch, to StGit. The patches are read from Guilt's \"series\" \
+                     and \"status\" files in \".git/patches/<branch>/\". Guilt's \
+                     applied patches are already commits on the branch and become \
+                     applied StGit patches. The remaining patches of the series are \
+                     imported as unapplied patches, in series order. The branch must \
+                     not have any StGit patches. Guilt's files are left in place.",
+                )
+                .value_name("branch")
+                .num_args(0..=1)
+                .value_parser(clap::value_parser!(BranchLocator))
+                .conflicts_with("source"),
+        )
+        .arg(
+            Arg::new("from-topgit")
+                .long("from-topgit")
+                .help("Migrate TopGit topic branches to StGit")
+                .long_help(
+                    "Migrate the TopGit topic branches based on the current branch to \
+                     StGit patches. Each topic branch whose dependencies, as listed in \
+                     its \".topdeps\" file, lead to the current branch becomes a patch \
+                     with the changes between the topic's base and its head. The \
+                     patches are ordered such that each patch follows the patches of \
+                     the topics it depends on. The patch message and author are taken \
+                     from the topic's \".topmsg\" file. The topic branches are left in \
+                     place.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("source"),
+        )
+        .group(ArgGroup::new("whence").args([
+            "mail",
+            "mbox",
+            "series",
+            "from-guilt",
+            "from-topgit",
+        ]));
 
     let app = if cfg!(feature = "import-url") {
         app.arg(
@@ -224,6 +315,9 @@ fn make() -> clap::Command {
 
 fn run(matches: &clap::ArgMatches) -> Result<()> {
     let repo = gix::Repository::open()?;
+    if matches.contains_id("from-guilt") {
+        return import_guilt(&repo, matches);
+    }
     let stack = Stack::current(&repo, InitializationPolicy::AutoInitialize)?;
     let stupid = repo.stupid();
 
@@ -243,12 +337,14 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
 
     if cfg!(feature = "import-url") && matches.get_flag("url") {
         import_url(stack, matches)
+    } else if matches.get_flag("from-topgit") {
+        import_topgit(stack, matches)
     } else if matches.get_flag("series") {
         import_series(stack, matches, source_path.as_deref())
     } else if matches.get_flag("mail") || matches.get_flag("mbox") {
         import_mail(stack, matches, source_path.as_deref())
     } else {
-        import_file(stack, matches, source_path.as_deref(), None)?;
+        import_file(stack, matches, source_path.as_deref(), None, false)?;
         Ok(())
     }
 }
@@ -307,57 +403,138 @@ fn import_url(stack: Stack, matches: &clap::ArgMatches) -> Result<()> {
     } else if matches.get_flag("mail") || matches.get_flag("mbox") {
         import_mail(stack, matches, Some(download_path.as_path()))
     } else {
-        import_file(stack, matches, Some(download_path.as_path()), None)?;
+        import_file(stack, matches, Some(download_path.as_path()), None, false)?;
         Ok(())
     }
 }
 
-#[cfg(feature = "import-compressed")]
-fn import_tgz_series(stack: Stack, matches: &clap::ArgMatches, source_path: &Path) -> Result<()> {
-    let source_file = std::fs::File::open(source_path)?;
-    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(source_file));
-    let temp_dir = tempfile::tempdir()?;
-    archive.unpack(temp_dir.path())?;
-    let series_path = find_series_path(temp_dir.path())?;
-    return import_series(stack, matches, Some(series_path.as_path()));
+/// Compression formats recognized for imported patches and series archives.
+#[derive(Clone, Copy)]
+enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
 }
 
-#[cfg(feature = "import-compressed")]
-fn import_tbz2_series(stack: Stack, matches: &clap::ArgMatches, source_path: &Path) -> Result<()> {
-    let source_file = std::fs::File::open(source_path)?;
-    let mut archive = tar::Archive::new(bzip2::read::BzDecoder::new(source_file));
-    let temp_dir = tempfile::tempdir()?;
-    archive.unpack(temp_dir.path())?;
-    let series_path = find_series_path(temp_dir.path())?;
-    return import_series(stack, matches, Some(series_path.as_path()));
+impl Compression {
+    /// Determine compression from a lowercase file name's extension.
+    fn from_file_name(file_name: &str) -> Option<Self> {
+        if file_name.ends_with(".gz") || file_name.ends_with(".tgz") {
+            Some(Self::Gzip)
+        } else if file_name.ends_with(".bz2") || file_name.ends_with(".tbz2") {
+            Some(Self::Bzip2)
+        } else if file_name.ends_with(".xz") || file_name.ends_with(".txz") {
+            Some(Self::Xz)
+        } else if file_name.ends_with(".zst") || file_name.ends_with(".tzst") {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Determine compression from the magic bytes at the start of the content.
+    fn from_magic(content: &[u8]) -> Option<Self> {
+        if content.starts_with(b"\x1f\x8b") {
+            Some(Self::Gzip)
+        } else if content.len() >= 4
+            && content.starts_with(b"BZh")
+            && (b'1'..=b'9').contains(&content[3])
+        {
+            Some(Self::Bzip2)
+        } else if content.starts_with(b"\xfd7zXZ\x00") {
+            Some(Self::Xz)
+        } else if content.starts_with(b"\x28\xb5\x2f\xfd") {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
 }
 
-#[cfg(feature = "import-compressed")]
-fn import_tar_series(stack: Stack, matches: &clap::ArgMatches, source_path: &Path) -> Result<()> {
-    let source_file = std::fs::File::open(source_path)?;
-    let mut archive = tar::Archive::new(source_file);
-    let temp_dir = tempfile::tempdir()?;
-    archive.unpack(temp_dir.path())?;
-    let series_path = find_series_path(temp_dir.path())?;
-    return import_series(stack, matches, Some(series_path.as_path()));
+/// Magic bytes at the start of a zip archive.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Archive formats recognized for imported series.
+#[derive(Clone, Copy)]
+#[cfg_attr(not(feature = "import-compressed"), allow(dead_code))]
+enum SeriesArchive {
+    Tar(Option<Compression>),
+    Zip,
 }
 
-#[cfg(not(feature = "import-compressed"))]
-fn import_tgz_series(_: Stack, _: &clap::ArgMatches, _: &Path) -> Result<()> {
-    Err(anyhow!(
-        "StGit not built with support for compressed series"
-    ))
+impl SeriesArchive {
+    /// Determine the archive format from a lowercase file name's extension.
+    fn from_file_name(file_name: &str) -> Option<Self> {
+        if file_name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if file_name.ends_with(".tar") {
+            Some(Self::Tar(None))
+        } else if file_name.ends_with(".tgz")
+            || file_name.ends_with(".tbz2")
+            || file_name.ends_with(".txz")
+            || file_name.ends_with(".tzst")
+            || file_name
+                .rsplit_once('.')
+                .map_or(false, |(stem, _)| stem.ends_with(".tar"))
+        {
+            Compression::from_file_name(file_name).map(|compression| Self::Tar(Some(compression)))
+        } else {
+            None
+        }
+    }
+
+    /// Determine the archive format from the magic bytes at the start of the content.
+    ///
+    /// Compressed content must be decompressed before checking for a tar header.
+    fn from_magic(content: &[u8]) -> Option<Self> {
+        if content.starts_with(ZIP_MAGIC) {
+            Some(Self::Zip)
+        } else if content.get(257..262) == Some(&b"ustar"[..]) {
+            Some(Self::Tar(None))
+        } else {
+            None
+        }
+    }
 }
 
-#[cfg(not(feature = "import-compressed"))]
-fn import_tbz2_series(_: Stack, _: &clap::ArgMatches, _: &Path) -> Result<()> {
-    Err(anyhow!(
-        "StGit not built with support for compressed series"
-    ))
+#[cfg(feature = "import-compressed")]
+fn import_archive_series(
+    stack: Stack,
+    matches: &clap::ArgMatches,
+    archive: SeriesArchive,
+    content: Vec<u8>,
+) -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    match archive {
+        SeriesArchive::Tar(compression) => {
+            let content = if let Some(compression) = compression {
+                decompress(compression, content.as_slice())?
+            } else {
+                content
+            };
+            tar::Archive::new(content.as_slice()).unpack(temp_dir.path())?;
+        }
+        #[cfg(feature = "import-zip")]
+        SeriesArchive::Zip => {
+            zip::ZipArchive::new(std::io::Cursor::new(content))?.extract(temp_dir.path())?;
+        }
+        #[cfg(not(feature = "import-zip"))]
+        SeriesArchive::Zip => {
+            return Err(anyhow!("StGit not built with support for zip archives"));
+        }
+    }
+    let series_path = find_series_path(temp_dir.path())?;
+    import_series(stack, matches, Some(series_path.as_path()))
 }
 
 #[cfg(not(feature = "import-compressed"))]
-fn import_tar_series(_: Stack, _: &clap::ArgMatches, _: &Path) -> Result<()> {
+fn import_archive_series(
+    _: Stack,
+    _: &clap::ArgMatches,
+    _: SeriesArchive,
+    _: Vec<u8>,
+) -> Result<()> {
     Err(anyhow!(
         "StGit not built with support for compressed series"
     ))
@@ -369,17 +546,27 @@ fn import_series(
     source_path: Option<&Path>,
 ) -> Result<()> {
     let series = if let Some(source_path) = source_path {
-        if let Some(filename) = source_path.file_name() {
-            let filename = filename.to_string_lossy().to_ascii_lowercase();
-            if filename.ends_with(".tar.gz") || filename.ends_with(".tgz") {
-                return import_tgz_series(stack, matches, source_path);
-            } else if filename.ends_with(".tar.bz2") {
-                return import_tbz2_series(stack, matches, source_path);
-            } else if filename.ends_with(".tar") {
-                return import_tar_series(stack, matches, source_path);
-            }
+        let content = std::fs::read(source_path)?;
+        let file_name = source_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        if let Some(archive) = SeriesArchive::from_file_name(&file_name) {
+            return import_archive_series(stack, matches, archive, content);
         }
-        std::fs::read(source_path)?
+        // Compressed content is either a compressed tar archive or a compressed
+        // series file, which is only known once it is decompressed.
+        let content = if let Some(compression) =
+            Compression::from_file_name(&file_name).or_else(|| Compression::from_magic(&content))
+        {
+            decompress(compression, content.as_slice())?
+        } else {
+            content
+        };
+        if let Some(archive) = SeriesArchive::from_magic(&content) {
+            return import_archive_series(stack, matches, archive, content);
+        }
+        content
     } else {
         let stdin = std::io::stdin();
         let mut stdin = stdin.lock();
@@ -390,6 +577,89 @@ fn import_series(
 
     let mut stack = stack;
 
+    // Quilt keeps the names of its applied patches in ".pc/applied-patches", in the
+    // directory containing the "patches" directory with the series file.
+    let quilt_applied: Option<Vec<String>> = if let Some(pc_dir) = source_path
+        .and_then(Path::parent)
+        .and_then(Path::parent)
+        .map(|dir| dir.join(".pc"))
+    {
+        match std::fs::read(pc_dir.join("applied-patches")) {
+            Ok(applied) => Some(
+                applied
+                    .lines()
+                    .map(|line| line.trim().to_str_lossy().to_string())
+                    .filter(|line| !line.is_empty())
+                    .collect(),
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).context("reading quilt .pc/applied-patches"),
+        }
+    } else {
+        None
+    };
+
+    let mut to_pop: Vec<PatchName> = Vec::new();
+
+    for entry in parse_series(&series)? {
+        let patch_path = source_path.map_or_else(
+            || PathBuf::from(&entry.name),
+            |p| p.with_file_name(&entry.name),
+        );
+        let prev_top = stack.applied().last().cloned();
+        stack = import_file(
+            stack,
+            matches,
+            Some(patch_path.as_path()),
+            entry.strip_level,
+            entry.reverse,
+        )?;
+        if let Some(quilt_applied) = quilt_applied.as_ref() {
+            if !quilt_applied.contains(&entry.name) {
+                if let Some(top) = stack
+                    .applied()
+                    .last()
+                    .filter(|&top| Some(top) != prev_top.as_ref())
+                {
+                    to_pop.push(top.clone());
+                }
+            }
+        }
+    }
+
+    if !to_pop.is_empty() {
+        stack
+            .setup_transaction()
+            .with_output_stream(get_color_stdout(matches))
+            .use_index_and_worktree(true)
+            .transact(|trans| {
+                trans.pop_patches(|pn| to_pop.contains(pn))?;
+                Ok(())
+            })
+            .execute("import: quilt status")?;
+    }
+
+    Ok(())
+}
+
+/// A patch entry of a quilt series file.
+pub(super) struct SeriesEntry {
+    /// Patch file name, relative to the series file's directory.
+    pub(super) name: String,
+
+    /// Strip level from a "-p<n>" option.
+    pub(super) strip_level: Option<usize>,
+
+    /// Whether the patch is to be applied in reverse, from a "-R" option.
+    pub(super) reverse: bool,
+}
+
+/// Parse the entries of a quilt series file.
+///
+/// Each non-empty line has a patch file name optionally followed by "-p<n>" and "-R"
+/// options. Comments following '#' are ignored.
+pub(super) fn parse_series(series: &[u8]) -> Result<Vec<SeriesEntry>> {
+    let mut entries = Vec::new();
     for line in series.lines() {
         let line = line
             .find_char('#')
@@ -400,37 +670,46 @@ fn import_series(
         }
 
         let mut fields = line.fields_with(|c| c.is_ascii_whitespace());
-        let raw_patchname = fields
+        let name = fields
             .next()
             .expect("non-empty line must have first field")
-            .to_os_str()
-            .context("converting patch name from series to file name")?;
-        let patch_path = source_path.map_or_else(
-            || PathBuf::from(raw_patchname),
-            |p| p.with_file_name(raw_patchname),
-        );
-
-        let strip_level = if let Some(extra) = fields.next() {
-            if extra.starts_with_str("-p") {
-                if extra == b"-p0" {
-                    Some(0)
-                } else {
-                    return Err(anyhow!(
-                        "patch `{}` has unsupported strip level \"{}\"",
-                        raw_patchname.to_string_lossy(),
-                        extra.to_str_lossy()
-                    ));
-                }
+            .to_str()
+            .map_err(|_| anyhow!("series has non-UTF-8 patch name"))?
+            .to_string();
+
+        let mut strip_level = None;
+        let mut reverse = false;
+        for option in fields {
+            if option == b"-R" {
+                reverse = true;
+            } else if let Some(level) = option.strip_prefix(b"-p") {
+                strip_level = Some(
+                    level
+                        .to_str()
+                        .ok()
+                        .and_then(|level| level.parse::<usize>().ok())
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "patch `{name}` has invalid strip level \"{}\"",
+                                option.to_str_lossy()
+                            )
+                        })?,
+                );
             } else {
-                None
+                return Err(anyhow!(
+                    "patch `{name}` has unsupported option \"{}\"",
+                    option.to_str_lossy()
+                ));
             }
-        } else {
-            None
-        };
+        }
 
-        stack = import_file(stack, matches, Some(patch_path.as_path()), strip_level)?;
+        entries.push(SeriesEntry {
+            name,
+            strip_level,
+            reverse,
+        });
     }
-    Ok(())
+    Ok(entries)
 }
 
 #[cfg(feature = "import-compressed")]
@@ -460,71 +739,597 @@ fn import_mail(stack: Stack, matches: &clap::ArgMatches, source_path: Option<&Pa
     let message_id = use_message_id(matches, &stack.repo.config_snapshot());
     let stupid = stack.repo.stupid();
     let num_patches = stupid.mailsplit(source_path, out_dir.path(), keep_cr, missing_from_ok)?;
-    let mut stack = stack;
+    let mut mails = Vec::with_capacity(num_patches);
     for i in 1..=num_patches {
         let patch_path = out_dir.path().join(format!("{i:04}"));
         let patch_file = std::fs::File::open(patch_path)?;
-        let (mailinfo, message, diff) = stupid.mailinfo(Some(patch_file), message_id)?;
-        let headers = Headers::parse_mailinfo(mailinfo.as_bstr()).unwrap_or_default();
+        let (mailinfo, message, diff) = stupid.mailinfo(Some(patch_file), message_id, true)?;
+        let mut headers = Headers::parse_mailinfo(mailinfo.as_bstr()).unwrap_or_default();
+        let subject = headers.subject.take().unwrap_or_default();
+        let subject = EmailSubject::parse(&subject);
+        if !subject.title.is_empty() {
+            headers.subject = Some(subject.title.to_string());
+        }
+        mails.push(Mail {
+            headers,
+            message,
+            diff,
+            is_reply: subject.is_reply,
+            version: subject.version,
+            number: subject.number,
+        });
+    }
+
+    let mails = if matches.get_flag("mbox") {
+        select_series(matches, mails)?
+    } else {
+        mails
+    };
+
+    let mut stack = stack;
+    for mail in mails {
         stack = create_patch(
             stack,
             matches,
             None,
+            mail.headers,
+            mail.message.as_bstr(),
+            mail.diff.as_bstr(),
+            None,
+            false,
+        )?;
+    }
+    Ok(())
+}
+
+/// Patch email split from a mail file or mbox.
+struct Mail {
+    headers: Headers,
+    message: BString,
+    diff: BString,
+    is_reply: bool,
+    version: Option<usize>,
+    number: Option<(usize, usize)>,
+}
+
+/// Patch emails of one revision of a series, by patch number.
+#[derive(Default)]
+struct Revision {
+    len: usize,
+    mails: BTreeMap<usize, Mail>,
+}
+
+impl Revision {
+    fn missing(&self) -> impl Iterator<Item = usize> + '_ {
+        (1..=self.len).filter(move |number| !self.mails.contains_key(number))
+    }
+}
+
+/// Select the patch emails of one revision of a series from an mbox.
+///
+/// When none of the emails have a numbered subject, such as "[PATCH v2 1/3]", all the
+/// emails are imported in mbox order. Otherwise, the emails of the requested revision,
+/// or else the newest complete revision, are ordered by number. Cover letters, replies,
+/// and emails without a number are skipped.
+fn select_series(matches: &clap::ArgMatches, mails: Vec<Mail>) -> Result<Vec<Mail>> {
+    let requested_version = matches.get_one::<usize>("version").copied();
+
+    if !mails
+        .iter()
+        .any(|mail| !mail.is_reply && mail.number.is_some())
+    {
+        return if let Some(version) = requested_version {
+            Err(anyhow!(
+                "cannot select version {version}: no numbered patch emails found in mbox"
+            ))
+        } else {
+            Ok(mails)
+        };
+    }
+
+    let mut revisions: BTreeMap<usize, Revision> = BTreeMap::new();
+    for mail in mails {
+        if mail.is_reply {
+            continue;
+        }
+        if let Some((number, len)) = mail.number.filter(|(number, _)| *number > 0) {
+            let revision = revisions.entry(mail.version.unwrap_or(1)).or_default();
+            revision.len = revision.len.max(len);
+            // A resent patch email replaces an earlier one.
+            revision.mails.insert(number, mail);
+        }
+    }
+
+    let version = if let Some(version) = requested_version {
+        if !revisions.contains_key(&version) {
+            return Err(anyhow!("version {version} of the series not found in mbox"));
+        }
+        version
+    } else {
+        let newest = *revisions
+            .keys()
+            .next_back()
+            .ok_or_else(|| anyhow!("no patch emails found in mbox"))?;
+        let version = revisions
+            .iter()
+            .rev()
+            .find(|(_, revision)| revision.missing().next().is_none())
+            .map_or(newest, |(&version, _)| version);
+        if version != newest {
+            crate::print_info_message(
+                matches,
+                &format!(
+                    "version {newest} of the series is incomplete, importing version {version}"
+                ),
+            );
+        }
+        version
+    };
+
+    let revision = revisions.remove(&version).expect("revision is present");
+    for number in revision.missing() {
+        crate::print_warning_message(
+            matches,
+            &format!(
+                "patch {number}/{} of version {version} is missing from mbox",
+                revision.len
+            ),
+        );
+    }
+
+    Ok(revision.mails.into_values().collect())
+}
+
+/// Migrate a Guilt patch stack to StGit.
+///
+/// Guilt's applied patches are the topmost commits of the branch, as listed in its
+/// "status" file, and are uncommitted into applied patches. The other patches of the
+/// "series" file become unapplied patches.
+fn import_guilt(repo: &gix::Repository, matches: &clap::ArgMatches) -> Result<()> {
+    let stack = Stack::from_branch_locator(
+        repo,
+        matches.get_one::<BranchLocator>("from-guilt"),
+        InitializationPolicy::AutoInitialize,
+    )?;
+    let branch_name = stack.get_branch_name().to_string();
+    if stack.all_patches().next().is_some() {
+        return Err(anyhow!("branch `{branch_name}` already has StGit patches"));
+    }
+
+    let guilt_dir = repo.git_dir().join("patches").join(&branch_name);
+    let series = std::fs::read(guilt_dir.join("series"))
+        .with_context(|| format!("reading Guilt series for branch `{branch_name}`"))?;
+    let status = match std::fs::read(guilt_dir.join("status")) {
+        Ok(status) => status,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            return Err(e).with_context(|| format!("reading Guilt status for `{branch_name}`"))
+        }
+    };
+
+    // Series lines may have comments and guards following '#'.
+    let series: Vec<&str> = series
+        .lines()
+        .map(|line| line.find_char('#').map_or(line, |pos| &line[..pos]).trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.to_str()
+                .map_err(|_| anyhow!("Guilt series has non-UTF-8 patch name"))
+        })
+        .collect::<Result<_>>()?;
+
+    // Status lines are either "<commit-id>:<name>" or just "<name>".
+    let status: Vec<(Option<gix::ObjectId>, &str)> = status
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let line = line
+                .to_str()
+                .map_err(|_| anyhow!("Guilt status has non-UTF-8 patch name"))?;
+            Ok(line
+                .split_once(':')
+                .and_then(|(hex, name)| {
+                    gix::ObjectId::from_hex(hex.as_bytes())
+                        .ok()
+                        .map(|id| (Some(id), name))
+                })
+                .unwrap_or((None, line)))
+        })
+        .collect::<Result<_>>()?;
+
+    let mut patchnames: Vec<PatchName> = Vec::with_capacity(series.len());
+    let mut make_patchname = |name: &str| {
+        let patchname = PatchName::make(name, false, None).uniquify(&[], &patchnames);
+        patchnames.push(patchname.clone());
+        patchname
+    };
+
+    let mut applied_commits = Vec::with_capacity(status.len());
+    let mut commit = stack.get_branch_head().clone();
+    for (status_id, name) in status.iter().rev() {
+        if status_id.map_or(false, |id| id != commit.id) {
+            return Err(anyhow!(
+                "Guilt patch `{name}` is not commit `{}` on branch `{branch_name}`",
+                commit.id
+            ));
+        }
+        if commit.parent_ids().count() != 1 {
+            return Err(anyhow!(
+                "Guilt patch `{name}` commit `{}` does not have exactly one parent",
+                commit.id
+            ));
+        }
+        let parent = Rc::new(commit.get_parent_commit()?);
+        applied_commits.push(std::mem::replace(&mut commit, parent).id);
+    }
+    applied_commits.reverse();
+    let applied: Vec<(PatchName, gix::ObjectId)> = status
+        .iter()
+        .zip(applied_commits)
+        .map(|((_, name), commit_id)| (make_patchname(name), commit_id))
+        .collect();
+
+    let mut unapplied: Vec<(PatchName, gix::ObjectId)> = Vec::new();
+    let mut parent_id = stack.get_branch_head().id;
+    for &name in series
+        .iter()
+        .filter(|&&name| !status.iter().any(|&(_, applied_name)| applied_name == name))
+    {
+        let content = std::fs::read(guilt_dir.join(name))
+            .with_context(|| format!("reading Guilt patch `{name}`"))?;
+        parent_id = commit_patch_file(repo, parent_id, content, None, false)
+            .with_context(|| format!("importing Guilt patch `{name}`"))?;
+        unapplied.push((make_patchname(name), parent_id));
+    }
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(false)
+        .with_output_stream(get_color_stdout(matches))
+        .set_head(false)
+        .transact(|trans| {
+            trans.uncommit_patches(
+                applied
+                    .iter()
+                    .map(|(patchname, commit_id)| (patchname, *commit_id)),
+            )?;
+            for (pos, (patchname, commit_id)) in unapplied.iter().enumerate() {
+                trans.new_unapplied(patchname, *commit_id, pos)?;
+            }
+            Ok(())
+        })
+        .execute("import: from guilt")?;
+
+    Ok(())
+}
+
+/// TopGit topic branch.
+struct Topic {
+    base_id: gix::ObjectId,
+    head_id: gix::ObjectId,
+    deps: Vec<String>,
+    topmsg: Vec<u8>,
+}
+
+/// Migrate the TopGit topic branches based on the current branch to StGit.
+///
+/// Each topic's patch is the difference between the topic's base, found under
+/// `refs/top-bases/`, and the topic's head. The patches are applied to the stack in
+/// dependency order.
+fn import_topgit(stack: Stack, matches: &clap::ArgMatches) -> Result<()> {
+    let repo = stack.repo;
+    let branch_name = stack.get_branch_name().to_string();
+
+    let mut topics: BTreeMap<String, Topic> = BTreeMap::new();
+    for reference in repo.references()?.all()?.filter_map(Result::ok) {
+        let refname = reference.name().as_bstr();
+        let topic_name = if let Some(topic_name) = refname
+            .strip_prefix(b"refs/top-bases/")
+            .or_else(|| refname.strip_prefix(b"refs/heads/{top-bases}/"))
+            .and_then(|topic_name| topic_name.to_str().ok())
+        {
+            topic_name.to_string()
+        } else {
+            continue;
+        };
+        let head_refname = format!("refs/heads/{topic_name}");
+        let head_id = repo
+            .try_find_reference(head_refname.as_str())?
+            .ok_or_else(|| anyhow!("TopGit topic branch `{topic_name}` not found"))?
+            .into_fully_peeled_id()?
+            .detach();
+        let head_commit = repo.find_commit(head_id)?;
+        let deps = read_topgit_file(&head_commit, ".topdeps")?
+            .lines()
+            .filter_map(|line| line.trim().to_str().ok())
+            .filter(|dep| !dep.is_empty())
+            .map(ToString::to_string)
+            .collect();
+        let topmsg = read_topgit_file(&head_commit, ".topmsg")?;
+        let base_id = reference.into_fully_peeled_id()?.detach();
+        topics.insert(
+            topic_name,
+            Topic {
+                base_id,
+                head_id,
+                deps,
+                topmsg,
+            },
+        );
+    }
+
+    let mut order: Vec<&str> = Vec::new();
+    let mut visiting: Vec<&str> = Vec::new();
+    for topic_name in topics.keys() {
+        order_topics(&topics, topic_name, &branch_name, &mut visiting, &mut order)?;
+    }
+    if order.is_empty() {
+        return Err(anyhow!(
+            "no TopGit topic branches based on `{branch_name}` found"
+        ));
+    }
+
+    let stupid = repo.stupid();
+    let name_len_limit = PatchName::get_length_limit(&repo.config_snapshot());
+    let mut disallow: Vec<PatchName> = stack.all_patches().cloned().collect();
+    let mut patches: Vec<(PatchName, gix::ObjectId)> = Vec::with_capacity(order.len());
+    let mut parent_id = stack.get_branch_head().id;
+    for topic_name in order {
+        let topic = &topics[topic_name];
+        let diff = stupid.diff_tree_patch(
+            repo.find_commit(topic.base_id)?.tree_id()?.detach(),
+            repo.find_commit(topic.head_id)?.tree_id()?.detach(),
+            Some([":(top,exclude).topdeps", ":(top,exclude).topmsg"]),
+            false,
+            ["--binary"],
+        )?;
+        let (mut headers, message) = Headers::parse_message(topic.topmsg.as_bstr())?;
+        headers.subject = Some(
+            headers
+                .subject
+                .as_deref()
+                .map(|subject| EmailSubject::parse(subject).title)
+                .filter(|title| !title.is_empty())
+                .unwrap_or(topic_name)
+                .to_string(),
+        );
+        parent_id = commit_diff(
+            repo,
+            parent_id,
             headers,
             message.as_bstr(),
             diff.as_bstr(),
             None,
-        )?;
+            false,
+        )
+        .with_context(|| format!("importing TopGit topic `{topic_name}`"))?;
+        let patchname = PatchName::make(
+            topic_name.strip_prefix("t/").unwrap_or(topic_name),
+            true,
+            name_len_limit,
+        )
+        .uniquify(&[], &disallow);
+        disallow.push(patchname.clone());
+        patches.push((patchname, parent_id));
     }
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            for (patchname, commit_id) in &patches {
+                trans.new_applied(patchname, *commit_id)?;
+            }
+            Ok(())
+        })
+        .execute("import: from topgit")?;
+
     Ok(())
 }
 
-#[cfg(feature = "import-compressed")]
-fn read_gz(source_file: std::fs::File, content: &mut Vec<u8>) -> Result<()> {
-    flate2::read::GzDecoder::new(source_file).read_to_end(content)?;
-    Ok(())
+/// Order a TopGit topic after the topics it depends on.
+///
+/// Only topics that depend, directly or indirectly, on the branch are added to
+/// `order`. Returns whether the topic depends on the branch.
+fn order_topics<'a>(
+    topics: &'a BTreeMap<String, Topic>,
+    topic_name: &'a str,
+    branch_name: &str,
+    visiting: &mut Vec<&'a str>,
+    order: &mut Vec<&'a str>,
+) -> Result<bool> {
+    if order.contains(&topic_name) {
+        return Ok(true);
+    } else if visiting.contains(&topic_name) {
+        return Err(anyhow!(
+            "TopGit topic `{topic_name}` has circular dependencies"
+        ));
+    }
+    let topic = if let Some(topic) = topics.get(topic_name) {
+        topic
+    } else {
+        return Ok(topic_name == branch_name);
+    };
+
+    visiting.push(topic_name);
+    let mut is_based = false;
+    for dep in &topic.deps {
+        is_based |= order_topics(topics, dep, branch_name, visiting, order)?;
+    }
+    visiting.pop();
+
+    if is_based {
+        order.push(topic_name);
+    }
+    Ok(is_based)
 }
 
-#[cfg(not(feature = "import-compressed"))]
-fn read_gz(_source_file: std::fs::File, _content: &mut Vec<u8>) -> Result<()> {
-    Err(anyhow!(
-        "StGit not built with support for compressed patches"
-    ))
+/// Read one of the `.topdeps` or `.topmsg` files from a TopGit topic's head commit.
+fn read_topgit_file(commit: &gix::Commit, path: &str) -> Result<Vec<u8>> {
+    if let Some(entry) = commit.tree()?.lookup_entry_by_path(path)? {
+        let blob = entry.object()?.peel_to_kind(gix::objs::Kind::Blob)?;
+        Ok(blob.data.clone())
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Commit the contents of a patch file on top of a parent commit.
+///
+/// The patch's description and author details are parsed the same way as for a
+/// patch file imported to the stack. The index and worktree are not used.
+pub(super) fn commit_patch_file(
+    repo: &gix::Repository,
+    parent_id: gix::ObjectId,
+    content: Vec<u8>,
+    strip_level: Option<usize>,
+    reverse: bool,
+) -> Result<gix::ObjectId> {
+    let (message, diff) = split_patch(content)?;
+    let (headers, message) = Headers::parse_message(message.as_ref())?;
+    commit_diff(
+        repo,
+        parent_id,
+        headers,
+        message.as_bstr(),
+        diff.as_bstr(),
+        strip_level,
+        reverse,
+    )
+}
+
+/// Commit a diff on top of a parent commit without using the index or worktree.
+fn commit_diff(
+    repo: &gix::Repository,
+    parent_id: gix::ObjectId,
+    headers: Headers,
+    message: &BStr,
+    diff: &BStr,
+    strip_level: Option<usize>,
+    reverse: bool,
+) -> Result<gix::ObjectId> {
+    let parent_tree_id = repo.find_commit(parent_id)?.tree_id()?.detach();
+    let trimmed_diff = diff.trim_end_with(|c| c.is_ascii_whitespace());
+    let tree_id = if trimmed_diff.is_empty() || trimmed_diff == b"---" {
+        parent_tree_id
+    } else {
+        repo.stupid().with_temp_index(|stupid_temp| {
+            stupid_temp.read_tree(parent_tree_id)?;
+            stupid_temp.apply_to_index(diff, strip_level, reverse)?;
+            stupid_temp.write_tree()
+        })?
+    };
+    let author = make_author(
+        repo,
+        headers.author_name.as_deref(),
+        headers.author_email.as_deref(),
+        headers.author_date.as_deref(),
+        headers.partial_author,
+    )?;
+    let message = make_message(headers.subject, message);
+    repo.commit_ex(
+        &author,
+        repo.get_committer()?,
+        &Message::from(message),
+        tree_id,
+        [parent_id],
+    )
 }
 
 #[cfg(feature = "import-compressed")]
-fn read_bz2(source_file: std::fs::File, content: &mut Vec<u8>) -> Result<()> {
-    bzip2::read::BzDecoder::new(source_file).read_to_end(content)?;
-    Ok(())
+fn decompress(compression: Compression, source: &[u8]) -> Result<Vec<u8>> {
+    let mut content = Vec::with_capacity(source.len() * 4);
+    match compression {
+        Compression::Gzip => flate2::read::GzDecoder::new(source).read_to_end(&mut content),
+        Compression::Bzip2 => bzip2::read::BzDecoder::new(source).read_to_end(&mut content),
+        #[cfg(feature = "import-xz")]
+        Compression::Xz => xz2::read::XzDecoder::new(source).read_to_end(&mut content),
+        #[cfg(not(feature = "import-xz"))]
+        Compression::Xz => {
+            return Err(anyhow!(
+                "StGit not built with support for xz compressed patches"
+            ))
+        }
+        #[cfg(feature = "import-zstd")]
+        Compression::Zstd => zstd::stream::read::Decoder::new(source)?.read_to_end(&mut content),
+        #[cfg(not(feature = "import-zstd"))]
+        Compression::Zstd => {
+            return Err(anyhow!(
+                "StGit not built with support for zstd compressed patches"
+            ))
+        }
+    }?;
+    Ok(content)
 }
 
 #[cfg(not(feature = "import-compressed"))]
-fn read_bz2(_source_file: std::fs::File, _content: &mut Vec<u8>) -> Result<()> {
+fn decompress(_compression: Compression, _source: &[u8]) -> Result<Vec<u8>> {
     Err(anyhow!(
         "StGit not built with support for compressed patches"
     ))
 }
 
+/// Read the single patch file contained in a zip archive.
+#[cfg(feature = "import-zip")]
+fn read_zip(source: Vec<u8>) -> Result<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(source))?;
+    let file_names: Vec<String> = archive
+        .file_names()
+        .filter(|file_name| !file_name.ends_with('/'))
+        .map(String::from)
+        .collect();
+    let file_name = match file_names.as_slice() {
+        [file_name] => file_name,
+        [] => return Err(anyhow!("zip archive does not contain a patch")),
+        _ => {
+            return Err(anyhow!(
+                "zip archive contains multiple files; use `--series` to import a series"
+            ))
+        }
+    };
+    let mut content = Vec::with_capacity(4096);
+    archive.by_name(file_name)?.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+#[cfg(not(feature = "import-zip"))]
+fn read_zip(_source: Vec<u8>) -> Result<Vec<u8>> {
+    Err(anyhow!("StGit not built with support for zip archives"))
+}
+
 fn import_file<'repo>(
     stack: Stack<'repo>,
     matches: &clap::ArgMatches,
     source_path: Option<&Path>,
     strip_level: Option<usize>,
+    reverse: bool,
 ) -> Result<Stack<'repo>> {
     let mut content = Vec::with_capacity(4096);
-    if let Some(source_path) = source_path {
-        let source_file = std::fs::File::open(source_path)?;
-        match source_path.extension().and_then(std::ffi::OsStr::to_str) {
-            Some("gz") => read_gz(source_file, &mut content)?,
-            Some("bz2") => read_bz2(source_file, &mut content)?,
-            _ => {
-                let mut source_file = source_file;
-                source_file.read_to_end(&mut content)?;
-            }
-        }
+    let file_name = if let Some(source_path) = source_path {
+        let mut source_file = std::fs::File::open(source_path)?;
+        source_file.read_to_end(&mut content)?;
+        source_path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default()
     } else {
         let stdin = std::io::stdin();
         stdin.lock().read_to_end(&mut content)?;
+        String::new()
+    };
+
+    // Compressed patches are recognized by their file name's extension or, failing
+    // that, by the magic bytes at the start of the content.
+    let content = if file_name.ends_with(".zip") || content.starts_with(ZIP_MAGIC) {
+        read_zip(content)?
+    } else if let Some(compression) =
+        Compression::from_file_name(&file_name).or_else(|| Compression::from_magic(&content))
+    {
+        decompress(compression, content.as_slice())?
+    } else {
+        content
     };
 
     let (message, diff) = split_patch(content)?;
@@ -554,9 +1359,11 @@ fn import_file<'repo>(
         message.as_bstr(),
         diff.as_bstr(),
         strip_level,
+        reverse,
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_patch<'repo>(
     stack: Stack<'repo>,
     matches: &clap::ArgMatches,
@@ -565,6 +1372,7 @@ fn create_patch<'repo>(
     message: &BStr,
     diff: &BStr,
     strip_level: Option<usize>,
+    reverse: bool,
 ) -> Result<Stack<'repo>> {
     let config = stack.repo.config_snapshot();
 
@@ -575,15 +1383,10 @@ fn create_patch<'repo>(
         author_date,
         subject,
         message_id: _message_id,
+        partial_author,
     } = headers;
 
-    let message = if let Some(mut subject) = subject {
-        subject.push_str("\n\n");
-        subject.push_str(&message.to_str_lossy());
-        subject
-    } else {
-        message.to_str_lossy().to_string()
-    };
+    let message = make_message(subject, message);
 
     let patchname = if patchname.is_some() {
         patchname.as_deref()
@@ -622,27 +1425,13 @@ fn create_patch<'repo>(
         patchname
     };
 
-    let author_date = author_date.and_then(|date| gix::actor::Time::parse_time(&date).ok());
-    let author = if let (Some(name), Some(email), Some(time)) =
-        (author_name.as_deref(), author_email.as_deref(), author_date)
-    {
-        gix::actor::Signature {
-            name: BString::from(name),
-            email: BString::from(email),
-            time,
-        }
-    } else {
-        let default_author = stack.repo.get_author()?;
-        if let (Some(name), Some(email)) = (author_name.as_deref(), author_email.as_deref()) {
-            gix::actor::Signature {
-                name: BString::from(name),
-                email: BString::from(email),
-                time: default_author.time,
-            }
-        } else {
-            default_author.to_owned()
-        }
-    };
+    let author = make_author(
+        stack.repo,
+        author_name.as_deref(),
+        author_email.as_deref(),
+        author_date.as_deref(),
+        partial_author,
+    )?;
 
     let strip_level = strip_level.or_else(|| matches.get_one::<usize>("strip").copied());
 
@@ -656,6 +1445,7 @@ fn create_patch<'repo>(
             diff,
             matches.get_flag("reject"),
             matches.get_flag("3way"),
+            reverse,
             strip_level,
             matches
                 .get_one::<PathBuf>("directory")
@@ -702,6 +1492,57 @@ fn create_patch<'repo>(
         .execute(&format!("import: {new_patchname}"))
 }
 
+/// Make the patch message from the subject and the remaining message.
+fn make_message(subject: Option<String>, message: &BStr) -> String {
+    if let Some(mut subject) = subject {
+        subject.push_str("\n\n");
+        subject.push_str(&message.to_str_lossy());
+        subject
+    } else {
+        message.to_str_lossy().to_string()
+    }
+}
+
+/// Make the patch author from the imported author details.
+///
+/// The configured author is used if the name or email is missing, and the current
+/// time is used if the date is missing or invalid. With `partial_author`, only the
+/// missing details are taken from the configured author.
+fn make_author(
+    repo: &gix::Repository,
+    name: Option<&str>,
+    email: Option<&str>,
+    date: Option<&str>,
+    partial_author: bool,
+) -> Result<gix::actor::Signature> {
+    let date = date.and_then(|date| gix::actor::Time::parse_time(date).ok());
+    let author = if let (Some(name), Some(email), Some(time)) = (name, email, date) {
+        gix::actor::Signature {
+            name: BString::from(name),
+            email: BString::from(email),
+            time,
+        }
+    } else {
+        let default_author = repo.get_author()?;
+        if partial_author {
+            gix::actor::Signature {
+                name: name.map_or_else(|| default_author.name.to_owned(), BString::from),
+                email: email.map_or_else(|| default_author.email.to_owned(), BString::from),
+                time: date.unwrap_or(default_author.time),
+            }
+        } else if let (Some(name), Some(email)) = (name, email) {
+            gix::actor::Signature {
+                name: BString::from(name),
+                email: BString::from(email),
+                time: default_author.time,
+            }
+        } else {
+            default_author.to_owned()
+        }
+    };
+    Ok(author)
+}
+
 fn stripname(name: &str) -> &str {
     let name = name.trim_start_matches(|c: char| c.is_ascii_digit() || c == '-');
         name.rsplit_once(".diff")
@@ -734,10 +1575,28 @@ fn split_patch(content: Vec<u8>) -> Result<(BString, BString)> {
     Ok((message, diff))
 }
 
+/// Convert an `hg export` date, such as "1234567890 -3600", to Git's raw format.
+///
+/// Mercurial's timezone offset is in seconds west of UTC, whereas Git's is in hours and
+/// minutes east of UTC.
+fn parse_hg_date(date: &str) -> Option<String> {
+    let mut parts = date.split_whitespace();
+    let seconds = parts.next()?.parse::<i64>().ok()?;
+    let offset = parts.next().map_or(Ok(0), str::parse::<i64>).ok()?;
+    let sign = if offset > 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    Some(format!(
+        "{seconds} {sign}{:02}{:02}",
+        offset / 3600,
+        offset % 3600 / 60
+    ))
+}
+
 #[cfg(test)]
 mod test {
     use bstr::B;
 
+    use super::parse_hg_date;
     use super::split_patch;
     use super::stripname;
 
@@ -837,6 +1696,23 @@ mod test {
         let name = String::from("01-patch-name.diff.patch");
         assert_eq!(stripname(&name), "patch-name");
     }
+
+    #[test]
+    fn hg_dates() {
+        assert_eq!(
+            parse_hg_date("1234567890 -3600").as_deref(),
+            Some("1234567890 +0100")
+        );
+        assert_eq!(
+            parse_hg_date("1234567890 16200").as_deref(),
+            Some("1234567890 -0430")
+        );
+        assert_eq!(
+            parse_hg_date("1234567890 0").as_deref(),
+            Some("1234567890 +0000")
+        );
+        assert_eq!(parse_hg_date("yesterday"), None);
+    }
 }
 
 #[derive(Default, Debug)]
@@ -847,6 +1723,10 @@ struct Headers {
     author_date: Option<String>,
     subject: Option<String>,
     message_id: Option<String>,
+
+    /// Whether only the missing author details are taken from the configured author,
+    /// as for `hg export` users without an email address.
+    partial_author: bool,
 }
 
 impl Headers {
@@ -888,6 +1768,7 @@ impl Headers {
                 author_date,
                 subject,
                 message_id: None,
+                partial_author: false,
             })
         } else {
             None
@@ -895,6 +1776,10 @@ impl Headers {
     }
 
     fn parse_message(message: &BStr) -> Result<(Headers, BString)> {
+        if message.starts_with(b"# HG changeset patch") {
+            return Self::parse_hg_export(message);
+        }
+
         let mut headers = Headers::default();
         let mut dedent = "";
         let mut split_message = BString::from(Vec::with_capacity(message.len()));
@@ -979,4 +1864,65 @@ impl Headers {
 
         Ok((headers, split_message))
     }
+
+    /// Parse the header block and message of a patch from `hg export`.
+    ///
+    /// The "# User" and "# Date" lines provide the author and date. Since Mercurial
+    /// users are free-form, a user without an email address only provides the author
+    /// name, and an otherwise unparsable user is ignored in favor of the default author.
+    /// The other header lines, such as "# Node ID" and "# Parent", are ignored. The
+    /// first non-empty line after the header block is the subject.
+    fn parse_hg_export(message: &BStr) -> Result<(Headers, BString)> {
+        let mut headers = Headers {
+            partial_author: true,
+            ..Headers::default()
+        };
+        let mut lines = message.lines_with_terminator().peekable();
+
+        while let Some(line) = lines.next_if(|line| line.starts_with(b"#")) {
+            let line = line.trim_end();
+            if let Some(user) = line.strip_prefix(b"# User ") {
+                let user = user
+                    .to_str()
+                    .map_err(|_| anyhow!("User is not UTF-8"))
+                    .context("parsing `# User` header")?
+                    .trim();
+                if let Ok((name, email)) = patchedit::parse_name_email(user) {
+                    headers.author_name = Some(name.to_string());
+                    headers.author_email = Some(email.to_string());
+                } else if !user.is_empty() && !user.contains(['<', '>']) {
+                    headers.author_name = Some(user.to_string());
+                }
+            } else if let Some(date) = line.strip_prefix(b"# Date ") {
+                headers.author_date = date.to_str().ok().and_then(parse_hg_date);
+            }
+        }
+
+        let mut split_message = BString::from(Vec::with_capacity(message.len()));
+        for line in lines {
+            if headers.subject.is_some() {
+                if !split_message.is_empty() || !line.trim().is_empty() {
+                    split_message.push_str(line);
+                }
+            } else if !line.trim().is_empty() {
+                headers.subject = Some(
+                    line.trim()
+                        .to_str()
+                        .map_err(|_| anyhow!("message is not UTF-8"))
+                        .context("parsing patch message")?
+                        .to_string(),
+                );
+            }
+        }
+
+        // The blank line separating the message from the diff is not part of the
+        // message.
+        let message_len = split_message.trim_end().len();
+        split_message.truncate(message_len);
+        if !split_message.is_empty() {
+            split_message.push(b'\n');
+        }
+
+        Ok((headers, split_message))
+    }
 }
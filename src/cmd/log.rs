@@ -2,15 +2,20 @@
 
 //! `stg log` implementation.
 
+use std::{io::Write, rc::Rc};
+
 use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
 use clap::{Arg, ArgMatches};
+use termcolor::WriteColor;
 
 use crate::{
     argset,
     branchloc::BranchLocator,
+    color::get_color_stdout,
     ext::RepositoryExtended,
-    patch::{patchrange, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackAccess},
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
+    stack::{InitializationPolicy, Stack, StackAccess, StackState, StackStateAccess},
     stupid::Stupid,
 };
 
@@ -32,6 +37,20 @@ fn make() -> clap::Command {
              through historical stack states. The 'stg reset' command may be used to \
              reset the stack directly to a historic state.\n\
              \n\
+             With '--graph', the stack history is rendered in the terminal. Each stack \
+             state is shown with its commit, operation, and time followed by the \
+             changes from the previous stack state: the patches created, deleted, \
+             renamed, modified, pushed, popped, hidden, and unhidden. A patch is \
+             considered modified if its commit changed without only its parent \
+             changing, e.g. when refreshed or edited, but not when merely rebased. \
+             With '--json', the same information is output as a JSON array with one \
+             object per stack state.\n\
+             \n\
+             The '--patch' option also limits the history to changes affecting the \
+             given patch, but unlike the [patch] arguments, the patch need not exist \
+             in the current stack. This allows showing the history of deleted or \
+             renamed patches.\n\
+             \n\
              The '--clear' option may be used to delete the stack's change history. \
              Undo and redo are unavailable on a stack without change history. Clearing \
              the stack state history cannot be undone.",
@@ -48,6 +67,15 @@ fn make() -> clap::Command {
                 // .allow_hyphen_values() breaks parsing of options such as "-n1"
                 .value_parser(clap::value_parser!(PatchRange)),
         )
+        .arg(
+            Arg::new("patch")
+                .long("patch")
+                .help("Only show history for <name>, which need not exist")
+                .value_name("name")
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(PatchName)),
+        )
         .arg(argset::branch_arg())
         .arg(
             Arg::new("diff")
@@ -79,13 +107,36 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with_all(["diff", "number", "full"]),
         )
+        .arg(
+            Arg::new("graph")
+                .long("graph")
+                .help("Show stack history as a graph with stack changes")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["diff", "full", "graphical"]),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Output stack history with stack changes in JSON format")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["diff", "full", "graphical", "graph"]),
+        )
         .arg(
             Arg::new("clear")
                 .long("clear")
                 .help("Clear the stack history")
                 // .exclusive(true),
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with_all(["patchranges-all", "diff", "number", "full", "graphical"]),
+                .conflicts_with_all([
+                    "patchranges-all",
+                    "patch",
+                    "diff",
+                    "number",
+                    "full",
+                    "graphical",
+                    "graph",
+                    "json",
+                ]),
         )
 }
 
@@ -100,18 +151,47 @@ fn run(matches: &ArgMatches) -> Result<()> {
     if matches.get_flag("clear") {
         stack.clear_state_log("clear log")
     } else {
-        let pathspecs: Option<Vec<String>> =
-            if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-all") {
-                Some(
-                    patchrange::resolve_names(&stack, range_specs, RangeConstraint::All)?
-                        .iter()
-                        .map(|pn| format!("patches/{pn}"))
-                        .collect(),
-                )
+        let patchnames: Option<Vec<PatchName>> =
+            if matches.contains_id("patchranges-all") || matches.contains_id("patch") {
+                let mut patchnames =
+                    if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-all") {
+                        patchrange::resolve_names(&stack, range_specs, RangeConstraint::All)?
+                    } else {
+                        Vec::new()
+                    };
+                if let Some(names) = matches.get_many::<PatchName>("patch") {
+                    for patchname in names {
+                        if !patchnames.contains(patchname) {
+                            patchnames.push(patchname.clone());
+                        }
+                    }
+                }
+                Some(patchnames)
             } else {
                 None
             };
 
+        if matches.get_flag("graph") || matches.get_flag("json") {
+            let num_entries = matches.get_one::<usize>("number").copied();
+            let entries = read_log_entries(&stack, patchnames.as_deref(), num_entries)?;
+            return if matches.get_flag("json") {
+                let stdout = std::io::stdout();
+                let mut stdout = stdout.lock();
+                serde_json::to_writer_pretty(&mut stdout, &entries)?;
+                writeln!(stdout)?;
+                Ok(())
+            } else {
+                write_graph(matches, &entries)
+            };
+        }
+
+        let pathspecs: Option<Vec<String>> = patchnames.map(|patchnames| {
+            patchnames
+                .iter()
+                .map(|pn| format!("patches/{pn}"))
+                .collect()
+        });
+
         let simplified_parent_id = stack
             .repo
             .find_reference(stack.get_stack_refname())?
@@ -139,3 +219,279 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     }
 }
+
+/// Stack state log entry shown with `--graph` and `--json`.
+#[derive(serde::Serialize)]
+struct LogEntry {
+    commit: String,
+    operation: String,
+    time: String,
+    #[serde(flatten)]
+    changes: StackChanges,
+}
+
+/// Changes to the patches of a stack from one stack state to the next.
+#[derive(Default, serde::Serialize)]
+struct StackChanges {
+    created: Vec<String>,
+    deleted: Vec<String>,
+    renamed: Vec<PatchRename>,
+    modified: Vec<String>,
+    pushed: Vec<String>,
+    popped: Vec<String>,
+    hidden: Vec<String>,
+    unhidden: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+struct PatchRename {
+    from: String,
+    to: String,
+}
+
+impl StackChanges {
+    fn affects(&self, patchname: &PatchName) -> bool {
+        let patchname: &str = patchname.as_ref();
+        [
+            &self.created,
+            &self.deleted,
+            &self.modified,
+            &self.pushed,
+            &self.popped,
+            &self.hidden,
+            &self.unhidden,
+        ]
+        .iter()
+        .any(|names| names.iter().any(|name| name == patchname))
+            || self
+                .renamed
+                .iter()
+                .any(|rename| rename.from == patchname || rename.to == patchname)
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (label, names) in [("created", &self.created), ("deleted", &self.deleted)] {
+            if !names.is_empty() {
+                lines.push(format!("{label}: {}", names.join(", ")));
+            }
+        }
+        if !self.renamed.is_empty() {
+            let renames: Vec<String> = self
+                .renamed
+                .iter()
+                .map(|rename| format!("{} -> {}", rename.from, rename.to))
+                .collect();
+            lines.push(format!("renamed: {}", renames.join(", ")));
+        }
+        for (label, names) in [
+            ("modified", &self.modified),
+            ("pushed", &self.pushed),
+            ("popped", &self.popped),
+            ("hidden", &self.hidden),
+            ("unhidden", &self.unhidden),
+        ] {
+            if !names.is_empty() {
+                lines.push(format!("{label}: {}", names.join(", ")));
+            }
+        }
+        lines
+    }
+}
+
+/// Read the stack state log, most recent state first.
+///
+/// Only entries affecting the given patches are read, if any patches are given.
+fn read_log_entries(
+    stack: &Stack,
+    patchnames: Option<&[PatchName]>,
+    num_entries: Option<usize>,
+) -> Result<Vec<LogEntry>> {
+    let repo = stack.repo;
+    let mut state_commit = Rc::new(
+        repo.find_reference(stack.get_stack_refname())?
+            .into_fully_peeled_id()?
+            .object()?
+            .try_into_commit()?,
+    );
+    let mut state = StackState::from_commit(repo, &state_commit)?;
+    let mut entries = Vec::new();
+
+    loop {
+        if num_entries.map_or(false, |n| entries.len() >= n) {
+            break;
+        }
+
+        let prev = if let Some(prev_commit) = state.prev.clone() {
+            let prev_state = StackState::from_commit(repo, &prev_commit)?;
+            Some((prev_commit, prev_state))
+        } else {
+            None
+        };
+
+        let changes = compare_states(prev.as_ref().map(|(_, prev_state)| prev_state), &state)?;
+        if patchnames.map_or(true, |patchnames| {
+            patchnames
+                .iter()
+                .any(|patchname| changes.affects(patchname))
+        }) {
+            // The simplified parent is the commit shown by plain `stg log`.
+            let commit_id = state_commit
+                .parent_ids()
+                .next()
+                .map_or(state_commit.id, |id| id.detach());
+            entries.push(LogEntry {
+                commit: commit_id.to_string(),
+                operation: state_commit
+                    .message_raw()?
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_str_lossy()
+                    .to_string(),
+                time: state_commit
+                    .decode()?
+                    .time()
+                    .format(gix::date::time::format::ISO8601),
+                changes,
+            });
+        }
+
+        if let Some((prev_commit, prev_state)) = prev {
+            state_commit = prev_commit;
+            state = prev_state;
+        } else {
+            break;
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Where a patch is in a stack state.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PatchLocation {
+    Applied,
+    Unapplied,
+    Hidden,
+}
+
+fn locate(state: &StackState, patchname: &PatchName) -> Option<PatchLocation> {
+    if state.applied().contains(patchname) {
+        Some(PatchLocation::Applied)
+    } else if state.unapplied().contains(patchname) {
+        Some(PatchLocation::Unapplied)
+    } else if state.hidden().contains(patchname) {
+        Some(PatchLocation::Hidden)
+    } else {
+        None
+    }
+}
+
+/// Determine the changes to the patches from the previous stack state to a stack state.
+///
+/// A patch that disappears while a patch with the same commit appears is considered
+/// renamed.
+fn compare_states(prev_state: Option<&StackState>, state: &StackState) -> Result<StackChanges> {
+    let mut changes = StackChanges::default();
+
+    let mut vanished: Vec<&PatchName> = prev_state
+        .map(|prev_state| {
+            prev_state
+                .all_patches()
+                .filter(|patchname| !state.has_patch(patchname))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    for patchname in state.all_patches() {
+        let location = locate(state, patchname);
+        let commit = state.get_patch_commit(patchname);
+
+        let prev_patchname = if let Some(prev_state) = prev_state {
+            if prev_state.has_patch(patchname) {
+                Some(patchname)
+            } else if let Some(pos) = vanished.iter().position(|prev_patchname| {
+                prev_state.get_patch_commit_id(prev_patchname) == commit.id
+            }) {
+                let prev_patchname = vanished.remove(pos);
+                changes.renamed.push(PatchRename {
+                    from: prev_patchname.to_string(),
+                    to: patchname.to_string(),
+                });
+                Some(prev_patchname)
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        let (prev_state, prev_patchname) =
+            if let (Some(prev_state), Some(prev_patchname)) = (prev_state, prev_patchname) {
+                (prev_state, prev_patchname)
+            } else {
+                changes.created.push(patchname.to_string());
+                continue;
+            };
+
+        let prev_location = locate(prev_state, prev_patchname);
+        let name = patchname.to_string();
+        if is_modified(prev_state.get_patch_commit(prev_patchname), commit)? {
+            changes.modified.push(name.clone());
+        }
+        if location == Some(PatchLocation::Applied) && prev_location != location {
+            changes.pushed.push(name.clone());
+        } else if prev_location == Some(PatchLocation::Applied) && prev_location != location {
+            changes.popped.push(name.clone());
+        }
+        if location == Some(PatchLocation::Hidden) && prev_location != location {
+            changes.hidden.push(name);
+        } else if prev_location == Some(PatchLocation::Hidden) && prev_location != location {
+            changes.unhidden.push(name);
+        }
+    }
+
+    changes.deleted = vanished.iter().map(ToString::to_string).collect();
+
+    Ok(changes)
+}
+
+/// Determine whether a patch's commit changed other than by being rebased.
+fn is_modified(prev_commit: &gix::Commit, commit: &gix::Commit) -> Result<bool> {
+    if prev_commit.id == commit.id {
+        Ok(false)
+    } else {
+        let prev_parent_id = prev_commit.parent_ids().next().map(|id| id.detach());
+        let parent_id = commit.parent_ids().next().map(|id| id.detach());
+        Ok(prev_parent_id == parent_id || prev_commit.message_raw()? != commit.message_raw()?)
+    }
+}
+
+/// Output the log entries as a graph.
+fn write_graph(matches: &ArgMatches, entries: &[LogEntry]) -> Result<()> {
+    let mut stdout = get_color_stdout(matches);
+    let mut color_spec = termcolor::ColorSpec::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let continuation = if i + 1 < entries.len() { '|' } else { ' ' };
+
+        write!(stdout, "* ")?;
+        stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Yellow)))?;
+        write!(stdout, "{}", &entry.commit[..7])?;
+        color_spec.clear();
+        stdout.set_color(&color_spec)?;
+        write!(stdout, " {} ", entry.operation)?;
+        stdout.set_color(color_spec.set_dimmed(true))?;
+        write!(stdout, "({})", entry.time)?;
+        color_spec.clear();
+        stdout.set_color(&color_spec)?;
+        writeln!(stdout)?;
+
+        for line in entry.changes.lines() {
+            writeln!(stdout, "{continuation}   {line}")?;
+        }
+    }
+
+    Ok(())
+}
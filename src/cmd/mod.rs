@@ -7,7 +7,10 @@
 
 use clap::builder::StyledStr;
 
+pub(crate) mod blame;
 pub(crate) mod branch;
+pub(crate) mod bundle;
+pub(crate) mod check;
 pub(crate) mod clean;
 pub(crate) mod commit;
 pub(crate) mod completion;
@@ -33,6 +36,8 @@ pub(crate) mod pop;
 pub(crate) mod prev;
 pub(crate) mod pull;
 pub(crate) mod push;
+pub(crate) mod quilt;
+pub(crate) mod range_diff;
 pub(crate) mod rebase;
 pub(crate) mod redo;
 pub(crate) mod refresh;
@@ -44,7 +49,9 @@ pub(crate) mod show;
 pub(crate) mod sink;
 pub(crate) mod spill;
 pub(crate) mod squash;
+pub(crate) mod status;
 pub(crate) mod sync;
+pub(crate) mod templates;
 pub(crate) mod top;
 pub(crate) mod uncommit;
 pub(crate) mod undo;
@@ -81,7 +88,10 @@ pub(crate) struct StGitCommand {
 /// This is used in [`crate::main`] for command line argument parsing and
 /// eventual dispatch of a subcommand.
 pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
+    blame::STGIT_COMMAND,
     branch::STGIT_COMMAND,
+    bundle::STGIT_COMMAND,
+    check::STGIT_COMMAND,
     clean::STGIT_COMMAND,
     commit::STGIT_COMMAND,
     completion::STGIT_COMMAND,
@@ -107,6 +117,8 @@ pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
     prev::STGIT_COMMAND,
     pull::STGIT_COMMAND,
     push::STGIT_COMMAND,
+    quilt::STGIT_COMMAND,
+    range_diff::STGIT_COMMAND,
     rebase::STGIT_COMMAND,
     redo::STGIT_COMMAND,
     refresh::STGIT_COMMAND,
@@ -118,7 +130,9 @@ pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
     sink::STGIT_COMMAND,
     spill::STGIT_COMMAND,
     squash::STGIT_COMMAND,
+    status::STGIT_COMMAND,
     sync::STGIT_COMMAND,
+    templates::STGIT_COMMAND,
     top::STGIT_COMMAND,
     uncommit::STGIT_COMMAND,
     undo::STGIT_COMMAND,
@@ -5,13 +5,13 @@
 use std::path::PathBuf;
 
 use anyhow::{anyhow, Result};
-use clap::{Arg, ArgGroup, ArgMatches};
+use clap::{Arg, ArgGroup, ArgMatches, ValueHint};
 
-use super::refresh;
+use super::{refresh, squash};
 use crate::{
     color::get_color_stdout,
     ext::{RepositoryExtended, SignatureExtended},
-    patch::{patchedit, PatchName},
+    patch::{patchedit, LocationConstraint, PatchLocator, PatchName},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
@@ -47,7 +47,11 @@ fn make() -> clap::Command {
              An editor will be launched to edit the commit message to be used for the \
              patch, unless the '--message' flag already specified one. The \
              'patchdescr.tmpl' template file (if available) is used to pre-fill the \
-             editor.",
+             editor. The template is used verbatim unless it starts with a '{# ... #}' \
+             comment, in which case it is rendered with the template language \
+             described for `stg export` and may use the 'name', 'authname', \
+             'authemail', 'authdate', 'commname', 'commemail', and 'commdate' \
+             variables.",
         )
         .override_usage(super::make_usage(
             "stg new",
@@ -90,6 +94,21 @@ fn make() -> clap::Command {
                 .value_parser(clap::value_parser!(PatchName))
                 .conflicts_with("patchname"),
         )
+        .arg(
+            Arg::new("fixup")
+                .long("fixup")
+                .help("Create a fixup! patch for <patch>")
+                .long_help(
+                    "Create a patch with the message \"fixup! <subject>\", where \
+                     <subject> is the subject of <patch>. Such patches are squashed \
+                     into <patch> by 'stg squash --auto' or 'stg rebase --autosquash'.",
+                )
+                .value_name("patch")
+                .value_hint(ValueHint::Other)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator))
+                .conflicts_with("save-template"),
+        )
         .next_help_heading("Refresh Options")
         .arg(
             Arg::new("refresh")
@@ -179,6 +198,15 @@ fn run(matches: &ArgMatches) -> Result<()> {
         Ok(None)
     }?;
 
+    let fixup_message = if let Some(patch_loc) = matches.get_one::<PatchLocator>("fixup") {
+        let target_patchname = patch_loc
+            .resolve_name(&stack)?
+            .constrain(&stack, LocationConstraint::Visible)?;
+        Some(squash::fixup_message(&stack, &target_patchname)?)
+    } else {
+        None
+    };
+
     let is_refreshing = matches.get_flag("refresh") || matches.contains_id("pathspecs");
 
     let tree_id = if is_refreshing {
@@ -189,17 +217,21 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     let parent_id = stack.get_branch_head().id;
 
-    let (patchname, commit_id) = match patchedit::EditBuilder::default()
+    let mut edit_builder = patchedit::EditBuilder::default()
         .allow_autosign(true)
         .allow_diff_edit(false)
-        .allow_implicit_edit(true)
+        .allow_implicit_edit(fixup_message.is_none())
         .allow_template_save(!is_refreshing)
         .original_patchname(patchname.as_ref())
         .default_author(repo.get_author()?.override_author(matches))
         .override_tree_id(tree_id)
-        .override_parent_id(parent_id)
-        .edit(&stack, &repo, matches)?
-    {
+        .override_parent_id(parent_id);
+
+    if let Some(message) = fixup_message {
+        edit_builder = edit_builder.default_message(message);
+    }
+
+    let (patchname, commit_id) = match edit_builder.edit(&stack, &repo, matches)? {
         patchedit::EditOutcome::TemplateSaved(_) => return Ok(()),
         patchedit::EditOutcome::Edited {
             new_patchname,
@@ -15,6 +15,7 @@ use crate::{
     print_info_message,
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
+    wrap::PartialRefName,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -50,7 +51,7 @@ fn make() -> clap::Command {
                 .short('n')
                 .help("Do not push back patches after pulling")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with("merged"),
+                .conflicts_with_all(["merged", "delete-merged"]),
         )
         .arg(argset::merged_arg().long_help(
             "Check for patches that may have been merged upstream.\n\
@@ -60,7 +61,9 @@ fn make() -> clap::Command {
              have already been merged upstream, the patch will still exist in the \
              stack, but become empty after the pull operation.",
         ))
+        .arg(argset::delete_merged_arg())
         .arg(argset::push_conflicts_arg())
+        .arg(argset::cascade_arg().conflicts_with("nopush"))
 }
 
 enum PullPolicy {
@@ -151,6 +154,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     stack.check_head_top_mismatch()?;
 
     let applied = stack.applied().to_vec();
+    let old_base_id = stack.base().id;
 
     stack
         .setup_transaction()
@@ -262,13 +266,31 @@ fn run(matches: &ArgMatches) -> Result<()> {
     if !matches.get_flag("nopush") {
         stack.check_head_top_mismatch()?;
         let check_merged = matches.get_flag("merged");
+        let upstream_ids = if matches.get_flag("delete-merged") {
+            Some(stupid.rev_list(old_base_id, stack.base().id, None::<Vec<&str>>)?)
+        } else {
+            None
+        };
         stack
             .setup_transaction()
             .use_index_and_worktree(true)
             .allow_push_conflicts(allow_push_conflicts)
             .with_output_stream(get_color_stdout(matches))
-            .transact(|trans| trans.push_patches(&applied, check_merged))
+            .transact(|trans| {
+                if let Some(upstream_ids) = upstream_ids.as_ref() {
+                    trans.push_patches_delete_merged(&applied, upstream_ids)
+                } else {
+                    trans.push_patches(&applied, check_merged)
+                }
+            })
             .execute("pull (reapply)")?;
+
+        let branch_name = PartialRefName::from_str(&branch_name)?;
+        if matches.get_flag("cascade") {
+            super::branch::cascade_rebase(&repo, matches, &branch_name, allow_push_conflicts)?;
+        } else {
+            super::branch::hint_stacked_children(&repo, matches, &branch_name)?;
+        }
     }
 
     if config.boolean("stgit.keepoptimized").unwrap_or(false) {
@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg quilt` implementation.
+
+mod record;
+mod sync;
+
+use anyhow::Result;
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "quilt",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Interoperate with quilt patch series")
+        .long_about(
+            "Interoperate with quilt patch series.\n\
+             \n\
+             Quilt keeps a series of patch files in a \"patches\" directory, in the \
+             order listed in the directory's \"series\" file. Such a series may be \
+             imported once with `stg import --series`. Use `stg quilt sync` to keep \
+             a quilt patches directory and the stack in step as either one changes.",
+        )
+        .subcommand_required(true)
+        .subcommand(sync::command())
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("sync", sub_matches)) => sync::dispatch(sub_matches),
+        _ => panic!("valid subcommand is expected"),
+    }
+}
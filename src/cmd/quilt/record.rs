@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Record of the last synchronization with a quilt patches directory.
+//!
+//! Each time `stg quilt sync` completes, a record is committed to the branch's
+//! `refs/stgit/quilt/<branch>` reference. The record commit's tree contains a single
+//! `sync.json` blob with the synchronized directory and, for each quilt patch file,
+//! the name and commit id of the corresponding StGit patch along with the blob id of
+//! the file's content. Comparing against the record is how a later sync determines
+//! which side of each patch has changed.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{
+    ext::{CommitOptions, RepositoryExtended},
+    patch::PatchName,
+    stack::quilt_sync_refname_from_branch_name,
+    wrap::Message,
+};
+
+/// Name of the blob containing the sync record.
+const SYNC_JSON: &str = "sync.json";
+
+/// Record of a synchronized quilt patches directory.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct SyncRecord {
+    /// Absolute path of the quilt patches directory.
+    pub(super) dir: String,
+
+    /// Synchronized patches, by quilt patch file name.
+    pub(super) patches: BTreeMap<String, PatchRecord>,
+}
+
+/// Record of one synchronized patch.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(super) struct PatchRecord {
+    pub(super) name: PatchName,
+
+    /// Commit id of the StGit patch.
+    pub(super) commit: String,
+
+    /// Blob id of the quilt patch file's content.
+    pub(super) blob: String,
+}
+
+/// Read the sync record of a branch, if any.
+pub(super) fn read(repo: &gix::Repository, branch_name: &str) -> Result<Option<SyncRecord>> {
+    let refname = quilt_sync_refname_from_branch_name(branch_name);
+    let commit = if let Some(record_ref) = repo.try_find_reference(refname.as_str())? {
+        record_ref
+            .into_fully_peeled_id()?
+            .object()?
+            .try_into_commit()?
+    } else {
+        return Ok(None);
+    };
+
+    let sync_json = commit
+        .tree()?
+        .lookup_entry_by_path(SYNC_JSON)?
+        .ok_or_else(|| anyhow!("`{SYNC_JSON}` not found in `{refname}` ({})", commit.id))?;
+    let sync_json_blob = sync_json.object()?.peel_to_kind(gix::objs::Kind::Blob)?;
+    let record =
+        serde_json::from_slice(&sync_json_blob.data).context("deserializing quilt sync record")?;
+    Ok(Some(record))
+}
+
+/// Write the sync record of a branch, replacing any previous record.
+pub(super) fn write(repo: &gix::Repository, branch_name: &str, record: &SyncRecord) -> Result<()> {
+    let refname = quilt_sync_refname_from_branch_name(branch_name);
+    let sync_json_id = repo.write_blob(serde_json::to_string_pretty(record)?.as_bytes())?;
+    let tree = gix::objs::Tree {
+        entries: vec![gix::objs::tree::Entry {
+            mode: gix::objs::tree::EntryMode::Blob,
+            filename: SYNC_JSON.into(),
+            oid: sync_json_id.detach(),
+        }],
+    };
+    let tree_id = repo.write_object(tree)?.detach();
+
+    let message = format!("quilt sync {}", record.dir);
+    let message = Message::from(message.as_str());
+    let commit_id = repo.commit_with_options(
+        repo.get_author()?,
+        repo.get_committer()?,
+        &message,
+        tree_id,
+        std::iter::empty(),
+        &CommitOptions {
+            commit_encoding: None,
+            gpgsign: false,
+        },
+    )?;
+
+    repo.reference(
+        refname.as_str(),
+        commit_id,
+        gix::refs::transaction::PreviousValue::Any,
+        message.raw_bytes(),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,507 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg quilt sync` implementation.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::Arg;
+
+use super::super::{
+    export,
+    import::{self, SeriesEntry},
+};
+use super::record::{self, PatchRecord, SyncRecord};
+use crate::{
+    argset,
+    color::get_color_stdout,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::PatchName,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::Stupid,
+    templates,
+};
+
+pub(super) fn command() -> clap::Command {
+    clap::Command::new("sync")
+        .about("Synchronize a quilt patches directory with the stack")
+        .long_about(
+            "Synchronize a quilt patches directory with the stack, in both \
+             directions.\n\
+             \n\
+             Each patch file listed in the directory's \"series\" file is matched to a \
+             StGit patch by file name. A patch named \"<name>\" is matched with the \
+             file \"<name>.patch\", \"<name>.diff\", or \"<name>\". New patch files \
+             are written as \"<name>.patch\", and a new quilt patch file becomes a \
+             patch named after the file without its extension. The matching is \
+             recorded so that subsequent syncs pair the same patches.\n\
+             \n\
+             Each sync carries the changes made on either side since the previous \
+             sync over to the other side:\n\
+             \n  - patches changed in StGit are exported to their quilt patch files\
+             \n  - changed quilt patch files update their StGit patches\
+             \n  - new patches on either side are added to the other side\
+             \n  - patches deleted on either side are deleted from the other side\
+             \n\
+             \n\
+             New quilt patches become unapplied StGit patches. Patches deleted from \
+             the stack are removed from the quilt series, but their patch files are \
+             kept. The series file is rewritten in stack order, keeping the '-p<n>' \
+             and '-R' options of patch files that were not rewritten.\n\
+             \n\
+             A patch changed on both sides is a conflict, as is a patch that differs \
+             between the two sides when there is no record of a previous sync. \
+             Conflicts are reported without changing either side unless '--prefer' \
+             is used to choose which side's version to keep.\n\
+             \n\
+             Hidden patches are not synchronized. Nor is which patches are applied; \
+             quilt's \".pc\" directory is left as-is.",
+        )
+        .arg(
+            Arg::new("dir")
+                .help("Quilt patches directory")
+                .value_name("dir")
+                .default_value("patches")
+                .value_hint(clap::ValueHint::DirPath)
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("prefer")
+                .long("prefer")
+                .help("Resolve conflicts by keeping the <side> version")
+                .value_name("side")
+                .value_parser(["quilt", "stgit"]),
+        )
+        .arg(argset::diff_opts_arg())
+}
+
+/// How a quilt patch file and its StGit patch are brought in step.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// Both sides are already the same.
+    None,
+
+    /// Write the StGit patch to the quilt patch file.
+    Export,
+
+    /// Update the StGit patch from the quilt patch file.
+    Update,
+
+    /// Create a new StGit patch from the quilt patch file.
+    Import,
+
+    /// Delete the StGit patch.
+    Delete,
+
+    /// Remove the quilt patch file from the series.
+    Drop,
+}
+
+/// A quilt patch file paired with a StGit patch.
+///
+/// Either side may be missing, but not both.
+struct Pair {
+    file_name: String,
+    patchname: Option<PatchName>,
+    entry: Option<SeriesEntry>,
+    content: Option<Vec<u8>>,
+    action: Action,
+}
+
+pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::current(&repo, InitializationPolicy::AutoInitialize)?;
+    repo.stupid()
+        .statuses(None)?
+        .check_index_and_worktree_clean()?;
+    stack.check_head_top_mismatch()?;
+
+    let branch_name = stack.get_branch_name().to_string();
+    let dir = matches
+        .get_one::<PathBuf>("dir")
+        .expect("dir has default value");
+    std::fs::create_dir_all(dir).with_context(|| format!("creating {dir:?}"))?;
+    let dir_key = gix::path::realpath(dir)?.to_string_lossy().to_string();
+
+    let series_path = dir.join("series");
+    let series = match std::fs::read(&series_path) {
+        Ok(series) => import::parse_series(&series)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e).with_context(|| format!("reading {series_path:?}")),
+    };
+
+    // A record of a sync with a different directory does not apply.
+    let recorded = record::read(&repo, &branch_name)?
+        .filter(|record| record.dir == dir_key)
+        .map_or_else(BTreeMap::new, |record| record.patches);
+
+    let patchnames: Vec<&PatchName> = stack.applied_and_unapplied().collect();
+
+    // Match patches to files as recorded by the previous sync, then by file name.
+    let mut file_names: HashMap<&PatchName, String> = HashMap::new();
+    for (file_name, patch_record) in &recorded {
+        if let Some(&patchname) = patchnames.iter().find(|&&pn| pn == &patch_record.name) {
+            file_names
+                .entry(patchname)
+                .or_insert_with(|| file_name.clone());
+        }
+    }
+    for &patchname in &patchnames {
+        if !file_names.contains_key(patchname) {
+            let candidates = [
+                format!("{patchname}.patch"),
+                format!("{patchname}.diff"),
+                patchname.to_string(),
+            ];
+            let file_name = candidates
+                .iter()
+                .find(|&candidate| {
+                    series.iter().any(|entry| &entry.name == candidate)
+                        && !recorded.contains_key(candidate)
+                        && !file_names.values().any(|file_name| file_name == candidate)
+                })
+                .unwrap_or(&candidates[0])
+                .clone();
+            file_names.insert(patchname, file_name);
+        }
+    }
+
+    let mut pairs: Vec<Pair> = Vec::with_capacity(series.len() + patchnames.len());
+    for entry in series {
+        if pairs.iter().any(|pair| pair.file_name == entry.name) {
+            continue;
+        }
+        let path = dir.join(&entry.name);
+        let content =
+            std::fs::read(&path).with_context(|| format!("reading quilt patch {path:?}"))?;
+        pairs.push(Pair {
+            file_name: entry.name.clone(),
+            patchname: file_names
+                .iter()
+                .find(|(_, file_name)| **file_name == entry.name)
+                .map(|(&patchname, _)| patchname.clone()),
+            entry: Some(entry),
+            content: Some(content),
+            action: Action::None,
+        });
+    }
+    for &patchname in &patchnames {
+        let file_name = &file_names[patchname];
+        if !pairs.iter().any(|pair| &pair.file_name == file_name) {
+            pairs.push(Pair {
+                file_name: file_name.clone(),
+                patchname: Some(patchname.clone()),
+                entry: None,
+                content: None,
+                action: Action::None,
+            });
+        }
+    }
+
+    let prefer = matches.get_one::<String>("prefer").map(String::as_str);
+    let mut conflicts: Vec<String> = Vec::new();
+    for pair in &mut pairs {
+        let patch_record = recorded.get(&pair.file_name).filter(|patch_record| {
+            pair.patchname
+                .as_ref()
+                .map_or(true, |patchname| patchname == &patch_record.name)
+        });
+        let commit_id = pair
+            .patchname
+            .as_ref()
+            .map(|patchname| stack.get_patch_commit(patchname).id.to_string());
+        let blob_id = if let Some(content) = pair.content.as_ref() {
+            Some(repo.write_blob(content)?.to_string())
+        } else {
+            None
+        };
+        let stgit_changed = patch_record.map_or(true, |r| Some(&r.commit) != commit_id.as_ref());
+        let quilt_changed = patch_record.map_or(true, |r| Some(&r.blob) != blob_id.as_ref());
+
+        // Conflicts are a reason along with the quilt and StGit resolutions.
+        let (action, conflict) = match (&pair.patchname, &pair.content, patch_record) {
+            (Some(_), Some(_), Some(_)) => match (quilt_changed, stgit_changed) {
+                (false, false) => (Action::None, None),
+                (true, false) => (Action::Update, None),
+                (false, true) => (Action::Export, None),
+                (true, true) => (
+                    Action::None,
+                    Some((
+                        "changed in both quilt and StGit",
+                        Action::Update,
+                        Action::Export,
+                    )),
+                ),
+            },
+            (Some(patchname), Some(content), None) => {
+                let entry = pair.entry.as_ref().expect("quilt file has series entry");
+                if is_same_change(&repo, stack.get_patch_commit(patchname), content, entry)? {
+                    (Action::None, None)
+                } else {
+                    (
+                        Action::None,
+                        Some((
+                            "differs between quilt and StGit",
+                            Action::Update,
+                            Action::Export,
+                        )),
+                    )
+                }
+            }
+            (Some(_), None, Some(_)) if stgit_changed => (
+                Action::None,
+                Some((
+                    "changed in StGit but removed from quilt series",
+                    Action::Delete,
+                    Action::Export,
+                )),
+            ),
+            (Some(_), None, Some(_)) => (Action::Delete, None),
+            (Some(_), None, None) => (Action::Export, None),
+            (None, Some(_), Some(_)) if quilt_changed => (
+                Action::None,
+                Some((
+                    "changed in quilt but deleted from StGit",
+                    Action::Import,
+                    Action::Drop,
+                )),
+            ),
+            (None, Some(_), Some(_)) => (Action::Drop, None),
+            (None, Some(_), None) => (Action::Import, None),
+            (None, None, _) => unreachable!("pair has a quilt file or StGit patch"),
+        };
+
+        pair.action = if let Some((reason, quilt_action, stgit_action)) = conflict {
+            match prefer {
+                Some("quilt") => quilt_action,
+                Some("stgit") => stgit_action,
+                _ => {
+                    conflicts.push(format!("`{}` {reason}", pair.file_name));
+                    action
+                }
+            }
+        } else {
+            action
+        };
+    }
+
+    if !conflicts.is_empty() {
+        for conflict in &conflicts {
+            crate::print_warning_message(matches, conflict);
+        }
+        return Err(anyhow!(
+            "{} conflicting patch(es); use `--prefer quilt` or `--prefer stgit` to resolve",
+            conflicts.len()
+        ));
+    }
+
+    // New and updated StGit patches are committed on the same parent as the patch
+    // preceding them in the quilt series.
+    let mut updates: Vec<(PatchName, gix::ObjectId)> = Vec::new();
+    let mut imports: Vec<(Option<PatchName>, PatchName, gix::ObjectId)> = Vec::new();
+    let mut deletes: Vec<PatchName> = Vec::new();
+    let mut all_patchnames: Vec<PatchName> = stack.all_patches().cloned().collect();
+    let mut prev: Option<(PatchName, gix::ObjectId)> = None;
+    for pair in &mut pairs {
+        let commit_id = match pair.action {
+            Action::Update => {
+                let patchname = pair.patchname.as_ref().expect("updated patch exists");
+                let parent_id = stack.get_patch_commit(patchname).get_parent_commit()?.id;
+                let commit_id = commit_pair(&repo, pair, parent_id)?;
+                updates.push((patchname.clone(), commit_id));
+                Some(commit_id)
+            }
+            Action::Import => {
+                let parent_id = prev
+                    .as_ref()
+                    .map_or(stack.get_branch_head().id, |(_, commit_id)| *commit_id);
+                let commit_id = commit_pair(&repo, pair, parent_id)?;
+                let patchname = PatchName::make(stem(&pair.file_name), false, None)
+                    .uniquify(&[], &all_patchnames);
+                all_patchnames.push(patchname.clone());
+                imports.push((
+                    prev.as_ref().map(|(patchname, _)| patchname.clone()),
+                    patchname.clone(),
+                    commit_id,
+                ));
+                pair.patchname = Some(patchname);
+                Some(commit_id)
+            }
+            Action::Delete => {
+                deletes.push(pair.patchname.clone().expect("deleted patch exists"));
+                None
+            }
+            Action::Drop => None,
+            Action::None | Action::Export => pair
+                .patchname
+                .as_ref()
+                .map(|patchname| stack.get_patch_commit(patchname).id),
+        };
+        if let (Some(patchname), Some(commit_id), Some(_)) =
+            (pair.patchname.as_ref(), commit_id, pair.entry.as_ref())
+        {
+            prev = Some((patchname.clone(), commit_id));
+        }
+    }
+
+    let stack = if updates.is_empty() && imports.is_empty() && deletes.is_empty() {
+        stack
+    } else {
+        let applied = stack.applied();
+        let to_pop: Vec<PatchName> = applied
+            .iter()
+            .position(|pn| deletes.contains(pn) || updates.iter().any(|(updated, _)| updated == pn))
+            .map_or_else(Vec::new, |pos| applied[pos..].to_vec());
+        let to_push: Vec<&PatchName> = to_pop.iter().filter(|pn| !deletes.contains(pn)).collect();
+
+        stack
+            .setup_transaction()
+            .use_index_and_worktree(true)
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| {
+                trans.pop_patches(|pn| to_pop.contains(pn))?;
+                trans.delete_patches(|pn| deletes.contains(pn))?;
+                for (patchname, commit_id) in &updates {
+                    trans.update_patch(patchname, *commit_id)?;
+                }
+                for (prev_patchname, patchname, commit_id) in &imports {
+                    let insert_pos = prev_patchname
+                        .as_ref()
+                        .and_then(|prev_patchname| {
+                            trans.unapplied().iter().position(|pn| pn == prev_patchname)
+                        })
+                        .map_or(0, |pos| pos + 1);
+                    trans.new_unapplied(patchname, *commit_id, insert_pos)?;
+                }
+                trans.push_patches(&to_push, false)
+            })
+            .execute("quilt sync")?
+    };
+
+    let template = export::get_template(&repo, None)?;
+    let diff_opts = argset::get_diff_opts(matches, &repo.config_snapshot(), false, true);
+    let mut series = String::new();
+    let mut patches: BTreeMap<String, PatchRecord> = BTreeMap::new();
+    for patchname in stack.applied_and_unapplied() {
+        let pair = pairs
+            .iter()
+            .find(|pair| pair.patchname.as_ref() == Some(patchname))
+            .expect("every patch is paired with a quilt patch file");
+        let patch_commit = stack.get_patch_commit(patchname);
+        series.push_str(&pair.file_name);
+        let content = if pair.action == Action::Export {
+            let path = dir.join(&pair.file_name);
+            let mut context = templates::Context::default();
+            context.insert("branch", stack.get_branch_name());
+            let content = export::render_patch(
+                &repo,
+                patchname,
+                patch_commit,
+                context,
+                &template,
+                &diff_opts,
+            )?;
+            std::fs::write(&path, &content).with_context(|| format!("writing {path:?}"))?;
+            crate::print_info_message(
+                matches,
+                &format!("exported `{patchname}` to `{}`", pair.file_name),
+            );
+            content
+        } else {
+            if let Some(entry) = pair.entry.as_ref() {
+                if let Some(strip_level) = entry.strip_level {
+                    write!(series, " -p{strip_level}")?;
+                }
+                if entry.reverse {
+                    series.push_str(" -R");
+                }
+            }
+            pair.content
+                .clone()
+                .expect("quilt patch file content is read")
+        };
+        series.push('\n');
+        patches.insert(
+            pair.file_name.clone(),
+            PatchRecord {
+                name: patchname.clone(),
+                commit: patch_commit.id.to_string(),
+                blob: repo.write_blob(&content)?.to_string(),
+            },
+        );
+    }
+
+    for pair in pairs.iter().filter(|pair| pair.action == Action::Drop) {
+        crate::print_info_message(
+            matches,
+            &format!("removed `{}` from quilt series", pair.file_name),
+        );
+    }
+
+    std::fs::write(&series_path, series.as_str())
+        .with_context(|| format!("writing {series_path:?}"))?;
+
+    record::write(
+        &repo,
+        &branch_name,
+        &SyncRecord {
+            dir: dir_key,
+            patches,
+        },
+    )
+}
+
+/// Commit the quilt patch file of a pair on top of the given parent.
+fn commit_pair(
+    repo: &gix::Repository,
+    pair: &Pair,
+    parent_id: gix::ObjectId,
+) -> Result<gix::ObjectId> {
+    let content = pair
+        .content
+        .clone()
+        .expect("quilt patch file content is read");
+    let entry = pair.entry.as_ref().expect("quilt file has series entry");
+    import::commit_patch_file(repo, parent_id, content, entry.strip_level, entry.reverse)
+        .with_context(|| format!("applying quilt patch `{}`", pair.file_name))
+}
+
+/// Determine whether a quilt patch file makes the same change as a StGit patch.
+///
+/// The quilt patch file is applied to the StGit patch's parent and the resulting tree
+/// is compared with the StGit patch's tree.
+fn is_same_change(
+    repo: &gix::Repository,
+    patch_commit: &gix::Commit<'_>,
+    content: &[u8],
+    entry: &SeriesEntry,
+) -> Result<bool> {
+    let parent_id = patch_commit.get_parent_commit()?.id;
+    if let Ok(commit_id) = import::commit_patch_file(
+        repo,
+        parent_id,
+        content.to_vec(),
+        entry.strip_level,
+        entry.reverse,
+    ) {
+        Ok(repo.find_commit(commit_id)?.tree_id()? == patch_commit.tree_id()?)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Get the patch name stem of a quilt patch file name.
+fn stem(file_name: &str) -> &str {
+    let file_name = Path::new(file_name)
+        .file_name()
+        .and_then(std::ffi::OsStr::to_str)
+        .unwrap_or(file_name);
+    file_name
+        .strip_suffix(".patch")
+        .or_else(|| file_name.strip_suffix(".diff"))
+        .unwrap_or(file_name)
+}
@@ -0,0 +1,353 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg range-diff` implementation.
+
+use std::{collections::HashMap, io::Write, rc::Rc};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::{BString, ByteSlice, ByteVec};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::PatchName,
+    stack::{
+        state_refname_from_branch_name, InitializationPolicy, Stack, StackAccess, StackState,
+        StackStateAccess,
+    },
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "range-diff",
+    category: super::CommandCategory::StackInspection,
+    make,
+    run,
+};
+
+/// Minimum similarity for pairing patches whose names do not match.
+const SIMILARITY_THRESHOLD: f64 = 0.5;
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Show how the patch series changed between two stack states")
+        .long_about(
+            "Compare the patch series of two stack states, showing which patches were \
+             added, removed, reordered, or modified. This is useful for reviewing how \
+             a whole series changed after a rebase or a round of review fixes.\n\
+             \n\
+             Each state may be given as the name of a branch with a StGit stack, in \
+             which case that branch's current stack state is used, or as a commit \
+             from the stack log as shown by 'stg log' (e.g. 'refs/stacks/<branch>~3'). \
+             When no states are given, the states before and after the last stack \
+             operation are compared. When only one state is given, it is compared \
+             with the current stack state.\n\
+             \n\
+             The applied and unapplied patches of each state are compared; hidden \
+             patches are ignored. Patches are paired by name. Patches that were \
+             renamed are paired by the similarity of their diffs.\n\
+             \n\
+             The output resembles git-range-diff(1). Each line shows a pair of \
+             patches with their positions and commit ids in the old and new series, \
+             and one of the following markers:\n\
+             \n  \
+             = the patch is unchanged\n  \
+             ! the patch is modified; the diff of the patch diffs follows\n  \
+             < the patch was removed\n  \
+             > the patch was added\n\
+             \n\
+             Reordered patches are apparent from their differing positions. Renamed \
+             patches are shown as '<old-name> -> <new-name>'.",
+        )
+        .arg(
+            Arg::new("state-a")
+                .help("Old stack state")
+                .value_name("state-a"),
+        )
+        .arg(
+            Arg::new("state-b")
+                .help("New stack state")
+                .value_name("state-b"),
+        )
+        .arg(argset::branch_arg())
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let state_a_spec = argset::get_one_str(matches, "state-a");
+    let state_b_spec = argset::get_one_str(matches, "state-b");
+
+    let (state_a, state_b) = match (state_a_spec, state_b_spec) {
+        (Some(spec_a), Some(spec_b)) => {
+            (resolve_state(&repo, spec_a)?, resolve_state(&repo, spec_b)?)
+        }
+        (spec_a, _) => {
+            let stack = Stack::from_branch_locator(
+                &repo,
+                matches.get_one::<BranchLocator>("branch"),
+                InitializationPolicy::RequireInitialized,
+            )?;
+            let current_commit = repo
+                .find_reference(stack.get_stack_refname())?
+                .into_fully_peeled_id()?
+                .object()?
+                .try_into_commit()?;
+            let current_state = StackState::from_commit(&repo, &current_commit)?;
+            if let Some(spec_a) = spec_a {
+                (resolve_state(&repo, spec_a)?, current_state)
+            } else {
+                let prev_commit = current_state
+                    .prev
+                    .clone()
+                    .ok_or_else(|| anyhow!("no previous stack state to compare with"))?;
+                (StackState::from_commit(&repo, &prev_commit)?, current_state)
+            }
+        }
+    };
+
+    let output = range_diff(
+        &repo,
+        &state_series(&state_a, true),
+        &state_series(&state_b, true),
+        crate::color::use_color(matches),
+    )?;
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    stdout.write_all(&output)?;
+    Ok(())
+}
+
+/// A patch in a series being compared.
+pub(super) struct SeriesPatch<'repo> {
+    pub(super) name: PatchName,
+    pub(super) commit: Rc<gix::Commit<'repo>>,
+}
+
+/// Resolve a branch name or stack log committish to a stack state.
+pub(super) fn resolve_state<'repo>(
+    repo: &'repo gix::Repository,
+    spec: &str,
+) -> Result<StackState<'repo>> {
+    let state_refname = state_refname_from_branch_name(spec);
+    let commit = if let Some(state_ref) = repo
+        .try_find_reference(state_refname.as_str())
+        .ok()
+        .flatten()
+    {
+        state_ref
+            .into_fully_peeled_id()?
+            .object()?
+            .try_into_commit()?
+    } else {
+        repo.rev_parse_single(spec)
+            .map_err(|_| anyhow!("invalid stack state `{spec}`"))?
+            .object()?
+            .peel_tags_to_end()?
+            .try_into_commit()
+            .map_err(|_| anyhow!("stack state `{spec}` is not a commit"))?
+    };
+    StackState::from_commit(repo, &commit).with_context(|| format!("`{spec}` is not a stack state"))
+}
+
+/// Get the patches of a stack state's series, in stack order.
+///
+/// Applied patches are always included. Unapplied patches are optionally included.
+/// Hidden patches are never included.
+pub(super) fn state_series<'repo>(
+    state: &StackState<'repo>,
+    include_unapplied: bool,
+) -> Vec<SeriesPatch<'repo>> {
+    let unapplied: &[PatchName] = if include_unapplied {
+        state.unapplied()
+    } else {
+        &[]
+    };
+    state
+        .applied()
+        .iter()
+        .chain(unapplied.iter())
+        .map(|patchname| SeriesPatch {
+            name: patchname.clone(),
+            commit: state.get_patch(patchname).commit.clone(),
+        })
+        .collect()
+}
+
+/// Generate range-diff output comparing an old and new patch series.
+pub(super) fn range_diff(
+    repo: &gix::Repository,
+    old: &[SeriesPatch],
+    new: &[SeriesPatch],
+    use_color: bool,
+) -> Result<BString> {
+    let stupid = repo.stupid();
+    let pairs = pair_patches(repo, old, new)?;
+
+    let num_width = old.len().max(new.len()).max(1).to_string().len();
+    let write_header = |output: &mut BString,
+                        old_entry: Option<(usize, &SeriesPatch)>,
+                        marker: char,
+                        new_entry: Option<(usize, &SeriesPatch)>| {
+        if let Some((i, patch)) = old_entry {
+            output.push_str(format!(
+                "{:>num_width$}:  {} ",
+                i + 1,
+                patch.commit.id.to_hex_with_len(7)
+            ));
+        } else {
+            output.push_str(format!("{:>num_width$}:  ------- ", "-"));
+        }
+        output.push_char(marker);
+        if let Some((j, patch)) = new_entry {
+            output.push_str(format!(
+                " {:>num_width$}:  {} ",
+                j + 1,
+                patch.commit.id.to_hex_with_len(7)
+            ));
+        } else {
+            output.push_str(format!(" {:>num_width$}:  ------- ", "-"));
+        }
+        match (old_entry, new_entry) {
+            (Some((_, old_patch)), Some((_, new_patch))) if old_patch.name != new_patch.name => {
+                output.push_str(format!("{} -> {}", old_patch.name, new_patch.name));
+            }
+            (_, Some((_, patch))) | (Some((_, patch)), None) => {
+                output.push_str(AsRef::<str>::as_ref(&patch.name));
+            }
+            (None, None) => unreachable!(),
+        }
+        output.push_byte(b'\n');
+    };
+
+    let mut output = BString::from(Vec::new());
+    let mut old_shown = vec![false; old.len()];
+    let old_paired: Vec<bool> = (0..old.len()).map(|i| pairs.contains(&Some(i))).collect();
+
+    for (j, new_patch) in new.iter().enumerate() {
+        if let Some(i) = pairs[j] {
+            // Show removed patches that preceded the paired old patch.
+            for (k, removed_patch) in old.iter().enumerate().take(i) {
+                if !old_paired[k] && !old_shown[k] {
+                    write_header(&mut output, Some((k, removed_patch)), '<', None);
+                    old_shown[k] = true;
+                }
+            }
+            let old_patch = &old[i];
+            let interdiff = if old_patch.commit.id == new_patch.commit.id {
+                BString::from(Vec::new())
+            } else {
+                stupid.range_diff_commits(old_patch.commit.id, new_patch.commit.id, use_color)?
+            };
+            let marker = if interdiff.trim().is_empty() {
+                '='
+            } else {
+                '!'
+            };
+            write_header(
+                &mut output,
+                Some((i, old_patch)),
+                marker,
+                Some((j, new_patch)),
+            );
+            if marker == '!' {
+                output.push_str(&interdiff);
+            }
+            old_shown[i] = true;
+        } else {
+            write_header(&mut output, None, '>', Some((j, new_patch)));
+        }
+    }
+
+    for (k, old_patch) in old.iter().enumerate() {
+        if !old_shown[k] {
+            write_header(&mut output, Some((k, old_patch)), '<', None);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Pair patches from the new series with patches from the old series.
+///
+/// The returned vector has an entry for each new patch with the index of the paired
+/// old patch, if any. Patches are first paired by name and then, for the remaining
+/// patches, by the similarity of their diffs.
+fn pair_patches(
+    repo: &gix::Repository,
+    old: &[SeriesPatch],
+    new: &[SeriesPatch],
+) -> Result<Vec<Option<usize>>> {
+    let old_indices: HashMap<&PatchName, usize> = old
+        .iter()
+        .enumerate()
+        .map(|(i, patch)| (&patch.name, i))
+        .collect();
+    let mut pairs: Vec<Option<usize>> = new
+        .iter()
+        .map(|patch| old_indices.get(&patch.name).copied())
+        .collect();
+
+    let mut old_unpaired: Vec<usize> = (0..old.len())
+        .filter(|i| !pairs.contains(&Some(*i)))
+        .collect();
+    let new_unpaired: Vec<usize> = (0..new.len()).filter(|j| pairs[*j].is_none()).collect();
+
+    if old_unpaired.is_empty() || new_unpaired.is_empty() {
+        return Ok(pairs);
+    }
+
+    let stupid = repo.stupid();
+    let changed_lines = |patch: &SeriesPatch| -> Result<String> {
+        let parent_tree_id = patch.commit.get_parent_commit()?.tree_id()?.detach();
+        let diff = stupid.diff_tree_patch(
+            parent_tree_id,
+            patch.commit.tree_id()?.detach(),
+            <Option<Vec<&str>>>::None,
+            false,
+            ["--no-ext-diff"],
+        )?;
+        let mut lines = String::new();
+        for line in diff.lines() {
+            if (line.starts_with(b"+") && !line.starts_with(b"+++"))
+                || (line.starts_with(b"-") && !line.starts_with(b"---"))
+            {
+                lines.push_str(&line.to_str_lossy());
+                lines.push('\n');
+            }
+        }
+        Ok(lines)
+    };
+
+    let old_lines: Vec<String> = old_unpaired
+        .iter()
+        .map(|&i| changed_lines(&old[i]))
+        .collect::<Result<_>>()?;
+
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+    for &j in &new_unpaired {
+        let new_lines = changed_lines(&new[j])?;
+        for (&i, old_lines) in old_unpaired.iter().zip(old_lines.iter()) {
+            let similarity = if old[i].commit.id == new[j].commit.id || *old_lines == new_lines {
+                1.0
+            } else {
+                strsim::sorensen_dice(old_lines, &new_lines)
+            };
+            if similarity >= SIMILARITY_THRESHOLD {
+                candidates.push((similarity, i, j));
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (_, i, j) in candidates {
+        if pairs[j].is_none() && old_unpaired.contains(&i) {
+            pairs[j] = Some(i);
+            old_unpaired.retain(|&k| k != i);
+        }
+    }
+
+    Ok(pairs)
+}
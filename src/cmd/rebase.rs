@@ -4,18 +4,19 @@
 
 use std::{fmt::Write, str::FromStr};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use bstr::ByteSlice;
 use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
     color::get_color_stdout,
-    ext::RepositoryExtended,
+    ext::{CommitExtended, RepositoryExtended},
     patch::{patchedit, PatchName, SingleRevisionSpec},
     print_info_message,
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
+    wrap::PartialRefName,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -44,13 +45,20 @@ fn make() -> clap::Command {
             \n    \
             stg undo --hard\n    \
             stg push next-patch..top-patch\n\
+            \n\
+            With '--interactive', an editor is opened with instructions for each \
+            patch, which may be reordered and changed to edit, reword, rename, \
+            squash, split, unapply, hide, or delete patches, or to run commands \
+            between patches. If the rebase stops because of a 'break' or 'split' \
+            instruction, a failed 'exec' command, or push conflicts, resume it with \
+            'stg rebase --continue' once the work tree is clean.\n\
             ",
         )
         .arg(
             Arg::new("committish")
                 .help("New base commit for the stack")
                 .value_parser(clap::value_parser!(SingleRevisionSpec))
-                .required_unless_present("interactive"),
+                .required_unless_present_any(["interactive", "continue"]),
         )
         .arg(
             Arg::new("interactive")
@@ -59,13 +67,27 @@ fn make() -> clap::Command {
                 .help("Interactively manipulate patches in editor")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("continue")
+                .long("continue")
+                .help("Continue a stopped interactive rebase")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "committish",
+                    "interactive",
+                    "nopush",
+                    "delete-merged",
+                    "autostash",
+                    "autosquash",
+                ]),
+        )
         .arg(
             Arg::new("nopush")
                 .long("nopush")
                 .short('n')
                 .help("Do not push back patches after rebasing")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with("merged"),
+                .conflicts_with_all(["merged", "delete-merged", "autosquash"]),
         )
         .arg(argset::merged_arg().long_help(
             "Check for patches that may have been merged upstream.\n\
@@ -75,6 +97,20 @@ fn make() -> clap::Command {
              have been merged, the patch will still exist in the stack, but become \
              empty after the rebase operation.",
         ))
+        .arg(argset::delete_merged_arg())
+        .arg(
+            Arg::new("autosquash")
+                .long("autosquash")
+                .help("Squash fixup! and squash! patches into their targets")
+                .long_help(
+                    "Squash patches whose subject starts with \"fixup! \" or \"squash! \" \
+                     into the earlier patch named by the rest of the subject, as with \
+                     'stg squash --auto'. With '--interactive', the instructions are \
+                     instead pre-arranged with each such patch moved after its target \
+                     and marked for fixup or squash.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(argset::committer_date_is_author_date_arg())
         .arg(
             Arg::new("autostash")
@@ -89,6 +125,12 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue),
         )
         .arg(argset::push_conflicts_arg())
+        .arg(argset::cascade_arg().conflicts_with_all([
+            "interactive",
+            "continue",
+            "nopush",
+            "autostash",
+        ]))
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -100,6 +142,15 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let allow_push_conflicts = argset::resolve_allow_push_conflicts(&config, matches);
     let committer_date_is_author_date = matches.get_flag("committer-date-is-author-date");
 
+    if matches.get_flag("continue") {
+        return continue_interactive(
+            stack,
+            matches,
+            allow_push_conflicts,
+            committer_date_is_author_date,
+        );
+    }
+
     let target_commit =
         if let Some(committish) = matches.get_one::<SingleRevisionSpec>("committish") {
             committish.resolve(&repo, Some(&stack))?.commit
@@ -157,6 +208,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     };
 
     let applied = stack.applied().to_vec();
+    let old_base_id = stack.base().id;
 
     stack
         .setup_transaction()
@@ -189,6 +241,12 @@ fn run(matches: &ArgMatches) -> Result<()> {
         stack.log_external_mods(Some("rebase"))?
     };
 
+    let upstream_ids = if matches.get_flag("delete-merged") {
+        Some(stupid.rev_list(old_base_id, stack.base().id, None::<Vec<&str>>)?)
+    } else {
+        None
+    };
+
     if matches.get_flag("interactive") {
         interactive_pushback(
             stack,
@@ -196,20 +254,41 @@ fn run(matches: &ArgMatches) -> Result<()> {
             &config,
             matches,
             &applied,
+            upstream_ids.as_deref(),
+            matches.get_flag("autosquash"),
             allow_push_conflicts,
             committer_date_is_author_date,
         )?;
     } else if !matches.get_flag("nopush") {
         stack.check_head_top_mismatch()?;
         let check_merged = matches.get_flag("merged");
+        let autosquash = matches.get_flag("autosquash");
         stack
             .setup_transaction()
             .use_index_and_worktree(true)
             .allow_push_conflicts(allow_push_conflicts)
             .committer_date_is_author_date(committer_date_is_author_date)
             .with_output_stream(get_color_stdout(matches))
-            .transact(|trans| trans.push_patches(&applied, check_merged))
+            .transact(|trans| {
+                let to_push = if autosquash {
+                    super::squash::autosquash(trans, &applied)?
+                } else {
+                    applied.clone()
+                };
+                if let Some(upstream_ids) = upstream_ids.as_ref() {
+                    trans.push_patches_delete_merged(&to_push, upstream_ids)
+                } else {
+                    trans.push_patches(&to_push, check_merged)
+                }
+            })
             .execute("rebase (reapply)")?;
+
+        let branch_name = PartialRefName::from_str(&branch_name)?;
+        if matches.get_flag("cascade") {
+            super::branch::cascade_rebase(&repo, matches, &branch_name, allow_push_conflicts)?;
+        } else {
+            super::branch::hint_stacked_children(&repo, matches, &branch_name)?;
+        }
     }
 
     if using_stash {
@@ -229,289 +308,741 @@ const INTERACTIVE_HELP_LINES: &str = "\
 #
 #   k, keep <patch> = do not modify this patch
 #   e, edit <patch> = interactively edit this patch
+#   r, reword <patch> = interactively edit this patch's message only
+#   n, rename <patch> <new-name> = rename this patch
 #   s, squash <patch> = squash patch into the previous patch
 #   f, fixup <patch> = like \"squash\", but discard this patch's commit message
+#   split <patch> = apply this patch, then stop with its changes uncommitted
+#   u, unapply <patch> = leave this patch unapplied
 #   h, hide <patch> = hide patch
 #   d, delete <patch> = delete patch
+#   x, exec <command> = run command with the shell, stopping if it fails
+#   b, break = stop here; resume with 'stg rebase --continue'
 #
-# These lines can be reordered; they are executed from top to bottom.
+# These lines can be reordered; they are executed from top to bottom and the
+# patches are pushed in the order given.
 #
 # Patches above the APPLY_LINE are applied; other patches are kept unapplied.
 ";
+const INTERACTIVE_ERROR_PREFIX: &str = "# ERROR: ";
+const INTERACTIVE_ERROR_HINT: &str =
+    "# Correct the instructions below, or leave them unchanged to abort.";
+
+/// Name of the file, in the git directory, with the remaining instructions of a
+/// stopped interactive rebase.
+const INTERACTIVE_TODO_FILENAME: &str = "stgit-rebase-todo";
+
+/// Name of the file, in the git directory, with the upstream commits that merged
+/// patches are detected in when a stopped interactive rebase uses `--delete-merged`.
+const INTERACTIVE_UPSTREAM_FILENAME: &str = "stgit-rebase-upstream";
 
 #[derive(Debug, Clone)]
 struct Instruction {
-    patchname: PatchName,
     action: Action,
     apply: bool,
+    line_num: usize,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Action {
-    Keep,
-    Edit,
-    Squash,
-    Fixup,
-    Hide,
-    Delete,
+    Keep(PatchName),
+    Edit(PatchName),
+    Reword(PatchName),
+    Rename(PatchName, PatchName),
+    Squash(PatchName),
+    Fixup(PatchName),
+    Split(PatchName),
+    Unapply(PatchName),
+    Hide(PatchName),
+    Delete(PatchName),
+    Exec(String),
+    Break,
+}
+
+impl Action {
+    fn patchname(&self) -> Option<&PatchName> {
+        match self {
+            Action::Keep(patchname)
+            | Action::Edit(patchname)
+            | Action::Reword(patchname)
+            | Action::Rename(patchname, _)
+            | Action::Squash(patchname)
+            | Action::Fixup(patchname)
+            | Action::Split(patchname)
+            | Action::Unapply(patchname)
+            | Action::Hide(patchname)
+            | Action::Delete(patchname) => Some(patchname),
+            Action::Exec(_) | Action::Break => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Keep(_) => "keep",
+            Action::Edit(_) => "edit",
+            Action::Reword(_) => "reword",
+            Action::Rename(..) => "rename",
+            Action::Squash(_) => "squash",
+            Action::Fixup(_) => "fixup",
+            Action::Split(_) => "split",
+            Action::Unapply(_) => "unapply",
+            Action::Hide(_) => "hide",
+            Action::Delete(_) => "delete",
+            Action::Exec(_) => "exec",
+            Action::Break => "break",
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.name();
+        match self {
+            Action::Rename(patchname, new_patchname) => {
+                write!(f, "{name} {patchname} {new_patchname}")
+            }
+            Action::Exec(command) => write!(f, "{name} {command}"),
+            Action::Break => f.write_str(name),
+            _ => write!(f, "{name} {}", self.patchname().expect("action has patch")),
+        }
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn interactive_pushback(
     stack: Stack,
     repo: &gix::Repository,
     config: &gix::config::Snapshot,
     matches: &ArgMatches,
     previously_applied: &[PatchName],
+    upstream_ids: Option<&[gix::ObjectId]>,
+    autosquash: bool,
     allow_push_conflicts: bool,
     committer_date_is_author_date: bool,
 ) -> Result<()> {
-    let mut stack = stack;
-
     if stack.all_patches().next().is_none() {
         return Ok(());
     }
 
+    remove_todo(repo)?;
+
+    let template = make_instructions_template(&stack, previously_applied, autosquash)?;
+    let instructions = edit_instructions(&stack, config, template)?;
+
+    InstructionRunner {
+        repo: stack.repo,
+        matches,
+        upstream_ids,
+        allow_push_conflicts,
+        committer_date_is_author_date,
+    }
+    .run(stack, instructions)
+}
+
+/// Resume a stopped interactive rebase from its saved instructions.
+fn continue_interactive(
+    stack: Stack,
+    matches: &ArgMatches,
+    allow_push_conflicts: bool,
+    committer_date_is_author_date: bool,
+) -> Result<()> {
+    let repo = stack.repo;
+    let todo_path = repo.path().join(INTERACTIVE_TODO_FILENAME);
+    let buf = match std::fs::read_to_string(&todo_path) {
+        Ok(buf) => buf,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(anyhow!("no interactive rebase in progress"));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    repo.check_repository_state()?;
+    stack.check_head_top_mismatch()?;
+    repo.stupid()
+        .statuses(None)?
+        .check_index_and_worktree_clean()?;
+
+    let instructions = parse_instructions(&buf)
+        .and_then(|instructions| {
+            validate_instructions(&stack, &instructions)?;
+            Ok(instructions)
+        })
+        .map_err(|e| {
+            anyhow!(
+                "{e:#}; correct `{}` and run `stg rebase --continue` again",
+                todo_path.display()
+            )
+        })?;
+
+    let upstream_ids =
+        match std::fs::read_to_string(repo.path().join(INTERACTIVE_UPSTREAM_FILENAME)) {
+            Ok(buf) => Some(
+                buf.lines()
+                    .map(|line| {
+                        gix::ObjectId::from_hex(line.trim().as_bytes())
+                            .map_err(|_| anyhow!("invalid upstream commit id `{line}`"))
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+
+    InstructionRunner {
+        repo,
+        matches,
+        upstream_ids: upstream_ids.as_deref(),
+        allow_push_conflicts,
+        committer_date_is_author_date,
+    }
+    .run(stack, instructions)
+}
+
+/// Have the user edit the instructions until they are valid.
+///
+/// When the instructions fail to parse or validate, the editor is reopened with the
+/// error shown at the top of the file. Leaving the instructions unchanged aborts with
+/// the error.
+fn edit_instructions(
+    stack: &Stack,
+    config: &gix::config::Snapshot,
+    template: String,
+) -> Result<Vec<Instruction>> {
     let filename = ".stgit-rebase-interactive.txt";
-    std::fs::write(
-        filename,
-        make_instructions_template(&stack, previously_applied),
-    )?;
+    let mut content = template;
+    let mut previous: Option<String> = None;
+
+    loop {
+        std::fs::write(filename, &content)?;
+        let buf = patchedit::call_editor(filename, config)?;
+        let buf = buf
+            .to_str()
+            .map_err(|_| anyhow!("`{filename}` is not valid UTF-8"))?;
+        let buf = strip_error_header(buf);
+
+        match parse_instructions(&buf).and_then(|instructions| {
+            validate_instructions(stack, &instructions)?;
+            Ok(instructions)
+        }) {
+            Ok(instructions) => return Ok(instructions),
+            Err(e) => {
+                if previous.as_ref() == Some(&buf) {
+                    return Err(e);
+                }
+                let mut header = String::new();
+                for line in format!("{e:#}").lines() {
+                    writeln!(header, "{INTERACTIVE_ERROR_PREFIX}{line}")?;
+                }
+                writeln!(header, "{INTERACTIVE_ERROR_HINT}")?;
+                content = header + &buf;
+                previous = Some(buf);
+            }
+        }
+    }
+}
+
+/// Remove the error header added by [`edit_instructions()`].
+fn strip_error_header(buf: &str) -> String {
+    let mut stripped = String::with_capacity(buf.len());
+    let mut in_header = true;
+    for line in buf.split_inclusive('\n') {
+        if in_header
+            && (line.starts_with(INTERACTIVE_ERROR_PREFIX)
+                || line.trim_end() == INTERACTIVE_ERROR_HINT)
+        {
+            continue;
+        }
+        in_header = false;
+        stripped.push_str(line);
+    }
+    stripped
+}
 
-    let buf = patchedit::call_editor(filename, config)?;
-    let buf = buf
-        .to_str()
-        .map_err(|_| anyhow!("`{filename}` is not valid UTF-8"))?;
-    let mut instructions = parse_instructions(buf)?;
+fn remove_todo(repo: &gix::Repository) -> Result<()> {
+    for filename in [INTERACTIVE_TODO_FILENAME, INTERACTIVE_UPSTREAM_FILENAME] {
+        match std::fs::remove_file(repo.path().join(filename)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Carries out interactive rebase instructions.
+///
+/// Patches are pushed in instruction order. Pushes are deferred until an instruction
+/// needs the preceding patches to be applied, i.e. `exec`, `break`, and `split`, or
+/// until all instructions are done.
+///
+/// Before anything that may stop the rebase, the remaining instructions are saved to
+/// the git directory so that `stg rebase --continue` may resume from there.
+struct InstructionRunner<'a, 'repo> {
+    repo: &'repo gix::Repository,
+    matches: &'a ArgMatches,
+    upstream_ids: Option<&'a [gix::ObjectId]>,
+    allow_push_conflicts: bool,
+    committer_date_is_author_date: bool,
+}
 
-    validate_instructions(&stack, &instructions)?;
+/// Progress of the patches handled by [`InstructionRunner`].
+#[derive(Default)]
+struct Schedule {
+    /// Patches handled so far, in instruction order.
+    patchnames: Vec<PatchName>,
 
-    let mut index: usize = 0;
+    /// Patches from `patchnames` to be kept unapplied.
+    unapplied: Vec<PatchName>,
 
-    while index < instructions.len() {
-        let instruction = instructions[index].clone();
-        let patchname = &instruction.patchname;
+    /// Patches from `patchnames` waiting to be pushed.
+    pending: Vec<PatchName>,
+}
+
+impl Schedule {
+    fn add(&mut self, patchname: &PatchName, apply: bool) {
+        self.patchnames.push(patchname.clone());
+        if apply {
+            self.pending.push(patchname.clone());
+        } else {
+            self.unapplied.push(patchname.clone());
+        }
+    }
 
-        match instruction.action {
-            Action::Keep => {
-                index += 1;
+    fn rename(&mut self, old_patchname: &PatchName, new_patchname: &PatchName) {
+        for pn in self
+            .patchnames
+            .iter_mut()
+            .chain(self.unapplied.iter_mut())
+            .chain(self.pending.iter_mut())
+        {
+            if pn == old_patchname {
+                *pn = new_patchname.clone();
             }
+        }
+    }
 
-            Action::Delete => {
-                // Find contiguous delete instructions in order to delete in batches.
-                let mut delete_instructions: Vec<Instruction> = Vec::new();
-                while index < instructions.len() && instructions[index].action == Action::Delete {
-                    delete_instructions.push(instructions.remove(index));
+    fn last(&self) -> Option<&PatchName> {
+        self.patchnames.last()
+    }
+}
+
+impl<'a, 'repo> InstructionRunner<'a, 'repo> {
+    fn run(&self, stack: Stack<'repo>, instructions: Vec<Instruction>) -> Result<()> {
+        let mut stack = stack;
+        let mut schedule = Schedule::default();
+        let mut index: usize = 0;
+
+        while index < instructions.len() {
+            let instruction = &instructions[index];
+
+            match &instruction.action {
+                Action::Keep(patchname) => {
+                    schedule.add(patchname, instruction.apply);
+                    index += 1;
                 }
-                assert!(!delete_instructions.is_empty());
-                assert!(delete_instructions
-                    .iter()
-                    .all(|inst| inst.action == Action::Delete));
-                let to_delete: Vec<&PatchName> = delete_instructions
-                    .iter()
-                    .map(|inst| &inst.patchname)
-                    .collect();
-                stack = stack
-                    .setup_transaction()
-                    .with_output_stream(get_color_stdout(matches))
-                    .transact(|trans| {
-                        let popped_extra = trans.delete_patches(|pn| to_delete.contains(&pn))?;
-                        assert!(popped_extra.is_empty());
-                        Ok(())
-                    })
-                    .execute("delete")?;
-            }
 
-            Action::Hide => {
-                let mut hide_instructions: Vec<Instruction> = Vec::new();
-                while index < instructions.len() && instructions[index].action == Action::Hide {
-                    hide_instructions.push(instructions.remove(index));
+                Action::Unapply(patchname) => {
+                    schedule.add(patchname, false);
+                    index += 1;
                 }
-                assert!(!hide_instructions.is_empty());
-                assert!(hide_instructions
-                    .iter()
-                    .all(|inst| inst.action == Action::Hide));
-                let to_hide: Vec<PatchName> = hide_instructions
-                    .iter()
-                    .map(|inst| inst.patchname.clone())
-                    .collect();
-                stack = stack
-                    .setup_transaction()
-                    .with_output_stream(get_color_stdout(matches))
-                    .transact(|trans| trans.hide_patches(&to_hide))
-                    .execute("hide")?;
-            }
 
-            Action::Edit => {
-                let dummy_edit_command = clap::Command::new("dummy-edit");
-                let dummy_edit_command = patchedit::add_args(dummy_edit_command, false, false);
-                let edit_matches = dummy_edit_command
-                    .try_get_matches_from(["dummy-edit", "--edit", "--diff"])
-                    .expect("dummy command has valid arguments");
-                match patchedit::EditBuilder::default()
-                    .original_patchname(Some(patchname))
-                    .existing_patch_commit(stack.get_patch_commit(patchname))
-                    .allow_diff_edit(true)
-                    .edit(&stack, repo, &edit_matches)?
-                {
-                    patchedit::EditOutcome::TemplateSaved(_) => panic!("template save not enabled"),
-                    patchedit::EditOutcome::Edited {
-                        new_patchname,
-                        new_commit_id,
-                    } => {
-                        if new_patchname.is_some() || new_commit_id.is_some() {
-                            stack = stack
-                                .setup_transaction()
-                                .committer_date_is_author_date(committer_date_is_author_date)
-                                .with_output_stream(get_color_stdout(matches))
-                                .transact(|trans| {
-                                    let patchname =
-                                        if let Some(new_patchname) = new_patchname.as_ref() {
-                                            trans.rename_patch(patchname, new_patchname)?;
-                                            new_patchname
-                                        } else {
-                                            patchname
-                                        };
-                                    if let Some(commit_id) = new_commit_id {
-                                        trans.update_patch(patchname, commit_id)?;
-                                    }
-                                    Ok(())
-                                })
-                                .execute(&format!("edit: {patchname}"))?;
-                        }
-
-                        instructions[index] = Instruction {
-                            action: Action::Keep,
-                            patchname: new_patchname.unwrap_or_else(|| patchname.clone()),
-                            apply: instruction.apply,
-                        };
+                Action::Delete(_) => {
+                    // Find contiguous delete instructions in order to delete in batches.
+                    let mut to_delete: Vec<&PatchName> = Vec::new();
+                    while let Some(Action::Delete(patchname)) =
+                        instructions.get(index).map(|inst| &inst.action)
+                    {
+                        to_delete.push(patchname);
+                        index += 1;
+                    }
+                    stack = stack
+                        .setup_transaction()
+                        .with_output_stream(get_color_stdout(self.matches))
+                        .transact(|trans| {
+                            let popped_extra =
+                                trans.delete_patches(|pn| to_delete.contains(&pn))?;
+                            assert!(popped_extra.is_empty());
+                            Ok(())
+                        })
+                        .execute("delete")?;
+                }
 
+                Action::Hide(_) => {
+                    let mut to_hide: Vec<PatchName> = Vec::new();
+                    while let Some(Action::Hide(patchname)) =
+                        instructions.get(index).map(|inst| &inst.action)
+                    {
+                        to_hide.push(patchname.clone());
                         index += 1;
                     }
+                    stack = stack
+                        .setup_transaction()
+                        .with_output_stream(get_color_stdout(self.matches))
+                        .transact(|trans| trans.hide_patches(&to_hide))
+                        .execute("hide")?;
                 }
-            }
 
-            Action::Squash | Action::Fixup => {
-                let action_str = match instruction.action {
-                    Action::Squash => "squash",
-                    Action::Fixup => "fixup",
-                    _ => panic!("only squash and fixup expected"),
-                };
+                Action::Edit(patchname) | Action::Reword(patchname) => {
+                    let allow_diff_edit = matches!(instruction.action, Action::Edit(_));
+                    let (new_stack, patchname) =
+                        self.edit_patch(stack, patchname, allow_diff_edit)?;
+                    stack = new_stack;
+                    schedule.add(&patchname, instruction.apply);
+                    index += 1;
+                }
 
-                let squash_patchnames: Vec<PatchName> = if index > 0 {
-                    let mut patchnames: Vec<PatchName> =
-                        vec![instructions[index - 1].patchname.clone()];
-                    while index < instructions.len()
-                        && instructions[index].action == instruction.action
+                Action::Rename(patchname, new_patchname) => {
+                    stack = stack
+                        .setup_transaction()
+                        .with_output_stream(get_color_stdout(self.matches))
+                        .transact(|trans| trans.rename_patch(patchname, new_patchname))
+                        .execute(&format!("rename {patchname} {new_patchname}"))?;
+                    schedule.add(new_patchname, instruction.apply);
+                    index += 1;
+                }
+
+                Action::Squash(_) | Action::Fixup(_) => {
+                    let is_squash = matches!(instruction.action, Action::Squash(_));
+                    let target_patchname = schedule
+                        .last()
+                        .expect("validated squash and fixup have a preceding patch")
+                        .clone();
+                    let mut squash_patchnames: Vec<PatchName> = vec![target_patchname.clone()];
+                    while let Some(Action::Squash(patchname) | Action::Fixup(patchname)) =
+                        instructions
+                            .get(index)
+                            .map(|inst| &inst.action)
+                            .filter(|action| matches!(action, Action::Squash(_)) == is_squash)
                     {
-                        let Instruction { patchname, .. } = instructions.remove(index);
-                        patchnames.push(patchname);
+                        squash_patchnames.push(patchname.clone());
+                        index += 1;
                     }
-                    patchnames
-                } else {
-                    return Err(anyhow!(
-                        "cannot {action_str} `{patchname}`: no preceding patch"
-                    ));
-                };
 
-                let target_patchname = &squash_patchnames[0];
+                    let squash_matches =
+                        super::squash::make_squash_matches(&stack, &target_patchname, is_squash)?;
+                    let should_push_squashed = stack.applied().contains(&target_patchname);
+                    let mut new_patchname: Option<PatchName> = None;
 
-                let dummy_squash_command = clap::Command::new("dummy-squash");
-                let dummy_squash_command = patchedit::add_args(dummy_squash_command, true, false);
-                let squash_matches = match instruction.action {
-                    Action::Squash => {
-                        dummy_squash_command.try_get_matches_from(["dummy-squash", "--edit"])
-                    }
-                    Action::Fixup => {
-                        let commit = stack.get_patch_commit(target_patchname);
-                        let message = commit.message_raw()?.to_str().map_err(|_| {
-                            anyhow!("fixup target patch `{target_patchname}` has non-UTF-8 message")
-                        })?;
-                        dummy_squash_command.try_get_matches_from([
-                            "dummy-squash",
-                            "--message",
-                            message,
-                        ])
-                    }
-                    _ => panic!("only squash and fixup expected"),
+                    stack = stack
+                        .setup_transaction()
+                        .with_output_stream(get_color_stdout(self.matches))
+                        .transact(|trans| {
+                            new_patchname = Some(super::squash::squash(
+                                trans,
+                                &squash_matches,
+                                &squash_patchnames,
+                                Some(&target_patchname),
+                                should_push_squashed,
+                            )?);
+                            Ok(())
+                        })
+                        .execute("squash")?;
+
+                    let new_patchname = new_patchname.expect("squash sets new patch name");
+                    schedule.rename(&target_patchname, &new_patchname);
+                }
+
+                Action::Split(patchname) => {
+                    stack = self.push_pending(stack, &mut schedule, &instructions[index..])?;
+                    schedule.add(patchname, true);
+                    stack = self.push_pending(stack, &mut schedule, &instructions[index + 1..])?;
+                    self.spill_top(stack)?;
+                    self.save_todo(&schedule, &instructions[index + 1..])?;
+                    print_info_message(
+                        self.matches,
+                        &format!(
+                            "Stopped to split `{patchname}`; its changes are left in the index \
+                             and work tree. Refresh them into `{patchname}` and new \
+                             patches, then run `stg rebase --continue`"
+                        ),
+                    );
+                    return Ok(());
+                }
+
+                Action::Exec(command) => {
+                    // The saved todo starts with this exec so that `--continue` retries it
+                    // if it fails.
+                    stack = self.push_pending(stack, &mut schedule, &instructions[index..])?;
+                    run_exec(command)?;
+                    index += 1;
+                }
+
+                Action::Break => {
+                    self.push_pending(stack, &mut schedule, &instructions[index..])?;
+                    self.save_todo(&schedule, &instructions[index + 1..])?;
+                    print_info_message(
+                        self.matches,
+                        "Stopped at break; run `stg rebase --continue` to resume",
+                    );
+                    return Ok(());
                 }
-                .expect("dummy command has valid arguments");
-
-                stack = stack
-                    .setup_transaction()
-                    .with_output_stream(get_color_stdout(matches))
-                    .transact(|trans| {
-                        let new_patchname = super::squash::squash(
-                            trans,
-                            &squash_matches,
-                            &squash_patchnames,
-                            Some(target_patchname),
-                            false,
-                        )?;
-                        instructions[index - 1] = Instruction {
-                            action: Action::Keep,
-                            patchname: new_patchname,
-                            apply: instruction.apply,
-                        };
-                        Ok(())
-                    })
-                    .execute("squash")?;
             }
         }
+
+        self.push_pending(stack, &mut schedule, &[])?;
+        remove_todo(self.repo)
     }
 
-    let to_push: Vec<PatchName> = instructions
-        .iter()
-        .filter_map(|inst| {
-            if inst.apply {
-                Some(inst.patchname.clone())
-            } else {
-                None
+    /// Interactively edit a patch's message and, optionally, its diff.
+    fn edit_patch(
+        &self,
+        stack: Stack<'repo>,
+        patchname: &PatchName,
+        allow_diff_edit: bool,
+    ) -> Result<(Stack<'repo>, PatchName)> {
+        let dummy_edit_command = clap::Command::new("dummy-edit");
+        let dummy_edit_command = patchedit::add_args(dummy_edit_command, false, false);
+        let edit_args: &[&str] = if allow_diff_edit {
+            &["dummy-edit", "--edit", "--diff"]
+        } else {
+            &["dummy-edit", "--edit"]
+        };
+        let edit_matches = dummy_edit_command
+            .try_get_matches_from(edit_args)
+            .expect("dummy command has valid arguments");
+        match patchedit::EditBuilder::default()
+            .original_patchname(Some(patchname))
+            .existing_patch_commit(stack.get_patch_commit(patchname))
+            .allow_diff_edit(allow_diff_edit)
+            .edit(&stack, self.repo, &edit_matches)?
+        {
+            patchedit::EditOutcome::TemplateSaved(_) => panic!("template save not enabled"),
+            patchedit::EditOutcome::Edited {
+                new_patchname,
+                new_commit_id,
+            } => {
+                let stack = if new_patchname.is_some() || new_commit_id.is_some() {
+                    stack
+                        .setup_transaction()
+                        .committer_date_is_author_date(self.committer_date_is_author_date)
+                        .with_output_stream(get_color_stdout(self.matches))
+                        .transact(|trans| {
+                            let patchname = if let Some(new_patchname) = new_patchname.as_ref() {
+                                trans.rename_patch(patchname, new_patchname)?;
+                                new_patchname
+                            } else {
+                                patchname
+                            };
+                            if let Some(commit_id) = new_commit_id {
+                                trans.update_patch(patchname, commit_id)?;
+                            }
+                            Ok(())
+                        })
+                        .execute(&format!("edit: {patchname}"))?
+                } else {
+                    stack
+                };
+                Ok((stack, new_patchname.unwrap_or_else(|| patchname.clone())))
             }
-        })
-        .collect();
-    let check_merged = matches.get_flag("merged");
+        }
+    }
 
-    stack.check_head_top_mismatch()?;
-    stack
-        .setup_transaction()
-        .use_index_and_worktree(true)
-        .allow_push_conflicts(allow_push_conflicts)
-        .committer_date_is_author_date(committer_date_is_author_date)
-        .with_output_stream(get_color_stdout(matches))
-        .transact(|trans| trans.push_patches(&to_push, check_merged))
-        .execute("rebase (reapply)")?;
+    /// Push the pending patches and order the unapplied patches per the instructions.
+    ///
+    /// The instructions are saved beforehand, with `remaining` following the already
+    /// handled patches, such that the rebase may be continued if the push conflicts.
+    fn push_pending(
+        &self,
+        stack: Stack<'repo>,
+        schedule: &mut Schedule,
+        remaining: &[Instruction],
+    ) -> Result<Stack<'repo>> {
+        self.save_todo(schedule, remaining)?;
+        let check_merged = self.matches.get_flag("merged");
+        stack.check_head_top_mismatch()?;
+        let stack = stack
+            .setup_transaction()
+            .use_index_and_worktree(true)
+            .allow_push_conflicts(self.allow_push_conflicts)
+            .committer_date_is_author_date(self.committer_date_is_author_date)
+            .with_output_stream(get_color_stdout(self.matches))
+            .transact(|trans| {
+                // Patches may already be applied when continuing after conflicts.
+                let to_push: Vec<PatchName> = schedule
+                    .pending
+                    .iter()
+                    .filter(|pn| !trans.applied().contains(pn))
+                    .cloned()
+                    .collect();
+                if let Some(upstream_ids) = self.upstream_ids {
+                    trans.push_patches_delete_merged(&to_push, upstream_ids)?;
+                } else {
+                    trans.push_patches(&to_push, check_merged)?;
+                }
 
-    Ok(())
+                let mut unapplied: Vec<PatchName> = schedule
+                    .unapplied
+                    .iter()
+                    .filter(|pn| trans.unapplied().contains(pn))
+                    .cloned()
+                    .collect();
+                let others: Vec<PatchName> = trans
+                    .unapplied()
+                    .iter()
+                    .filter(|pn| !unapplied.contains(pn))
+                    .cloned()
+                    .collect();
+                unapplied.extend(others);
+                trans.reorder_patches(None, Some(&unapplied), None)
+            })
+            .execute("rebase (reapply)")?;
+        schedule.pending.clear();
+        Ok(stack)
+    }
+
+    /// Empty the topmost patch, leaving its changes staged in the index.
+    fn spill_top(&self, stack: Stack<'repo>) -> Result<Stack<'repo>> {
+        let patchname = stack
+            .applied()
+            .last()
+            .expect("split patch was just pushed")
+            .clone();
+        let patch_commit = stack.get_patch_commit(&patchname);
+        let parent_tree_id = patch_commit.get_parent_commit()?.tree_id()?.detach();
+        let commit_id = self.repo.commit_ex(
+            &patch_commit.author_strict()?,
+            self.repo.get_committer()?,
+            &patch_commit.message_ex(),
+            parent_tree_id,
+            patch_commit.parent_ids().map(|id| id.detach()),
+        )?;
+        let stack = stack
+            .setup_transaction()
+            .use_index_and_worktree(false)
+            .with_output_stream(get_color_stdout(self.matches))
+            .transact(|trans| trans.update_patch(&patchname, commit_id))
+            .execute(&format!("spill {patchname}"))?;
+        Ok(stack)
+    }
+
+    /// Save the instructions needed to continue the rebase.
+    ///
+    /// The patches handled so far are recorded ahead of the `remaining` instructions
+    /// such that the patch ordering and squash targets are preserved. The upstream
+    /// commits used to detect merged patches are saved alongside.
+    fn save_todo(&self, schedule: &Schedule, remaining: &[Instruction]) -> Result<()> {
+        let mut todo = String::new();
+        let mut apply = true;
+        for patchname in &schedule.patchnames {
+            let action = if schedule.unapplied.contains(patchname) {
+                Action::Unapply(patchname.clone())
+            } else {
+                Action::Keep(patchname.clone())
+            };
+            writeln!(todo, "{action}")?;
+        }
+        for instruction in remaining {
+            if apply && !instruction.apply {
+                writeln!(todo, "{INTERACTIVE_APPLY_LINE}")?;
+                apply = false;
+            }
+            writeln!(todo, "{}", instruction.action)?;
+        }
+        std::fs::write(self.repo.path().join(INTERACTIVE_TODO_FILENAME), todo)?;
+        if let Some(upstream_ids) = self.upstream_ids {
+            let mut upstream = String::new();
+            for upstream_id in upstream_ids {
+                writeln!(upstream, "{upstream_id}")?;
+            }
+            std::fs::write(
+                self.repo.path().join(INTERACTIVE_UPSTREAM_FILENAME),
+                upstream,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Run an `exec` instruction's command with the shell.
+fn run_exec(command: &str) -> Result<()> {
+    let shell = if cfg!(target_os = "windows") {
+        "sh"
+    } else {
+        "/bin/sh"
+    };
+    let status = std::process::Command::new(shell)
+        .arg("-c")
+        .arg(command)
+        .status()
+        .with_context(|| format!("could not execute `{command}`"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "exec `{command}` failed; fix the problem and run `stg rebase --continue`"
+        ))
+    }
 }
 
-fn make_instructions_template(stack: &Stack, previously_applied: &[PatchName]) -> String {
+fn make_instructions_template(
+    stack: &Stack,
+    previously_applied: &[PatchName],
+    autosquash: bool,
+) -> Result<String> {
     let name_width = stack.all_patches().map(PatchName::len).max().unwrap();
+    let patchnames: Vec<PatchName> = stack
+        .applied()
+        .iter()
+        .chain(stack.unapplied())
+        .cloned()
+        .collect();
+    let groups = if autosquash {
+        super::squash::find_autosquash_groups(stack, &patchnames)?
+    } else {
+        Vec::new()
+    };
+    let is_squashed = |patchname: &PatchName| {
+        groups
+            .iter()
+            .any(|group| group.patchnames[1..].contains(patchname))
+    };
+
     let mut template = String::with_capacity(4096);
     let mut found_apply_boundary = false;
-    for patchname in stack.all_patches() {
-        if !found_apply_boundary && !previously_applied.contains(patchname) {
-            writeln!(template, "{INTERACTIVE_APPLY_LINE}").unwrap();
-            found_apply_boundary = true;
-        }
+    let write_line = |template: &mut String, action: &str, patchname: &PatchName| {
         let commit = stack.get_patch_commit(patchname);
         let subject = commit
             .message()
             .map(|message_ref| message_ref.title.to_str_lossy())
             .unwrap_or_default();
-        writeln!(template, "keep {patchname:name_width$} # {subject}").unwrap();
+        writeln!(template, "{action} {patchname:name_width$} # {subject}").unwrap();
+    };
+    for patchname in stack.all_patches() {
+        if is_squashed(patchname) {
+            continue;
+        }
+        if !found_apply_boundary && !previously_applied.contains(patchname) {
+            writeln!(template, "{INTERACTIVE_APPLY_LINE}").unwrap();
+            found_apply_boundary = true;
+        }
+        write_line(&mut template, "keep", patchname);
+        if let Some(group) = groups
+            .iter()
+            .find(|group| &group.patchnames[0] == patchname)
+        {
+            for fixup_patchname in &group.patchnames[1..] {
+                let commit = stack.get_patch_commit(fixup_patchname);
+                let is_squash = commit
+                    .message()
+                    .map(|message_ref| message_ref.title.starts_with(b"squash! "))
+                    .unwrap_or(false);
+                let action = if is_squash { "squash" } else { "fixup" };
+                write_line(&mut template, action, fixup_patchname);
+            }
+        }
     }
     if !found_apply_boundary {
         writeln!(template, "{INTERACTIVE_APPLY_LINE}").unwrap();
     }
     template.push_str(INTERACTIVE_HELP_LINES);
-    template
+    Ok(template)
 }
 
 fn parse_instructions(buf: &str) -> Result<Vec<Instruction>> {
     let mut instructions = Vec::new();
     let mut apply = true;
 
-    for line in buf.lines() {
+    for (line_idx, line) in buf.lines().enumerate() {
+        let line_num = line_idx + 1;
         let line = line.trim();
 
         if line.contains(INTERACTIVE_APPLY_LINE) {
@@ -522,54 +1053,139 @@ fn parse_instructions(buf: &str) -> Result<Vec<Instruction>> {
             }
         }
 
-        let instruction_str = if let Some((instruction_str, _comment)) = line.split_once('#') {
-            instruction_str
-        } else {
-            line
-        }
-        .trim();
-
-        if instruction_str.is_empty() {
+        if line.is_empty() || line.starts_with('#') {
             continue;
         }
 
-        if let Some((action_str, patchname_str)) =
-            instruction_str.split_once(|c: char| c.is_ascii_whitespace())
-        {
-            let action = match action_str {
-                "k" | "keep" => Action::Keep,
-                "e" | "edit" => Action::Edit,
-                "s" | "squash" => Action::Squash,
-                "f" | "fix" | "fixup" => Action::Fixup,
-                "h" | "hide" => Action::Hide,
-                "d" | "delete" => Action::Delete,
-                _ => return Err(anyhow!("unknown instruction action `{action_str}`")),
-            };
-
-            let patchname = PatchName::from_str(patchname_str)?;
+        let (action_str, args) = line
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .map_or((line, ""), |(action_str, args)| (action_str, args.trim()));
 
-            instructions.push(Instruction {
-                patchname,
-                action,
-                apply,
-            });
+        // The command of an exec instruction may contain '#'; other instructions may
+        // have trailing comments.
+        let args = if matches!(action_str, "x" | "exec") {
+            args
+        } else if let Some((args, _comment)) = args.split_once('#') {
+            args.trim()
         } else {
-            return Err(anyhow!("bad instruction line: `{line}`"));
-        }
+            args
+        };
+
+        let bad_line = || anyhow!("bad instruction line: `{line}`");
+        let patchname_arg = || -> Result<PatchName> {
+            if args.is_empty() || args.contains(|c: char| c.is_ascii_whitespace()) {
+                Err(bad_line())
+            } else {
+                Ok(PatchName::from_str(args)?)
+            }
+        };
+
+        let action = match action_str {
+            "k" | "keep" => Action::Keep(patchname_arg()?),
+            "e" | "edit" => Action::Edit(patchname_arg()?),
+            "r" | "reword" => Action::Reword(patchname_arg()?),
+            "n" | "rename" => {
+                if let Some((patchname_str, new_patchname_str)) =
+                    args.split_once(|c: char| c.is_ascii_whitespace())
+                {
+                    let new_patchname_str = new_patchname_str.trim();
+                    if new_patchname_str.contains(|c: char| c.is_ascii_whitespace()) {
+                        return Err(bad_line());
+                    }
+                    Action::Rename(
+                        PatchName::from_str(patchname_str)?,
+                        PatchName::from_str(new_patchname_str).map_err(|e| {
+                            anyhow!("invalid new patch name on line {line_num}: {e}")
+                        })?,
+                    )
+                } else {
+                    return Err(bad_line());
+                }
+            }
+            "s" | "squash" => Action::Squash(patchname_arg()?),
+            "f" | "fix" | "fixup" => Action::Fixup(patchname_arg()?),
+            "split" => Action::Split(patchname_arg()?),
+            "u" | "unapply" => Action::Unapply(patchname_arg()?),
+            "h" | "hide" => Action::Hide(patchname_arg()?),
+            "d" | "delete" => Action::Delete(patchname_arg()?),
+            "x" | "exec" => {
+                if args.is_empty() {
+                    return Err(bad_line());
+                }
+                Action::Exec(args.to_string())
+            }
+            "b" | "break" => {
+                if !args.is_empty() {
+                    return Err(bad_line());
+                }
+                Action::Break
+            }
+            _ => return Err(anyhow!("unknown instruction action `{action_str}`")),
+        };
+
+        instructions.push(Instruction {
+            action,
+            apply,
+            line_num,
+        });
     }
     Ok(instructions)
 }
 
 fn validate_instructions(stack: &Stack, instructions: &[Instruction]) -> Result<()> {
     let mut seen_patchnames: Vec<&PatchName> = Vec::new();
+    let mut new_patchnames: Vec<&PatchName> = Vec::new();
+    let mut has_preceding_patch = false;
+
     for instruction in instructions {
-        let patchname = &instruction.patchname;
-        if !stack.has_patch(patchname) {
-            return Err(anyhow!("unknown patch name `{patchname}`"));
-        } else if seen_patchnames.contains(&patchname) {
-            return Err(anyhow!("duplicated patch name `{patchname}`"));
-        } else {
-            seen_patchnames.push(patchname);
+        let line_num = instruction.line_num;
+        let action = &instruction.action;
+
+        if let Some(patchname) = action.patchname() {
+            if !stack.has_patch(patchname) {
+                return Err(anyhow!(
+                    "unknown patch name `{patchname}` on line {line_num}"
+                ));
+            } else if seen_patchnames.contains(&patchname) {
+                return Err(anyhow!(
+                    "duplicated patch name `{patchname}` on line {line_num}"
+                ));
+            } else {
+                seen_patchnames.push(patchname);
+            }
+        }
+
+        match action {
+            Action::Squash(patchname) | Action::Fixup(patchname) if !has_preceding_patch => {
+                let action_str = action.name();
+                return Err(anyhow!(
+                    "cannot {action_str} `{patchname}`: no preceding patch on line {line_num}"
+                ));
+            }
+            Action::Rename(patchname, new_patchname) => {
+                if let Some(colliding_patchname) = stack
+                    .collides(new_patchname)
+                    .filter(|pn| pn != &patchname)
+                    .or_else(|| {
+                        new_patchnames
+                            .iter()
+                            .find(|pn| new_patchname.collides(pn))
+                            .copied()
+                    })
+                {
+                    return Err(anyhow!(
+                        "cannot rename `{patchname}` to `{new_patchname}` on line {line_num}: \
+                         patch `{colliding_patchname}` already exists"
+                    ));
+                }
+                new_patchnames.push(new_patchname);
+            }
+            _ => {}
+        }
+
+        match action {
+            Action::Hide(_) | Action::Delete(_) | Action::Exec(_) | Action::Break => {}
+            _ => has_preceding_patch = true,
         }
     }
     Ok(())
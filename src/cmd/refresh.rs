@@ -112,6 +112,25 @@ fn make() -> clap::Command {
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchLocator)),
         )
+        .arg(
+            Arg::new("fixup")
+                .long("fixup")
+                .help("Refresh into a new fixup! patch for <patch>")
+                .long_help(
+                    "Instead of refreshing an existing patch, create a new patch on \
+                     top of the stack with the message \"fixup! <subject>\", where \
+                     <subject> is the subject of <patch>. Such patches are squashed \
+                     into <patch> by 'stg squash --auto' or 'stg rebase --autosquash'. \
+                     With '--update', only the files already modified by <patch> are \
+                     refreshed.",
+                )
+                .num_args(1)
+                .value_name("patch")
+                .value_hint(ValueHint::Other)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator))
+                .conflicts_with_all(["patch", "annotate"]),
+        )
         .arg(
             Arg::new("annotate")
                 .long("annotate")
@@ -141,7 +160,8 @@ fn make() -> clap::Command {
                 .help("OBSOLETE: use 'stg spill'")
                 .hide(true)
                 .action(clap::ArgAction::SetTrue),
-        );
+        )
+        .args(super::check::gate_args());
 
     patchedit::add_args(app, true, false)
 }
@@ -160,6 +180,13 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     stack.check_head_top_mismatch()?;
 
+    if let Some(patch_loc) = matches.get_one::<PatchLocator>("fixup") {
+        let target_patchname = patch_loc
+            .resolve_name(&stack)?
+            .constrain(&stack, LocationConstraint::Visible)?;
+        return refresh_fixup(stack, matches, &target_patchname);
+    }
+
     let patchname = if let Some(patch_loc) = matches.get_one::<PatchLocator>("patch") {
         patch_loc
             .resolve_name(&stack)?
@@ -170,6 +197,13 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Err(super::Error::NoAppliedPatches.into());
     };
 
+    gate_refresh(
+        &stack,
+        matches,
+        matches.get_flag("update").then_some(&patchname),
+        &patchname,
+    )?;
+
     let tree_id = assemble_refresh_tree(
         &stack,
         matches,
@@ -337,6 +371,51 @@ fn run(matches: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Refresh changes into a new `fixup!` patch targeting `target_patchname`.
+fn refresh_fixup(stack: Stack, matches: &ArgMatches, target_patchname: &PatchName) -> Result<()> {
+    let repo = stack.repo;
+    gate_refresh(
+        &stack,
+        matches,
+        matches.get_flag("update").then_some(target_patchname),
+        target_patchname,
+    )?;
+
+    let tree_id = assemble_refresh_tree(
+        &stack,
+        matches,
+        matches.get_flag("update").then_some(target_patchname),
+    )?;
+
+    let (patchname, commit_id) = match patchedit::EditBuilder::default()
+        .allow_autosign(true)
+        .allow_diff_edit(false)
+        .allow_implicit_edit(false)
+        .allow_template_save(false)
+        .default_author(repo.get_author()?.override_author(matches))
+        .default_message(super::squash::fixup_message(&stack, target_patchname)?)
+        .override_tree_id(tree_id)
+        .override_parent_id(stack.get_branch_head().id)
+        .edit(&stack, repo, matches)?
+    {
+        patchedit::EditOutcome::Edited {
+            new_patchname,
+            new_commit_id,
+        } => (
+            new_patchname.expect("must have new patch name because no original name"),
+            new_commit_id.expect("must have new commit id because no original patch commit"),
+        ),
+        patchedit::EditOutcome::TemplateSaved(_) => panic!("not allowed for refresh"),
+    };
+
+    stack
+        .setup_transaction()
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| trans.new_applied(&patchname, commit_id))
+        .execute(&format!("refresh --fixup {target_patchname}: {patchname}"))?;
+    Ok(())
+}
+
 fn determine_refresh_paths(
     stupid: &StupidContext,
     statuses: &Statuses,
@@ -398,6 +477,52 @@ fn determine_refresh_paths(
     Ok(refresh_paths)
 }
 
+/// Check the changes to be refreshed into `patchname` if refresh is gated.
+///
+/// The changes are checked before any of them are staged such that the index is left
+/// untouched when a problem is found.
+fn gate_refresh(
+    stack: &Stack,
+    matches: &ArgMatches,
+    limit_to_patchname: Option<&PatchName>,
+    patchname: &PatchName,
+) -> Result<()> {
+    let repo = stack.repo;
+    if !super::check::is_gated(repo, matches, "refresh") {
+        return Ok(());
+    }
+
+    let (refresh_paths, is_path_limiting) = get_refresh_paths(stack, matches, limit_to_patchname)?;
+    let stupid = repo.stupid();
+    let base_tree_id = if is_path_limiting {
+        stack.get_branch_head().tree_id()?.detach()
+    } else {
+        stupid.write_tree()?
+    };
+    let tree_id = stupid.with_temp_index(|stupid_temp| {
+        stupid_temp.read_tree(base_tree_id)?;
+        if !refresh_paths.is_empty() {
+            stupid_temp.update_index(Some(&refresh_paths))?;
+        }
+        stupid_temp.write_tree()
+    })?;
+    let commit_id = repo.commit_ex(
+        &repo.get_author()?.override_author(matches),
+        repo.get_committer()?,
+        &Message::from(format!("Refresh of {patchname}")),
+        tree_id,
+        [stack.get_branch_head().id],
+    )?;
+
+    super::check::gate(
+        repo,
+        matches,
+        "refresh",
+        &[(patchname.to_string(), commit_id)],
+        true,
+    )
+}
+
 fn write_tree(
     stack: &Stack,
     refresh_paths: &IndexSet<PathBuf>,
@@ -423,11 +548,12 @@ fn write_tree(
     }
 }
 
-pub(crate) fn assemble_refresh_tree(
+/// Get the paths to be refreshed and whether refreshing is limited to those paths.
+fn get_refresh_paths(
     stack: &Stack,
     matches: &ArgMatches,
     limit_to_patchname: Option<&PatchName>,
-) -> Result<gix::ObjectId> {
+) -> Result<(IndexSet<PathBuf>, bool)> {
     let stupid = stack.repo.stupid();
     let opt_pathspecs = matches.get_many::<PathBuf>("pathspecs");
     let is_path_limiting = limit_to_patchname.is_some() || opt_pathspecs.is_some();
@@ -465,6 +591,17 @@ pub(crate) fn assemble_refresh_tree(
         )?
     };
 
+    Ok((refresh_paths, is_path_limiting))
+}
+
+pub(crate) fn assemble_refresh_tree(
+    stack: &Stack,
+    matches: &ArgMatches,
+    limit_to_patchname: Option<&PatchName>,
+) -> Result<gix::ObjectId> {
+    let stupid = stack.repo.stupid();
+    let (refresh_paths, is_path_limiting) = get_refresh_paths(stack, matches, limit_to_patchname)?;
+
     let tree_id = write_tree(stack, &refresh_paths, is_path_limiting)?;
 
     let tree_id = if matches.get_flag("no-verify")
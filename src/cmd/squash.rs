@@ -2,9 +2,10 @@
 
 //! `stg squash` implementation.
 
-use std::fmt::Write;
+use std::{collections::HashMap, fmt::Write};
 
 use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
 use clap::{Arg, ArgMatches};
 
 use crate::{
@@ -47,7 +48,15 @@ fn make() -> clap::Command {
             \n\
             Conflicts can occur whenever a patch is pushed; this is, in steps (2) and \
             (5). If conflicts occur, the squash command will halt such that the \
-            conflicts may be resolved manually.",
+            conflicts may be resolved manually.\n\
+            \n\
+            With '--auto', patches whose subject starts with \"fixup! \" or \
+            \"squash! \" are instead squashed into the earlier patch named by the \
+            rest of the subject, which may be either that patch's subject or its \
+            patch name. Such patches are created with 'stg new --fixup' or \
+            'stg refresh --fixup'. Fixup patches take on the target patch's \
+            message, whereas squash patches cause the combined message to be \
+            edited.",
         )
         .arg(
             Arg::new("patchranges")
@@ -56,7 +65,14 @@ fn make() -> clap::Command {
                 .num_args(1..)
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchRange))
-                .required(true),
+                .required_unless_present("auto"),
+        )
+        .arg(
+            Arg::new("auto")
+                .long("auto")
+                .help("Squash fixup! and squash! patches into their targets")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["patchranges", "name", "save-template"]),
         )
         .arg(
             Arg::new("name")
@@ -80,6 +96,31 @@ fn run(matches: &ArgMatches) -> Result<()> {
     statuses.check_conflicts()?;
     stack.check_head_top_mismatch()?;
 
+    if matches.get_flag("auto") {
+        let patchnames: Vec<PatchName> = stack
+            .applied()
+            .iter()
+            .chain(stack.unapplied())
+            .cloned()
+            .collect();
+        if find_autosquash_groups(&stack, &patchnames)?.is_empty() {
+            print_info_message(matches, "no fixup! or squash! patches to squash");
+            return Ok(());
+        }
+        stack
+            .setup_transaction()
+            .allow_conflicts(true)
+            .use_index_and_worktree(true)
+            .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| {
+                autosquash(trans, &patchnames)?;
+                Ok(())
+            })
+            .execute("squash --auto")?;
+        return Ok(());
+    }
+
     let squash_patchnames: Vec<PatchName> = patchrange::resolve_names(
         &stack,
         matches
@@ -274,3 +315,192 @@ fn try_squash(
         Ok(None)
     }
 }
+
+/// Subject prefixes marking a patch to be squashed into an earlier patch.
+///
+/// The boolean indicates whether the patch's message is to be kept, as opposed to
+/// being discarded in favor of the target patch's message.
+const AUTOSQUASH_PREFIXES: [(&str, bool); 2] = [("fixup! ", false), ("squash! ", true)];
+
+/// A target patch along with the `fixup!` and `squash!` patches to squash into it.
+pub(super) struct AutosquashGroup {
+    /// Target patch followed by the patches to be squashed into it.
+    pub(super) patchnames: Vec<PatchName>,
+
+    /// Whether any of the patches is a `squash!` patch.
+    pub(super) keep_messages: bool,
+}
+
+/// Get the subject line of a patch's commit message.
+fn patch_subject<'repo>(
+    stack_state: &impl StackStateAccess<'repo>,
+    patchname: &PatchName,
+) -> Result<String> {
+    let message = stack_state.get_patch_commit(patchname).message_ex();
+    let message = message.decode()?;
+    Ok(message
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string())
+}
+
+/// Make the commit message for a new `fixup!` patch targeting the given patch.
+pub(super) fn fixup_message<'repo>(
+    stack_state: &impl StackStateAccess<'repo>,
+    target_patchname: &PatchName,
+) -> Result<String> {
+    let subject = patch_subject(stack_state, target_patchname)?;
+    Ok(format!("fixup! {subject}\n"))
+}
+
+/// Find the `fixup!` and `squash!` patches amongst `patchnames`.
+///
+/// A patch's target is the earliest preceding patch in `patchnames` whose subject
+/// matches the remainder of the patch's subject. Failing that, the target may be
+/// named by its patch name. Patches without a matching target are left out of the
+/// returned groups, which are ordered by target.
+pub(super) fn find_autosquash_groups<'repo>(
+    stack_state: &impl StackStateAccess<'repo>,
+    patchnames: &[PatchName],
+) -> Result<Vec<AutosquashGroup>> {
+    let mut groups: Vec<AutosquashGroup> = Vec::new();
+    let mut group_indices: HashMap<PatchName, usize> = HashMap::new();
+    let mut by_subject: HashMap<String, &PatchName> = HashMap::new();
+    let mut seen: Vec<&PatchName> = Vec::new();
+
+    for patchname in patchnames {
+        let subject = patch_subject(stack_state, patchname)?;
+        let mut remainder = subject.as_str();
+        let mut is_squash_patch = false;
+        let mut keep_message = false;
+        while let Some((rest, keep)) = AUTOSQUASH_PREFIXES
+            .iter()
+            .find_map(|(prefix, keep)| remainder.strip_prefix(prefix).map(|rest| (rest, *keep)))
+        {
+            remainder = rest.trim_start();
+            is_squash_patch = true;
+            keep_message |= keep;
+        }
+
+        let target = if is_squash_patch {
+            by_subject.get(remainder).copied().or_else(|| {
+                seen.iter()
+                    .find(|pn| AsRef::<str>::as_ref(**pn) == remainder)
+                    .copied()
+            })
+        } else {
+            None
+        };
+
+        if let Some(target) = target {
+            let index = if let Some(index) = group_indices.get(target) {
+                *index
+            } else {
+                groups.push(AutosquashGroup {
+                    patchnames: vec![target.clone()],
+                    keep_messages: false,
+                });
+                group_indices.insert(target.clone(), groups.len() - 1);
+                groups.len() - 1
+            };
+            let group = &mut groups[index];
+            group.patchnames.push(patchname.clone());
+            group.keep_messages |= keep_message;
+        } else {
+            by_subject.entry(subject).or_insert(patchname);
+            seen.push(patchname);
+        }
+    }
+
+    groups.sort_by_key(|group| {
+        patchnames
+            .iter()
+            .position(|pn| pn == &group.patchnames[0])
+            .expect("target is one of the patchnames")
+    });
+    Ok(groups)
+}
+
+/// Make [`ArgMatches`] to drive [`squash()`] without user-provided options.
+///
+/// When `keep_messages` is false, the squashed patch takes on the message of the
+/// target patch. Otherwise the combined message is edited interactively.
+pub(super) fn make_squash_matches<'repo>(
+    stack_state: &impl StackStateAccess<'repo>,
+    target_patchname: &PatchName,
+    keep_messages: bool,
+) -> Result<ArgMatches> {
+    let dummy_squash_command = clap::Command::new("dummy-squash");
+    let dummy_squash_command = patchedit::add_args(dummy_squash_command, true, false);
+    let squash_matches = if keep_messages {
+        dummy_squash_command.try_get_matches_from(["dummy-squash", "--edit"])
+    } else {
+        let commit = stack_state.get_patch_commit(target_patchname);
+        let message = commit.message_raw()?.to_str().map_err(|_| {
+            anyhow!("fixup target patch `{target_patchname}` has non-UTF-8 message")
+        })?;
+        dummy_squash_command.try_get_matches_from(["dummy-squash", "--message", message])
+    }
+    .expect("dummy command has valid arguments");
+    Ok(squash_matches)
+}
+
+/// Squash `fixup!` and `squash!` patches amongst `patchnames` into their targets.
+///
+/// Each squashed patch retains the name and stack position of its target patch. The
+/// names of the remaining patches from `patchnames` are returned, in order.
+pub(super) fn autosquash(
+    trans: &mut StackTransaction,
+    patchnames: &[PatchName],
+) -> Result<Vec<PatchName>> {
+    let groups = find_autosquash_groups(trans, patchnames)?;
+    let order: Vec<PatchName> = trans
+        .applied()
+        .iter()
+        .chain(trans.unapplied())
+        .cloned()
+        .collect();
+    let mut renames: HashMap<PatchName, PatchName> = HashMap::new();
+
+    for group in &groups {
+        let target_patchname = &group.patchnames[0];
+        let squash_matches = make_squash_matches(trans, target_patchname, group.keep_messages)?;
+        let should_push_squashed = trans
+            .applied()
+            .iter()
+            .any(|pn| group.patchnames.contains(pn));
+        let new_patchname = squash(
+            trans,
+            &squash_matches,
+            &group.patchnames,
+            Some(target_patchname),
+            should_push_squashed,
+        )?;
+        renames.insert(target_patchname.clone(), new_patchname);
+    }
+
+    let squashed_away: Vec<&PatchName> = groups
+        .iter()
+        .flat_map(|group| &group.patchnames[1..])
+        .collect();
+    let rename = |patchname: &PatchName| -> Option<PatchName> {
+        if squashed_away.contains(&patchname) {
+            None
+        } else {
+            Some(renames.get(patchname).unwrap_or(patchname).clone())
+        }
+    };
+
+    // Squashed patches that end up unapplied are placed at the start of the
+    // unapplied list; restore the original ordering.
+    let unapplied: Vec<PatchName> = order
+        .iter()
+        .filter_map(rename)
+        .filter(|pn| trans.unapplied().contains(pn))
+        .collect();
+    trans.reorder_patches(None, Some(&unapplied), None)?;
+
+    Ok(patchnames.iter().filter_map(rename).collect())
+}
@@ -0,0 +1,357 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg status` implementation.
+
+use std::{borrow::Cow, collections::HashMap, io::Write, path::PathBuf};
+
+use anyhow::Result;
+use bstr::ByteSlice;
+use clap::{Arg, ArgMatches, ValueHint};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::PatchName,
+    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stupid::{StatusEntryKind, StatusOptions, Stupid},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "status",
+    category: super::CommandCategory::StackInspection,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Show the stack and work tree status")
+        .long_about(
+            "Show the state of the stack, the work tree, and the index.\n\
+             \n\
+             The output starts with the stack state: the topmost applied patch, the \
+             number of applied, unapplied, and hidden patches, whether the branch \
+             HEAD matches the top of the stack, and whether there are unresolved \
+             conflicts. Each modified file is then listed with its two-letter status \
+             code and its path relative to the top of the work tree, annotated with \
+             the applied patches that already modify that file. Paths given as \
+             arguments are also relative to the top of the work tree.\n\
+             \n\
+             With '--short', only the files are listed, without annotations, the \
+             same as 'git status --short' run from the top of the work tree.\n\
+             \n\
+             With '--porcelain', the stack state is output as '# stgit.<key> \
+             <value>' header lines. Each following file line has the status code \
+             and path, followed by a tab and the space-separated names of the \
+             applied patches modifying the file, if any.",
+        )
+        .arg(
+            Arg::new("pathspecs")
+                .help("Limit the file status to these paths")
+                .value_name("path")
+                .num_args(1..)
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(ValueHint::AnyPath),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("untracked-files")
+                .long("untracked-files")
+                .short('u')
+                .help("Show untracked files")
+                .long_help(
+                    "Show untracked files.\n\
+                     \n\
+                     The mode may be \"no\" to show no untracked files, \"normal\" to \
+                     show untracked files and directories, or \"all\" to also show the \
+                     files within untracked directories. The default is \"normal\", or \
+                     \"all\" if this option is given without a mode.",
+                )
+                .value_name("mode")
+                .num_args(0..=1)
+                .default_missing_value("all")
+                .require_equals(true)
+                .value_parser(["no", "normal", "all"]),
+        )
+        .arg(
+            Arg::new("short")
+                .long("short")
+                .short('s')
+                .help("Show only the file status, as with `git status --short`")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("porcelain")
+                .long("porcelain")
+                .help("Output in a stable format for scripts")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("short"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Output in JSON format")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["porcelain", "short"]),
+        )
+}
+
+#[derive(serde::Serialize)]
+struct StackStatus<'a> {
+    branch: &'a str,
+    top: Option<&'a str>,
+    applied: usize,
+    unapplied: usize,
+    hidden: usize,
+    head_top: bool,
+    conflicts: bool,
+    files: Vec<FileStatus<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct FileStatus<'a> {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orig_path: Option<String>,
+    index: char,
+    worktree: char,
+    patches: Vec<&'a str>,
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+    let stupid = repo.stupid();
+
+    let mut status_opts = StatusOptions::default();
+    let untracked_mode = matches
+        .get_one::<String>("untracked-files")
+        .map_or("normal", String::as_str);
+    status_opts
+        .include_untracked(untracked_mode != "no")
+        .recurse_untracked_dirs(untracked_mode == "all")
+        .include_submodules(true)
+        .in_work_root(true);
+    if let Some(pathspecs) = matches.get_many::<PathBuf>("pathspecs") {
+        status_opts.pathspecs(pathspecs);
+    }
+    let statuses = stupid.statuses(Some(&status_opts))?;
+
+    // The short output matches `git status --short` and thus has no annotations.
+    let annotate = !matches.get_flag("short");
+
+    let mut patches_by_path: HashMap<PathBuf, Vec<&PatchName>> = HashMap::new();
+    if annotate && !statuses.is_empty() {
+        for patchname in stack.applied() {
+            let patch_commit = stack.get_patch_commit(patchname);
+            let parent_tree_id = patch_commit.get_parent_commit()?.tree_id()?.detach();
+            let diff_files =
+                stupid.diff_tree_files(parent_tree_id, patch_commit.tree_id()?.detach())?;
+            for path in diff_files.iter() {
+                patches_by_path
+                    .entry(path.to_path_buf())
+                    .or_default()
+                    .push(patchname);
+            }
+        }
+    }
+
+    let mut conflicts = false;
+    let mut files = Vec::new();
+    for entry in statuses.iter() {
+        let (index, worktree) = match entry.kind() {
+            StatusEntryKind::Untracked => ('?', '?'),
+            StatusEntryKind::Ignored => continue,
+            kind => {
+                if matches!(kind, StatusEntryKind::Unmerged) {
+                    conflicts = true;
+                }
+                (
+                    entry.index_status().short_code(),
+                    entry.worktree_status().short_code(),
+                )
+            }
+        };
+        let orig_path = entry.orig_path_bytes();
+        let patches = patches_by_path
+            .get(entry.path())
+            .or_else(|| {
+                orig_path.and_then(|orig_path| {
+                    orig_path
+                        .to_path()
+                        .ok()
+                        .and_then(|orig_path| patches_by_path.get(orig_path))
+                })
+            })
+            .map(|patchnames| {
+                patchnames
+                    .iter()
+                    .map(|pn| AsRef::<str>::as_ref(*pn))
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.push(FileStatus {
+            path: entry.path_bytes().to_str_lossy().to_string(),
+            orig_path: orig_path.map(|orig_path| orig_path.to_str_lossy().to_string()),
+            index,
+            worktree,
+            patches,
+        });
+    }
+
+    let status = StackStatus {
+        branch: stack.get_branch_name(),
+        top: stack.applied().last().map(AsRef::<str>::as_ref),
+        applied: stack.applied().len(),
+        unapplied: stack.unapplied().len(),
+        hidden: stack.hidden().len(),
+        head_top: stack.is_head_top(),
+        conflicts,
+        files,
+    };
+
+    // Like `git status`, quote unusual paths in the text output.
+    let quote_non_ascii = repo
+        .config_snapshot()
+        .boolean("core.quotePath")
+        .unwrap_or(true);
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+
+    if matches.get_flag("json") {
+        serde_json::to_writer_pretty(&mut stdout, &status)?;
+        writeln!(stdout)?;
+    } else if matches.get_flag("porcelain") {
+        write_porcelain(&mut stdout, &status, quote_non_ascii)?;
+    } else if matches.get_flag("short") {
+        for file in &status.files {
+            write_file_status(&mut stdout, file, quote_non_ascii)?;
+            writeln!(stdout)?;
+        }
+    } else {
+        write_long(&mut stdout, &status, quote_non_ascii)?;
+    }
+
+    Ok(())
+}
+
+fn write_porcelain(
+    out: &mut impl Write,
+    status: &StackStatus,
+    quote_non_ascii: bool,
+) -> Result<()> {
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+    writeln!(out, "# stgit.branch {}", status.branch)?;
+    writeln!(out, "# stgit.top {}", status.top.unwrap_or("(none)"))?;
+    writeln!(out, "# stgit.applied {}", status.applied)?;
+    writeln!(out, "# stgit.unapplied {}", status.unapplied)?;
+    writeln!(out, "# stgit.hidden {}", status.hidden)?;
+    writeln!(out, "# stgit.head-top {}", yes_no(status.head_top))?;
+    writeln!(out, "# stgit.conflicts {}", yes_no(status.conflicts))?;
+    for file in &status.files {
+        write_file_status(out, file, quote_non_ascii)?;
+        if !file.patches.is_empty() {
+            write!(out, "\t{}", file.patches.join(" "))?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_long(out: &mut impl Write, status: &StackStatus, quote_non_ascii: bool) -> Result<()> {
+    writeln!(out, "On branch {}", status.branch)?;
+    if let Some(top) = status.top {
+        write!(out, "Top patch: {top}")?;
+    } else {
+        write!(out, "No patches applied")?;
+    }
+    write!(
+        out,
+        " ({} applied, {} unapplied",
+        status.applied, status.unapplied
+    )?;
+    if status.hidden > 0 {
+        write!(out, ", {} hidden", status.hidden)?;
+    }
+    writeln!(out, ")")?;
+    if !status.head_top {
+        writeln!(
+            out,
+            "HEAD and stack top are not the same; see `stg repair --help`"
+        )?;
+    }
+    if status.conflicts {
+        writeln!(
+            out,
+            "Unresolved conflicts; resolve them and use `stg add` and `stg refresh`, \
+             or `stg undo --hard` to undo"
+        )?;
+    }
+
+    if !status.files.is_empty() {
+        writeln!(out)?;
+        for file in &status.files {
+            write_file_status(out, file, quote_non_ascii)?;
+            if !file.patches.is_empty() {
+                write!(out, "  ({})", file.patches.join(", "))?;
+            }
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_file_status(out: &mut impl Write, file: &FileStatus, quote_non_ascii: bool) -> Result<()> {
+    write!(out, "{}{} ", file.index, file.worktree)?;
+    if let Some(orig_path) = file.orig_path.as_ref() {
+        write!(out, "{} -> ", quote_path(orig_path, quote_non_ascii))?;
+    }
+    write!(out, "{}", quote_path(&file.path, quote_non_ascii))?;
+    Ok(())
+}
+
+/// Quote path the same way as `git status --short`.
+///
+/// Paths with spaces, double quotes, backslashes, or control characters are enclosed
+/// in double quotes with C-style escapes. Non-ASCII bytes are escaped as octal when
+/// `quote_non_ascii` is set, as with the default `core.quotePath`.
+fn quote_path(path: &str, quote_non_ascii: bool) -> Cow<'_, str> {
+    let needs_escape = |b: u8| b < 0x20 || b == b'"' || b == b'\\' || b == 0x7f;
+    let needs_quote = path
+        .bytes()
+        .any(|b| b == b' ' || needs_escape(b) || (quote_non_ascii && b >= 0x80));
+    if !needs_quote {
+        return Cow::Borrowed(path);
+    }
+
+    let mut quoted = String::with_capacity(path.len() + 2);
+    quoted.push('"');
+    for c in path.chars() {
+        match c {
+            '\x07' => quoted.push_str("\\a"),
+            '\x08' => quoted.push_str("\\b"),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\x0b' => quoted.push_str("\\v"),
+            '\x0c' => quoted.push_str("\\f"),
+            '\r' => quoted.push_str("\\r"),
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 || c == '\x7f' || (quote_non_ascii && !c.is_ascii()) => {
+                let mut buf = [0u8; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    quoted.push_str(&format!("\\{b:03o}"));
+                }
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
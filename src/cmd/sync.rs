@@ -306,7 +306,7 @@ fn series_merge_patch(
     stupid.update_index_refresh()?;
     stupid.read_tree_checkout(trans_head_tree_id, parent_commit_ref.tree())?;
     stupid
-        .apply_to_worktree_and_index(diff.as_ref(), false, false, None, None, None)
+        .apply_to_worktree_and_index(diff.as_ref(), false, false, false, None, None, None)
         .with_context(|| format!("applying {patchname} from series"))?;
     stupid.update_index_refresh()?;
 
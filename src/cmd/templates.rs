@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg templates` implementation.
+
+use std::{collections::BTreeSet, io::Write};
+
+use anyhow::Result;
+
+use crate::{ext::RepositoryExtended, searchpath, templates};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "templates",
+    category: super::CommandCategory::Administration,
+    make,
+    run,
+};
+
+/// Template names used by StGit commands and whether each has a built-in default.
+const KNOWN_TEMPLATES: &[(&str, bool)] = &[
+    ("covermail.tmpl", false),
+    ("patchdescr.tmpl", false),
+    ("patchexport.tmpl", true),
+];
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Inspect StGit template files")
+        .long_about(
+            "Inspect StGit template files.\n\
+             \n\
+             Template files are searched for by name in the following directories, \
+             with the first file found being used:\n\
+             \n\
+             - The repository's git directory, e.g. \".git/\".\n\
+             - \"$XDG_CONFIG_HOME/stgit/templates/\" or \"~/.config/stgit/templates/\".\n\
+             - \"~/.stgit/templates/\".\n\
+             - \"/etc/stgit/templates/\".\n\
+             - \"$prefix/share/stgit/templates/\", where $prefix is the parent of the \
+             \"bin/\" directory containing the stg executable.",
+        )
+        .subcommand_required(true)
+        .subcommand(
+            clap::Command::new("list")
+                .about("List templates and the file used for each")
+                .long_about(
+                    "List templates and the file used for each.\n\
+                     \n\
+                     The templates used by StGit commands are listed along with any \
+                     other \"*.tmpl\" files found in the template directories. For \
+                     each template, the file that takes precedence is shown along with \
+                     its search level. Templates without a file show either \
+                     \"(built-in)\" or \"(none)\".",
+                )
+                .arg(
+                    clap::Arg::new("all")
+                        .long("all")
+                        .short('a')
+                        .help("Also show files shadowed by higher precedence files")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("names")
+                        .help("Only list the named templates")
+                        .value_name("name")
+                        .num_args(1..),
+                ),
+        )
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("list", sub_matches)) => list(sub_matches),
+        _ => panic!("valid subcommand is expected"),
+    }
+}
+
+fn list(matches: &clap::ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open().ok();
+    let show_all = matches.get_flag("all");
+
+    let names: BTreeSet<String> = if let Some(names) = matches.get_many::<String>("names") {
+        names.cloned().collect()
+    } else {
+        let mut names: BTreeSet<String> = KNOWN_TEMPLATES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        for (_, dir) in searchpath::template_dirs(repo.as_ref()) {
+            if let Ok(entries) = std::fs::read_dir(&dir) {
+                for entry in entries.filter_map(|entry| entry.ok()) {
+                    let is_file = entry.file_type().map_or(false, |t| !t.is_dir());
+                    if let Some(name) = entry.file_name().to_str() {
+                        if is_file && name.ends_with(".tmpl") {
+                            names.insert(name.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        names
+    };
+
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+
+    for name in &names {
+        let mut found = templates::template_paths(repo.as_ref(), name)
+            .into_iter()
+            .filter(|(_, path)| path.is_file())
+            .map(|(level, path)| format!("{:<6}  {}", level.as_str(), path.display()))
+            .collect::<Vec<_>>();
+        if KNOWN_TEMPLATES
+            .iter()
+            .any(|&(known, builtin)| builtin && known == name.as_str())
+        {
+            found.push("(built-in)".to_string());
+        }
+        if found.is_empty() {
+            found.push("(none)".to_string());
+        }
+
+        if show_all {
+            for (i, location) in found.iter().enumerate() {
+                let marker = if i == 0 { '*' } else { ' ' };
+                writeln!(stdout, "{marker} {name:<width$}  {location}")?;
+            }
+        } else {
+            writeln!(stdout, "{name:<width$}  {}", found[0])?;
+        }
+    }
+
+    Ok(())
+}
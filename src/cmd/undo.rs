@@ -26,7 +26,12 @@ fn make() -> clap::Command {
         .about("Undo the last command")
         .long_about(
             "Reset the patch stack to the state before the last operation. \
-             Consecutive undos will go back to yet older stack states.",
+             Consecutive undos will go back to yet older stack states.\n\
+             \n\
+             With '--branch-op', undo the last branch-level operation instead: \
+             `stg branch --delete`, `--rename`, `--cleanup`, or `--clone`. Branch-level \
+             operations are recorded in a repository-wide log separate from the stack \
+             logs of individual branches.",
         )
         .arg(
             Arg::new("number")
@@ -52,10 +57,20 @@ fn make() -> clap::Command {
                 .help("Discard changes in the index and worktree")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("branch-op")
+                .long("branch-op")
+                .help("Undo the last branch-level operation")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["number", "hard"]),
+        )
 }
 
 fn run(matches: &clap::ArgMatches) -> Result<()> {
     let repo = gix::Repository::open()?;
+    if matches.get_flag("branch-op") {
+        return super::branch::undo_branch_op(&repo, matches);
+    }
     let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
     let undo_steps = matches.get_one::<isize>("number").copied().unwrap_or(1);
 
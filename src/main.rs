@@ -15,6 +15,7 @@ mod color;
 mod ext;
 mod hook;
 mod patch;
+mod searchpath;
 mod signal;
 mod stack;
 mod stupid;
@@ -468,7 +469,8 @@ fn execute_stgit_alias(
 ///
 /// Since aliases are defined in git config files, an attempt is made to open a repo so
 /// that its local config can be inspected along with the user global and system
-/// configs.
+/// configs. StGit's own system-level config files, e.g. `/etc/stgit/config`, provide
+/// default aliases that may be overridden by any of the git config files.
 ///
 /// N.B. the outcome of this alias search depends on the current directory and thus
 /// depends on -C options having been previously processed.
@@ -483,6 +485,15 @@ pub(crate) fn get_aliases() -> Result<(alias::Aliases, Option<gix::Repository>)>
         global_config_file = gix::config::File::from_globals().ok();
         global_config_file.as_ref()
     };
+    let mut system_config_file = searchpath::system_config()?;
+    let config_file = if let Some(system_config_file) = system_config_file.as_mut() {
+        if let Some(config_file) = config_file {
+            system_config_file.append(config_file.clone());
+        }
+        Some(&*system_config_file)
+    } else {
+        config_file
+    };
     let aliases = alias::get_aliases(config_file, |name| {
         STGIT_COMMANDS.iter().any(|command| command.name == name) || name == "help"
     })?;
@@ -19,11 +19,16 @@ use std::{
     path::PathBuf,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use bstr::{BString, ByteSlice};
 use clap::ArgMatches;
 
-pub(crate) use self::{args::add_args, interactive::call_editor, parse::parse_name_email};
+pub(crate) use self::{
+    args::add_args,
+    interactive::call_editor,
+    parse::parse_name_email,
+    trailers::{append_trailers, parse_trailers},
+};
 use self::{
     description::{DiffBuffer, EditablePatchDescription, EditedPatchDescription},
     interactive::edit_interactive,
@@ -313,7 +318,29 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
             crate::templates::get_template(repo, "patchdescr.tmpl")?
         {
             need_interactive_edit = true;
-            Message::from(message_template)
+            if !crate::templates::uses_template_language(&message_template) {
+                Message::from(message_template)
+            } else {
+                let mut context = crate::templates::Context::default();
+                if let Some(patchname) = template_patchname
+                    .as_ref()
+                    .and_then(Option::as_ref)
+                    .or(original_patchname.as_ref())
+                {
+                    context.insert("name", patchname.to_string());
+                }
+                if let Some(author) = author.as_ref() {
+                    context.insert("authname", author.name.as_bstr());
+                    context.insert("authemail", author.email.as_bstr());
+                    context.insert("authdate", author.time);
+                }
+                context.insert("commname", default_committer.name);
+                context.insert("commemail", default_committer.email);
+                context.insert("commdate", default_committer.time);
+                let description = crate::templates::render(repo, &message_template, &context)
+                    .context("rendering patch description template")?;
+                Message::from(String::from_utf8_lossy(&description).into_owned())
+            }
         } else {
             need_interactive_edit = true;
             Message::default()
@@ -496,7 +523,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
 
             match stupid.with_temp_index(|stupid_temp| {
                 stupid_temp.read_tree(parent_id)?;
-                stupid_temp.apply_to_index(diff.as_ref())?;
+                stupid_temp.apply_to_index(diff.as_ref(), None, false)?;
                 stupid_temp.write_tree()
             }) {
                 Ok(tree_id) => tree_id,
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
-//! Add trailers to a commit message.
+//! Add and inspect trailers in commit messages.
 
 use anyhow::{anyhow, Result};
 use bstr::ByteSlice;
@@ -75,10 +75,103 @@ pub(crate) fn add_trailers<'a, 'b>(
     }
 }
 
+/// Append the given trailers to a commit message.
+///
+/// The trailers are added with `git interpret-trailers`, so they are appended to any
+/// existing trailer block.
+pub(crate) fn append_trailers<'a>(
+    repo: &gix::Repository,
+    message: Message,
+    trailers: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<Message<'static>> {
+    let message_str = message.decode()?;
+    let message_bytes = repo
+        .stupid()
+        .interpret_trailers(message_str.as_bytes(), trailers)?;
+    let message = String::from_utf8(message_bytes)
+        .map_err(|_| anyhow!("could not decode message after adding trailers"))?;
+    Ok(Message::from(message))
+}
+
+/// Get the trailers from the last paragraph of a commit message.
+///
+/// A trailer is a `<token>: <value>` line where the token does not contain whitespace.
+/// The last paragraph of the message is only considered to be a trailer block if every
+/// non-empty line in it is a trailer or a continuation (indented) line. The returned
+/// tokens retain their original case.
+pub(crate) fn parse_trailers(message: &str) -> Vec<(String, String)> {
+    let message = message.trim_end();
+    let last_paragraph = message
+        .rfind("\n\n")
+        .map_or(message, |pos| &message[pos + 2..]);
+
+    let mut trailers: Vec<(String, String)> = Vec::new();
+    for line in last_paragraph.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = trailers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+                continue;
+            } else {
+                return Vec::new();
+            }
+        }
+        if let Some((token, value)) = line.split_once(':') {
+            if !token.is_empty() && !token.contains(char::is_whitespace) {
+                trailers.push((token.to_string(), value.trim().to_string()));
+                continue;
+            }
+        }
+        return Vec::new();
+    }
+
+    // A message consisting of a single paragraph is the subject, not trailers.
+    if last_paragraph.len() == message.len() && !message.contains('\n') {
+        Vec::new()
+    } else {
+        trailers
+    }
+}
+
 #[cfg(test)]
 mod test {
     use clap::Arg;
 
+    use super::parse_trailers;
+
+    #[test]
+    fn parse_trailer_block() {
+        let message = "Subject line\n\
+                       \n\
+                       Body text: not a trailer\n\
+                       \n\
+                       Change-Id: I1234\n\
+                       Signed-off-by: A U Thor <author@example.com>\n\
+                       Link: https://example.com/\n\
+                       \x20 continued\n";
+        assert_eq!(
+            parse_trailers(message),
+            vec![
+                ("Change-Id".to_string(), "I1234".to_string()),
+                (
+                    "Signed-off-by".to_string(),
+                    "A U Thor <author@example.com>".to_string()
+                ),
+                (
+                    "Link".to_string(),
+                    "https://example.com/ continued".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_no_trailers() {
+        assert!(parse_trailers("Subject: with colon\n").is_empty());
+        assert!(parse_trailers("Subject\n\nJust some text.\n").is_empty());
+        assert!(parse_trailers("Subject\n\nAcked-by: x\nnot a trailer\n").is_empty());
+    }
+
     #[test]
     fn val_ind_occ() {
         let m = clap::Command::new("myapp")
@@ -312,6 +312,82 @@ impl TryFrom<String> for PatchName {
     }
 }
 
+/// Patch email subject with its "Re:" and bracketed prefixes removed.
+///
+/// The series version and patch number are parsed from bracketed prefixes such as
+/// "[PATCH v2 3/5]" or "[RFC PATCHv2 3/5]". The remaining `title` is suitable for use
+/// as the first line of a patch message and thus as the basis for [`PatchName::make()`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct EmailSubject<'a> {
+    /// The subject with its prefixes removed.
+    pub(crate) title: &'a str,
+
+    /// Whether the subject has a "Re:" prefix.
+    pub(crate) is_reply: bool,
+
+    /// The series version, e.g. 2 for "[PATCH v2 3/5]".
+    pub(crate) version: Option<usize>,
+
+    /// The patch number and series length, e.g. (3, 5) for "[PATCH v2 3/5]".
+    pub(crate) number: Option<(usize, usize)>,
+}
+
+impl<'a> EmailSubject<'a> {
+    /// Parse the prefixes of an email subject.
+    ///
+    /// Prefixes are removed the same way as `git mailinfo` does without `-k`.
+    pub(crate) fn parse(subject: &'a str) -> Self {
+        let mut rest = subject;
+        let mut is_reply = false;
+        let mut version = None;
+        let mut number = None;
+
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ':');
+            if rest
+                .get(..3)
+                .map_or(false, |prefix| prefix.eq_ignore_ascii_case("re:"))
+            {
+                is_reply = true;
+                rest = &rest[3..];
+            } else if let Some((prefix, after)) = rest
+                .strip_prefix('[')
+                .and_then(|inner| inner.split_once(']'))
+            {
+                for word in prefix.split_whitespace() {
+                    if let Some(n) = parse_version(word) {
+                        version = Some(n);
+                    } else if let Some((index, total)) = word.split_once('/') {
+                        if let (Ok(index), Ok(total)) = (index.parse(), total.parse()) {
+                            number = Some((index, total));
+                        }
+                    }
+                }
+                rest = after;
+            } else {
+                break;
+            }
+        }
+
+        Self {
+            title: rest.trim_end(),
+            is_reply,
+            version,
+            number,
+        }
+    }
+}
+
+/// Parse a version word from a subject prefix, such as "v2" or "PATCHv2".
+fn parse_version(word: &str) -> Option<usize> {
+    let (head, digits) = word.rsplit_once(['v', 'V'])?;
+    if head.is_empty() || head.eq_ignore_ascii_case("patch") {
+        digits.parse().ok()
+    } else {
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -409,6 +485,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_email_subjects() {
+        let cases = [
+            // subject, title, is_reply, version, number
+            ("Fix the thing", "Fix the thing", false, None, None),
+            ("[PATCH] Fix the thing", "Fix the thing", false, None, None),
+            (
+                "[PATCH 2/3] area: fix",
+                "area: fix",
+                false,
+                None,
+                Some((2, 3)),
+            ),
+            (
+                "[PATCH v2 0/3] Cover",
+                "Cover",
+                false,
+                Some(2),
+                Some((0, 3)),
+            ),
+            ("[RFC PATCHv3 1/1] x ", "x", false, Some(3), Some((1, 1))),
+            ("Re: [PATCH v2 1/2] x", "x", true, Some(2), Some((1, 2))),
+            (
+                "[PATCH net-next v4 10/12] x",
+                "x",
+                false,
+                Some(4),
+                Some((10, 12)),
+            ),
+            ("[PATCH] [fs] vfs: x", "vfs: x", false, None, None),
+            (
+                "[PATCH] Revert v1 behavior",
+                "Revert v1 behavior",
+                false,
+                None,
+                None,
+            ),
+        ];
+
+        for (subject, title, is_reply, version, number) in cases {
+            assert_eq!(
+                EmailSubject::parse(subject),
+                EmailSubject {
+                    title,
+                    is_reply,
+                    version,
+                    number,
+                },
+                "{subject}"
+            );
+        }
+    }
+
     #[test]
     fn make_unique_patch_names() {
         let allow = [PatchName("allow".into())];
@@ -11,6 +11,10 @@
 //! - Names of patches in the current stack may be specified. E.g. a specification of
 //!   `patch` would refer to the patch `patch`'s commit. This is equivalent to
 //!   specifying `refs/stacks/<branch>/patch`.
+//! - Earlier versions of a patch may be specified using the stack log. E.g.
+//!   `patch@{3}` refers to `patch` as it was three stack states ago and
+//!   `patch@{2 hours ago}` refers to `patch` as of the newest stack state recorded at
+//!   or before that time.
 
 use std::{rc::Rc, str::FromStr};
 
@@ -21,8 +25,11 @@ use super::{
     SingleRevisionSpec, StGitBoundaryRevisions, StGitRevision,
 };
 use crate::{
-    ext::RepositoryExtended,
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    ext::{RepositoryExtended, TimeExtended},
+    stack::{
+        state_refname_from_branch_name, InitializationPolicy, Stack, StackAccess, StackState,
+        StackStateAccess,
+    },
 };
 
 /// StGit revision specification error variants.
@@ -34,6 +41,18 @@ pub(crate) enum Error {
     #[error("revision not found `{0}`")]
     RevisionNotFound(String),
 
+    #[error("invalid stack log selector `{0}`")]
+    InvalidLogSelector(String),
+
+    #[error("stack log only has {0} states")]
+    LogTooShort(usize),
+
+    #[error("stack log has no states at or before `{0}`")]
+    LogTooRecent(String),
+
+    #[error("branch `{0}` does not have a stack log")]
+    NoStackLog(String),
+
     #[error(transparent)]
     Name(#[from] super::name::Error),
 
@@ -210,6 +229,15 @@ pub(crate) fn resolve<'a, 'repo>(
     Ok(revs)
 }
 
+/// Selector for a historical stack state, i.e. the `{...}` of `patch@{...}`.
+enum StackLogSelector {
+    /// Number of stack log states to go back from the current state.
+    Steps(usize),
+
+    /// Newest stack log state recorded at or before the given time.
+    Time(gix::actor::Time, String),
+}
+
 impl PatchLikeSpec {
     /// Resolve a patch-like revision specification.
     pub(crate) fn resolve<'a, 'repo>(
@@ -217,6 +245,21 @@ impl PatchLikeSpec {
         repo: &'repo gix::Repository,
         stack: &'a impl StackAccess<'repo>,
     ) -> Result<StGitRevision<'repo>> {
+        if let Some((selector, suffix)) = self.split_log_selector()? {
+            let rev = self.resolve_in_log(repo, stack, &selector)?;
+            return if suffix.is_empty() {
+                Ok(rev)
+            } else {
+                let spec = format!("{}{suffix}", rev.commit.id);
+                let object = repo.rev_parse_single_ex(&spec)?.object()?;
+                let commit = object.peel_tags_to_end()?.try_into_commit()?;
+                Ok(StGitRevision {
+                    patchname: None,
+                    commit: Rc::new(commit),
+                })
+            };
+        }
+
         let rev = self.patch_loc.resolve_revision(stack)?;
         if self.suffix.as_ref().is_empty() {
             Ok(rev)
@@ -237,6 +280,16 @@ impl PatchLikeSpec {
         repo: &'repo gix::Repository,
         stack: &'a impl StackAccess<'repo>,
     ) -> Result<gix::Object<'repo>> {
+        if let Some((selector, suffix)) = self.split_log_selector()? {
+            let rev = self.resolve_in_log(repo, stack, &selector)?;
+            return if suffix.is_empty() {
+                Ok(rev.commit.id().object()?)
+            } else {
+                let spec = format!("{}{suffix}", rev.commit.id);
+                Ok(repo.rev_parse_single_ex(&spec)?.object()?)
+            };
+        }
+
         let rev = self.patch_loc.resolve_revision(stack)?;
         if self.suffix.as_ref().is_empty() {
             Ok(rev.commit.id().object()?)
@@ -245,6 +298,113 @@ impl PatchLikeSpec {
             Ok(repo.rev_parse_single_ex(&spec)?.object()?)
         }
     }
+
+    /// Split a leading stack log selector, e.g. `@{3}`, from the git revision suffix.
+    ///
+    /// Returns the selector along with the remainder of the suffix. Selectors with
+    /// meaning to git for branches, i.e. `@{upstream}`, `@{push}`, and `@{-<n>}`, are
+    /// not treated as stack log selectors.
+    fn split_log_selector(&self) -> Result<Option<(StackLogSelector, &str)>, Error> {
+        let suffix = self.suffix.as_ref();
+        let braced = if let Some(braced) = suffix.strip_prefix("@{") {
+            braced
+        } else {
+            return Ok(None);
+        };
+
+        let mut escaped = false;
+        let close_pos = braced.char_indices().find_map(|(i, c)| {
+            if escaped {
+                escaped = false;
+                None
+            } else if c == '\\' {
+                escaped = true;
+                None
+            } else {
+                (c == '}').then_some(i)
+            }
+        });
+        let close_pos = if let Some(pos) = close_pos {
+            pos
+        } else {
+            return Ok(None);
+        };
+
+        let selector_str = braced[..close_pos].trim();
+        let rest = &braced[close_pos + 1..];
+
+        if selector_str.starts_with('-')
+            || ["u", "upstream", "push"]
+                .iter()
+                .any(|keyword| selector_str.eq_ignore_ascii_case(keyword))
+        {
+            Ok(None)
+        } else if let Ok(steps) = selector_str.parse::<usize>() {
+            Ok(Some((StackLogSelector::Steps(steps), rest)))
+        } else if let Ok(time) = gix::actor::Time::parse_time(selector_str) {
+            Ok(Some((
+                StackLogSelector::Time(time, selector_str.to_string()),
+                rest,
+            )))
+        } else {
+            Err(Error::InvalidLogSelector(selector_str.to_string()))
+        }
+    }
+
+    /// Resolve the patch locator against a historical state from the stack log.
+    fn resolve_in_log<'repo>(
+        &self,
+        repo: &'repo gix::Repository,
+        stack: &impl StackAccess<'repo>,
+        selector: &StackLogSelector,
+    ) -> Result<StGitRevision<'repo>> {
+        let state = find_log_state(repo, stack.get_branch_name(), selector)?;
+        let patchname = self.patch_loc.resolve_name(&state)?;
+        let commit = state.get_patch_commit(&patchname).clone();
+        Ok(StGitRevision {
+            patchname: Some(patchname),
+            commit,
+        })
+    }
+}
+
+/// Find the state matching the selector from the named branch's stack log.
+fn find_log_state<'repo>(
+    repo: &'repo gix::Repository,
+    branch_name: &str,
+    selector: &StackLogSelector,
+) -> Result<StackState<'repo>> {
+    let stack_refname = state_refname_from_branch_name(branch_name);
+    let mut state_commit = Rc::new(
+        repo.try_find_reference(stack_refname.as_str())?
+            .ok_or_else(|| Error::NoStackLog(branch_name.to_string()))?
+            .into_fully_peeled_id()?
+            .object()?
+            .try_into_commit()?,
+    );
+    let mut steps = 0;
+    loop {
+        let is_match = match selector {
+            StackLogSelector::Steps(n) => steps == *n,
+            StackLogSelector::Time(time, _) => {
+                state_commit.decode()?.time().seconds() <= time.seconds()
+            }
+        };
+        let state = StackState::from_commit(repo, &state_commit)?;
+        if is_match {
+            break Ok(state);
+        }
+        state_commit = if let Some(prev) = state.prev {
+            prev
+        } else {
+            break Err(match selector {
+                StackLogSelector::Steps(_) => Error::LogTooShort(steps + 1),
+                StackLogSelector::Time(_, time_str) => Error::LogTooRecent(time_str.clone()),
+            }
+            .into());
+        };
+        steps += 1;
+    }
 }
 
 /// Resolve git-like revision specification.
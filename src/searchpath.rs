@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Directories searched for StGit templates and configuration.
+//!
+//! Files are searched for in the following locations, from highest to lowest
+//! precedence:
+//!
+//! 1. The repository's git directory.
+//! 2. The user's XDG configuration directory, `$XDG_CONFIG_HOME/stgit/` or
+//!    `~/.config/stgit/`.
+//! 3. The user's StGit directory, `~/.stgit/`.
+//! 4. The system configuration directory, `/etc/stgit/`.
+//! 5. The installation's data directory, `$prefix/share/stgit/`, where `$prefix` is the
+//!    parent of the `bin/` directory containing the `stg` executable.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// Level of a StGit search directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SearchLevel {
+    Repo,
+    Xdg,
+    Home,
+    System,
+    Prefix,
+}
+
+impl SearchLevel {
+    /// Short description of the search level for user-facing output.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            SearchLevel::Repo => "repo",
+            SearchLevel::Xdg => "xdg",
+            SearchLevel::Home => "home",
+            SearchLevel::System => "system",
+            SearchLevel::Prefix => "prefix",
+        }
+    }
+}
+
+/// Get the user-level and system-level StGit directories in precedence order.
+///
+/// The repository level is not included since its layout differs from the other levels.
+pub(crate) fn stgit_dirs() -> Vec<(SearchLevel, PathBuf)> {
+    let mut dirs = Vec::with_capacity(4);
+
+    let user_home = std::env::var_os("HOME").filter(|home| !home.is_empty());
+
+    if let Some(config_home) = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        // I.e. $XDG_CONFIG_HOME/stgit
+        dirs.push((SearchLevel::Xdg, Path::new(&config_home).join("stgit")));
+    } else if let Some(user_home) = user_home.as_ref() {
+        // I.e. ~/.config/stgit
+        dirs.push((
+            SearchLevel::Xdg,
+            Path::new(user_home).join(".config").join("stgit"),
+        ));
+    }
+
+    if let Some(user_home) = user_home.as_ref() {
+        // I.e. ~/.stgit
+        dirs.push((SearchLevel::Home, Path::new(user_home).join(".stgit")));
+    }
+
+    dirs.push((SearchLevel::System, PathBuf::from("/etc/stgit")));
+
+    if let Some(prefix) = install_prefix() {
+        // I.e. /usr/share/stgit
+        dirs.push((SearchLevel::Prefix, prefix.join("share").join("stgit")));
+    }
+
+    dirs
+}
+
+/// Get the template directories in precedence order.
+///
+/// Templates in the repository level are located directly in the git directory, i.e.
+/// `.git/<name>`, whereas the other levels have a `templates/` subdirectory.
+pub(crate) fn template_dirs(repo: Option<&gix::Repository>) -> Vec<(SearchLevel, PathBuf)> {
+    let mut dirs = Vec::with_capacity(5);
+    if let Some(repo) = repo {
+        dirs.push((SearchLevel::Repo, repo.common_dir().to_owned()));
+    }
+    dirs.extend(
+        stgit_dirs()
+            .into_iter()
+            .map(|(level, dir)| (level, dir.join("templates"))),
+    );
+    dirs
+}
+
+/// Read the system-level StGit configuration files.
+///
+/// The `config` files in the system and installation prefix directories are git config
+/// formatted files that provide defaults, e.g. `stgit.alias.*`, which may be overridden
+/// by any of the usual git config files. The returned config file has the lowest
+/// precedence values first.
+pub(crate) fn system_config() -> Result<Option<gix::config::File<'static>>> {
+    let mut config: Option<gix::config::File<'static>> = None;
+    for (_, dir) in stgit_dirs()
+        .into_iter()
+        .rev()
+        .filter(|(level, _)| matches!(level, SearchLevel::System | SearchLevel::Prefix))
+    {
+        let path = dir.join("config");
+        if path.is_file() {
+            let file =
+                gix::config::File::from_path_no_includes(path.clone(), gix::config::Source::System)
+                    .with_context(|| format!("reading config file `{}`", path.display()))?;
+            if let Some(config) = config.as_mut() {
+                config.append(file);
+            } else {
+                config = Some(file);
+            }
+        }
+    }
+    Ok(config)
+}
+
+/// Determine the installation prefix from the location of the running executable.
+///
+/// The prefix is only determined if the executable is in a directory named `bin`.
+fn install_prefix() -> Option<PathBuf> {
+    let exe_path = std::env::current_exe().ok()?;
+    let bin_dir = exe_path.parent()?;
+    if bin_dir.file_name()? == "bin" {
+        bin_dir.parent().map(Path::to_path_buf)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,257 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Detection of patches that have been merged upstream.
+//!
+//! Patches that landed upstream unmodified are found by reverse-applying their diffs
+//! to the upstream tree (see [`super::StackTransaction::push_patches()`]). Patches
+//! that landed in modified form are instead matched against individual upstream
+//! commits, by stable patch-id, by `Change-Id` trailer, or by `Link` trailer together
+//! with the subject. Matching by subject and author is only done when
+//! `stgit.merged.match-subject` is enabled.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use bstr::ByteSlice;
+
+use super::StackStateAccess;
+use crate::{
+    ext::CommitExtended,
+    patch::{patchedit::parse_trailers, PatchName},
+    stupid::Stupid,
+};
+
+/// Trailers that uniquely identify a change across rebases and reworks.
+const IDENTIFYING_TRAILERS: &[&str] = &["Change-Id"];
+
+/// Trailers that may be shared by several changes, e.g. a bug tracker or series URL.
+///
+/// These only identify a change when the subject also matches.
+const LINKING_TRAILERS: &[&str] = &["Link"];
+
+/// A patch determined to have been merged upstream.
+#[derive(Debug)]
+pub(crate) struct MergedPatch {
+    /// Name of the merged patch.
+    pub(crate) patchname: PatchName,
+
+    /// Upstream commit the patch was merged as, if known.
+    pub(crate) upstream_id: Option<gix::ObjectId>,
+
+    /// How the patch was determined to have been merged.
+    pub(crate) evidence: MergeEvidence,
+}
+
+/// The means by which a patch was determined to be merged upstream.
+#[derive(Debug)]
+pub(crate) enum MergeEvidence {
+    /// The patch's diff reverse-applies to the upstream tree.
+    ReverseApply,
+
+    /// The patch has the same stable patch-id as an upstream commit.
+    PatchId,
+
+    /// The patch has an identifying trailer in common with an upstream commit.
+    Trailer(String),
+
+    /// The patch has a linking trailer and the subject in common with an upstream
+    /// commit.
+    TrailerAndSubject(String),
+
+    /// The patch has the same subject and author email as an upstream commit.
+    SubjectAndAuthor,
+}
+
+impl std::fmt::Display for MergeEvidence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeEvidence::ReverseApply => f.write_str("reverse-apply"),
+            MergeEvidence::PatchId => f.write_str("patch-id"),
+            MergeEvidence::Trailer(token) => write!(f, "{token} trailer"),
+            MergeEvidence::TrailerAndSubject(token) => write!(f, "{token} trailer and subject"),
+            MergeEvidence::SubjectAndAuthor => f.write_str("subject and author"),
+        }
+    }
+}
+
+impl std::fmt::Display for MergedPatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(upstream_id) = self.upstream_id {
+            write!(
+                f,
+                "{}: merged as {upstream_id} (matched by {})",
+                self.patchname, self.evidence
+            )
+        } else {
+            write!(
+                f,
+                "{}: merged upstream (matched by {})",
+                self.patchname, self.evidence
+            )
+        }
+    }
+}
+
+/// Identifying information gathered from a commit for merge matching.
+struct CommitKeys {
+    trailers: Vec<(String, String)>,
+    links: Vec<(String, String)>,
+    subject: Option<String>,
+    author_email: Option<String>,
+}
+
+impl CommitKeys {
+    fn from_commit(commit: &gix::Commit) -> Result<Self> {
+        let message = commit.message_ex();
+        let message = message.decode()?;
+        let all_trailers = parse_trailers(&message);
+        let select = |tokens: &[&str]| -> Vec<(String, String)> {
+            all_trailers
+                .iter()
+                .filter(|(token, _)| tokens.iter().any(|t| t.eq_ignore_ascii_case(token)))
+                .map(|(token, value)| (token.to_ascii_lowercase(), value.clone()))
+                .collect()
+        };
+        let trailers = select(IDENTIFYING_TRAILERS);
+        let links = select(LINKING_TRAILERS);
+        let subject = message
+            .lines()
+            .next()
+            .map(str::trim)
+            .filter(|subject| !subject.is_empty())
+            .map(str::to_string);
+        let author_email = commit
+            .author_strict()
+            .ok()
+            .and_then(|author| author.email.to_str().ok().map(str::to_ascii_lowercase));
+        Ok(Self {
+            trailers,
+            links,
+            subject,
+            author_email,
+        })
+    }
+}
+
+/// Find patches that landed in the given upstream commits.
+///
+/// Each patch is matched against the upstream commits first by stable patch-id, then
+/// by identifying trailer (`Change-Id`), then by linking trailer (`Link`) plus subject,
+/// and finally, if `stgit.merged.match-subject` is enabled, by subject plus author
+/// email. The first matching upstream commit is recorded for each merged patch.
+pub(crate) fn find_merged_in_commits<'repo, P>(
+    repo: &'repo gix::Repository,
+    stack: &impl StackStateAccess<'repo>,
+    patchnames: &[P],
+    upstream_ids: &[gix::ObjectId],
+) -> Result<Vec<MergedPatch>>
+where
+    P: AsRef<PatchName>,
+{
+    if patchnames.is_empty() || upstream_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let match_subject = repo
+        .config_snapshot()
+        .boolean("stgit.merged.match-subject")
+        .unwrap_or(false);
+    let stupid = repo.stupid();
+    let patch_commit_ids: Vec<gix::ObjectId> = patchnames
+        .iter()
+        .map(|pn| stack.get_patch_commit_id(pn.as_ref()))
+        .collect();
+    let patch_patch_ids = stupid.patch_ids(patch_commit_ids.iter().copied())?;
+    let upstream_patch_ids = stupid.patch_ids(upstream_ids.iter().copied())?;
+
+    let mut by_patch_id: HashMap<gix::ObjectId, gix::ObjectId> = HashMap::new();
+    let mut by_trailer: HashMap<(String, String), gix::ObjectId> = HashMap::new();
+    let mut by_link_subject: HashMap<((String, String), String), gix::ObjectId> = HashMap::new();
+    let mut by_subject_author: HashMap<(String, String), gix::ObjectId> = HashMap::new();
+
+    // Upstream commits are listed newest first. Iterating in reverse allows the oldest
+    // matching upstream commit to win.
+    for upstream_id in upstream_ids.iter().rev() {
+        if let Some(patch_id) = upstream_patch_ids.get(upstream_id) {
+            by_patch_id.insert(*patch_id, *upstream_id);
+        }
+        let commit = repo.find_object(*upstream_id)?.try_into_commit()?;
+        let keys = CommitKeys::from_commit(&commit)?;
+        for trailer in keys.trailers {
+            by_trailer.entry(trailer).or_insert(*upstream_id);
+        }
+        if let Some(subject) = keys.subject.as_ref() {
+            for link in keys.links {
+                by_link_subject
+                    .entry((link, subject.clone()))
+                    .or_insert(*upstream_id);
+            }
+        }
+        if !match_subject {
+            continue;
+        }
+        if let (Some(subject), Some(email)) = (keys.subject, keys.author_email) {
+            by_subject_author
+                .entry((subject, email))
+                .or_insert(*upstream_id);
+        }
+    }
+
+    let mut merged = Vec::new();
+
+    for (patchname, commit_id) in patchnames.iter().zip(patch_commit_ids) {
+        let patchname = patchname.as_ref();
+        let commit = stack.get_patch_commit(patchname);
+        if commit.is_no_change()? {
+            continue;
+        }
+
+        let found = if let Some(upstream_id) = patch_patch_ids
+            .get(&commit_id)
+            .and_then(|patch_id| by_patch_id.get(patch_id))
+        {
+            Some((*upstream_id, MergeEvidence::PatchId))
+        } else {
+            let keys = CommitKeys::from_commit(commit)?;
+            if let Some((token, upstream_id)) = keys.trailers.iter().find_map(|trailer| {
+                by_trailer
+                    .get(trailer)
+                    .map(|upstream_id| (trailer.0.clone(), *upstream_id))
+            }) {
+                let token = IDENTIFYING_TRAILERS
+                    .iter()
+                    .find(|ident| ident.eq_ignore_ascii_case(&token))
+                    .map_or(token, |ident| ident.to_string());
+                Some((upstream_id, MergeEvidence::Trailer(token)))
+            } else if let Some((token, upstream_id)) = keys.subject.as_ref().and_then(|subject| {
+                keys.links.iter().find_map(|link| {
+                    by_link_subject
+                        .get(&(link.clone(), subject.clone()))
+                        .map(|upstream_id| (link.0.clone(), *upstream_id))
+                })
+            }) {
+                let token = LINKING_TRAILERS
+                    .iter()
+                    .find(|ident| ident.eq_ignore_ascii_case(&token))
+                    .map_or(token, |ident| ident.to_string());
+                Some((upstream_id, MergeEvidence::TrailerAndSubject(token)))
+            } else if let (Some(subject), Some(email)) = (keys.subject, keys.author_email) {
+                by_subject_author
+                    .get(&(subject, email))
+                    .map(|upstream_id| (*upstream_id, MergeEvidence::SubjectAndAuthor))
+            } else {
+                None
+            }
+        };
+
+        if let Some((upstream_id, evidence)) = found {
+            merged.push(MergedPatch {
+                patchname: patchname.clone(),
+                upstream_id: Some(upstream_id),
+                evidence,
+            });
+        }
+    }
+
+    Ok(merged)
+}
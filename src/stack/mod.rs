@@ -3,6 +3,7 @@
 //! The StGit stack data structure.
 mod access;
 mod iter;
+mod merged;
 mod serde;
 #[allow(clippy::module_inception)]
 mod stack;
@@ -11,6 +12,10 @@ mod transaction;
 mod upgrade;
 
 pub(crate) use access::{StackAccess, StackStateAccess};
-pub(crate) use stack::{state_refname_from_branch_name, InitializationPolicy, Stack};
+pub(crate) use merged::{find_merged_in_commits, MergedPatch};
+pub(crate) use stack::{
+    email_history_refname_from_branch_name, quilt_sync_refname_from_branch_name,
+    state_refname_from_branch_name, InitializationPolicy, Stack,
+};
 pub(crate) use state::{PatchState, StackState};
 pub(crate) use transaction::{Error as TransactionError, StackTransaction};
@@ -89,6 +89,15 @@ impl<'repo> Stack<'repo> {
         }
         state_ref.delete()?;
 
+        for refname in [
+            email_history_refname_from_branch_name(&branch_name),
+            quilt_sync_refname_from_branch_name(&branch_name),
+        ] {
+            if let Some(reference) = repo.try_find_reference(refname.as_str())? {
+                reference.delete()?;
+            }
+        }
+
         // It is ok if the StGit-specific config section does not exist.
         repo.stupid()
             .config_remove_section(&format!("branch.{branch_name}.stgit"))
@@ -434,6 +443,16 @@ pub(crate) fn state_refname_from_branch_name(branch_name: &str) -> String {
     format!("refs/stacks/{branch_name}")
 }
 
+/// Get reference name for the email series history of the given branch.
+pub(crate) fn email_history_refname_from_branch_name(branch_name: &str) -> String {
+    format!("refs/stgit/email/{branch_name}")
+}
+
+/// Get reference name for the quilt sync record of the given branch.
+pub(crate) fn quilt_sync_refname_from_branch_name(branch_name: &str) -> String {
+    format!("refs/stgit/quilt/{branch_name}")
+}
+
 /// Get reference name for a patch in the given branch.
 fn get_patch_refname(branch_name: &str, patch_spec: &str) -> String {
     format!("refs/patches/{branch_name}/{patch_spec}")
@@ -162,6 +162,7 @@ impl<'repo> TransactionBuilder<'repo> {
             updated_base: None,
             current_tree_id,
             error: None,
+            log_details: Vec::new(),
         };
 
         transaction.error = f(&mut transaction).err();
@@ -40,7 +40,11 @@ use self::{
     options::{ConflictMode, TransactionOptions},
     ui::TransactionUserInterface,
 };
-use super::{state::StackState, StackAccess};
+use super::{
+    merged::{find_merged_in_commits, MergeEvidence, MergedPatch},
+    state::StackState,
+    StackAccess,
+};
 use crate::{
     ext::{CommitExtended, RepositoryExtended},
     patch::PatchName,
@@ -73,6 +77,9 @@ pub(crate) struct StackTransaction<'repo> {
 
     current_tree_id: gix::ObjectId,
     error: Option<anyhow::Error>,
+
+    /// Additional lines recorded in the body of the stack log entry.
+    log_details: Vec<String>,
 }
 
 /// Status of a pushed patch.
@@ -142,6 +149,7 @@ impl<'repo> ExecuteContext<'repo> {
             updated_patches,
             current_tree_id,
             error,
+            log_details,
             ..
         } = transaction;
 
@@ -232,7 +240,13 @@ impl<'repo> ExecuteContext<'repo> {
             state.applied = applied;
             state.unapplied = unapplied;
             state.hidden = hidden;
-            let state_commit_id = state.commit(repo, None, state_reflog_msg)?;
+            let state_commit_id = if log_details.is_empty() {
+                state.commit(repo, None, state_reflog_msg)?
+            } else {
+                let state_commit_msg =
+                    format!("{state_reflog_msg}\n\n{}\n", log_details.join("\n"));
+                state.commit(repo, None, &state_commit_msg)?
+            };
 
             // Update various refs as a single transaction. This reference transaction is
             // not quite atomic--it is possible for some, but not all references to be
@@ -363,6 +377,14 @@ impl<'repo> StackTransaction<'repo> {
         self.stack.repo
     }
 
+    /// Record an additional line of detail in this transaction's stack log entry.
+    ///
+    /// The details appear in the body of the stack state commit message, below the
+    /// reflog message provided to [`ExecuteContext::execute()`].
+    pub(crate) fn add_log_detail(&mut self, detail: impl Into<String>) {
+        self.log_details.push(detail.into());
+    }
+
     /// Reset stack to a previous stack state.
     pub(crate) fn reset_to_state(&mut self, state: StackState<'repo>) -> Result<()> {
         for pn in self.all_patches().cloned().collect::<Vec<_>>() {
@@ -1162,6 +1184,76 @@ impl<'repo> StackTransaction<'repo> {
         }
     }
 
+    /// Find patches that have been merged upstream.
+    ///
+    /// The patches are first matched against the provided upstream commits, which
+    /// detects patches that landed in modified form. The changes of any remaining
+    /// patches are then checked for being already present in the stack base's tree.
+    pub(crate) fn find_merged_patches<P>(
+        &self,
+        patchnames: &[P],
+        upstream_ids: &[gix::ObjectId],
+    ) -> Result<Vec<MergedPatch>>
+    where
+        P: AsRef<PatchName>,
+    {
+        let mut merged = find_merged_in_commits(self.stack.repo, self, patchnames, upstream_ids)?;
+        let remaining: Vec<&PatchName> = patchnames
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|pn| !merged.iter().any(|merged| &merged.patchname == *pn))
+            .collect();
+        let reverse_applied: Vec<PatchName> =
+            self.stack.repo.stupid().with_temp_index(|stupid_temp| {
+                let mut temp_index_tree_id: Option<gix::ObjectId> = None;
+                Ok(self
+                    .check_merged(&remaining, stupid_temp, &mut temp_index_tree_id)?
+                    .into_iter()
+                    .cloned()
+                    .collect())
+            })?;
+        merged.extend(reverse_applied.into_iter().map(|patchname| MergedPatch {
+            patchname,
+            upstream_id: None,
+            evidence: MergeEvidence::ReverseApply,
+        }));
+        Ok(merged)
+    }
+
+    /// Push patches after deleting those that have been merged upstream.
+    ///
+    /// Merged patches are detected with [`StackTransaction::find_merged_patches()`]
+    /// using the provided upstream commits. Each deleted patch, along with the upstream
+    /// commit it was merged as, is recorded in this transaction's stack log entry.
+    pub(crate) fn push_patches_delete_merged<P>(
+        &mut self,
+        patchnames: &[P],
+        upstream_ids: &[gix::ObjectId],
+    ) -> Result<()>
+    where
+        P: AsRef<PatchName>,
+    {
+        let merged = self.find_merged_patches(patchnames, upstream_ids)?;
+        for merged_patch in &merged {
+            self.add_log_detail(merged_patch.to_string());
+        }
+        let is_merged = |pn: &PatchName| {
+            merged
+                .iter()
+                .any(|merged_patch| &merged_patch.patchname == pn)
+        };
+        let mut to_push = self.delete_patches(is_merged)?;
+        to_push.extend(
+            patchnames
+                .iter()
+                .map(AsRef::as_ref)
+                .filter(|pn| !is_merged(pn) && !to_push.contains(*pn))
+                .cloned()
+                .collect::<Vec<_>>(),
+        );
+        self.push_patches(&to_push, false)
+    }
+
     /// Find patches that have already been merged into the stack base's tree.
     ///
     /// The diffs for each provided patchname are applied to the stack's base tree (in
@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Support for parsing `git blame --line-porcelain` output.
+
+use anyhow::{anyhow, Result};
+use bstr::{BString, ByteSlice};
+
+use super::oid::parse_oid;
+
+/// Attribution of a single line of a blamed file.
+pub(crate) struct BlameLine {
+    /// Commit that introduced the line.
+    pub(crate) commit_id: gix::ObjectId,
+
+    /// Line number of the line in the commit that introduced it.
+    pub(crate) orig_line: usize,
+
+    /// Line number of the line in the blamed file.
+    pub(crate) final_line: usize,
+
+    /// Content of the line, without the line terminator.
+    pub(crate) content: BString,
+}
+
+/// Parse the output of `git blame --line-porcelain`.
+///
+/// Each line of the blamed file is described by a header line with the commit id and
+/// line numbers, followed by commit information lines, and finally the line content
+/// prefixed with a tab.
+pub(super) fn parse_line_porcelain(data: &[u8]) -> Result<Vec<BlameLine>> {
+    let mut blame_lines = Vec::new();
+    let mut header: Option<(gix::ObjectId, usize, usize)> = None;
+
+    for line in data.split_str("\n") {
+        if let Some(content) = line.strip_prefix(b"\t") {
+            let (commit_id, orig_line, final_line) = header
+                .take()
+                .ok_or_else(|| anyhow!("blame line content without header"))?;
+            blame_lines.push(BlameLine {
+                commit_id,
+                orig_line,
+                final_line,
+                content: content.into(),
+            });
+        } else if header.is_none() && !line.is_empty() {
+            let mut fields = line.fields();
+            let commit_id = parse_oid(fields.next().unwrap_or_default())?;
+            let mut parse_line_num = || -> Result<usize> {
+                fields
+                    .next()
+                    .and_then(|field| field.to_str().ok())
+                    .and_then(|field| field.parse::<usize>().ok())
+                    .ok_or_else(|| anyhow!("bad blame header `{}`", line.to_str_lossy()))
+            };
+            let orig_line = parse_line_num()?;
+            let final_line = parse_line_num()?;
+            header = Some((commit_id, orig_line, final_line));
+        }
+    }
+
+    if header.is_some() {
+        Err(anyhow!("blame header without line content"))
+    } else {
+        Ok(blame_lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_porcelain() {
+        let data = b"\
+1111111111111111111111111111111111111111 1 1 2\n\
+author A U Thor\n\
+summary first\n\
+filename foo.txt\n\
+\tline one\n\
+1111111111111111111111111111111111111111 2 2\n\
+author A U Thor\n\
+summary first\n\
+filename foo.txt\n\
+\t\tindented\n\
+2222222222222222222222222222222222222222 1 3 1\n\
+author A U Thor\n\
+boundary\n\
+filename foo.txt\n\
+\t\n";
+        let blame_lines = parse_line_porcelain(data).unwrap();
+        assert_eq!(blame_lines.len(), 3);
+        assert_eq!(
+            blame_lines[0].commit_id,
+            gix::ObjectId::from_hex(b"1111111111111111111111111111111111111111").unwrap()
+        );
+        assert_eq!(blame_lines[0].content, "line one");
+        assert_eq!(blame_lines[1].orig_line, 2);
+        assert_eq!(blame_lines[1].content, "\tindented");
+        assert_eq!(blame_lines[2].orig_line, 1);
+        assert_eq!(blame_lines[2].final_line, 3);
+        assert_eq!(blame_lines[2].content, "");
+    }
+
+    #[test]
+    fn parse_truncated_porcelain() {
+        let data = b"1111111111111111111111111111111111111111 1 1 1\nauthor A U Thor\n";
+        assert!(parse_line_porcelain(data).is_err());
+    }
+}
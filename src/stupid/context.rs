@@ -6,9 +6,10 @@
 
 use std::{
     cell::RefCell,
+    collections::HashMap,
     ffi::{OsStr, OsString},
     io::Write,
-    path::Path,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 
@@ -16,10 +17,11 @@ use anyhow::{anyhow, Context, Result};
 use bstr::{BStr, BString, ByteSlice, ByteVec};
 
 use super::{
+    blame::{parse_line_porcelain, BlameLine},
     command::{git_command_error, StupidCommand, StupidExitStatus, StupidOutput},
-    diff::DiffFiles,
+    diff::{parse_numstat, DiffFiles, NumStat},
     oid::parse_oid,
-    status::{StatusOptions, Statuses},
+    status::{BranchAheadBehind, StatusOptions, Statuses},
     tempindex::TempIndex,
     version::StupidVersion,
 };
@@ -112,20 +114,36 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
 
 impl<'repo, 'index> StupidContext<'repo, 'index> {
     /// Apply a patch (diff) to the specified index using `git apply --cached`.
-    pub(crate) fn apply_to_index(&self, diff: &BStr) -> Result<()> {
-        self.git_in_work_root()?
-            .args(["apply", "--cached"]) // TODO: use --recount?
+    ///
+    /// The diff is applied in reverse if `reverse` is true.
+    pub(crate) fn apply_to_index(
+        &self,
+        diff: &BStr,
+        strip_level: Option<usize>,
+        reverse: bool,
+    ) -> Result<()> {
+        let mut command = self.git_in_work_root()?;
+        command.args(["apply", "--cached"]); // TODO: use --recount?
+        if let Some(strip_level) = strip_level {
+            command.arg(format!("-p{strip_level}"));
+        }
+        if reverse {
+            command.arg("--reverse");
+        }
+        command
             .stdout(Stdio::null())
             .in_and_out(diff)?
             .require_success("apply")?;
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn apply_to_worktree_and_index(
         &self,
         diff: &BStr,
         reject: bool,
         threeway: bool,
+        reverse: bool,
         strip_level: Option<usize>,
         directory: Option<&Path>,
         context_lines: Option<usize>,
@@ -138,6 +156,9 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         if threeway {
             command.arg("--3way");
         }
+        if reverse {
+            command.arg("--reverse");
+        }
         if let Some(strip_level) = strip_level {
             command.arg(format!("-p{strip_level}"));
         }
@@ -306,6 +327,80 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         }
     }
 
+    /// Attribute each line of a file to the commit in `base..top` that introduced it.
+    ///
+    /// Lines originating from `base` or earlier are attributed to boundary commits. The
+    /// optional `line_ranges` use the same syntax as `git blame -L`.
+    pub(crate) fn blame(
+        &self,
+        base: gix::ObjectId,
+        top: gix::ObjectId,
+        path: &Path,
+        line_ranges: &[String],
+    ) -> Result<Vec<BlameLine>> {
+        self.blame_revision(&format!("{base}..{top}"), path, line_ranges)
+    }
+
+    /// Attribute each line of a file as of `commit_id` to the commit that introduced it.
+    ///
+    /// Unlike [`StupidContext::blame()`], the entire history of the commit is searched.
+    pub(crate) fn blame_commit(
+        &self,
+        commit_id: gix::ObjectId,
+        path: &Path,
+        line_ranges: &[String],
+    ) -> Result<Vec<BlameLine>> {
+        self.blame_revision(&commit_id.to_string(), path, line_ranges)
+    }
+
+    fn blame_revision(
+        &self,
+        revision: &str,
+        path: &Path,
+        line_ranges: &[String],
+    ) -> Result<Vec<BlameLine>> {
+        let mut command = self.git();
+        command.args(["blame", "--line-porcelain"]);
+        for line_range in line_ranges {
+            command.arg("-L").arg(line_range);
+        }
+        command.arg(revision).arg("--").arg(path);
+        let output = command.output_git()?.require_success("blame")?;
+        parse_line_porcelain(&output.stdout)
+    }
+
+    /// List the references contained in a bundle file with `git bundle list-heads`.
+    pub(crate) fn bundle_list_heads(&self, path: &Path) -> Result<Vec<(gix::ObjectId, String)>> {
+        let output = self
+            .git()
+            .args(["bundle", "list-heads"])
+            .arg(path)
+            .output_git()?
+            .require_success("bundle list-heads")?;
+        let mut heads = Vec::new();
+        for line in output.stdout.lines().filter(|line| !line.is_empty()) {
+            let (oid, refname) = line
+                .split_once_str(" ")
+                .ok_or_else(|| anyhow!("unexpected bundle head `{}`", line.as_bstr()))?;
+            let refname = refname
+                .to_str()
+                .map_err(|_| anyhow!("non-UTF-8 bundle ref `{}`", refname.as_bstr()))?;
+            heads.push((parse_oid(oid)?, refname.to_string()));
+        }
+        Ok(heads)
+    }
+
+    /// Verify that a bundle file is valid and applies to the repository.
+    pub(crate) fn bundle_verify(&self, path: &Path) -> Result<()> {
+        self.git()
+            .args(["bundle", "verify", "--quiet"])
+            .arg(path)
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("bundle verify")?;
+        Ok(())
+    }
+
     /// Copy branch
     ///
     /// Copies branch ref, reflog, and `branch.<name>` config sections.
@@ -433,6 +528,44 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Get the local config entries whose keys match the given regular expression.
+    ///
+    /// Entries without a value, i.e. implicit booleans, have an empty value.
+    pub(crate) fn config_get_regexp(&self, key_regexp: &str) -> Result<Vec<(String, String)>> {
+        let output = self
+            .git()
+            .args(["config", "--local", "--null", "--get-regexp"])
+            .arg(key_regexp)
+            .output_git()?;
+        // Exit code 1 means that no keys matched.
+        if output.status.code() == Some(1) {
+            return Ok(Vec::new());
+        }
+        let output = output.require_success("config --get-regexp")?;
+        Ok(output
+            .stdout
+            .split_str("\0")
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (key, value) = entry.split_once_str("\n").unwrap_or((entry, b""));
+                (
+                    key.to_str_lossy().to_string(),
+                    value.to_str_lossy().to_string(),
+                )
+            })
+            .collect())
+    }
+
+    /// Add a value to the local config, preserving any existing values of the key.
+    pub(crate) fn config_add(&self, key: &str, value: &str) -> Result<()> {
+        self.git()
+            .args(["config", "--local", "--add", key, value])
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("config --add")?;
+        Ok(())
+    }
+
     pub(crate) fn config_rename_section(&self, old_name: &str, new_name: &str) -> Result<()> {
         self.git()
             .args(["config", "--local", "--rename-section"])
@@ -562,6 +695,21 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
             .map(|output| DiffFiles::new(output.stdout))
     }
 
+    /// Get added and deleted line counts of files that differ between two trees.
+    pub(crate) fn diff_tree_numstat(
+        &self,
+        tree1: gix::ObjectId,
+        tree2: gix::ObjectId,
+    ) -> Result<Vec<NumStat>> {
+        let output = self
+            .git()
+            .args(["diff-tree", "-r", "--numstat", "--no-renames", "-z"])
+            .args([tree1.to_string(), tree2.to_string()])
+            .output_git()?
+            .require_success("diff-tree")?;
+        Ok(parse_numstat(&output.stdout))
+    }
+
     /// Interactive diff-tree (for 'stg files').
     pub(crate) fn diff_tree_files_status(
         &self,
@@ -642,6 +790,22 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(paths)
     }
 
+    /// Fetch references from a bundle file with `git fetch`.
+    pub(crate) fn fetch_bundle<'a>(
+        &self,
+        path: &Path,
+        refspecs: impl IntoIterator<Item = &'a str>,
+    ) -> Result<()> {
+        self.git()
+            .args(["fetch", "--quiet", "--no-tags"])
+            .arg(path)
+            .args(refspecs)
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("fetch")?;
+        Ok(())
+    }
+
     /// Run `git format-patch` with arbitrary arguments.
     pub(crate) fn format_patch<OptIter, OptArg>(&self, args: OptIter) -> Result<()>
     where
@@ -659,6 +823,48 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Run `git format-patch`, returning the paths of the generated files.
+    ///
+    /// Unlike [`StupidContext::format_patch()`], the list of generated files is
+    /// captured instead of being written to stdout.
+    pub(crate) fn format_patch_files<OptIter, OptArg>(&self, args: OptIter) -> Result<Vec<PathBuf>>
+    where
+        OptIter: IntoIterator<Item = OptArg>,
+        OptArg: AsRef<OsStr>,
+    {
+        let mut command = self.git();
+        command.arg("format-patch");
+        command.args(args);
+        let output = command
+            .stdin(Stdio::inherit())
+            .output_git()?
+            .require_success("format-patch")?;
+        let mut paths = Vec::new();
+        for line in output.stdout.lines() {
+            if !line.is_empty() {
+                let path = line.to_path().context("getting format-patch file name")?;
+                paths.push(path.to_path_buf());
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Run `git format-patch --stdout`, returning the formatted mbox content.
+    pub(crate) fn format_patch_stdout<OptIter, OptArg>(&self, args: OptIter) -> Result<BString>
+    where
+        OptIter: IntoIterator<Item = OptArg>,
+        OptArg: AsRef<OsStr>,
+    {
+        let mut command = self.git();
+        command.args(["format-patch", "--stdout"]);
+        command.args(args);
+        let output = command
+            .stdin(Stdio::null())
+            .output_git()?
+            .require_success("format-patch")?;
+        Ok(BString::from(output.stdout))
+    }
+
     /// Show log in gitk
     pub(crate) fn gitk<SpecIter, SpecArg>(
         &self,
@@ -758,9 +964,13 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         &self,
         input: Option<std::fs::File>,
         copy_message_id: bool,
+        keep_subject: bool,
     ) -> Result<(BString, BString, BString)> {
         let mut command = self.git();
         command.args(["mailinfo", "--scissors", "--encoding=UTF-8"]);
+        if keep_subject {
+            command.arg("-k");
+        }
         if copy_message_id {
             command.arg("--message-id");
         }
@@ -910,6 +1120,129 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Get the note attached to an object using `git notes show`.
+    ///
+    /// Returns `None` if the object does not have a note.
+    pub(crate) fn notes_show(&self, oid: gix::ObjectId) -> Result<Option<BString>> {
+        let output = self
+            .git()
+            .args(["notes", "show"])
+            .arg(oid.to_string())
+            .stderr(Stdio::null())
+            .output_git()?;
+        Ok(output
+            .status
+            .success()
+            .then(|| BString::from(output.stdout)))
+    }
+
+    /// Make pack data containing the objects reachable from the given commits.
+    ///
+    /// Uses `git pack-objects --revs` to generate a pack suitable for a bundle file.
+    pub(crate) fn pack_objects(
+        &self,
+        commit_ids: impl IntoIterator<Item = gix::ObjectId>,
+    ) -> Result<Vec<u8>> {
+        let mut input = Vec::new();
+        for commit_id in commit_ids {
+            writeln!(input, "{commit_id}")?;
+        }
+        let output = self
+            .git()
+            .args([
+                "pack-objects",
+                "--stdout",
+                "--revs",
+                "--delta-base-offset",
+                "--quiet",
+            ])
+            .stdout(Stdio::piped())
+            .in_and_out(&input)?
+            .require_success("pack-objects")?;
+        Ok(output.stdout)
+    }
+
+    /// Compute stable patch ids for commits using `git patch-id --stable`.
+    ///
+    /// A map of commit id to patch id is returned. Commits without a diff, e.g. empty
+    /// commits or merges, do not have a patch id and are thus absent from the map.
+    pub(crate) fn patch_ids(
+        &self,
+        commit_ids: impl IntoIterator<Item = gix::ObjectId>,
+    ) -> Result<HashMap<gix::ObjectId, gix::ObjectId>> {
+        let mut revs = String::new();
+        for commit_id in commit_ids {
+            revs.push_str(&commit_id.to_string());
+            revs.push('\n');
+        }
+        let mut patch_ids = HashMap::new();
+        if revs.is_empty() {
+            return Ok(patch_ids);
+        }
+
+        let log_output = self
+            .git()
+            .args([
+                "log",
+                "--no-walk=unsorted",
+                "--no-merges",
+                "--stdin",
+                "-p",
+                "--no-color",
+                "--no-ext-diff",
+                "--format=commit %H",
+            ])
+            .stdout(Stdio::piped())
+            .in_and_out(revs.as_bytes())?
+            .require_success("log")?;
+
+        let output = self
+            .git()
+            .args(["patch-id", "--stable"])
+            .stdout(Stdio::piped())
+            .in_and_out(&log_output.stdout)?
+            .require_success("patch-id")?;
+
+        for line in output.stdout.lines() {
+            if let Some((patch_id, commit_id)) = line.split_once_str(" ") {
+                patch_ids.insert(parse_oid(commit_id)?, parse_oid(patch_id)?);
+            }
+        }
+        Ok(patch_ids)
+    }
+
+    /// Compare two versions of a single commit using `git range-diff`.
+    ///
+    /// The commits are always paired, regardless of how much they differ. The returned
+    /// diff of diffs excludes `git range-diff`'s header line and is empty when the two
+    /// commits are equivalent.
+    pub(crate) fn range_diff_commits(
+        &self,
+        old_id: gix::ObjectId,
+        new_id: gix::ObjectId,
+        use_color: bool,
+    ) -> Result<BString> {
+        let output = self
+            .git()
+            .args(["range-diff", "--creation-factor=999"])
+            .arg(if use_color {
+                "--color=always"
+            } else {
+                "--color=never"
+            })
+            .arg(format!("{old_id}^..{old_id}"))
+            .arg(format!("{new_id}^..{new_id}"))
+            .output_git()?
+            .require_success("range-diff")?;
+        let body = output
+            .stdout
+            .find_byte(b'\n')
+            .map_or(&output.stdout[..0], |header_end| {
+                &output.stdout[header_end + 1..]
+            });
+        Ok(BString::from(body))
+    }
+
     /// Read content of a tree into specified index using `git read-tree`.
     pub(crate) fn read_tree(&self, tree_id: gix::ObjectId) -> Result<()> {
         self.git_in_work_root()?
@@ -994,6 +1327,67 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(oids)
     }
 
+    /// Count the commits in `left` but not `right`, and in `right` but not `left`.
+    ///
+    /// The ahead count is the number of commits only reachable from `left` and the
+    /// behind count is the number of commits only reachable from `right`.
+    pub(crate) fn ahead_behind(&self, left: &str, right: &str) -> Result<BranchAheadBehind> {
+        let output = self
+            .git()
+            .args(["rev-list", "--left-right", "--count"])
+            .arg(format!("{left}...{right}"))
+            .arg("--")
+            .output_git()?
+            .require_success("rev-list")?;
+        let counts = output.stdout.trim_end().to_str()?;
+        let (ahead, behind) = counts
+            .split_once('\t')
+            .and_then(|(ahead, behind)| Some((ahead.parse().ok()?, behind.parse().ok()?)))
+            .ok_or_else(|| anyhow!("unexpected `rev-list --count` output `{counts}`"))?;
+        Ok(BranchAheadBehind { ahead, behind })
+    }
+
+    /// Get the full name of the upstream branch of a local branch, if any.
+    pub(crate) fn branch_upstream(&self, branchname: &str) -> Result<Option<String>> {
+        let output = self
+            .git()
+            .args(["for-each-ref", "--format=%(upstream)"])
+            .arg(format!("refs/heads/{branchname}"))
+            .output_git()?
+            .require_success("for-each-ref")?;
+        let upstream = output.stdout.trim_end().to_str()?;
+        Ok(if upstream.is_empty() {
+            None
+        } else {
+            Some(upstream.to_string())
+        })
+    }
+
+    /// Get list of revisions in `base..top` committed at or after `since`.
+    pub(crate) fn rev_list_since(
+        &self,
+        base: gix::ObjectId,
+        top: gix::ObjectId,
+        since: gix::actor::Time,
+    ) -> Result<Vec<gix::ObjectId>> {
+        let output = self
+            .git()
+            .arg("rev-list")
+            .arg(format!("--since={}", since.seconds()))
+            .arg(format!("{base}..{top}"))
+            .output_git()?
+            .require_success("rev-list --since")?;
+        let mut oids: Vec<gix::ObjectId> = Vec::new();
+        for line in output
+            .stdout
+            .split_str("\n")
+            .filter(|line| !line.is_empty())
+        {
+            oids.push(parse_oid(line)?);
+        }
+        Ok(oids)
+    }
+
     /// Get cdup for current directory from `git rev-parse --show-cdup`.
     pub(crate) fn rev_parse_cdup(&self) -> Result<OsString> {
         let output = self
@@ -1056,6 +1450,65 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Get the password for a URL from the configured credential helpers.
+    #[cfg_attr(not(feature = "email-smtp"), allow(dead_code))]
+    pub(crate) fn credential_fill(&self, url: &str) -> Result<Option<String>> {
+        let output = self
+            .git()
+            .args(["credential", "fill"])
+            .stdout(Stdio::piped())
+            .in_and_out(format!("url={url}\n\n").as_bytes())?
+            .require_success("credential fill")?;
+        Ok(output
+            .stdout
+            .lines()
+            .find_map(|line| line.strip_prefix(b"password="))
+            .map(|password| password.to_str_lossy().to_string()))
+    }
+
+    /// Pipe an email to a sendmail-compatible command.
+    ///
+    /// Like `sendemail.sendmailCmd` with `git send-email`, the command is run with the
+    /// shell and given `-i` and the recipients as arguments.
+    pub(crate) fn sendmail(
+        &self,
+        sendmail_cmd: &str,
+        recipients: &[String],
+        email: &[u8],
+    ) -> Result<()> {
+        let mut command = Command::new("sh");
+        self.setup_git_env(&mut command);
+        command
+            .arg("-c")
+            .arg(format!("{sendmail_cmd} \"$@\""))
+            .arg(sendmail_cmd)
+            .arg("-i")
+            .args(recipients)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::piped());
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("could not execute `{sendmail_cmd}`"))?;
+        let mut stdin = child.stdin.take().unwrap();
+        let (write_result, output) = std::thread::scope(|scope| {
+            let handle = scope.spawn(move || stdin.write_all(email));
+            let output = child.wait_with_output();
+            (handle.join(), output)
+        });
+        let output = output?;
+        if output.status.success() {
+            write_result
+                .map_err(|_| anyhow!("panic while writing to stdin"))?
+                .with_context(|| format!("writing email to `{sendmail_cmd}`"))
+        } else {
+            // A failing command may exit without reading the email, so its exit status
+            // takes precedence over any error writing to its stdin.
+            let err_str = output.stderr.to_str_lossy();
+            Err(anyhow!("`{sendmail_cmd}` failed: {}", err_str.trim_end()))
+        }
+    }
+
     /// Show objects using `git show`.
     pub(crate) fn show<SpecIter, SpecArg, OptIter, OptArg>(
         &self,
@@ -1151,7 +1604,11 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
             default_options = StatusOptions::default();
             &default_options
         };
-        let mut command = self.git();
+        let mut command = if options.in_work_root {
+            self.git_in_work_root()?
+        } else {
+            self.git()
+        };
         command.args([
             "status",
             "--porcelain=v2",
@@ -4,7 +4,7 @@
 
 use std::path::Path;
 
-use bstr::ByteSlice;
+use bstr::{BString, ByteSlice};
 
 /// Diff output containing only names of differing files.
 ///
@@ -52,6 +52,37 @@ impl<'a> Iterator for DiffFilesIter<'a> {
     }
 }
 
+/// Added and deleted line counts for one file.
+///
+/// E.g. from `git diff-tree --numstat -z`
+pub(crate) struct NumStat {
+    pub(crate) path: BString,
+
+    /// Number of added lines, or `None` for binary files.
+    pub(crate) added: Option<usize>,
+
+    /// Number of deleted lines, or `None` for binary files.
+    pub(crate) deleted: Option<usize>,
+}
+
+/// Parse `--numstat -z` output without rename detection.
+pub(super) fn parse_numstat(data: &[u8]) -> Vec<NumStat> {
+    data.split_str(b"\0")
+        .filter(|record| !record.is_empty())
+        .filter_map(|record| {
+            let mut fields = record.splitn_str(3, b"\t");
+            let added = fields.next()?;
+            let deleted = fields.next()?;
+            let path = fields.next()?;
+            Some(NumStat {
+                path: path.into(),
+                added: added.to_str().ok().and_then(|n| n.parse().ok()),
+                deleted: deleted.to_str().ok().and_then(|n| n.parse().ok()),
+            })
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,4 +96,14 @@ mod tests {
         assert_eq!(it.next(), Some(Path::new("jkl")));
         assert!(it.next().is_none());
     }
+
+    #[test]
+    fn numstat_parsing() {
+        let stats = parse_numstat(b"3\t1\tsrc/a.rs\0-\t-\timage.png\0");
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].path, "src/a.rs");
+        assert_eq!((stats[0].added, stats[0].deleted), (Some(3), Some(1)));
+        assert_eq!(stats[1].path, "image.png");
+        assert_eq!((stats[1].added, stats[1].deleted), (None, None));
+    }
 }
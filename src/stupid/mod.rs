@@ -6,6 +6,7 @@
 //! StGit. This module originally existed to overcome limitations of libgit2, but
 //! remains until gitoxide can replace its behaviors.
 
+mod blame;
 mod command;
 mod context;
 mod diff;
@@ -18,7 +19,7 @@ use std::cell::RefCell;
 
 pub(crate) use self::{
     context::StupidContext,
-    status::{Status, StatusOptions, Statuses},
+    status::{Status, StatusEntryKind, StatusOptions, Statuses},
 };
 
 pub(crate) trait Stupid<'repo, 'index> {
@@ -22,6 +22,7 @@ pub(crate) struct StatusOptions {
     pub(super) recurse_untracked_dirs: bool,
     pub(super) include_branch_headers: bool,
     pub(super) include_stash_headers: bool,
+    pub(super) in_work_root: bool,
 }
 
 /// Options affecting the status information collected into a [`Statuses`] struct.
@@ -46,7 +47,6 @@ impl StatusOptions {
     }
 
     /// Include entries for untracked files found in the work tree.
-    #[allow(unused)]
     pub(crate) fn include_untracked(&mut self, include: bool) -> &mut Self {
         self.include_untracked = include;
         self
@@ -81,6 +81,14 @@ impl StatusOptions {
         self.include_stash_headers = include;
         self
     }
+
+    /// Run status from the top of the work tree instead of the current directory.
+    ///
+    /// Pathspecs are thus interpreted relative to the top of the work tree.
+    pub(crate) fn in_work_root(&mut self, in_work_root: bool) -> &mut Self {
+        self.in_work_root = in_work_root;
+        self
+    }
 }
 
 /// The kind of status entry, as reported by `git status --porcelain=v2`.
@@ -139,6 +147,19 @@ impl Status {
             _ => panic!("unknown status char '{}'", c.escape_ascii()),
         }
     }
+
+    /// Get the single character code used by `git status --short` for this status.
+    pub(crate) fn short_code(&self) -> char {
+        match self {
+            Status::Unmodified => ' ',
+            Status::Modified => 'M',
+            Status::FileTypeChanged => 'T',
+            Status::Added => 'A',
+            Status::Deleted => 'D',
+            Status::Renamed => 'R',
+            Status::Unmerged => 'U',
+        }
+    }
 }
 
 /// A snapshot of status information.
@@ -370,6 +391,22 @@ impl<'s> StatusEntry<'s> {
         }
     }
 
+    /// Get the original path of a renamed entry.
+    ///
+    /// Returns `None` for entries that are not renames.
+    pub(crate) fn orig_path_bytes(&self) -> Option<&'s [u8]> {
+        if matches!(self.kind(), StatusEntryKind::Renamed) {
+            Some(
+                self.data[self.range.clone()]
+                    .splitn_str(2, b"\0")
+                    .nth(1)
+                    .expect("rename entry has null terminated original path"),
+            )
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn path(&self) -> &'s Path {
         self.path_bytes()
             .to_path()
@@ -609,10 +646,7 @@ impl<'s> Iterator for HeaderIter<'s> {
 
 /// The number of commits ahead and behind of the associated upstream branch.
 pub(crate) struct BranchAheadBehind {
-    #[allow(unused)]
     pub(crate) ahead: usize,
-
-    #[allow(unused)]
     pub(crate) behind: usize,
 }
 
@@ -650,7 +684,9 @@ mod tests {
 
         assert_eq!(iter.next().unwrap().path(), Path::new("file1"));
         assert_eq!(iter.next().unwrap().path(), Path::new("file 3"));
-        assert_eq!(iter.next().unwrap().path(), Path::new("file-2"));
+        let renamed = iter.next().unwrap();
+        assert_eq!(renamed.path(), Path::new("file-2"));
+        assert_eq!(renamed.orig_path_bytes(), Some(b"file2".as_slice()));
         assert_eq!(iter.next().unwrap().path(), Path::new("foo.txt"));
         assert_eq!(iter.next().unwrap().path(), Path::new("hello.txt"));
         assert_eq!(iter.next().unwrap().path(), Path::new("intent to add.txt"));
@@ -0,0 +1,1007 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Template language for exported patches, patch descriptions, and cover letters.
+//!
+//! A template is text with embedded tags:
+//!
+//! - `{{ expr }}` outputs the value of an expression. An expression is a variable
+//!   name, with optional `.`-separated attribute names (e.g. `file.path`), or a quoted
+//!   string or number, followed by any number of `| filter` or `| filter(args)`.
+//! - `{% if expr %}`, `{% elif expr %}`, `{% else %}`, and `{% endif %}` conditionally
+//!   output their contents. A condition may be preceded by `not` and may compare two
+//!   expressions with `==` or `!=`. Undefined variables, empty strings and lists, and
+//!   zero are false.
+//! - `{% for name in expr %}` ... `{% endfor %}` outputs its contents for each item of
+//!   a list. Within the loop, `loop.index`, `loop.first`, and `loop.last` are defined.
+//! - `{% include "name" %}` outputs another named template.
+//! - `{# comment #}` outputs nothing.
+//! - `%(name)s` outputs a variable for compatibility with StGit's older Python-style
+//!   templates. Unknown names are output verbatim.
+//!
+//! A newline immediately following a `{% %}` or `{# #}` tag is not output, which allows
+//! such tags to be placed on lines of their own.
+//!
+//! Templates written for older StGit versions are parsed with
+//! [`Template::parse_compat()`], which only recognizes `%(name)s` so that any braces
+//! are output verbatim.
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+use anyhow::{anyhow, Result};
+use bstr::{BStr, BString, ByteSlice, ByteVec};
+
+/// Maximum depth of nested `{% include %}` tags.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Names of the supported filters.
+const FILTERS: &[&str] = &[
+    "date", "default", "indent", "join", "length", "lower", "trim", "upper", "wrap",
+];
+
+/// Value of a template variable.
+#[derive(Clone, Debug)]
+pub(crate) enum Value {
+    Text(BString),
+    Number(usize),
+    Bool(bool),
+    Time(gix::date::Time),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::Text(s.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Self::Text(s.into())
+    }
+}
+
+impl From<&BStr> for Value {
+    fn from(s: &BStr) -> Self {
+        Self::Text(s.into())
+    }
+}
+
+impl From<BString> for Value {
+    fn from(s: BString) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Self {
+        Self::Number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Self::Bool(b)
+    }
+}
+
+impl From<gix::date::Time> for Value {
+    fn from(time: gix::date::Time) -> Self {
+        Self::Time(time)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(list: Vec<Value>) -> Self {
+        Self::List(list)
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Value {
+    fn from(map: BTreeMap<String, Value>) -> Self {
+        Self::Map(map)
+    }
+}
+
+impl Value {
+    /// Determine whether the value is considered true in a condition.
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Text(s) => !s.is_empty(),
+            Value::Number(n) => *n != 0,
+            Value::Bool(b) => *b,
+            Value::Time(_) => true,
+            Value::List(list) => !list.is_empty(),
+            Value::Map(map) => !map.is_empty(),
+        }
+    }
+
+    /// Get the textual representation of the value.
+    ///
+    /// Times are represented in ISO 8601 format. Lists and maps do not have a textual
+    /// representation.
+    fn to_text(&self) -> Result<Cow<'_, BStr>> {
+        match self {
+            Value::Text(s) => Ok(Cow::Borrowed(s.as_bstr())),
+            Value::Number(n) => Ok(Cow::Owned(n.to_string().into())),
+            Value::Bool(b) => Ok(Cow::Borrowed(if *b { "true" } else { "false" }.into())),
+            Value::Time(time) => Ok(Cow::Owned(
+                time.format(gix::date::time::format::ISO8601).into(),
+            )),
+            Value::List(_) => Err(anyhow!(
+                "cannot output a list; use a `for` loop or the `join` filter"
+            )),
+            Value::Map(_) => Err(anyhow!("cannot output a map; use one of its attributes")),
+        }
+    }
+}
+
+/// Variables available to a template.
+#[derive(Default)]
+pub(crate) struct Context(BTreeMap<String, Value>);
+
+impl Context {
+    /// Set the value of a variable.
+    pub(crate) fn insert(&mut self, name: &str, value: impl Into<Value>) {
+        self.0.insert(name.to_string(), value.into());
+    }
+
+    /// Set the values of several variables.
+    pub(crate) fn extend(&mut self, vars: BTreeMap<String, Value>) {
+        self.0.extend(vars);
+    }
+}
+
+/// Operand of an expression.
+#[derive(Debug)]
+enum Operand {
+    Var(Vec<String>),
+    Str(String),
+    Number(usize),
+}
+
+#[derive(Debug)]
+struct Filter {
+    name: String,
+    args: Vec<Operand>,
+}
+
+#[derive(Debug)]
+struct Expr {
+    operand: Operand,
+    filters: Vec<Filter>,
+}
+
+#[derive(Debug)]
+struct Condition {
+    negate: bool,
+    lhs: Expr,
+    comparison: Option<(bool, Expr)>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Output(Expr),
+    Compat(String),
+    If {
+        branches: Vec<(Condition, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        var: String,
+        list: Expr,
+        body: Vec<Node>,
+    },
+    Include(String),
+}
+
+/// Parsed template.
+#[derive(Debug)]
+pub(crate) struct Template {
+    nodes: Vec<Node>,
+}
+
+/// Tag or text found when scanning the template.
+enum Token<'t> {
+    Text(&'t str),
+    Output(&'t str, usize),
+    Block(&'t str, usize),
+    Compat(&'t str),
+}
+
+/// Tag openers of the template language.
+const OPENERS: &[&str] = &["{{", "{%", "{#", "%("];
+
+/// Tag openers of older Python-style templates.
+const COMPAT_OPENERS: &[&str] = &["%("];
+
+/// Split template into text and the tags started by `openers`.
+fn tokenize<'t>(template: &'t str, openers: &[&str]) -> Result<Vec<Token<'t>>> {
+    let line_of = |pos: usize| template[..pos].matches('\n').count() + 1;
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    let mut offset = 0;
+
+    while !rest.is_empty() {
+        let next = openers.iter().filter_map(|opener| rest.find(opener)).min();
+        let start = if let Some(start) = next {
+            start
+        } else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        let line = line_of(offset + start);
+        let opener = &rest[start..start + 2];
+
+        let consumed = if opener == "%(" {
+            let tag = &rest[start + 2..];
+            if let Some(name) = tag
+                .find(')')
+                .filter(|&end| tag[end + 1..].starts_with('s'))
+                .map(|end| &tag[..end])
+            {
+                tokens.push(Token::Compat(name));
+                start + 2 + name.len() + 2
+            } else {
+                tokens.push(Token::Text("%("));
+                start + 2
+            }
+        } else {
+            let closer = match opener {
+                "{{" => "}}",
+                "{%" => "%}",
+                _ => "#}",
+            };
+            let tag = &rest[start + 2..];
+            let end = tag
+                .find(closer)
+                .ok_or_else(|| anyhow!("line {line}: unclosed `{opener}` tag"))?;
+            let content = tag[..end].trim();
+            match opener {
+                "{{" => tokens.push(Token::Output(content, line)),
+                "{%" => tokens.push(Token::Block(content, line)),
+                _ => {}
+            }
+            let mut consumed = start + 2 + end + 2;
+            if opener != "{{" {
+                if rest[consumed..].starts_with('\n') {
+                    consumed += 1;
+                } else if rest[consumed..].starts_with("\r\n") {
+                    consumed += 2;
+                }
+            }
+            consumed
+        };
+
+        rest = &rest[consumed..];
+        offset += consumed;
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ExprToken {
+    Ident(String),
+    Str(String),
+    Number(usize),
+    Dot,
+    Pipe,
+    LParen,
+    RParen,
+    Comma,
+    Eq,
+    Ne,
+}
+
+/// Split an expression into tokens.
+fn lex_expr(expr: &str) -> Result<Vec<ExprToken>> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '.' => tokens.push(ExprToken::Dot),
+            '|' => tokens.push(ExprToken::Pipe),
+            '(' => tokens.push(ExprToken::LParen),
+            ')' => tokens.push(ExprToken::RParen),
+            ',' => tokens.push(ExprToken::Comma),
+            '=' | '!' if chars.next_if(|&(_, c)| c == '=').is_some() => {
+                tokens.push(if c == '=' {
+                    ExprToken::Eq
+                } else {
+                    ExprToken::Ne
+                });
+            }
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => s.push('\n'),
+                            Some((_, 't')) => s.push('\t'),
+                            Some((_, c)) => s.push(c),
+                            None => return Err(anyhow!("unterminated string in `{expr}`")),
+                        },
+                        Some((_, c)) => s.push(c),
+                        None => return Err(anyhow!("unterminated string in `{expr}`")),
+                    }
+                }
+                tokens.push(ExprToken::Str(s));
+            }
+            c if c.is_ascii_digit() => {
+                let mut end = pos + c.len_utf8();
+                while let Some((next_pos, next_c)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    end = next_pos + next_c.len_utf8();
+                }
+                let n = expr[pos..end]
+                    .parse()
+                    .map_err(|_| anyhow!("invalid number `{}`", &expr[pos..end]))?;
+                tokens.push(ExprToken::Number(n));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut end = pos + c.len_utf8();
+                while let Some((next_pos, next_c)) =
+                    chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_' || *c == '-')
+                {
+                    end = next_pos + next_c.len_utf8();
+                }
+                tokens.push(ExprToken::Ident(expr[pos..end].to_string()));
+            }
+            c => return Err(anyhow!("unexpected `{c}` in `{expr}`")),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parser for the tokens of an expression or condition.
+struct ExprParser {
+    tokens: std::iter::Peekable<std::vec::IntoIter<ExprToken>>,
+    source: String,
+}
+
+impl ExprParser {
+    fn new(source: &str) -> Result<Self> {
+        Ok(Self {
+            tokens: lex_expr(source)?.into_iter().peekable(),
+            source: source.to_string(),
+        })
+    }
+
+    fn error(&self, what: &str) -> anyhow::Error {
+        anyhow!("{what} in `{}`", self.source)
+    }
+
+    fn finish(mut self) -> Result<()> {
+        if self.tokens.next().is_some() {
+            Err(self.error("unexpected trailing tokens"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        if let Some(ExprToken::Ident(ident)) = self.tokens.next() {
+            Ok(ident)
+        } else {
+            Err(self.error("expected a name"))
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        match self.tokens.next() {
+            Some(ExprToken::Ident(ident)) => {
+                let mut path = vec![ident];
+                while self.tokens.next_if_eq(&ExprToken::Dot).is_some() {
+                    path.push(self.ident()?);
+                }
+                Ok(Operand::Var(path))
+            }
+            Some(ExprToken::Str(s)) => Ok(Operand::Str(s)),
+            Some(ExprToken::Number(n)) => Ok(Operand::Number(n)),
+            _ => Err(self.error("expected a variable, string, or number")),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let operand = self.operand()?;
+        let mut filters = Vec::new();
+        while self.tokens.next_if_eq(&ExprToken::Pipe).is_some() {
+            let name = self.ident()?;
+            if !FILTERS.contains(&name.as_str()) {
+                return Err(self.error(&format!("unknown filter `{name}`")));
+            }
+            let mut args = Vec::new();
+            if self.tokens.next_if_eq(&ExprToken::LParen).is_some()
+                && self.tokens.next_if_eq(&ExprToken::RParen).is_none()
+            {
+                loop {
+                    args.push(self.operand()?);
+                    match self.tokens.next() {
+                        Some(ExprToken::Comma) => {}
+                        Some(ExprToken::RParen) => break,
+                        _ => return Err(self.error("expected `,` or `)`")),
+                    }
+                }
+            }
+            filters.push(Filter { name, args });
+        }
+        Ok(Expr { operand, filters })
+    }
+
+    fn condition(&mut self) -> Result<Condition> {
+        let negate = self
+            .tokens
+            .next_if_eq(&ExprToken::Ident("not".to_string()))
+            .is_some();
+        let lhs = self.expr()?;
+        let comparison = match self.tokens.peek() {
+            Some(ExprToken::Eq) | Some(ExprToken::Ne) => {
+                let equal = self.tokens.next() == Some(ExprToken::Eq);
+                Some((equal, self.expr()?))
+            }
+            _ => None,
+        };
+        Ok(Condition {
+            negate,
+            lhs,
+            comparison,
+        })
+    }
+}
+
+/// Terminating block tag's keyword, remaining content, and line number.
+type Terminator<'t> = (&'t str, &'t str, usize);
+
+/// Parser for the sequence of template tokens.
+struct Parser<'t> {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Token<'t>>>,
+}
+
+impl<'t> Parser<'t> {
+    /// Parse nodes until one of the terminating block tags or the end of the template.
+    ///
+    /// The terminating tag's keyword and remaining content is returned along with the
+    /// nodes. The `opening` block tag and line are used to report a missing terminator.
+    fn nodes(
+        &mut self,
+        terminators: &[&str],
+        opening: Option<(&str, usize)>,
+    ) -> Result<(Vec<Node>, Option<Terminator<'t>>)> {
+        let mut nodes = Vec::new();
+        while let Some(token) = self.tokens.next() {
+            match token {
+                Token::Text(text) => nodes.push(Node::Text(text.to_string())),
+                Token::Compat(name) => nodes.push(Node::Compat(name.to_string())),
+                Token::Output(content, line) => {
+                    let mut parser =
+                        ExprParser::new(content).map_err(|e| anyhow!("line {line}: {e}"))?;
+                    let expr = parser.expr().map_err(|e| anyhow!("line {line}: {e}"))?;
+                    parser.finish().map_err(|e| anyhow!("line {line}: {e}"))?;
+                    nodes.push(Node::Output(expr));
+                }
+                Token::Block(content, line) => {
+                    let (keyword, rest) = content
+                        .split_once(char::is_whitespace)
+                        .map_or((content, ""), |(keyword, rest)| (keyword, rest.trim()));
+                    if terminators.contains(&keyword) {
+                        return Ok((nodes, Some((keyword, rest, line))));
+                    }
+                    let node = self.block(keyword, rest, line)?;
+                    nodes.push(node);
+                }
+            }
+        }
+
+        if let Some((keyword, line)) = opening {
+            Err(anyhow!("line {line}: `{{% {keyword} %}}` is not closed"))
+        } else {
+            Ok((nodes, None))
+        }
+    }
+
+    /// Parse the block starting with the given tag.
+    ///
+    /// Errors in the tag itself are reported with the tag's line number.
+    fn block(&mut self, keyword: &str, rest: &str, line: usize) -> Result<Node> {
+        let at_line = |e: anyhow::Error| anyhow!("line {line}: {e}");
+        match keyword {
+            "if" => {
+                let mut branches = Vec::new();
+                let mut condition = parse_condition(rest).map_err(at_line)?;
+                let mut otherwise = Vec::new();
+                loop {
+                    let (body, terminator) =
+                        self.nodes(&["elif", "else", "endif"], Some(("if", line)))?;
+                    branches.push((condition, body));
+                    match terminator {
+                        Some(("elif", rest, elif_line)) => {
+                            condition = parse_condition(rest)
+                                .map_err(|e| anyhow!("line {elif_line}: {e}"))?;
+                        }
+                        Some(("else", _, _)) => {
+                            otherwise = self.nodes(&["endif"], Some(("if", line)))?.0;
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+                Ok(Node::If {
+                    branches,
+                    otherwise,
+                })
+            }
+            "for" => {
+                let (var, list) = parse_for(rest).map_err(at_line)?;
+                let body = self.nodes(&["endfor"], Some(("for", line)))?.0;
+                Ok(Node::For { var, list, body })
+            }
+            "include" => match lex_expr(rest).map_err(at_line)?.as_slice() {
+                [ExprToken::Str(name)] => Ok(Node::Include(name.clone())),
+                _ => Err(at_line(anyhow!("expected `{{% include \"<name>\" %}}`"))),
+            },
+            "elif" | "else" | "endif" | "endfor" => {
+                Err(at_line(anyhow!("unexpected `{{% {keyword} %}}`")))
+            }
+            _ => Err(at_line(anyhow!("unknown tag `{{% {keyword} %}}`"))),
+        }
+    }
+}
+
+fn parse_for(source: &str) -> Result<(String, Expr)> {
+    let mut parser = ExprParser::new(source)?;
+    let var = parser.ident()?;
+    if parser.ident().ok().as_deref() != Some("in") {
+        return Err(anyhow!("expected `{{% for <name> in <list> %}}`"));
+    }
+    let list = parser.expr()?;
+    parser.finish()?;
+    Ok((var, list))
+}
+
+fn parse_condition(source: &str) -> Result<Condition> {
+    let mut parser = ExprParser::new(source)?;
+    let condition = parser.condition()?;
+    parser.finish()?;
+    Ok(condition)
+}
+
+impl Template {
+    /// Parse template source.
+    pub(crate) fn parse(source: &str) -> Result<Self> {
+        Self::parse_with(source, OPENERS)
+    }
+
+    /// Parse template source where only `%(name)s` specifiers are substituted.
+    pub(crate) fn parse_compat(source: &str) -> Result<Self> {
+        Self::parse_with(source, COMPAT_OPENERS)
+    }
+
+    fn parse_with(source: &str, openers: &[&str]) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(source, openers)?.into_iter().peekable(),
+        };
+        let (nodes, _) = parser.nodes(&[], None)?;
+        Ok(Self { nodes })
+    }
+
+    /// Render template using the variables of the provided context.
+    ///
+    /// Templates named by `{% include %}` tags are obtained from `loader`.
+    pub(crate) fn render(
+        &self,
+        context: &Context,
+        loader: &mut dyn FnMut(&str) -> Result<Option<String>>,
+    ) -> Result<Vec<u8>> {
+        let mut renderer = Renderer {
+            context,
+            locals: Vec::new(),
+            loader,
+            depth: 0,
+        };
+        let mut output = BString::from(Vec::new());
+        renderer.render(&self.nodes, &mut output)?;
+        Ok(output.into())
+    }
+}
+
+struct Renderer<'a> {
+    context: &'a Context,
+    locals: Vec<(String, Value)>,
+    loader: &'a mut dyn FnMut(&str) -> Result<Option<String>>,
+    depth: usize,
+}
+
+impl<'a> Renderer<'a> {
+    fn render(&mut self, nodes: &[Node], output: &mut BString) -> Result<()> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Compat(name) => {
+                    if let Some(value) = self.lookup(std::slice::from_ref(name)) {
+                        output.extend_from_slice(&value.to_text()?);
+                    } else {
+                        output.push_str("%(");
+                        output.push_str(name);
+                        output.push_str(")s");
+                    }
+                }
+                Node::Output(expr) => {
+                    let value = self
+                        .evaluate(expr)?
+                        .ok_or_else(|| anyhow!("undefined variable `{}`", expr.describe()))?;
+                    output.extend_from_slice(&value.to_text()?);
+                }
+                Node::If {
+                    branches,
+                    otherwise,
+                } => {
+                    let mut body = otherwise;
+                    for (condition, branch_body) in branches {
+                        if self.test(condition)? {
+                            body = branch_body;
+                            break;
+                        }
+                    }
+                    self.render(body, output)?;
+                }
+                Node::For { var, list, body } => {
+                    let items = match self.evaluate(list)? {
+                        Some(Value::List(items)) => items,
+                        None => Vec::new(),
+                        Some(_) => return Err(anyhow!("`{}` is not a list", list.describe())),
+                    };
+                    let len = items.len();
+                    for (i, item) in items.into_iter().enumerate() {
+                        let mut loop_vars = BTreeMap::new();
+                        loop_vars.insert("index".to_string(), Value::Number(i + 1));
+                        loop_vars.insert("first".to_string(), Value::Bool(i == 0));
+                        loop_vars.insert("last".to_string(), Value::Bool(i + 1 == len));
+                        self.locals
+                            .push(("loop".to_string(), Value::Map(loop_vars)));
+                        self.locals.push((var.clone(), item));
+                        let result = self.render(body, output);
+                        self.locals.truncate(self.locals.len() - 2);
+                        result?;
+                    }
+                }
+                Node::Include(name) => {
+                    if self.depth >= MAX_INCLUDE_DEPTH {
+                        return Err(anyhow!("too many nested includes of template `{name}`"));
+                    }
+                    let source = (self.loader)(name)?
+                        .ok_or_else(|| anyhow!("included template `{name}` not found"))?;
+                    let template = Template::parse(&source)
+                        .map_err(|e| anyhow!("included template `{name}`: {e}"))?;
+                    self.depth += 1;
+                    let result = self.render(&template.nodes, output);
+                    self.depth -= 1;
+                    result?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, path: &[String]) -> Option<&Value> {
+        let (name, attributes) = path.split_first()?;
+        let mut value = self
+            .locals
+            .iter()
+            .rev()
+            .find_map(|(local_name, value)| (local_name == name).then_some(value))
+            .or_else(|| self.context.0.get(name))?;
+        for attribute in attributes {
+            if let Value::Map(map) = value {
+                value = map.get(attribute)?;
+            } else {
+                return None;
+            }
+        }
+        Some(value)
+    }
+
+    fn operand(&self, operand: &Operand) -> Option<Value> {
+        match operand {
+            Operand::Var(path) => self.lookup(path).cloned(),
+            Operand::Str(s) => Some(Value::from(s.as_str())),
+            Operand::Number(n) => Some(Value::Number(*n)),
+        }
+    }
+
+    fn evaluate(&self, expr: &Expr) -> Result<Option<Value>> {
+        let mut value = self.operand(&expr.operand);
+        for filter in &expr.filters {
+            let args: Vec<Option<Value>> =
+                filter.args.iter().map(|arg| self.operand(arg)).collect();
+            value = apply_filter(&filter.name, value, &args)
+                .map_err(|e| anyhow!("`{}` filter: {e}", filter.name))?;
+        }
+        Ok(value)
+    }
+
+    fn test(&self, condition: &Condition) -> Result<bool> {
+        let lhs = self.evaluate(&condition.lhs)?;
+        let result = if let Some((equal, rhs)) = &condition.comparison {
+            let rhs = self.evaluate(rhs)?;
+            let text = |value: &Option<Value>| -> Result<BString> {
+                Ok(value
+                    .as_ref()
+                    .map(|value| value.to_text().map(|text| text.into_owned()))
+                    .transpose()?
+                    .unwrap_or_default())
+            };
+            (text(&lhs)? == text(&rhs)?) == *equal
+        } else {
+            matches!(lhs, Some(value) if value.is_truthy())
+        };
+        Ok(result != condition.negate)
+    }
+}
+
+impl Expr {
+    /// Describe the expression's operand for error messages.
+    fn describe(&self) -> String {
+        match &self.operand {
+            Operand::Var(path) => path.join("."),
+            Operand::Str(s) => format!("{s:?}"),
+            Operand::Number(n) => n.to_string(),
+        }
+    }
+}
+
+/// Get filter argument by index, or a default if the argument is not provided.
+fn text_arg<'v>(args: &'v [Option<Value>], index: usize, default: &'v str) -> Result<Cow<'v, str>> {
+    match args.get(index) {
+        Some(Some(value)) => Ok(Cow::Owned(value.to_text()?.to_str_lossy().into_owned())),
+        Some(None) => Err(anyhow!("undefined argument")),
+        None => Ok(Cow::Borrowed(default)),
+    }
+}
+
+fn number_arg(args: &[Option<Value>], index: usize, default: usize) -> Result<usize> {
+    match args.get(index) {
+        Some(Some(Value::Number(n))) => Ok(*n),
+        Some(_) => Err(anyhow!("expected a number argument")),
+        None => Ok(default),
+    }
+}
+
+fn apply_filter(name: &str, value: Option<Value>, args: &[Option<Value>]) -> Result<Option<Value>> {
+    if name == "default" {
+        return Ok(match value {
+            Some(value) if value.is_truthy() => Some(value),
+            _ => args.first().cloned().flatten(),
+        });
+    }
+
+    let value = if let Some(value) = value {
+        value
+    } else {
+        return Ok(None);
+    };
+
+    let result = match name {
+        "date" => {
+            let time = if let Value::Time(time) = value {
+                time
+            } else {
+                return Err(anyhow!("expected a date"));
+            };
+            let format = text_arg(args, 0, "iso")?;
+            let formatted = match format.as_ref() {
+                "iso" => time.format(gix::date::time::format::ISO8601),
+                "iso-strict" => time.format(gix::date::time::format::ISO8601_STRICT),
+                "rfc" | "rfc2822" => time.format(gix::date::time::format::RFC2822),
+                "short" => time.format(gix::date::time::format::SHORT),
+                "raw" => time.format(gix::date::time::format::RAW),
+                "unix" => time.seconds_since_unix_epoch.to_string(),
+                format => return Err(anyhow!("unknown date format `{format}`")),
+            };
+            Value::from(formatted)
+        }
+        "indent" => {
+            let prefix = match args.first() {
+                Some(Some(Value::Number(n))) => " ".repeat(*n),
+                _ => text_arg(args, 0, "    ")?.into_owned(),
+            };
+            let text = value.to_text()?;
+            let mut indented = BString::from(Vec::with_capacity(text.len()));
+            for line in text.lines_with_terminator() {
+                if !line.trim().is_empty() {
+                    indented.push_str(&prefix);
+                }
+                indented.push_str(line);
+            }
+            Value::Text(indented)
+        }
+        "join" => {
+            let separator = text_arg(args, 0, "")?;
+            let items = if let Value::List(items) = &value {
+                items
+            } else {
+                return Err(anyhow!("expected a list"));
+            };
+            let mut joined = BString::from(Vec::new());
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    joined.push_str(separator.as_bytes());
+                }
+                joined.extend_from_slice(&item.to_text()?);
+            }
+            Value::Text(joined)
+        }
+        "length" => Value::Number(match &value {
+            Value::List(items) => items.len(),
+            Value::Map(map) => map.len(),
+            value => value.to_text()?.chars().count(),
+        }),
+        "lower" => Value::from(value.to_text()?.to_str_lossy().to_lowercase()),
+        "upper" => Value::from(value.to_text()?.to_str_lossy().to_uppercase()),
+        "trim" => Value::from(value.to_text()?.trim().as_bstr()),
+        "wrap" => {
+            let width = number_arg(args, 0, 72)?;
+            Value::from(wrap(&value.to_text()?.to_str_lossy(), width))
+        }
+        _ => unreachable!("filter names are checked when parsing"),
+    };
+    Ok(Some(result))
+}
+
+/// Rewrap the paragraphs of text to the given width.
+///
+/// Lines that are indented are considered preformatted and are left as-is.
+fn wrap(text: &str, width: usize) -> String {
+    let mut wrapped = String::with_capacity(text.len());
+    let mut line_len = 0;
+
+    let end_line = |wrapped: &mut String, line_len: &mut usize| {
+        if *line_len > 0 {
+            wrapped.push('\n');
+            *line_len = 0;
+        }
+    };
+
+    for line in text.lines() {
+        if line.trim().is_empty() || line.starts_with(char::is_whitespace) {
+            end_line(&mut wrapped, &mut line_len);
+            wrapped.push_str(line);
+            wrapped.push('\n');
+            continue;
+        }
+        for word in line.split_whitespace() {
+            let word_len = word.chars().count();
+            if line_len > 0 && line_len + 1 + word_len > width {
+                end_line(&mut wrapped, &mut line_len);
+            }
+            if line_len > 0 {
+                wrapped.push(' ');
+                line_len += 1;
+            }
+            wrapped.push_str(word);
+            line_len += word_len;
+        }
+    }
+    end_line(&mut wrapped, &mut line_len);
+
+    if !text.ends_with('\n') && wrapped.ends_with('\n') {
+        wrapped.pop();
+    }
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, context: &Context) -> String {
+        let mut loader = |name: &str| -> Result<Option<String>> {
+            Ok((name == "sig").then(|| "-- \n{{ authname }}\n".to_string()))
+        };
+        let output = Template::parse(template)
+            .unwrap()
+            .render(context, &mut loader)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn context() -> Context {
+        let mut context = Context::default();
+        context.insert("authname", "A U Thor");
+        context.insert("empty", "");
+        context.insert("index", 2usize);
+        let trailers: Vec<Value> = [("Acked-by", "Foo"), ("Reviewed-by", "Bar")]
+            .into_iter()
+            .map(|(key, value)| {
+                let mut map = BTreeMap::new();
+                map.insert("key".to_string(), Value::from(key));
+                map.insert("value".to_string(), Value::from(value));
+                Value::Map(map)
+            })
+            .collect();
+        context.insert("trailers", trailers);
+        context
+    }
+
+    #[test]
+    fn compat_specifiers() {
+        assert_eq!(
+            render("%(authname)s %(missing)s 100%(", &context()),
+            "A U Thor %(missing)s 100%("
+        );
+    }
+
+    #[test]
+    fn compat_template_keeps_braces() {
+        let output = Template::parse_compat("{{ x }} {% if %(authname)s {# %(index)s\n")
+            .unwrap()
+            .render(&context(), &mut |_| Ok(None))
+            .unwrap();
+        assert_eq!(output, b"{{ x }} {% if A U Thor {# 2\n");
+    }
+
+    #[test]
+    fn output_with_filters() {
+        assert_eq!(
+            render(
+                "{{ authname | lower }} {{ missing | default(\"none\") }}",
+                &context()
+            ),
+            "a u thor none"
+        );
+        assert_eq!(render("{{ trailers | length }}", &context()), "2");
+        assert_eq!(
+            render("{{ \"a\\nb\" | indent(2) }}", &context()),
+            "  a\n  b"
+        );
+    }
+
+    #[test]
+    fn conditionals() {
+        let template = "{% if empty %}\nA\n{% elif index == 2 %}\nB\n{% else %}\nC\n{% endif %}\n";
+        assert_eq!(render(template, &context()), "B\n");
+        assert_eq!(
+            render("{% if not missing %}yes{% endif %}", &context()),
+            "yes"
+        );
+    }
+
+    #[test]
+    fn loops() {
+        let template =
+            "{% for t in trailers %}\n{{ loop.index }}. {{ t.key }}: {{ t.value }}\n{% endfor %}\n";
+        assert_eq!(
+            render(template, &context()),
+            "1. Acked-by: Foo\n2. Reviewed-by: Bar\n"
+        );
+    }
+
+    #[test]
+    fn includes() {
+        assert_eq!(
+            render("x\n{% include \"sig\" %}", &context()),
+            "x\n-- \nA U Thor\n"
+        );
+    }
+
+    #[test]
+    fn wrap_paragraphs() {
+        assert_eq!(
+            wrap("one two three four\n\n  code line\nfive six", 9),
+            "one two\nthree\nfour\n\n  code line\nfive six"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Template::parse("{% if x %}").is_err());
+        assert!(Template::parse("{{ x | bogus }}").is_err());
+        assert!(Template::parse("{{ x ").is_err());
+        assert!(Template::parse("{% endfor %}").is_err());
+    }
+}
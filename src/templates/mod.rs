@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Support for StGit patch templates.
+
+mod engine;
+mod vars;
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use self::engine::Template;
+pub(crate) use self::{
+    engine::{Context, Value},
+    vars::patch_vars,
+};
+use crate::searchpath::{self, SearchLevel};
+
+/// Get named patch template from template file.
+///
+/// The template file is searched for in each of the [`template_paths()`] with the first
+/// readable file being used.
+pub(crate) fn get_template(repo: &gix::Repository, name: &str) -> Result<Option<String>> {
+    for (_, template_path) in template_paths(Some(repo), name) {
+        if let Ok(template_bytes) = std::fs::read(&template_path) {
+            let template = std::str::from_utf8(&template_bytes).map_err(|_| {
+                anyhow!(
+                    "template file `{}` contains non-UTF-8 data",
+                    template_path.display()
+                )
+            })?;
+
+            return Ok(Some(template.into()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Get the candidate paths for the named template in precedence order.
+pub(crate) fn template_paths(
+    repo: Option<&gix::Repository>,
+    name: &str,
+) -> Vec<(SearchLevel, PathBuf)> {
+    searchpath::template_dirs(repo)
+        .into_iter()
+        .map(|(level, dir)| (level, dir.join(name)))
+        .collect()
+}
+
+/// Render template with the provided variables.
+///
+/// Only templates that opt in to the template language, per
+/// [`uses_template_language()`], may use its tags. Other templates are rendered as
+/// older StGit versions did, substituting `%(name)s` specifiers and nothing else.
+///
+/// Templates included by the template with `{% include "<name>" %}` are looked up by
+/// name the same as with [`get_template()`].
+pub(crate) fn render(repo: &gix::Repository, template: &str, context: &Context) -> Result<Vec<u8>> {
+    let template = if uses_template_language(template) {
+        Template::parse(template)?
+    } else {
+        Template::parse_compat(template)?
+    };
+    template.render(context, &mut |name| get_template(repo, name))
+}
+
+/// Determine whether a template uses the template language.
+///
+/// Templates written for older StGit versions may contain literal braces, so only
+/// templates that start with a `{# ... #}` comment tag use the template language.
+/// Patch description templates that do not were historically used verbatim.
+pub(crate) fn uses_template_language(template: &str) -> bool {
+    template.starts_with("{#")
+}
+
+/// Default patch export template.
+pub(crate) const PATCHEXPORT_TMPL: &str = "\
+%(shortdescr)s
+
+From: %(authname)s <%(authemail)s>
+
+%(longdescr)s
+---
+%(diffstat)s
+";
@@ -0,0 +1,117 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Template variables describing patches.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use bstr::BStr;
+
+use super::Value;
+use crate::{
+    ext::CommitExtended,
+    patch::{patchedit, PatchName},
+    stupid::Stupid,
+};
+
+/// Get the template variables describing a patch commit.
+///
+/// The variables are:
+///
+/// - `name`: the patch name, if any.
+/// - `description`, `shortdescr`, and `longdescr`: the whole commit message, its first
+///   line, and the remainder following the first line.
+/// - `authname`, `authemail`, and `authdate`: the author's name, email, and date.
+/// - `commname`, `commemail`, and `commdate`: the committer's name, email, and date.
+/// - `commit` and `parent`: the ids of the patch commit and its parent.
+/// - `notes`: the commit's notes, or empty if there are no notes.
+/// - `trailers`: list of the message's trailers, each with `key` and `value`.
+/// - `files`: list of the changed files, each with `path`, `added`, `deleted`, and
+///   `binary`.
+/// - `diffstat`: the diffstat of the `diff`, if provided.
+pub(crate) fn patch_vars(
+    repo: &gix::Repository,
+    patchname: Option<&PatchName>,
+    commit: &gix::Commit<'_>,
+    diff: Option<&BStr>,
+) -> Result<BTreeMap<String, Value>> {
+    let stupid = repo.stupid();
+    let parent = commit.get_parent_commit()?;
+    let mut vars = BTreeMap::new();
+    let mut insert = |name: &str, value: Value| {
+        vars.insert(name.to_string(), value);
+    };
+
+    if let Some(patchname) = patchname {
+        insert("name", Value::from(patchname.to_string()));
+    }
+
+    let description = commit.message_ex().decode()?.into_owned();
+    let (shortdescr, longdescr) = if let Some((shortdescr, rest)) = description.split_once('\n') {
+        (shortdescr, rest.trim_start_matches('\n').trim_end())
+    } else {
+        (description.as_str(), "")
+    };
+    insert("shortdescr", Value::from(shortdescr));
+    insert("longdescr", Value::from(longdescr));
+
+    let trailers = patchedit::parse_trailers(&description)
+        .into_iter()
+        .map(|(key, value)| {
+            let mut trailer = BTreeMap::new();
+            trailer.insert("key".to_string(), Value::from(key));
+            trailer.insert("value".to_string(), Value::from(value));
+            Value::Map(trailer)
+        })
+        .collect::<Vec<_>>();
+    insert("trailers", Value::List(trailers));
+    insert("description", Value::from(description));
+
+    let author = commit.author()?;
+    insert("authname", Value::from(author.name));
+    insert("authemail", Value::from(author.email));
+    insert("authdate", Value::Time(author.time));
+    let committer = commit.committer()?;
+    insert("commname", Value::from(committer.name));
+    insert("commemail", Value::from(committer.email));
+    insert("commdate", Value::Time(committer.time));
+
+    insert("commit", Value::from(commit.id.to_string()));
+    insert("parent", Value::from(parent.id.to_string()));
+    insert(
+        "notes",
+        Value::from(stupid.notes_show(commit.id)?.unwrap_or_default()),
+    );
+
+    let parent_tree_id = parent.tree_id()?.detach();
+    let tree_id = commit.tree_id()?.detach();
+    let files = stupid
+        .diff_tree_numstat(parent_tree_id, tree_id)?
+        .into_iter()
+        .map(|stat| {
+            let mut file = BTreeMap::new();
+            file.insert("path".to_string(), Value::from(stat.path));
+            file.insert("added".to_string(), Value::from(stat.added.unwrap_or(0)));
+            file.insert(
+                "deleted".to_string(),
+                Value::from(stat.deleted.unwrap_or(0)),
+            );
+            file.insert("binary".to_string(), Value::from(stat.added.is_none()));
+            Value::Map(file)
+        })
+        .collect::<Vec<_>>();
+    insert("files", Value::List(files));
+
+    if let Some(diff) = diff {
+        insert(
+            "diffstat",
+            Value::from(if parent_tree_id == tree_id {
+                Default::default()
+            } else {
+                stupid.diffstat(diff)?
+            }),
+        );
+    }
+
+    Ok(vars)
+}
@@ -12,16 +12,18 @@ test_expect_success 'Run status on empty' '
     # Ignore our own output files.
     cat >>.git/info/exclude <<-\EOF &&
 	/expected
+	/err
+	/files
 	/out
 	EOF
     stg init &&
-    stg status >out &&
+    stg status --short >out &&
     test_must_be_empty out
 '
 
 test_expect_success 'Status with an untracked file' '
     touch foo &&
-    stg status >out &&
+    stg status --short >out &&
     cat >expected <<-\EOF &&
 	?? foo
 	EOF
@@ -31,13 +33,13 @@ test_expect_success 'Status with an untracked file' '
 
 test_expect_success 'Status with an empty directory' '
     mkdir foo &&
-    stg status >out &&
+    stg status --short >out &&
     test_must_be_empty out
 '
 
 test_expect_success 'Status with an untracked file in a subdir' '
     touch foo/bar &&
-    stg status >out &&
+    stg status --short >out &&
     cat >expected <<-\EOF &&
 	?? foo/
 	EOF
@@ -46,7 +48,7 @@ test_expect_success 'Status with an untracked file in a subdir' '
 
 test_expect_success 'Status with an added file' '
     stg add foo &&
-    stg status >out &&
+    stg status --short >out &&
     cat >expected <<-\EOF &&
 	A  foo/bar
 	EOF
@@ -56,13 +58,13 @@ test_expect_success 'Status with an added file' '
 test_expect_success 'Status after refresh' '
     stg new -m "first patch" &&
     stg refresh &&
-    stg status >out &&
+    stg status --short >out &&
     test_must_be_empty out
 '
 
 test_expect_success 'Status after modification' '
     echo "wee" >>foo/bar &&
-    stg status >out &&
+    stg status --short >out &&
     cat >expected <<-\EOF &&
 	 M foo/bar
 	EOF
@@ -71,7 +73,7 @@ test_expect_success 'Status after modification' '
 
 test_expect_success 'Status after refresh' '
     stg new -m "second patch" && stg refresh &&
-    stg status >out &&
+    stg status --short >out &&
     test_must_be_empty out
 '
 
@@ -90,7 +92,7 @@ test_expect_success 'Make a conflicting patch' '
 
 test_expect_success 'Status after conflicting push' '
     conflict stg push &&
-    stg status >out &&
+    stg status --short >out &&
     cat >expected <<-\EOF &&
 	A  fie
 	UU foo/bar
@@ -98,8 +100,15 @@ test_expect_success 'Status after conflicting push' '
     test_cmp expected out
 '
 
+test_expect_success 'Porcelain status shows conflicts' '
+    stg status --porcelain >out &&
+    grep "^# stgit.conflicts yes" out &&
+    stg status >out &&
+    grep "^Unresolved conflicts" out
+'
+
 test_expect_success 'Status of file' '
-    stg status foo/bar >out &&
+    stg status --short foo/bar >out &&
     cat >expected <<-\EOF &&
 	UU foo/bar
 	EOF
@@ -107,7 +116,7 @@ test_expect_success 'Status of file' '
 '
 
 test_expect_success 'Status of dir' '
-    stg status foo >out &&
+    stg status --short foo >out &&
     cat >expected <<-\EOF &&
 	UU foo/bar
 	EOF
@@ -115,7 +124,7 @@ test_expect_success 'Status of dir' '
 '
 
 test_expect_success 'Status of other file' '
-    stg status fie >out &&
+    stg status --short fie >out &&
     cat >expected <<-\EOF &&
 	A  fie
 	EOF
@@ -124,7 +133,7 @@ test_expect_success 'Status of other file' '
 
 test_expect_success 'Status after resolving the push' '
     stg add --update &&
-    stg status >out &&
+    stg status --short >out &&
     cat >expected <<-\EOF &&
 	A  fie
 	M  foo/bar
@@ -134,7 +143,7 @@ test_expect_success 'Status after resolving the push' '
 
 test_expect_success 'Status after deleting a file' '
     rm foo/bar &&
-    stg status >out &&
+    stg status --short >out &&
     cat >expected <<-\EOF &&
 	A  fie
 	MD foo/bar
@@ -147,7 +156,7 @@ test_expect_success 'Status of disappeared newborn' '
     touch foo/bar &&
     stg add foo/bar &&
     rm foo/bar &&
-    stg status >out &&
+    stg status --short >out &&
     cat >expected <<-\EOF &&
 	AD foo/bar
 	EOF
@@ -157,11 +166,124 @@ test_expect_success 'Status of disappeared newborn' '
 test_expect_success 'Status after renaming a file' '
     stg rm foo/bar &&
     stg mv fie fay &&
-    stg status >out &&
+    stg status --short >out &&
     cat >expected <<-\EOF &&
 	R  fie -> fay
 	EOF
     test_cmp expected out
 '
 
+test_expect_success 'Status from subdirectory is relative to top' '
+    mkdir -p sub &&
+    (
+        cd sub &&
+        stg status --short >../out &&
+        stg status --short fay >../files
+    ) &&
+    cat >expected <<-\EOF &&
+	R  fie -> fay
+	EOF
+    test_cmp expected out &&
+    cat >expected <<-\EOF &&
+	A  fay
+	EOF
+    test_cmp expected files
+'
+
+test_expect_success 'Setup stack for annotations' '
+    stg refresh &&
+    stg branch --create annotate &&
+    echo one >one.txt &&
+    echo two >two.txt &&
+    stg add one.txt two.txt &&
+    stg new -m "patch a" &&
+    stg refresh &&
+    echo one >>one.txt &&
+    stg new -m "patch b" &&
+    stg refresh &&
+    stg new -m "patch c" &&
+    stg pop
+'
+
+test_expect_success 'Short status is not annotated' '
+    echo more >>one.txt &&
+    echo more >>two.txt &&
+    echo new >three.txt &&
+    stg status --short >out &&
+    cat >expected <<-\EOF &&
+	 M one.txt
+	 M two.txt
+	?? three.txt
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Porcelain status shows the stack state and annotates files' '
+    stg status --porcelain >out &&
+    q_to_tab >expected <<-\EOF &&
+	# stgit.branch annotate
+	# stgit.top patch-b
+	# stgit.applied 2
+	# stgit.unapplied 1
+	# stgit.hidden 0
+	# stgit.head-top yes
+	# stgit.conflicts no
+	 M one.txtQpatch-a patch-b
+	 M two.txtQpatch-a
+	?? three.txt
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Status shows the stack state and annotates files' '
+    stg status >out &&
+    cat >expected <<-\EOF &&
+	On branch annotate
+	Top patch: patch-b (2 applied, 1 unapplied)
+
+	 M one.txt  (patch-a, patch-b)
+	 M two.txt  (patch-a)
+	?? three.txt
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Status limited to paths' '
+    stg status --porcelain two.txt >out &&
+    grep -v "^#" out >files &&
+    q_to_tab >expected <<-\EOF &&
+	 M two.txtQpatch-a
+	EOF
+    test_cmp expected files
+'
+
+test_expect_success 'JSON status' '
+    stg status --json >out &&
+    grep "\"branch\": \"annotate\"" out &&
+    grep "\"top\": \"patch-b\"" out &&
+    grep "\"unapplied\": 1" out &&
+    grep "\"head_top\": true" out &&
+    grep "\"conflicts\": false" out &&
+    grep "\"path\": \"three.txt\"" out
+'
+
+test_expect_success 'Porcelain and JSON are mutually exclusive' '
+    general_error stg status --porcelain --json 2>err &&
+    grep -e "the argument .--porcelain. cannot be used with .--json." err
+'
+
+test_expect_success 'Porcelain and short are mutually exclusive' '
+    general_error stg status --porcelain --short 2>err &&
+    grep -e "the argument .--porcelain. cannot be used with .--short." err
+'
+
+test_expect_success 'Status shows HEAD and stack top mismatch' '
+    git add one.txt two.txt three.txt &&
+    git commit -m "external change" &&
+    stg status --porcelain >out &&
+    grep "^# stgit.head-top no" out &&
+    stg status >out &&
+    grep "HEAD and stack top are not the same" out
+'
+
 test_done
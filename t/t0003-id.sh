@@ -28,4 +28,61 @@ test_expect_success 'Provide patch argument' '
     test "$(echo $(stg id))" = "$(echo $(stg id $(stg top)))"
 '
 
+test_expect_success 'Patch from stack log' '
+    old_id=$(stg id patch-2) &&
+    echo "line 3" >>foo.txt &&
+    stg refresh &&
+    test "$(stg id patch-2@{1})" = "$old_id" &&
+    test "$(stg id patch-2@{0})" = "$(stg id patch-2)" &&
+    test "$(stg id patch-2@{1}^)" = "$(stg id patch-1)"
+'
+
+test_expect_success 'Patch from stack log by date' '
+    test "$(stg id "patch-2@{now}")" = "$(stg id patch-2)"
+'
+
+test_expect_success 'Patch from stack log by relative date' '
+    now=$(date +%s) &&
+    GIT_COMMITTER_DATE="$((now - 10800)) +0000" stg branch --create dated &&
+    echo "dated 1" >>foo.txt &&
+    GIT_COMMITTER_DATE="$((now - 7200)) +0000" stg new -m "dated-patch" &&
+    GIT_COMMITTER_DATE="$((now - 7200)) +0000" stg refresh &&
+    two_hours_id=$(stg id dated-patch) &&
+    echo "dated 2" >>foo.txt &&
+    GIT_COMMITTER_DATE="$((now - 1800)) +0000" stg refresh &&
+    test "$(stg id dated-patch)" != "$two_hours_id" &&
+    test "$(stg id "dated-patch@{1 hour ago}")" = "$two_hours_id" &&
+    test "$(stg id "dated-patch@{now}")" = "$(stg id dated-patch)" &&
+    command_error stg id "dated-patch@{4 hours ago}" 2>err &&
+    grep -e "stack log has no states at or before \`4 hours ago\`" err &&
+    stg branch master
+'
+
+test_expect_success 'Stack log too short' '
+    command_error stg id patch-2@{100} 2>err &&
+    grep -e "stack log only has" err
+'
+
+test_expect_success 'Patch from another branch stack log' '
+    stg branch --clone other &&
+    stg new -m "patch-3" &&
+    echo "line 4" >>foo.txt &&
+    stg refresh &&
+    old_id=$(stg id patch-3) &&
+    echo "line 5" >>foo.txt &&
+    stg refresh &&
+    stg branch master &&
+    test "$(stg id other:patch-3@{1})" = "$old_id" &&
+    test "$(stg id other:patch-3@{0})" = "$(stg id other:patch-3)" &&
+    test "$(stg id other:patch-2@{2})" != "$(stg id patch-2@{2})" &&
+    command_error stg id patch-3@{1} 2>err &&
+    grep -e "patch \`patch-3\` does not exist" err
+'
+
+test_expect_success 'Stack log of uninitialized branch' '
+    git branch plain &&
+    command_error stg id plain:patch-2@{1} 2>err &&
+    grep -e "branch \`plain\` does not have a stack log" err
+'
+
 test_done
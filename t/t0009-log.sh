@@ -136,6 +136,60 @@ test_expect_success 'Log with number' '
     head -n 3 log.txt | tail -n 1 | grep -e "refresh"
 '
 
+test_expect_success 'Test invalid opts with graph' '
+    general_error stg log --graph --graphical 2>err >/dev/null &&
+    grep -e "the argument .--graph. cannot be used with .--graphical." err &&
+    general_error stg log --graph --json 2>err >/dev/null &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Log graph for p3' '
+    stg log --graph p3 >log.txt &&
+    sed -e "s/^\* [0-9a-f]\{7\} \(.*\) ([^)]*)$/* \1/" log.txt >graph.txt &&
+    cat >expected <<-\EOF &&
+	* edit: p3
+	|   modified: p3
+	* goto
+	|   pushed: p3
+	* goto
+	|   popped: p2, p3
+	* uncommit
+	    created: p0, p1, p2, p3
+	EOF
+    test_cmp expected graph.txt
+'
+
+test_expect_success 'Log graph with number' '
+    stg log --graph -n1 >log.txt &&
+    test_line_count = 2 log.txt &&
+    grep -e "^\* [0-9a-f]\{7\} edit: p3 (" log.txt &&
+    grep -e "^    modified: p3$" log.txt
+'
+
+test_expect_success 'Log graph for renamed patch' '
+    stg rename p0 q0 &&
+    stg log --graph --patch p0 >log.txt &&
+    sed -e "s/^\* [0-9a-f]\{7\} \(.*\) ([^)]*)$/* \1/" log.txt >graph.txt &&
+    cat >expected <<-\EOF &&
+	* rename p0 q0
+	|   renamed: p0 -> q0
+	* uncommit
+	    created: p0, p1, p2, p3
+	EOF
+    test_cmp expected graph.txt &&
+    stg rename q0 p0
+'
+
+test_expect_success 'Log json' '
+    stg log --json -n2 >log.json &&
+    grep -e "\"operation\": \"rename q0 p0\"" log.json &&
+    grep -e "\"operation\": \"rename p0 q0\"" log.json &&
+    grep -e "\"from\": \"q0\"" log.json &&
+    grep -e "\"to\": \"p0\"" log.json &&
+    grep -e "\"created\": \[\]" log.json &&
+    test "$(grep -c -e "\"commit\": " log.json)" = "2"
+'
+
 test_expect_success 'Clear the log' '
     stg log --clear &&
     test "$(echo $(stg series --noprefix))" = "p0 p1 p2 p3" &&
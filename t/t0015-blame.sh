@@ -0,0 +1,70 @@
+#!/bin/sh
+
+test_description='Test stg blame'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize repo with patches' '
+    printf "line 1\nline 2\nline 3\n" >foo.txt &&
+    git add foo.txt &&
+    git commit -m "base commit" &&
+    stg init &&
+    sed "s/line 2/line 2 from p0/" foo.txt >foo.txt.tmp && mv foo.txt.tmp foo.txt &&
+    stg new -m p0 &&
+    stg refresh &&
+    echo "line 4" >>foo.txt &&
+    stg new -m p1 &&
+    stg refresh &&
+    stg new -m p2
+'
+
+test_expect_success 'Blame lines to patches' '
+    stg blame foo.txt >out &&
+    cat >expected <<-\EOF &&
+	base 1) line 1
+	p0   2) line 2 from p0
+	base 3) line 3
+	p1   4) line 4
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Blame line range' '
+    stg blame -L 2,3 foo.txt >out &&
+    cat >expected <<-\EOF &&
+	p0   2) line 2 from p0
+	base 3) line 3
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Blame with porcelain output' '
+    stg blame --porcelain -L 4,4 foo.txt >out &&
+    printf "p1\t%s\t4\t4\tline 4\n" "$(stg id p1)" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Unapplied patches are not blamed' '
+    stg pop &&
+    stg pop &&
+    stg blame foo.txt >out &&
+    cat >expected <<-\EOF &&
+	base 1) line 1
+	p0   2) line 2 from p0
+	base 3) line 3
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Blame without applied patches' '
+    stg pop -a &&
+    stg blame foo.txt >out &&
+    cat >expected <<-\EOF &&
+	base 1) line 1
+	base 2) line 2
+	base 3) line 3
+	EOF
+    test_cmp expected out
+'
+
+test_done
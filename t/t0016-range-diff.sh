@@ -0,0 +1,98 @@
+#!/bin/sh
+
+test_description='Test stg range-diff'
+
+. ./test-lib.sh
+
+normalize () {
+    sed -e "s/[0-9a-f]\{7\}/HASH/g" "$@"
+}
+
+test_expect_success 'Initialize repo with patches' '
+    test_commit_bulk --filename="file%s.txt" --message="p%s" 3 &&
+    stg uncommit -n 3
+'
+
+test_expect_success 'Compare before and after last operation' '
+    stg range-diff >out &&
+    normalize out >normalized &&
+    cat >expected <<-\EOF &&
+	-:  ------- > 1:  HASH p1
+	-:  ------- > 2:  HASH p2
+	-:  ------- > 3:  HASH p3
+	EOF
+    test_cmp expected normalized
+'
+
+test_expect_success 'Show reordered patches' '
+    git rev-parse refs/stacks/master >state-before &&
+    stg float p1 &&
+    stg range-diff "$(cat state-before)" >out &&
+    normalize out >normalized &&
+    cat >expected <<-\EOF &&
+	2:  HASH = 1:  HASH p2
+	3:  HASH = 2:  HASH p3
+	1:  HASH = 3:  HASH p1
+	EOF
+    test_cmp expected normalized
+'
+
+test_expect_success 'Show modified patches with interdiff' '
+    git rev-parse refs/stacks/master >state-before &&
+    stg goto p2 &&
+    echo "more content" >>file2.txt &&
+    stg refresh &&
+    stg range-diff "$(cat state-before)" >out &&
+    normalize out >normalized &&
+    grep -e "^1:  HASH ! 1:  HASH p2$" normalized &&
+    grep -e "^    ++more content$" out &&
+    grep -e "^2:  HASH = 2:  HASH p3$" normalized &&
+    grep -e "^3:  HASH = 3:  HASH p1$" normalized
+'
+
+test_expect_success 'Show added, removed, and renamed patches' '
+    git rev-parse refs/stacks/master >state-before &&
+    stg rename p3 p3-renamed &&
+    stg delete p1 &&
+    stg new -m p4 &&
+    echo "something else entirely" >file4.txt &&
+    stg add file4.txt &&
+    stg refresh &&
+    stg range-diff "$(cat state-before)" >out &&
+    normalize out >normalized &&
+    cat >expected <<-\EOF &&
+	1:  HASH = 1:  HASH p2
+	-:  ------- > 2:  HASH p4
+	2:  HASH = 3:  HASH p3 -> p3-renamed
+	3:  HASH < -:  ------- p1
+	EOF
+    test_cmp expected normalized
+'
+
+test_expect_success 'Compare two explicit states' '
+    stg range-diff "$(cat state-before)" master >out &&
+    normalize out >normalized &&
+    test_cmp expected normalized
+'
+
+test_expect_success 'Compare with another branch' '
+    stg branch --clone cloned &&
+    stg range-diff master cloned >out &&
+    normalize out >normalized &&
+    cat >expected <<-\EOF &&
+	1:  HASH = 1:  HASH p2
+	2:  HASH = 2:  HASH p4
+	3:  HASH = 3:  HASH p3-renamed
+	EOF
+    test_cmp expected normalized &&
+    stg branch master
+'
+
+test_expect_success 'Invalid stack state' '
+    command_error stg range-diff not-a-state 2>err &&
+    grep -e "invalid stack state \`not-a-state\`" err &&
+    command_error stg range-diff HEAD 2>err &&
+    grep -e "\`HEAD\` is not a stack state" err
+'
+
+test_done
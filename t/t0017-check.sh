@@ -0,0 +1,140 @@
+#!/bin/sh
+
+test_description="Test 'stg check'"
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stack with good and bad patches' '
+    test_commit base &&
+    printf "caf\351\n" >latin1-msg &&
+    echo latin1 >latin1.txt &&
+    git add latin1.txt &&
+    git -c i18n.commitEncoding=ISO-8859-1 commit -F latin1-msg &&
+    stg init &&
+    stg uncommit latin1 &&
+    cat >good-msg <<-\EOF &&
+	area: good
+
+	Signed-off-by: A U Thor <author@example.com>
+	EOF
+    stg new -f good-msg p1 &&
+    echo good >good.txt &&
+    git add good.txt &&
+    stg refresh &&
+    stg new -m bad p2 &&
+    printf "trailing   \n<<<<<<< HEAD\n" >bad.txt &&
+    git add bad.txt &&
+    stg refresh
+'
+
+test_expect_success 'Good patch passes' '
+    stg check p1 >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Bad patch problems are reported' '
+    command_error stg check p2 >out 2>err &&
+    cat >expected <<-\EOF &&
+	p2: signoff: missing Signed-off-by from author `A Ú Thor <author@example.com>`
+	p2: trailing-whitespace: bad.txt:1: trailing whitespace
+	p2: conflict-markers: bad.txt:2: conflict marker `<<<<<<< HEAD`
+	EOF
+    test_cmp expected out &&
+    grep "found 3 problems" err
+'
+
+test_expect_success 'Non-UTF-8 message is reported' '
+    command_error stg check latin1 >out &&
+    grep "^latin1: message-encoding: message encoding is \`ISO-8859-1\`, not UTF-8$" out
+'
+
+test_expect_success 'All applied patches are checked by default' '
+    command_error stg check >out &&
+    grep "^latin1: " out &&
+    ! grep "^p1: " out &&
+    grep "^p2: " out
+'
+
+test_expect_success 'Rules may be disabled' '
+    test_config stgit.check.signoff false &&
+    test_config stgit.check.trailing-whitespace false &&
+    test_config stgit.check.conflict-markers false &&
+    stg check p2
+'
+
+test_expect_success 'Subject length limit' '
+    test_config stgit.check.subjectLimit 5 &&
+    command_error stg check p1 >out &&
+    cat >expected <<-\EOF &&
+	p1: subject-length: subject is 10 characters long; the limit is 5
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Subject prefix rule is opt-in' '
+    test_config stgit.check.subject-prefix true &&
+    stg check p1 &&
+    command_error stg check p2 >out &&
+    grep "^p2: subject-prefix: subject has no \`area: \` prefix$" out
+'
+
+test_expect_success 'Added file size limit' '
+    test_config stgit.check.fileSizeLimit 4 &&
+    command_error stg check p1 >out &&
+    cat >expected <<-\EOF &&
+	p1: large-file: good.txt: added file is 5 bytes; the limit is 4
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Author differing from committer is opt-in' '
+    stg check p1 &&
+    test_config stgit.check.author-from true &&
+    command_error stg check p1 >out &&
+    grep "^p1: author-from: author \`A Ú Thor <author@example.com>\` differs from committer" out &&
+    test_config format.from true &&
+    stg check p1
+'
+
+test_expect_success 'Email format is gated with --check' '
+    command_error stg email format --check -o fmt1 p2 2>err &&
+    grep "use \`--no-check\` to skip checking" err &&
+    test_path_is_missing fmt1 &&
+    stg email format --check -o fmt1 p1 &&
+    test_path_is_file fmt1/0001-area-good.patch
+'
+
+test_expect_success 'Email format is gated by configuration' '
+    test_config stgit.check.gate "commit, email-format" &&
+    command_error stg email format -o fmt2 p2 &&
+    test_path_is_missing fmt2 &&
+    stg email format --no-check -o fmt2 p2 &&
+    test_path_is_file fmt2/0001-bad.patch
+'
+
+test_expect_success 'Refresh checks only the refreshed changes' '
+    stg series >series-before &&
+    echo "more   " >>good.txt &&
+    command_error stg refresh -p p1 --check >out &&
+    cat >expected <<-\EOF &&
+	p1: trailing-whitespace: good.txt:2: trailing whitespace
+	EOF
+    test_cmp expected out &&
+    stg series >series-after &&
+    test_cmp series-before series-after &&
+    git diff --cached --quiet &&
+    git checkout good.txt &&
+    echo more >>good.txt &&
+    stg refresh -p p1 --check
+'
+
+test_expect_success 'Commit is gated by configuration' '
+    test_config stgit.check.gate commit &&
+    command_error stg commit >out &&
+    grep "^latin1: message-encoding: " out &&
+    test "$(stg series --applied -c)" = "3" &&
+    stg commit --no-check &&
+    test "$(stg series --applied -c)" = "2"
+'
+
+test_done
@@ -0,0 +1,82 @@
+#!/bin/sh
+
+test_description='Test template and config search paths'
+
+. ./test-lib.sh
+
+test_expect_success 'List templates without template files' '
+    cat >expected <<-\EOF &&
+	covermail.tmpl    (none)
+	patchdescr.tmpl   (none)
+	patchexport.tmpl  (built-in)
+	EOF
+    stg templates list >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Repo template shadows user templates' '
+    mkdir -p "$HOME/.stgit/templates" "$HOME/.config/stgit/templates" &&
+    test_when_finished "rm -rf \"$HOME/.stgit\" \"$HOME/.config/stgit\" .git/patchdescr.tmpl" &&
+    echo home >"$HOME/.stgit/templates/patchdescr.tmpl" &&
+    echo home >"$HOME/.stgit/templates/extra.tmpl" &&
+    echo xdg >"$HOME/.config/stgit/templates/patchdescr.tmpl" &&
+    stg templates list patchdescr.tmpl >out &&
+    grep -e "^patchdescr.tmpl  xdg     .*/.config/stgit/templates/patchdescr.tmpl$" out &&
+    echo repo >.git/patchdescr.tmpl &&
+    stg templates list >out &&
+    grep -e "^extra.tmpl        home    .*/.stgit/templates/extra.tmpl$" out &&
+    grep -e "^patchdescr.tmpl   repo    .*/.git/patchdescr.tmpl$" out &&
+    stg templates list --all patchdescr.tmpl >out &&
+    test_line_count = 3 out &&
+    grep -e "^\* patchdescr.tmpl  repo" out &&
+    grep -e "^  patchdescr.tmpl  xdg" out &&
+    grep -e "^  patchdescr.tmpl  home" out
+'
+
+test_expect_success 'XDG_CONFIG_HOME template is used' '
+    mkdir -p xdg/stgit/templates &&
+    echo "xdg message" >xdg/stgit/templates/patchdescr.tmpl &&
+    XDG_CONFIG_HOME="$(pwd)/xdg" stg templates list patchdescr.tmpl >out &&
+    grep -e "xdg/stgit/templates/patchdescr.tmpl$" out
+'
+
+test_expect_success 'Setup installation prefix' '
+    mkdir -p prefix/bin prefix/share/stgit/templates &&
+    if test "${STG_PROFILE:=dev}" = "dev"
+    then
+        profile_dir=debug
+    else
+        profile_dir="$STG_PROFILE"
+    fi &&
+    cp "$STG_ROOT/target/$profile_dir/stg" prefix/bin/stg &&
+    test_commit base &&
+    prefix/bin/stg init &&
+    cat >prefix/share/stgit/templates/patchdescr.tmpl <<-\EOF &&
+	{# Installation prefix template #}
+	Prefix template for {{ name }}
+	EOF
+    cat >prefix/share/stgit/config <<-\EOF
+	[stgit "alias"]
+	    prefix-alias = !echo PREFIX-ALIAS
+	    shadowed-alias = !echo PREFIX-SHADOWED
+	EOF
+'
+
+test_expect_success 'Prefix template is used' '
+    prefix/bin/stg templates list patchdescr.tmpl >out &&
+    grep -e "^patchdescr.tmpl  prefix  .*/prefix/share/stgit/templates/patchdescr.tmpl$" out &&
+    stg templates list patchdescr.tmpl >out &&
+    grep -e "(none)$" out &&
+    prefix/bin/stg new p0 &&
+    git cat-file -p HEAD >commit &&
+    grep -e "^Prefix template for p0$" commit
+'
+
+test_expect_success 'Prefix config provides aliases' '
+    test "$(prefix/bin/stg prefix-alias)" = "PREFIX-ALIAS" &&
+    test_config stgit.alias.shadowed-alias "!echo GIT-CONFIG" &&
+    test "$(prefix/bin/stg shadowed-alias)" = "GIT-CONFIG" &&
+    general_error stg prefix-alias
+'
+
+test_done
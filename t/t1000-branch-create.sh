@@ -60,7 +60,7 @@ test_expect_success 'Create branch with worktree changes' '
     echo bye >file.txt &&
     stg branch --create branch-with-change &&
     test "$(stg branch)" = "branch-with-change" &&
-    test "$(stg status file.txt)" = " M file.txt" &&
+    test "$(stg status --short file.txt)" = " M file.txt" &&
     test "$(stg series --noprefix --all)" = "" &&
     grep -e bye file.txt &&
     git checkout file.txt
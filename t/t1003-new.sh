@@ -140,4 +140,35 @@ test_expect_success 'Patch with escaped leading hyphen' '
     [ "$(echo $(stg top))" = "-patch-" ]
 '
 
+test_expect_success 'New with variables in patchdescr.tmpl' '
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    test_when_finished "rm .git/patchdescr.tmpl" &&
+    cat >.git/patchdescr.tmpl <<-\EOF &&
+	{# StGit patch description template #}
+	Patch {{ name }}
+
+	{% if authemail %}
+	Author-Email: {{ authemail }}
+	{% endif %}
+	EOF
+    stg new described-patch &&
+    git cat-file -p HEAD >commit &&
+    grep "^Patch described-patch$" commit &&
+    grep "^Author-Email: author@example.com$" commit &&
+    ! grep "StGit patch description template" commit
+'
+
+test_expect_success 'New with verbatim patchdescr.tmpl' '
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    test_when_finished "rm .git/patchdescr.tmpl" &&
+    cat >.git/patchdescr.tmpl <<-\EOF &&
+	Summary {{ not a tag }} %(name)s {% if
+	EOF
+    stg new verbatim-patch &&
+    git cat-file -p HEAD >commit &&
+    grep "^Summary {{ not a tag }} %(name)s {% if$" commit
+'
+
 test_done
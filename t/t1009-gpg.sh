@@ -39,7 +39,7 @@ test_expect_success GPG 'changes rolled back when gpg fails' '
     test_config stgit.gpgsign true &&
     test_config gpg.program false &&
     command_error stg pop 2>err &&
-    stg status --untracked-files=no >status.txt &&
+    stg status --short --untracked-files=no >status.txt &&
     test_must_be_empty status.txt &&
     test "$(echo $(stg series))" = "> p0" &&
     git config --unset gpg.program &&
@@ -70,7 +70,7 @@ test_expect_success !MINGW,GPG 'changes rolled back when gpg killed' '
         echo "Invalid exit code: $exit_code" &&
         false
     fi &&
-    stg status --untracked-files=no >status.txt &&
+    stg status --short --untracked-files=no >status.txt &&
     test_must_be_empty status.txt &&
     test "$(echo $(stg series))" = "> p0" &&
     git config --unset gpg.program &&
@@ -0,0 +1,117 @@
+#!/bin/sh
+
+test_description='Test stacked branches'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize parent branch' '
+    git branch upstream &&
+    stg init &&
+    stg new -m p1 &&
+    echo "p1" >p1.txt &&
+    stg add p1.txt &&
+    stg refresh
+'
+
+test_expect_success 'Stacked branch requires parent stack' '
+    command_error stg branch --create --stacked nostack upstream 2>err &&
+    grep "parent branch .upstream. does not have a StGit stack" err &&
+    test_must_fail git rev-parse --verify -q refs/heads/nostack
+'
+
+test_expect_success 'Create stacked branches' '
+    stg branch --create --stacked feature &&
+    test "$(git config branch.feature.stgit.parentbranch)" = "master" &&
+    test "$(git config --bool branch.feature.stgit.stacked)" = "true" &&
+    stg new -m f1 &&
+    echo "f1" >f1.txt &&
+    stg add f1.txt &&
+    stg refresh &&
+    stg branch --create --stacked feature2 &&
+    test "$(git config branch.feature2.stgit.parentbranch)" = "feature" &&
+    test "$(git config --bool branch.feature2.stgit.stacked)" = "true" &&
+    stg new -m f2 &&
+    echo "f2" >f2.txt &&
+    stg add f2.txt &&
+    stg refresh
+'
+
+test_expect_success 'Non-stacked branch' '
+    stg branch --create other master &&
+    test "$(git config branch.other.stgit.parentbranch)" = "master" &&
+    test_must_fail git config branch.other.stgit.stacked
+'
+
+test_expect_success 'List stacked branches as tree' '
+    stg branch --list | cut -f2 | sed -e "s/ *|.*//" >list &&
+    cat >expected <<-\EOF &&
+	master
+	  feature
+	    feature2
+	other
+	upstream
+	EOF
+    test_cmp expected list
+'
+
+test_expect_success 'Rebase without cascade hints at stacked branches' '
+    stg branch upstream &&
+    echo "upstream" >upstream.txt &&
+    git add upstream.txt &&
+    git commit -m upstream &&
+    stg branch master &&
+    stg rebase upstream 2>err &&
+    grep "Stacked branches may need to be rebased: .feature." err &&
+    test "$(stg id feature:{base})" != "$(git rev-parse master)"
+'
+
+test_expect_success 'Rebase with cascade' '
+    stg rebase --cascade upstream &&
+    test "$(stg branch)" = "master" &&
+    test "$(stg id feature:{base})" = "$(git rev-parse master)" &&
+    test "$(stg id feature2:{base})" = "$(git rev-parse feature)" &&
+    test "$(stg series --branch feature --noprefix)" = "f1" &&
+    test "$(stg series --branch feature2 --noprefix)" = "f2" &&
+    git merge-base --is-ancestor upstream feature2
+'
+
+test_expect_success 'Cascade conflicts with nopush' '
+    general_error stg rebase --cascade --nopush upstream 2>err &&
+    grep "cannot be used with" err
+'
+
+test_expect_success 'Rename updates stacked children' '
+    stg branch --rename feature feat &&
+    test "$(git config branch.feat.stgit.parentbranch)" = "master" &&
+    test "$(git config --bool branch.feat.stgit.stacked)" = "true" &&
+    test "$(git config branch.feature2.stgit.parentbranch)" = "feat" &&
+    test "$(git config --bool branch.feature2.stgit.stacked)" = "true"
+'
+
+test_expect_success 'Stack branch on itself' '
+    command_error stg branch --stack-on master master 2>err &&
+    grep "cannot stack branch .master. on itself" err
+'
+
+test_expect_success 'Stack branch on its descendant' '
+    command_error stg branch --stack-on feature2 master 2>err &&
+    grep "cannot stack .master. on .feature2. since .feature2. is stacked on .master." err &&
+    test_must_fail git config branch.master.stgit.stacked
+'
+
+test_expect_success 'Stack existing branch' '
+    stg branch --stack-on feature2 other &&
+    test "$(git config branch.other.stgit.parentbranch)" = "feature2" &&
+    test "$(git config --bool branch.other.stgit.stacked)" = "true" &&
+    stg branch --list | cut -f2 | sed -e "s/ *|.*//" >list &&
+    cat >expected <<-\EOF &&
+	master
+	  feat
+	    feature2
+	      other
+	upstream
+	EOF
+    test_cmp expected list
+'
+
+test_done
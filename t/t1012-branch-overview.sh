@@ -0,0 +1,119 @@
+#!/bin/sh
+
+test_description='Test branch overview and batch operations'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize branches' '
+    git branch upstream &&
+    stg init &&
+    stg new -m p1 &&
+    echo "p1" >p1.txt &&
+    stg add p1.txt &&
+    stg refresh &&
+    stg new -m p2 &&
+    echo "p2" >p2.txt &&
+    stg add p2.txt &&
+    stg refresh &&
+    stg pop &&
+    stg branch --create other upstream &&
+    stg new -m o1 &&
+    echo "o1" >o1.txt &&
+    stg add o1.txt &&
+    stg refresh &&
+    stg branch --create --stacked child &&
+    stg new -m c1 &&
+    echo "c1" >c1.txt &&
+    stg add c1.txt &&
+    stg refresh &&
+    stg branch --create prot upstream &&
+    stg new -m x1 &&
+    echo "x1" >x1.txt &&
+    stg add x1.txt &&
+    stg refresh &&
+    stg branch --protect &&
+    stg branch master
+'
+
+test_expect_success 'Verbose and json are mutually exclusive' '
+    general_error stg branch --list --verbose --json 2>err &&
+    grep "cannot be used with" err
+'
+
+test_expect_success 'List branches verbosely' '
+    stg branch --list --verbose >out &&
+    grep "1 applied, 1 unapplied, 0 hidden; top: p1; last: pop (" out &&
+    grep "1 applied, 0 unapplied, 0 hidden; top: o1; last: " out &&
+    grep "1 applied, 0 unapplied, 0 hidden; top: c1; last: " out &&
+    test_line_count = 9 out
+'
+
+test_expect_success 'Stale stack base' '
+    stg branch upstream &&
+    echo "upstream" >upstream.txt &&
+    git add upstream.txt &&
+    git commit -m upstream &&
+    stg branch master &&
+    stg branch --list --verbose >out &&
+    grep "top: o1; stale base; last: " out &&
+    grep "top: x1; stale base; last: " out &&
+    grep "top: c1; last: " out &&
+    grep "top: p1; last: " out
+'
+
+test_expect_success 'List branches as json' '
+    stg branch --list --json >out &&
+    grep "\"name\": \"child\"" out &&
+    grep "\"parent\": \"other\"" out &&
+    grep "\"stacked\": true" out &&
+    grep "\"protected\": true" out &&
+    grep "\"stale_base\": true" out &&
+    grep "\"stack\": null" out &&
+    grep "\"top\": \"p1\"" out &&
+    grep "\"last_operation\": \"pop\"" out
+'
+
+test_expect_success 'Batch operation requires pull or rebase' '
+    general_error stg branch --all 2>err &&
+    grep "the following required arguments were not provided" err
+'
+
+test_expect_success 'Rebase all branches' '
+    stg branch --all --rebase upstream >out &&
+    sed -n "/^Summary:/,\$p" out >summary &&
+    cat >expected <<-\EOF &&
+	Summary:
+	  master  updated
+	  other   updated
+	  prot    skipped (protected)
+	EOF
+    test_cmp expected summary &&
+    test "$(stg branch)" = "master" &&
+    test "$(stg id master:{base})" = "$(git rev-parse upstream)" &&
+    test "$(stg id other:{base})" = "$(git rev-parse upstream)" &&
+    test "$(stg id child:{base})" = "$(git rev-parse other)" &&
+    test "$(stg id prot:{base})" = "$(git rev-parse upstream~1)" &&
+    test "$(stg series --branch master --noprefix --applied)" = "p1"
+'
+
+test_expect_success 'Rebase all branches with conflicts' '
+    stg branch upstream &&
+    echo "upstream p1" >p1.txt &&
+    git add p1.txt &&
+    git commit -m "upstream p1" &&
+    stg branch master &&
+    conflict stg branch --all --rebase upstream >out 2>err &&
+    sed -n "/^Summary:/,\$p" out >summary &&
+    cat >expected <<-\EOF &&
+	Summary:
+	  master  conflicts
+	  other   not updated
+	  prot    not updated
+	EOF
+    test_cmp expected summary &&
+    grep "resolve the conflicts on branch .master." err &&
+    test "$(stg branch)" = "master" &&
+    test "$(stg id other:{base})" = "$(git rev-parse upstream~1)"
+'
+
+test_done
@@ -0,0 +1,140 @@
+#!/bin/sh
+
+test_description='Test undoing branch operations'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize branches' '
+    stg init &&
+    stg new -m p1 &&
+    echo "p1" >p1.txt &&
+    stg add p1.txt &&
+    stg refresh &&
+    stg branch --create foo &&
+    stg new -m f1 &&
+    echo "f1" >f1.txt &&
+    stg add f1.txt &&
+    stg refresh &&
+    stg new -m f2 &&
+    echo "f2" >f2.txt &&
+    stg add f2.txt &&
+    stg refresh &&
+    stg pop &&
+    stg branch --describe "foo branch" &&
+    stg branch master
+'
+
+test_expect_success 'Nothing to undo' '
+    command_error stg undo --branch-op 2>err &&
+    grep "no branch operations to undo" err
+'
+
+test_expect_success 'Branch op conflicts with number and hard' '
+    general_error stg undo --branch-op --number 2 2>err &&
+    grep "cannot be used with" err &&
+    general_error stg undo --branch-op --hard 2>err &&
+    grep "cannot be used with" err
+'
+
+test_expect_success 'Delete and undelete branch' '
+    foo_head="$(git rev-parse foo)" &&
+    f2_id="$(git rev-parse refs/patches/foo/f2)" &&
+    git update-ref refs/stgit/email/foo "$foo_head" &&
+    git update-ref refs/stgit/quilt/foo "$foo_head" &&
+    stg branch --delete --force foo &&
+    test_must_fail git rev-parse --verify -q refs/heads/foo &&
+    test_must_fail git rev-parse --verify -q refs/stgit/email/foo &&
+    test_must_fail git rev-parse --verify -q refs/stgit/quilt/foo &&
+    test_must_fail git config branch.foo.description &&
+    stg branch --undelete foo &&
+    test "$(git rev-parse foo)" = "$foo_head" &&
+    test "$(git rev-parse refs/patches/foo/f2)" = "$f2_id" &&
+    test "$(git rev-parse refs/stgit/email/foo)" = "$foo_head" &&
+    test "$(git rev-parse refs/stgit/quilt/foo)" = "$foo_head" &&
+    test "$(stg series --branch foo --noprefix --applied)" = "f1" &&
+    test "$(stg series --branch foo --noprefix --unapplied)" = "f2" &&
+    test "$(git config branch.foo.description)" = "foo branch" &&
+    test "$(git config branch.foo.stgit.parentbranch)" = "master"
+'
+
+test_expect_success 'Undelete unknown branch' '
+    command_error stg branch --undelete foo 2>err &&
+    grep "no deleted branch .foo. in the branch operation log" err
+'
+
+test_expect_success 'Undelete existing branch' '
+    stg branch --delete --force foo &&
+    git branch foo &&
+    command_error stg branch --undelete foo 2>err &&
+    grep "branch .foo. already exists" err &&
+    git branch -D foo &&
+    stg branch --undelete foo &&
+    test "$(stg series --branch foo --noprefix --applied)" = "f1" &&
+    test "$(git config branch.foo.description)" = "foo branch"
+'
+
+test_expect_success 'Undo delete with branch op' '
+    stg branch --delete --force foo &&
+    stg undo --branch-op 2>err &&
+    grep "Undid branch operation .delete foo." err &&
+    test "$(stg series --branch foo --noprefix --applied)" = "f1"
+'
+
+test_expect_success 'Undo rename' '
+    stg branch --rename foo bar &&
+    test "$(stg series --branch bar --noprefix --applied)" = "f1" &&
+    stg undo --branch-op &&
+    test_must_fail git rev-parse --verify -q refs/heads/bar &&
+    test "$(stg series --branch foo --noprefix --applied)" = "f1" &&
+    test "$(stg series --branch foo --noprefix --unapplied)" = "f2" &&
+    test "$(git config branch.foo.description)" = "foo branch"
+'
+
+test_expect_success 'Undo cleanup' '
+    stg branch --cleanup --force foo &&
+    test_must_fail git rev-parse --verify -q refs/stacks/foo &&
+    test_must_fail git rev-parse --verify -q refs/patches/foo/f1 &&
+    stg series --branch foo >series &&
+    test_must_be_empty series &&
+    stg undo --branch-op &&
+    test "$(stg series --branch foo --noprefix --applied)" = "f1" &&
+    test "$(stg series --branch foo --noprefix --unapplied)" = "f2" &&
+    test "$(git config branch.foo.stgit.parentbranch)" = "master"
+'
+
+test_expect_success 'Undo clone' '
+    stg branch foo &&
+    stg branch --clone foo-clone &&
+    test "$(stg branch)" = "foo-clone" &&
+    stg undo --branch-op &&
+    test "$(stg branch)" = "foo" &&
+    test_must_fail git rev-parse --verify -q refs/heads/foo-clone &&
+    test_must_fail git config --get-regexp "^branch\.foo-clone\." &&
+    command_error stg series --branch foo-clone
+'
+
+test_expect_success 'Undo changed clone' '
+    stg branch --clone foo-clone &&
+    stg new -m c1 &&
+    command_error stg undo --branch-op 2>err &&
+    grep "branch .foo-clone. has changed since it was cloned" err &&
+    test "$(stg branch)" = "foo-clone"
+'
+
+test_expect_success 'Undelete older record' '
+    stg branch master &&
+    stg branch --delete --force foo-clone &&
+    stg branch --rename foo baz &&
+    stg branch --undelete foo-clone &&
+    stg series --branch foo-clone --noprefix --applied >series &&
+    cat >expected <<-\EOF &&
+	f1
+	c1
+	EOF
+    test_cmp expected series &&
+    stg undo --branch-op &&
+    test "$(stg series --branch foo --noprefix --applied)" = "f1" &&
+    test_must_fail git rev-parse --verify -q refs/heads/baz
+'
+
+test_done
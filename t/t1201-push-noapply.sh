@@ -75,7 +75,7 @@ test_expect_success 'Push allowed with dirty worktree' '
     stg pop -n 2 &&
     echo "foobar" >b.txt &&
     test_when_finished git checkout b.txt &&
-    test "$(stg status b.txt)" = " M b.txt" &&
+    test "$(stg status --short b.txt)" = " M b.txt" &&
     stg push --noapply a1 a2 a3 &&
     test "$(echo $(stg series --applied --noprefix))" = "b1 b2 b3" &&
     test "$(echo $(stg series --unapplied --noprefix))" = "a1 a2 a3"
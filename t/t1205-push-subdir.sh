@@ -50,7 +50,7 @@ test_expect_success 'Conflicting push from subdir' '
     cd foo &&
     conflict stg push p2 &&
     cd .. &&
-    [ "$(echo $(stg status))" = "UU foo/y.txt UU x.txt" ]
+    [ "$(echo $(stg status --short))" = "UU foo/y.txt UU x.txt" ]
 '
 
 test_expect_success 'Conflicting add/unknown file in subdir' '
@@ -89,7 +89,7 @@ test_expect_success 'attempt sink with same to and target' '
 test_expect_success 'sink with conflict' '
     conflict stg sink --to=p2 p22 &&
     test "$(echo $(stg series --applied --noprefix))" = "p1 p22" &&
-    test "$(echo $(stg status))" = "DU f2"
+    test "$(echo $(stg status --short))" = "DU f2"
 '
 
 test_done
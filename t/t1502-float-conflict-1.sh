@@ -23,7 +23,7 @@ test_expect_success 'Float a patch, causing a conflict with the next patch' '
     test "$(echo $(stg series))" = "+ p0 > p2 - p1" &&
     test "$(stg id p2)" = "$(git rev-list HEAD~0 -n 1)" &&
     test "$(stg id p0)" = "$(git rev-list HEAD~1 -n 1)" &&
-    test "$(stg status)" = "UU foo.txt" &&
+    test "$(stg status --short)" = "UU foo.txt" &&
     cat >expected.txt <<-\EOF &&
 	first line
 	<<<<<<< current
@@ -46,7 +46,7 @@ test_expect_success 'Unclean worktree and floating only unapplied' '
     test "$(echo $(stg series --unapplied --noprefix))" = "p3 p2 p1" &&
     echo "foobar" >4.t &&
     test_when_finished git checkout 4.t &&
-    test "$(stg status 4.t)" = " M 4.t" &&
+    test "$(stg status --short 4.t)" = " M 4.t" &&
     stg float --noapply p1 p2 p3 &&
     test "$(echo $(stg series --unapplied --noprefix))" = "p1 p2 p3"
 '
@@ -57,7 +57,7 @@ test_expect_success 'Unclean worktree and floating applied patches' '
     test "$(echo $(stg series --unapplied --noprefix))" = "p2 p3" &&
     echo "foobar" >4.t &&
     test_when_finished git checkout 4.t &&
-    test "$(stg status 4.t)" = " M 4.t" &&
+    test "$(stg status --short 4.t)" = " M 4.t" &&
     command_error stg float --noapply p4 2>err &&
     grep -e "worktree not clean" err
 '
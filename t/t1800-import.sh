@@ -240,6 +240,75 @@ test_expect_success 'Apply a gzip patch with a .gz suffix' '
     stg delete ..
 '
 
+test_lazy_prereq XZ '
+    xz --version &&
+    printf "\375\067zXZ\000" >probe.xz &&
+    ! stg import probe.xz 2>err &&
+    ! grep "not built with support" err
+'
+
+test_lazy_prereq ZSTD '
+    zstd --version &&
+    printf "\050\265\057\375" >probe.zst &&
+    ! stg import probe.zst 2>err &&
+    ! grep "not built with support" err
+'
+
+test_lazy_prereq ZIP '
+    zip -v &&
+    printf "PK\003\004" >probe.zip &&
+    ! stg import probe.zip 2>err &&
+    ! grep "not built with support" err
+'
+
+test_expect_success XZ 'Apply an xz patch with a .xz suffix' '
+    xz -c "$TEST_DIRECTORY"/t1800/git-diff >git-diff.xz &&
+    stg import git-diff.xz &&
+    [ $(git cat-file -p $(stg id) \
+        | grep -c "tree e96b1fba2160890ff600b675d7140d46b022b155") = 1 ] &&
+    stg delete ..
+'
+
+test_expect_success ZSTD 'Apply a zstd patch with a .zst suffix' '
+    zstd -q -c "$TEST_DIRECTORY"/t1800/git-diff >git-diff.zst &&
+    stg import git-diff.zst &&
+    [ $(git cat-file -p $(stg id) \
+        | grep -c "tree e96b1fba2160890ff600b675d7140d46b022b155") = 1 ] &&
+    stg delete ..
+'
+
+test_expect_success 'Apply a compressed patch without a suffix' '
+    gzip -c "$TEST_DIRECTORY"/t1800/gnu-diff >gnu-diff-compressed &&
+    stg import gnu-diff-compressed &&
+    [ $(git cat-file -p $(stg id) \
+        | grep -c "tree e96b1fba2160890ff600b675d7140d46b022b155") = 1 ] &&
+    stg delete .. &&
+    bzip2 -c "$TEST_DIRECTORY"/t1800/gnu-diff | stg import --name from-stdin &&
+    [ $(git cat-file -p $(stg id) \
+        | grep -c "tree e96b1fba2160890ff600b675d7140d46b022b155") = 1 ] &&
+    stg delete ..
+'
+
+test_expect_success ZIP 'Apply a patch from a zip archive' '
+    (
+        cd "$TEST_DIRECTORY"/t1800 &&
+        zip -q "$HOME"/git-diff.zip git-diff
+    ) &&
+    stg import git-diff.zip &&
+    [ $(git cat-file -p $(stg id) \
+        | grep -c "tree e96b1fba2160890ff600b675d7140d46b022b155") = 1 ] &&
+    stg delete ..
+'
+
+test_expect_success ZIP 'Attempt to apply a zip archive with multiple files' '
+    (
+        cd "$TEST_DIRECTORY"/t1800 &&
+        zip -q "$HOME"/two-diffs.zip git-diff gnu-diff
+    ) &&
+    command_error stg import two-diffs.zip 2>err &&
+    grep "zip archive contains multiple files" err
+'
+
 test_expect_success 'Apply a series from a tarball' '
     rm -f jabberwocky.txt &&
     touch jabberwocky.txt &&
@@ -267,6 +336,57 @@ test_expect_success MINGW 'Apply a series from a abs tarball path' '
         | grep -c "tree 2c33937252a21f1550c0bf21f1de534b68f69635") = 1 ]
 '
 
+test_expect_success XZ 'Apply a series from an xz tarball' '
+    (
+        cd "$TEST_DIRECTORY"/t1800 &&
+        tar -cf - patches | xz -c >"$HOME"/jabberwocky.tar.xz
+    ) &&
+    stg delete .. &&
+    stg import --series jabberwocky.tar.xz &&
+    [ $(git cat-file -p $(stg id) \
+        | grep -c "tree 2c33937252a21f1550c0bf21f1de534b68f69635") = 1 ]
+'
+
+test_expect_success ZSTD 'Apply a series from a zstd tarball' '
+    (
+        cd "$TEST_DIRECTORY"/t1800 &&
+        tar -cf - patches | zstd -q -c >"$HOME"/jabberwocky.tar.zst
+    ) &&
+    stg delete .. &&
+    stg import --series jabberwocky.tar.zst &&
+    [ $(git cat-file -p $(stg id) \
+        | grep -c "tree 2c33937252a21f1550c0bf21f1de534b68f69635") = 1 ]
+'
+
+test_expect_success ZIP 'Apply a series from a zip archive' '
+    (
+        cd "$TEST_DIRECTORY"/t1800 &&
+        zip -q -r "$HOME"/jabberwocky.zip patches
+    ) &&
+    stg delete .. &&
+    stg import --series jabberwocky.zip &&
+    [ $(git cat-file -p $(stg id) \
+        | grep -c "tree 2c33937252a21f1550c0bf21f1de534b68f69635") = 1 ]
+'
+
+test_expect_success 'Apply a compressed series file' '
+    mkdir compressed-series &&
+    cp "$TEST_DIRECTORY"/t1800/patches/*.patch compressed-series/ &&
+    gzip -c "$TEST_DIRECTORY"/t1800/patches/series >compressed-series/series.gz &&
+    stg delete .. &&
+    stg import --series compressed-series/series.gz &&
+    [ $(git cat-file -p $(stg id) \
+        | grep -c "tree 2c33937252a21f1550c0bf21f1de534b68f69635") = 1 ]
+'
+
+test_expect_success 'Apply a series from a tarball without a suffix' '
+    cp jabberwocky.tar.bz2 jabberwocky-archive &&
+    stg delete .. &&
+    stg import --series jabberwocky-archive &&
+    [ $(git cat-file -p $(stg id) \
+        | grep -c "tree 2c33937252a21f1550c0bf21f1de534b68f69635") = 1 ]
+'
+
 test_expect_success 'Import with author options' '
     stg show | grep -e "Author: Clark Williams <williams@redhat.com>" &&
     stg delete --top --spill &&
@@ -349,6 +469,86 @@ test_expect_success 'Import with root directory' '
     stg delete dirs from-relative
 '
 
+test_expect_success 'Import patch from hg export' '
+    cat >patch <<-\EOF &&
+	# HG changeset patch
+	# User Joe Example <joe@example.com>
+	# Date 1234567890 -3600
+	#      Sat Feb 14 00:31:30 2009 +0100
+	# Node ID 0b5fbf6e7e2f6bd9b2a7c2b3d2e6c3f6a1d9e0f1
+	# Parent  2f5e9a3b8f9c1d0e4b7a6c5d8e9f0a1b2c3d4e5f
+	hg: the subject
+
+	hg body
+	
+	diff --git a/bar.txt b/bar.txt
+	new file mode 100644
+	--- /dev/null
+	+++ b/bar.txt
+	@@ -0,0 +1 @@
+	+hello
+	EOF
+    test_when_finished "rm patch" &&
+    stg import patch &&
+    test "$(stg top)" = "patch" &&
+    git log -1 --pretty=format:"%an <%ae>%n%ad%n%s%n%b" --date=raw >out &&
+    cat >expected <<-\EOF &&
+	Joe Example <joe@example.com>
+	1234567890 +0100
+	hg: the subject
+	hg body
+	EOF
+    test_when_finished "rm out expected" &&
+    test_cmp expected out &&
+    test_cmp bar.txt - <<-\EOF &&
+	hello
+	EOF
+    stg delete --top
+'
+
+test_expect_success 'Import series of hg export patches' '
+    mkdir hg-series &&
+    test_when_finished "rm -rf hg-series" &&
+    cat >hg-series/first.patch <<-\EOF &&
+	# HG changeset patch
+	# User Joe Example <joe@example.com>
+	# Date 1234567890 0
+	hg first
+
+	diff --git a/bar.txt b/bar.txt
+	new file mode 100644
+	--- /dev/null
+	+++ b/bar.txt
+	@@ -0,0 +1 @@
+	+hello
+	EOF
+    echo first.patch >hg-series/series &&
+    stg import --series hg-series/series &&
+    test "$(git log -1 --pretty=format:"%an %ad %s" --date=raw)" = \
+        "Joe Example 1234567890 +0000 hg first" &&
+    stg delete --top
+'
+
+test_expect_success 'Import hg export patch with user lacking an email' '
+    cat >patch <<-\EOF &&
+	# HG changeset patch
+	# User joe
+	# Date 1234567890 0
+	hg no email
+
+	diff --git a/bar.txt b/bar.txt
+	new file mode 100644
+	--- /dev/null
+	+++ b/bar.txt
+	@@ -0,0 +1 @@
+	+hello
+	EOF
+    test_when_finished "rm patch" &&
+    stg import patch &&
+    test "$(git log -1 --pretty=format:"%an <%ae>")" = "joe <author@example.com>" &&
+    stg delete --top
+'
+
 test_expect_success 'Import series from stdin' '
     echo "some.patch" |
     stg import --series &&
@@ -214,4 +214,77 @@ test_expect_success 'Import from git format-patch output' '
     )
 '
 
+
+test_expect_success 'Setup mbox thread with several series versions' '
+    test_create_repo thread &&
+    (
+        cd thread &&
+        echo base >base.txt &&
+        git add base.txt &&
+        git commit -m "base" &&
+        echo a >a.txt && git add a.txt && git commit -m "area: first change" &&
+        echo b >b.txt && git add b.txt && git commit -m "area: second change" &&
+        git format-patch --cover-letter -o v1 -2 &&
+        echo c >c.txt && git add c.txt && git commit -m "area: third change" &&
+        git format-patch --cover-letter -v2 -o v2 -3 &&
+        git format-patch --cover-letter -v3 -o v3 -3 &&
+        cat >reply <<-\EOF &&
+	From nobody Mon Sep 17 00:00:00 2001
+	From: Rev Iewer <reviewer@example.com>
+	Subject: Re: [PATCH v2 1/3] area: first change
+	Date: Thu, 1 Jan 2009 00:00:00 +0000
+
+	Reviewed-by: Rev Iewer <reviewer@example.com>
+	EOF
+        cat v1/* v2/v2-0000-* v2/v2-0003-* v2/v2-0001-* reply v2/v2-0002-* \
+            v3/v3-0000-* v3/v3-0001-* v3/v3-0003-* >../thread.mbox &&
+        git reset --hard HEAD~3 &&
+        stg init
+    )
+'
+
+test_expect_success 'Import newest complete series version from mbox' '
+    (
+        cd thread &&
+        stg import -M ../thread.mbox 2>err &&
+        grep "version 3 of the series is incomplete, importing version 2" err &&
+        stg series --noprefix >series &&
+        printf "area-first-change\narea-second-change\narea-third-change\n" >expected &&
+        test_cmp expected series &&
+        test "$(git log -1 --format=%s $(stg id area-first-change))" = "area: first change" &&
+        stg delete ..
+    )
+'
+
+test_expect_success 'Import older series version from mbox' '
+    (
+        cd thread &&
+        stg import -M --version 1 ../thread.mbox &&
+        test "$(stg series --count)" = "2" &&
+        test_path_is_missing c.txt &&
+        stg delete ..
+    )
+'
+
+test_expect_success 'Import incomplete series version from mbox' '
+    (
+        cd thread &&
+        stg import -M --version 3 ../thread.mbox 2>err &&
+        grep "patch 2/3 of version 3 is missing from mbox" err &&
+        stg series --noprefix >series &&
+        printf "area-first-change\narea-third-change\n" >expected &&
+        test_cmp expected series &&
+        stg delete ..
+    )
+'
+
+test_expect_success 'Import missing series version from mbox' '
+    (
+        cd thread &&
+        command_error stg import -M --version 4 ../thread.mbox 2>err &&
+        grep "version 4 of the series not found in mbox" err &&
+        test "$(stg series --count)" = "0"
+    )
+'
+
 test_done
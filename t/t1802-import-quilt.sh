@@ -136,12 +136,12 @@ test_expect_success QUILT 'Import a quilt series with unexpected "-p" in series'
     cleanup_test
 '
 
-test_expect_success QUILT 'Import a quilt series with unexpected "-p1" in series' '
+test_expect_success QUILT 'Import a quilt series with "-p1" in series' '
     test_import_quilt_series_should_fail_p_something_ok 1 &&
     after_test &&
     sed -e "s/^\(.*\)$/\1 -p1/g" patches/series >series_messed &&
     mv series_messed patches/series &&
-    test_expect_code 2 check_test &&
+    check_test &&
     cleanup_test
 '
 
@@ -213,4 +213,75 @@ test_expect_success QUILT 'Quilt import series with subdirs' '
     stg delete ..
 '
 
+test_expect_success 'Setup series with strip levels and a reversed patch' '
+    echo 1 >r.txt &&
+    git add r.txt &&
+    git commit -m "add r.txt" &&
+    mkdir -p q/patches &&
+    cat >q/patches/p0.diff <<-\EOF &&
+	p0 change
+
+	--- r.txt
+	+++ r.txt
+	@@ -1 +1 @@
+	-1
+	+2
+	EOF
+    cat >q/patches/p2.diff <<-\EOF &&
+	p2 change
+
+	--- x/y/r.txt
+	+++ x/y/r.txt
+	@@ -1 +1 @@
+	-2
+	+3
+	EOF
+    cat >q/patches/rev.diff <<-\EOF &&
+	reversed change
+
+	--- a/r.txt
+	+++ b/r.txt
+	@@ -1 +1 @@
+	-4
+	+3
+	EOF
+    cat >q/patches/series <<-\EOF
+	p0.diff -p0
+	p2.diff -p2 # comment
+	rev.diff -R
+	EOF
+'
+
+test_expect_success 'Import series with strip levels and a reversed patch' '
+    stg import -S q/patches/series &&
+    stg series --applied --noprefix >series.txt &&
+    test_line_count = 3 series.txt &&
+    test "$(cat r.txt)" = "4" &&
+    stg delete .. &&
+    test "$(cat r.txt)" = "1"
+'
+
+test_expect_success 'Import series with unsupported series option' '
+    echo "p0.diff -p0 -E" >q/patches/bad-series &&
+    command_error stg import -S q/patches/bad-series 2>err &&
+    grep "patch \`p0.diff\` has unsupported option \"-E\"" err
+'
+
+test_expect_success 'Import series with quilt applied-patches status' '
+    mkdir q/.pc &&
+    printf "p0.diff\np2.diff\n" >q/.pc/applied-patches &&
+    stg import -S q/patches/series &&
+    stg series --applied --noprefix >applied.txt &&
+    cat >expected <<-\EOF &&
+	p0.diff
+	p2.diff
+	EOF
+    test_cmp expected applied.txt &&
+    stg series --unapplied --noprefix >unapplied.txt &&
+    echo rev.diff >expected &&
+    test_cmp expected unapplied.txt &&
+    test "$(cat r.txt)" = "3" &&
+    stg delete p0.diff p2.diff rev.diff
+'
+
 test_done
@@ -0,0 +1,151 @@
+#!/bin/sh
+
+test_description='Test migrating Guilt and TopGit stacks with stg import'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup Guilt patch stack' '
+    echo base >base.txt &&
+    git add base.txt &&
+    git commit -m "base" &&
+    git checkout -b guilt &&
+    echo a >a.txt && git add a.txt && git commit -m "first change" &&
+    echo b >b.txt && git add b.txt && git commit -m "second change" &&
+    mkdir -p .git/patches/guilt &&
+    cat >.git/patches/guilt/series <<-\EOF &&
+	first.patch
+	# a comment
+	second.patch
+	third.patch #+guard
+	fourth.patch
+	EOF
+    echo "$(git rev-parse HEAD~1):first.patch" >.git/patches/guilt/status &&
+    echo "$(git rev-parse HEAD):second.patch" >>.git/patches/guilt/status &&
+    cat >.git/patches/guilt/third.patch <<-\EOF &&
+	From: Other Author <other@example.com>
+	Subject: third change
+
+	Third body.
+	---
+	diff --git a/c.txt b/c.txt
+	new file mode 100644
+	--- /dev/null
+	+++ b/c.txt
+	@@ -0,0 +1 @@
+	+c
+	EOF
+    cat >.git/patches/guilt/fourth.patch <<-\EOF &&
+	fourth change
+
+	diff --git a/c.txt b/c.txt
+	--- a/c.txt
+	+++ b/c.txt
+	@@ -1 +1,2 @@
+	 c
+	+d
+	EOF
+    git checkout master
+'
+
+test_expect_success 'Migrate Guilt patch stack of another branch' '
+    stg import --from-guilt guilt &&
+    stg series -b guilt >series &&
+    cat >expected <<-\EOF &&
+	+ first.patch
+	> second.patch
+	- third.patch
+	- fourth.patch
+	EOF
+    test_cmp expected series &&
+    test "$(git rev-parse guilt)" = "$(stg id -b guilt second.patch)" &&
+    test "$(git log -1 --format="%an %s" $(stg id -b guilt third.patch))" = \
+        "Other Author third change" &&
+    test_path_is_missing a.txt
+'
+
+test_expect_success 'Push migrated Guilt patches' '
+    git checkout guilt &&
+    stg push -a &&
+    test_cmp c.txt - <<-\EOF &&
+	c
+	d
+	EOF
+    git checkout master
+'
+
+test_expect_success 'Refuse to migrate Guilt stack onto existing patches' '
+    command_error stg import --from-guilt guilt 2>err &&
+    grep "branch \`guilt\` already has StGit patches" err
+'
+
+test_expect_success 'Refuse to migrate mismatched Guilt status' '
+    git checkout -b guilt-bad guilt &&
+    mkdir -p .git/patches/guilt-bad &&
+    echo "second.patch" >.git/patches/guilt-bad/series &&
+    echo "$(git rev-parse HEAD~1):second.patch" >.git/patches/guilt-bad/status &&
+    command_error stg import --from-guilt 2>err &&
+    grep "Guilt patch \`second.patch\` is not commit" err &&
+    git checkout master
+'
+
+test_expect_success 'Setup TopGit topic branches' '
+    git checkout -b tg master &&
+    git update-ref refs/top-bases/t/one tg &&
+    git checkout -b t/one &&
+    echo tg >.topdeps &&
+    cat >.topmsg <<-\EOF &&
+	From: One Author <one@example.com>
+	Subject: [PATCH] t/one: first topic
+
+	One body.
+	EOF
+    echo one >one.txt &&
+    git add .topdeps .topmsg one.txt &&
+    git commit -m "one" &&
+    git update-ref refs/top-bases/t/two t/one &&
+    git checkout -b t/two &&
+    echo t/one >.topdeps &&
+    cat >.topmsg <<-\EOF &&
+	From: Two Author <two@example.com>
+	Subject: [PATCH] t/two: second topic
+	EOF
+    echo two >two.txt &&
+    git add .topdeps .topmsg two.txt &&
+    git commit -m "two" &&
+    git update-ref refs/top-bases/t/other master &&
+    git checkout -b t/other master &&
+    echo master >.topdeps &&
+    echo "Subject: [PATCH] t/other: other topic" >.topmsg &&
+    echo other >other.txt &&
+    git add .topdeps .topmsg other.txt &&
+    git commit -m "other" &&
+    git checkout tg
+'
+
+test_expect_success 'Migrate TopGit topic branches' '
+    stg import --from-topgit &&
+    stg series --noprefix >series &&
+    cat >expected <<-\EOF &&
+	one
+	two
+	EOF
+    test_cmp expected series &&
+    test_path_is_file one.txt &&
+    test_path_is_file two.txt &&
+    test_path_is_missing other.txt &&
+    test_path_is_missing .topdeps &&
+    test_path_is_missing .topmsg &&
+    test "$(git log -1 --format="%an %s" $(stg id one))" = \
+        "One Author t/one: first topic" &&
+    test "$(git log -1 --format=%b $(stg id one))" = "One body." &&
+    test "$(git log -1 --format="%an %s" $(stg id two))" = \
+        "Two Author t/two: second topic"
+'
+
+test_expect_success 'Migrate TopGit without topics based on branch' '
+    git checkout -b no-topics master &&
+    command_error stg import --from-topgit 2>err &&
+    grep "no TopGit topic branches based on \`no-topics\` found" err
+'
+
+test_done
@@ -0,0 +1,153 @@
+#!/bin/sh
+
+test_description='Test synchronizing a quilt patches directory with stg quilt sync'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stack' '
+    echo base >file.txt &&
+    git add file.txt &&
+    git commit -m "base" &&
+    stg new -m "patch a" a &&
+    echo a >>file.txt &&
+    stg refresh &&
+    stg new -m "patch b" b &&
+    echo b >>file.txt &&
+    stg refresh
+'
+
+test_expect_success 'Export stack to new quilt directory' '
+    stg quilt sync &&
+    cat >expected <<-\EOF &&
+	a.patch
+	b.patch
+	EOF
+    test_cmp expected patches/series &&
+    grep "^+a$" patches/a.patch &&
+    grep "^+b$" patches/b.patch
+'
+
+test_expect_success 'Sync without changes' '
+    cp patches/a.patch a.patch.orig &&
+    stg quilt sync 2>err &&
+    test_must_be_empty err &&
+    test_cmp a.patch.orig patches/a.patch &&
+    test_cmp expected patches/series
+'
+
+test_expect_success 'Update patch from changed quilt patch file' '
+    sed -e "s/^+b$/+B/" patches/b.patch >b.patch.new &&
+    mv b.patch.new patches/b.patch &&
+    stg quilt sync &&
+    test_cmp file.txt - <<-\EOF &&
+	base
+	a
+	B
+	EOF
+    test "$(stg series --noprefix --applied | tr "\n" " ")" = "a b "
+'
+
+test_expect_success 'Export patch changed in StGit' '
+    stg edit -m "patch a edited" a &&
+    stg quilt sync &&
+    grep "^patch a edited$" patches/a.patch &&
+    grep "^+B$" patches/b.patch
+'
+
+test_expect_success 'Import new quilt patch as unapplied' '
+    cat >patches/c.diff <<-\EOF &&
+	patch c
+
+	--- file.txt
+	+++ file.txt
+	@@ -1,3 +1,4 @@
+	 base
+	 a
+	 B
+	+c
+	EOF
+    echo "c.diff -p0" >>patches/series &&
+    stg quilt sync &&
+    test "$(stg series --noprefix --unapplied)" = "c" &&
+    test "$(stg top)" = "b" &&
+    grep "^c.diff -p0$" patches/series &&
+    stg push c &&
+    test "$(tail -n 1 file.txt)" = "c"
+'
+
+test_expect_success 'Export new StGit patch' '
+    stg new -m "patch d" d &&
+    echo d >d.txt &&
+    git add d.txt &&
+    stg refresh &&
+    stg quilt sync &&
+    cat >expected <<-\EOF &&
+	a.patch
+	b.patch
+	c.diff -p0
+	d.patch
+	EOF
+    test_cmp expected patches/series &&
+    grep "^+d$" patches/d.patch
+'
+
+test_expect_success 'Delete patch removed from quilt series' '
+    grep -v "^c.diff" patches/series >series.new &&
+    mv series.new patches/series &&
+    stg quilt sync &&
+    test "$(stg series --noprefix | tr "\n" " ")" = "a b d " &&
+    test_path_is_file patches/c.diff
+'
+
+test_expect_success 'Remove deleted StGit patch from quilt series' '
+    stg delete d &&
+    test_path_is_missing d.txt &&
+    stg quilt sync &&
+    cat >expected <<-\EOF &&
+	a.patch
+	b.patch
+	EOF
+    test_cmp expected patches/series &&
+    test_path_is_file patches/d.patch
+'
+
+test_expect_success 'Report patch changed on both sides' '
+    stg edit -m "patch b edited" b &&
+    sed -e "s/^+B$/+bb/" patches/b.patch >b.patch.new &&
+    mv b.patch.new patches/b.patch &&
+    cp patches/b.patch b.patch.quilt &&
+    command_error stg quilt sync 2>err &&
+    grep "\`b.patch\` changed in both quilt and StGit" err &&
+    test_cmp b.patch.quilt patches/b.patch &&
+    test "$(tail -n 1 file.txt)" = "B"
+'
+
+test_expect_success 'Resolve conflict in favor of quilt' '
+    stg quilt sync --prefer quilt &&
+    test "$(tail -n 1 file.txt)" = "bb" &&
+    test "$(git log -1 --format=%s $(stg id b))" = "patch b"
+'
+
+test_expect_success 'Resolve conflict in favor of StGit' '
+    stg edit -m "patch b edited again" b &&
+    echo "extra" >>patches/b.patch &&
+    stg quilt sync --prefer stgit &&
+    grep "^patch b edited again$" patches/b.patch &&
+    ! grep "^extra$" patches/b.patch
+'
+
+test_expect_success 'Sync without record of previous sync' '
+    git update-ref -d refs/stgit/quilt/master &&
+    cp patches/a.patch a.patch.orig &&
+    stg quilt sync &&
+    test_cmp a.patch.orig patches/a.patch &&
+    git rev-parse --verify refs/stgit/quilt/master
+'
+
+test_expect_success 'Sync requires clean worktree' '
+    echo dirty >>file.txt &&
+    command_error stg quilt sync &&
+    git checkout file.txt
+'
+
+test_done
@@ -80,4 +80,82 @@ test_expect_success 'With cover letter' '
     rmdir out
 '
 
+test_expect_success 'Embed stack range-diff in cover letter' '
+    git rev-parse refs/stacks/master >state-before &&
+    stg edit --message "p2 reworded" p2 &&
+    stg email format -o out --all --cover-letter -v2 \
+        --stack-range-diff="$(cat state-before)" &&
+    test_path_exists out/v2-0000-cover-letter.patch &&
+    grep -e "^Range-diff against v1:$" out/v2-0000-cover-letter.patch &&
+    grep -e "^1:  [0-9a-f]* = 1:  [0-9a-f]* p1$" out/v2-0000-cover-letter.patch &&
+    grep -e "^2:  [0-9a-f]* ! 2:  [0-9a-f]* p2$" out/v2-0000-cover-letter.patch &&
+    grep -e "^    +    p2 reworded$" out/v2-0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'Embed stack range-diff in single patch' '
+    stg email format -o out --stack-range-diff="$(cat state-before)" p1 &&
+    test_path_exists out/0001-p1.patch &&
+    sed -n "/^---$/,/^diff --git/p" out/0001-p1.patch >commentary &&
+    grep -e "^Range-diff:$" commentary &&
+    grep -e "^1:  [0-9a-f]* = 1:  [0-9a-f]* p1$" commentary &&
+    grep -e "^2:  [0-9a-f]* < -:  ------- p2$" commentary &&
+    rm -r out
+'
+
+test_expect_success 'Stack range-diff requires cover letter for multiple patches' '
+    command_error stg email format -o out --stack-range-diff="$(cat state-before)" p1 p2 2>err &&
+    grep -e "requires \`--cover-letter\` or a single patch" err &&
+    rm -r out
+'
+
+test_expect_success 'Stack range-diff conflicts with range-diff' '
+    general_error stg email format -o out --all \
+        --stack-range-diff=master --range-diff=HEAD 2>err &&
+    grep -e "cannot be used with" err
+'
+
+test_expect_success 'Fill in cover letter from template' '
+    cat >cover.tmpl <<-\EOF &&
+	{# Cover letter template #}
+	Series of {{ total }} patches on {{ branch }}
+
+	{% for patch in patches %}
+	{{ patch.index }}. {{ patch.name }} by {{ patch.authemail }}
+	{% endfor %}
+	EOF
+    stg email format -o out --cover-template cover.tmpl p1 p2 &&
+    cover=out/0000-cover-letter.patch &&
+    grep -e "^Subject: \[PATCH 0/2\] Series of 2 patches on master$" $cover &&
+    grep -e "^1. p1 by author@example.com$" $cover &&
+    grep -e "^2. p2 by author@example.com$" $cover &&
+    ! grep -e "HERE \*\*\*" $cover &&
+    rm -r out
+'
+
+test_expect_success 'Fill in cover letter from covermail.tmpl' '
+    test_when_finished "rm .git/covermail.tmpl" &&
+    echo "Default cover subject" >.git/covermail.tmpl &&
+    stg email format -o out --cover-letter p1 p2 &&
+    grep -e "^Subject: \[PATCH 0/2\] Default cover subject$" out/0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'Legacy covermail.tmpl with braces is not parsed' '
+    test_when_finished "rm .git/covermail.tmpl" &&
+    cat >.git/covermail.tmpl <<-\EOF &&
+	Cover for %(branch)s
+	Keep {{ this }} and {% that %} as is
+	EOF
+    stg email format -o out --cover-letter p1 p2 &&
+    grep -e "^Subject: \[PATCH 0/2\] Cover for master$" out/0000-cover-letter.patch &&
+    grep -e "^Keep {{ this }} and {% that %} as is$" out/0000-cover-letter.patch &&
+    rm -r out
+'
+
+test_expect_success 'Cover letter template conflicts with stdout' '
+    command_error stg email format -G --stdout --cover-template cover.tmpl p1 p2 2>err &&
+    grep -e "cannot fill in the cover letter" err
+'
+
 test_done
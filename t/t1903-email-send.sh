@@ -61,4 +61,80 @@ test_expect_success GITSENDEMAIL 'Send patches from other branch' '
     test_cmp expected subjects
 '
 
+test_expect_success 'Setup fake sendmail' '
+    write_script fake-sendmail <<-\EOF
+	cat >>sent-mail
+	EOF
+'
+
+test_expect_success GITSENDEMAIL 'Dry run does not record sent series' '
+    stg email send --dry-run --to someone@example.com --all >out &&
+    test_must_fail git rev-parse --verify -q refs/stgit/email/master
+'
+
+test_expect_success GITSENDEMAIL 'Send formatted series records history' '
+    stg email format -o v1 --cover-letter --thread --all &&
+    stg email send --to someone@example.com --confirm=never \
+        -G --smtp-server="$(pwd)/fake-sendmail" v1 &&
+    git show refs/stgit/email/master:series.json >series.json &&
+    grep -e "\"version\": 1" series.json &&
+    grep -e "\"sent\": true" series.json &&
+    grep -e "\"name\": \"p4\"" series.json &&
+    cover_id=$(sed -n "s/^Message-Id: <\(.*\)>$/\1/p" v1/0000-cover-letter.patch) &&
+    grep -e "\"cover_message_id\": \"$cover_id\"" series.json
+'
+
+test_expect_success GITSENDEMAIL 'Reroll formats next version in reply to previous' '
+    stg goto p2 &&
+    echo "more" >>extra.txt &&
+    stg add extra.txt &&
+    stg refresh &&
+    stg goto p4 &&
+    stg email format -o v2 --cover-letter --all --reroll &&
+    test_path_exists v2/v2-0000-cover-letter.patch &&
+    cover_id=$(sed -n "s/^Message-Id: \(.*\)$/\1/p" v1/0000-cover-letter.patch) &&
+    grep -e "^In-Reply-To: $cover_id$" v2/v2-0000-cover-letter.patch &&
+    grep -e "^Subject: \[PATCH v2 0/4\]" v2/v2-0000-cover-letter.patch &&
+    grep -e "^Range-diff against v1:$" v2/v2-0000-cover-letter.patch &&
+    grep -e "^2:  [0-9a-f]* ! 2:  [0-9a-f]* p2$" v2/v2-0000-cover-letter.patch &&
+    git show refs/stgit/email/master:series.json >series.json &&
+    grep -e "\"version\": 2" series.json &&
+    grep -e "\"sent\": false" series.json
+'
+
+test_expect_success GITSENDEMAIL 'Reroll with interdiff' '
+    stg email format -o v2i --cover-letter --all --reroll=interdiff &&
+    grep -e "^Interdiff against v1:$" v2i/v2-0000-cover-letter.patch &&
+    ! grep -e "^Range-diff" v2i/v2-0000-cover-letter.patch
+'
+
+test_expect_success GITSENDEMAIL 'Explicit reroll count overrides next version' '
+    stg email format -o v5 --cover-letter --all --reroll -v5 &&
+    test_path_exists v5/v5-0000-cover-letter.patch &&
+    grep -e "^Range-diff against v4:$" v5/v5-0000-cover-letter.patch
+'
+
+test_expect_success GITSENDEMAIL 'Send by patch name records Message-IDs and next version' '
+    rm -f sent-mail &&
+    stg email send --to someone@example.com --confirm=never \
+        -G --smtp-server="$(pwd)/fake-sendmail" --all &&
+    grep -e "^Subject: \[PATCH v2 1/4\]" sent-mail &&
+    git show refs/stgit/email/master:series.json >series.json &&
+    grep -e "\"version\": 2" series.json &&
+    grep -e "\"sent\": true" series.json &&
+    sed -n "s/^Message-Id: <\(.*\)>$/\1/p" sent-mail >sent-ids &&
+    test_line_count = 4 sent-ids &&
+    while read -r id
+    do
+        grep -e "\"message_id\": \"$id\"" series.json || return 1
+    done <sent-ids
+'
+
+test_expect_success GITSENDEMAIL 'Renaming branch keeps series history' '
+    stg branch --rename master renamed &&
+    git rev-parse --verify -q refs/stgit/email/renamed &&
+    test_must_fail git rev-parse --verify -q refs/stgit/email/master &&
+    stg branch --rename renamed master
+'
+
 test_done
@@ -0,0 +1,102 @@
+#!/bin/sh
+
+test_description="Test 'stg email collect-trailers'"
+
+. ./test-lib.sh
+
+reply_count=0
+
+message_id () {
+    sed -n "s/^Message-Id: \(.*\)$/\1/p" "$1"
+}
+
+write_reply () {
+    in_reply_to="$1" &&
+    subject="$2" &&
+    shift 2 &&
+    echo "From reviewer@example.com Mon Sep 17 00:00:00 2001" &&
+    echo "From: Reviewer <reviewer@example.com>" &&
+    echo "Subject: $subject" &&
+    if test -n "$in_reply_to"
+    then
+        echo "In-Reply-To: $in_reply_to" &&
+        echo "References: $in_reply_to"
+    fi &&
+    reply_count=$((reply_count + 1)) &&
+    echo "Message-Id: <reply-$reply_count@example.com>" &&
+    echo &&
+    for line in "$@"
+    do
+        echo "$line"
+    done &&
+    echo
+}
+
+test_expect_success 'Setup StGit stack and format series' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    stg email format -o out --cover-letter --thread --all
+'
+
+test_expect_success 'Dry run shows trailers without adding them' '
+    write_reply "$(message_id out/0002-p2.patch)" "Re: [PATCH 2/3] p2" \
+        "> quoted line" \
+        "Reviewed-by: Reviewer <reviewer@example.com>" >replies.mbox &&
+    stg email collect-trailers --dry-run replies.mbox >out.txt &&
+    cat >expected <<-\EOF &&
+	p2: Reviewed-by: Reviewer <reviewer@example.com>
+	EOF
+    test_cmp expected out.txt &&
+    ! git log -1 --format=%B $(stg id p2) | grep "Reviewed-by"
+'
+
+test_expect_success 'Add trailer from reply to patch' '
+    stg email collect-trailers replies.mbox &&
+    git log -1 --format=%B $(stg id p2) | grep -e "^Reviewed-by: Reviewer <reviewer@example.com>$" &&
+    ! git log -1 --format=%B $(stg id p1) | grep "Reviewed-by" &&
+    ! git log -1 --format=%B $(stg id p3) | grep "Reviewed-by" &&
+    test "$(stg top)" = "p3" &&
+    test "$(stg series --applied --count)" = "3"
+'
+
+test_expect_success 'Existing trailers are not added again' '
+    stg email collect-trailers replies.mbox >out.txt &&
+    test_must_be_empty out.txt &&
+    test "$(git log -1 --format=%B $(stg id p2) | grep -c "Reviewed-by")" = "1"
+'
+
+test_expect_success 'Reply to cover letter applies to all patches' '
+    write_reply "$(message_id out/0000-cover-letter.patch)" \
+        "Re: [PATCH 0/3] *** SUBJECT HERE ***" \
+        "For the series:" \
+        "" \
+        "Acked-by: Maintainer <maintainer@example.com>" >cover.mbox &&
+    stg email collect-trailers cover.mbox &&
+    for p in p1 p2 p3
+    do
+        git log -1 --format=%B $(stg id $p) |
+        grep -e "^Acked-by: Maintainer <maintainer@example.com>$" || return 1
+    done
+'
+
+test_expect_success 'Match reply by subject' '
+    write_reply "" "Re: [PATCH v2 1/3] p1" \
+        "Tested-by: Tester <tester@example.com>" >subject.mbox &&
+    stg email collect-trailers subject.mbox >out.txt &&
+    grep "^p[0-9]: " out.txt >trailers.txt &&
+    cat >expected <<-\EOF &&
+	p1: Tested-by: Tester <tester@example.com>
+	EOF
+    test_cmp expected trailers.txt &&
+    git log -1 --format=%B $(stg id p1) | grep -e "^Tested-by: Tester <tester@example.com>$"
+'
+
+test_expect_success 'Quoted trailers are ignored' '
+    write_reply "$(message_id out/0003-p3.patch)" "Re: [PATCH 3/3] p3" \
+        "> Reviewed-by: Someone <someone@example.com>" \
+        "Thanks" >quoted.mbox &&
+    stg email collect-trailers quoted.mbox >out.txt &&
+    test_must_be_empty out.txt
+'
+
+test_done
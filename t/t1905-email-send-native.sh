@@ -0,0 +1,232 @@
+#!/bin/sh
+
+test_description="Test 'stg email send --native'"
+
+. ./test-lib.sh
+
+test_expect_success 'Setup StGit stack and fake sendmail' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    write_script fake-sendmail <<-\EOF &&
+	n=$(($(ls mail-*.eml 2>/dev/null | wc -l) + 1))
+	echo "$@" >"mail-$n.args"
+	cat >"mail-$n.eml"
+	EOF
+    git config sendemail.sendmailCmd "\"$(pwd)/fake-sendmail\""
+'
+
+test_expect_success 'Dry run does not invoke sendmail' '
+    stg email send --native --dry-run --to someone@example.com --all >out &&
+    grep "^Dry-OK. Log says:" out &&
+    grep "^RCPT TO:<someone@example.com>" out &&
+    test_path_is_missing mail-1.eml &&
+    test_must_fail git rev-parse --verify refs/stgit/email/master
+'
+
+test_expect_success 'Send all applied patches' '
+    stg email send --native --to someone@example.com \
+        --cc "Other Person <other@example.com>" --bcc hidden@example.com --all >out &&
+    grep "^OK. Log says:" out &&
+    test_path_exists mail-3.eml &&
+    test_path_is_missing mail-4.eml &&
+    for n in 1 2 3
+    do
+        echo "-i someone@example.com other@example.com hidden@example.com" >expected &&
+        test_cmp expected mail-$n.args || return 1
+    done &&
+    grep "^Subject: " mail-1.eml mail-2.eml mail-3.eml >subjects &&
+    cat >expected <<-\EOF &&
+	mail-1.eml:Subject: [PATCH 1/3] p1
+	mail-2.eml:Subject: [PATCH 2/3] p2
+	mail-3.eml:Subject: [PATCH 3/3] p3
+	EOF
+    test_cmp expected subjects &&
+    grep "^To: someone@example.com$" mail-1.eml &&
+    grep "^Cc: Other Person <other@example.com>$" mail-1.eml &&
+    ! grep -i "^Bcc:" mail-1.eml
+'
+
+test_expect_success 'Sender is the committer and the author is kept in the body' '
+    grep "^From: =?UTF-8?q?C=20=C3=93=20Mitter?= <committer@example.com>$" mail-1.eml &&
+    grep -A2 "^$" mail-1.eml | grep "^From: A Ú Thor <author@example.com>$" &&
+    grep "^Content-Type: text/plain; charset=UTF-8$" mail-1.eml
+'
+
+test_expect_success 'Emails are threaded to the first email' '
+    sed -n -e "s/^Message-Id: //p" mail-1.eml >first-id &&
+    test_file_not_empty first-id &&
+    ! grep "^In-Reply-To:" mail-1.eml &&
+    for n in 2 3
+    do
+        sed -n -e "s/^In-Reply-To: //p" mail-$n.eml >in-reply-to &&
+        test_cmp first-id in-reply-to || return 1
+    done
+'
+
+test_expect_success 'Sent series is recorded' '
+    git cat-file -p refs/stgit/email/master:series.json >series.json &&
+    grep "\"sent\": true" series.json &&
+    sed -n -e "s/^Message-Id: <\(.*\)>$/\1/p" mail-2.eml >p2-id &&
+    grep "\"message_id\": \"$(cat p2-id)\"" series.json
+'
+
+test_expect_success 'Send with explicit sender without threading' '
+    rm -f mail-* &&
+    stg email send --native --no-thread --from "A Ú Thor <author@example.com>" \
+        --to someone@example.com --in-reply-to "<orig@example.com>" --quiet p2..p3 >out &&
+    cat >expected <<-\EOF &&
+	Sent [PATCH v2 1/2] p2
+	Sent [PATCH v2 2/2] p3
+	EOF
+    test_cmp expected out &&
+    grep "^From: =?UTF-8?q?A=20=C3=9A=20Thor?= <author@example.com>$" mail-1.eml &&
+    ! grep -A2 "^$" mail-1.eml | grep "^From:" &&
+    grep "^In-Reply-To: <orig@example.com>$" mail-1.eml &&
+    grep "^In-Reply-To: <orig@example.com>$" mail-2.eml
+'
+
+test_expect_success 'Send formatted email files' '
+    rm -f mail-* &&
+    stg email format --thread -o outdir --all &&
+    stg email send --native --to someone@example.com outdir >out &&
+    test_path_exists mail-3.eml &&
+    sed -n -e "s/^Message-Id: //p" outdir/v3-0001-p1.patch >expected &&
+    sed -n -e "s/^Message-Id: //p" mail-1.eml >sent-id &&
+    test_cmp expected sent-id
+'
+
+test_expect_success 'Native sending is enabled by configuration' '
+    rm -f mail-* &&
+    test_config stgit.email.native true &&
+    stg email send --to someone@example.com p1 &&
+    test_path_exists mail-1.eml
+'
+
+test_expect_success 'Options requiring git send-email are rejected' '
+    command_error stg email send --native --compose --to someone@example.com p1 2>err &&
+    grep "requires \`git send-email\`" err &&
+    command_error stg email send --native -G --suppress-cc=all --to someone@example.com p1 2>err &&
+    grep "\`--git-opt\` requires \`git send-email\`" err
+'
+
+test_expect_success 'Recipients are required' '
+    command_error stg email send --native p1 2>err &&
+    grep "no recipients" err
+'
+
+test_expect_success 'Failing sendmail command is reported' '
+    test_config sendemail.sendmailCmd false &&
+    command_error stg email send --native --to someone@example.com p1 2>err &&
+    grep "\`false\` failed" err
+'
+
+test_expect_success 'Invalid SMTP encryption is reported' '
+    test_unconfig sendemail.sendmailCmd &&
+    test_config sendemail.smtpServer smtp.example.com &&
+    test_config sendemail.smtpEncryption bogus &&
+    command_error stg email send --native --to someone@example.com p1 2>err &&
+    grep "invalid \`sendemail.smtpEncryption\` value \`bogus\`" err
+'
+
+test_lazy_prereq EMAIL_SMTP '
+    ! env GIT_CONFIG_COUNT=3 \
+        GIT_CONFIG_KEY_0=sendemail.sendmailCmd GIT_CONFIG_VALUE_0= \
+        GIT_CONFIG_KEY_1=sendemail.smtpServer GIT_CONFIG_VALUE_1=127.0.0.1 \
+        GIT_CONFIG_KEY_2=sendemail.smtpServerPort GIT_CONFIG_VALUE_2=1 \
+        stg email send --native --to someone@example.com p1 2>err &&
+    ! grep "not built with support for SMTP" err
+'
+
+test_expect_success EMAIL_SMTP 'Setup fake SMTP server' '
+    cat >fake-smtp.pl <<-\EOF &&
+	use strict;
+	use IO::Socket::INET;
+
+	my $server = IO::Socket::INET->new(
+	    LocalAddr => "127.0.0.1",
+	    LocalPort => 0,
+	    Listen => 5,
+	    ReuseAddr => 1,
+	) or die "cannot listen: $!";
+	open(my $pid, ">", "smtp-pid") or die;
+	print $pid "$$\n";
+	close $pid;
+	open(my $port, ">", "smtp-port.tmp") or die;
+	print $port $server->sockport, "\n";
+	close $port;
+	rename "smtp-port.tmp", "smtp-port";
+
+	my $n = 0;
+	while (my $client = $server->accept) {
+	    $client->autoflush(1);
+	    print $client "220 localhost fake SMTP\r\n";
+	    while (my $line = <$client>) {
+	        open(my $log, ">>", "smtp.log") or die;
+	        print $log $line =~ s/\r\n$/\n/r;
+	        close $log;
+	        if ($line =~ /^EHLO/i) {
+	            print $client "250-localhost\r\n250-8BITMIME\r\n250 AUTH PLAIN\r\n";
+	        } elsif ($line =~ /^AUTH/i) {
+	            print $client "235 authenticated\r\n";
+	        } elsif ($line =~ /^DATA/i) {
+	            print $client "354 go ahead\r\n";
+	            $n++;
+	            open(my $mail, ">", "smtp-$n.eml") or die;
+	            while (my $data = <$client>) {
+	                last if $data eq ".\r\n";
+	                $data =~ s/^\.\././;
+	                $data =~ s/\r\n$/\n/;
+	                print $mail $data;
+	            }
+	            close $mail;
+	            print $client "250 queued\r\n";
+	        } elsif ($line =~ /^QUIT/i) {
+	            print $client "221 bye\r\n";
+	            last;
+	        } else {
+	            print $client "250 ok\r\n";
+	        }
+	    }
+	    close $client;
+	}
+	EOF
+    { "$PERL_PATH" fake-smtp.pl & } &&
+    for i in $(test_seq 50)
+    do
+        test -f smtp-port || sleep 0.1 || return 1
+    done &&
+    test_file_not_empty smtp-port &&
+    test_atexit "kill $(cat smtp-pid)" &&
+    git config sendemail.smtpServer 127.0.0.1 &&
+    git config sendemail.smtpServerPort "$(cat smtp-port)" &&
+    git update-ref -d refs/stgit/email/master
+'
+
+test_expect_success EMAIL_SMTP 'Send patches to an SMTP server' '
+    rm -f smtp.log &&
+    stg email send --native --to someone@example.com --cc other@example.com \
+        --quiet p1..p2 >out &&
+    cat >expected <<-\EOF &&
+	Sent [PATCH 1/2] p1
+	Sent [PATCH 2/2] p2
+	EOF
+    test_cmp expected out &&
+    test_path_is_missing smtp-3.eml &&
+    grep "^Subject: \[PATCH 1/2\] p1$" smtp-1.eml &&
+    grep "^Subject: \[PATCH 2/2\] p2$" smtp-2.eml &&
+    grep "^MAIL FROM:<committer@example.com>" smtp.log &&
+    grep "^RCPT TO:<someone@example.com>" smtp.log &&
+    grep "^RCPT TO:<other@example.com>" smtp.log &&
+    ! grep "^AUTH" smtp.log
+'
+
+test_expect_success EMAIL_SMTP 'Authenticate with the SMTP server' '
+    rm -f smtp.log &&
+    test_config sendemail.smtpUser someone &&
+    test_config sendemail.smtpPass secret &&
+    stg email send --native --to someone@example.com --quiet p3 &&
+    grep "^Subject: \[PATCH v2\] p3$" smtp-3.eml &&
+    grep "^AUTH PLAIN" smtp.log
+'
+
+test_done
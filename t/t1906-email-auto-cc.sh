@@ -0,0 +1,122 @@
+#!/bin/sh
+
+test_description="Test 'stg email format/send --auto-cc'"
+
+. ./test-lib.sh
+
+test_expect_success 'Setup MAINTAINERS and StGit stack' '
+    cat >MAINTAINERS <<-\EOF &&
+	FOO
+	M: Foo Maintainer <foo@example.com>
+	L: foo-list@example.com (open list)
+	F: foo/
+
+	BAR
+	R: Bar Reviewer <bar@example.com>
+	F: bar/*.c
+	X: bar/skip.c
+	EOF
+    mkdir foo bar &&
+    printf "one\ntwo\nthree\n" >foo/a.txt &&
+    echo b >bar/b.c &&
+    echo skip >bar/skip.c &&
+    git add MAINTAINERS foo bar &&
+    GIT_AUTHOR_NAME="Old Author" GIT_AUTHOR_EMAIL=old@example.com \
+        git commit -m "base" &&
+    stg init &&
+    cat >msg1 <<-\EOF &&
+	p1
+
+	Reported-by: Rep Orter <rep@example.com>
+	Signed-off-by: A U Thor <author@example.com>
+	EOF
+    stg new -f msg1 &&
+    printf "one\nTWO\nthree\nfour\n" >foo/a.txt &&
+    stg refresh &&
+    cat >msg2 <<-\EOF &&
+	p2
+
+	Cc: Some One <someone@example.com>
+	Signed-off-by: A U Thor <author@example.com>
+	EOF
+    stg new -f msg2 &&
+    echo b2 >>bar/b.c &&
+    echo skip2 >>bar/skip.c &&
+    stg refresh
+'
+
+test_expect_success 'Explain recipients from trailers and MAINTAINERS' '
+    stg email format --auto-cc --explain --all >out &&
+    cat >expected <<-\EOF &&
+	Rep Orter <rep@example.com>
+	    Reported-by trailer in p1
+	A U Thor <author@example.com>
+	    Signed-off-by trailer in p1
+	    Signed-off-by trailer in p2
+	Some One <someone@example.com>
+	    Cc trailer in p2
+	Foo Maintainer <foo@example.com>
+	    maintainer of foo/a.txt changed in p1 (FOO)
+	foo-list@example.com
+	    mailing list of foo/a.txt changed in p1 (FOO)
+	Bar Reviewer <bar@example.com>
+	    reviewer of bar/b.c changed in p2 (BAR)
+	EOF
+    test_cmp expected out &&
+    test_path_is_missing 0001-p1.patch
+'
+
+test_expect_success 'Explain recipients from blame' '
+    stg email format --auto-cc=blame --explain p1 >out &&
+    cat >expected <<-\EOF &&
+	Old Author <old@example.com>
+	    author of lines in foo/a.txt changed by p1
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Given addresses are not repeated' '
+    stg email format --auto-cc=trailers,maintainers --explain \
+        --cc "Foo <FOO@example.com>" --to author@example.com p1 >out &&
+    cat >expected <<-\EOF &&
+	Rep Orter <rep@example.com>
+	    Reported-by trailer in p1
+	foo-list@example.com
+	    mailing list of foo/a.txt changed in p1 (FOO)
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Format with automatic Cc' '
+    stg email format --auto-cc -o outdir --all &&
+    for address in rep@example.com author@example.com someone@example.com \
+        foo@example.com foo-list@example.com bar@example.com
+    do
+        grep "$address" outdir/0001-p1.patch >/dev/null &&
+        grep "$address" outdir/0002-p2.patch >/dev/null || return 1
+    done
+'
+
+test_expect_success 'Send natively with automatic Cc' '
+    write_script fake-sendmail <<-\EOF &&
+	echo "$@" >sendmail.args
+	cat >/dev/null
+	EOF
+    test_config sendemail.sendmailCmd "\"$(pwd)/fake-sendmail\"" &&
+    stg email send --native --to someone@example.com --auto-cc=trailers p2 &&
+    echo "-i someone@example.com author@example.com" >expected &&
+    test_cmp expected sendmail.args
+'
+
+test_expect_success 'Explain requires auto-cc' '
+    general_error stg email format --explain --all 2>err &&
+    grep -e "--auto-cc" err
+'
+
+test_expect_success 'Configured MAINTAINERS path must exist' '
+    test_config stgit.email.maintainers OWNERS &&
+    command_error stg email format --auto-cc --explain --all 2>err &&
+    grep "maintainers file \`OWNERS\` not found" err
+'
+
+test_done
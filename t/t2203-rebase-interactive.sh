@@ -277,4 +277,173 @@ test_expect_success 'No patches exits early' '
     stg rebase --interactive
 '
 
+test_expect_success 'Reorder patches' '
+    stg new -m p0 &&
+    stg new -m p1 &&
+    stg new -m p2 &&
+    stg new -m p3 &&
+    stg pop p3 &&
+    write_script fake-editor <<-\EOF &&
+	printf "keep p1\nkeep p0\n# --- APPLY_LINE ---\nkeep p3\nkeep p2\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(echo $(stg series --applied --noprefix))" = "p1 p0" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p3 p2"
+'
+
+test_expect_success 'Rename and unapply patches' '
+    write_script fake-editor <<-\EOF &&
+	printf "rename p1 q1\nunapply p0\nkeep p2\n# --- APPLY_LINE ---\nkeep p3\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(echo $(stg series --applied --noprefix))" = "q1 p2" &&
+    test "$(echo $(stg series --unapplied --noprefix))" = "p0 p3"
+'
+
+test_expect_success 'Reword a patch' '
+    write_script fake-editor <<-\EOF &&
+	case "$(basename "$1")" in
+	.stgit-rebase-interactive.txt)
+	    printf "keep q1\nreword p2\n" >"$1";;
+	*)
+	    sed "s/^p2$/p2 reworded/" "$1" >"$1".tmp && mv "$1".tmp "$1";;
+	esac
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(echo $(stg series --applied --noprefix))" = "q1 p2" &&
+    test "$(git log -1 --format=%s)" = "p2 reworded"
+'
+
+test_expect_success 'Invalid instructions reopen the editor' '
+    write_script fake-editor <<-\EOF &&
+	if grep -q "^# ERROR: unknown patch name .not-a-patch. on line 2" "$1"
+	then
+	    printf "keep q1\nkeep p2\n" >"$1"
+	else
+	    printf "keep q1\nkeep not-a-patch\n" >"$1"
+	fi
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(echo $(stg series --applied --noprefix))" = "q1 p2"
+'
+
+test_expect_success 'Invalid rename is rejected' '
+    write_script fake-editor <<-\EOF &&
+	printf "rename q1 p2\nkeep p2\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    command_error stg rebase --interactive 2>err &&
+    grep -e "cannot rename .q1. to .p2. on line 1: patch .p2. already exists" err
+'
+
+test_expect_success 'Exec runs after pushing preceding patches' '
+    write_script fake-editor <<-\EOF &&
+	printf "keep q1\nexec stg top >exec-out\nkeep p2\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(cat exec-out)" = "q1" &&
+    test "$(echo $(stg series --applied --noprefix))" = "q1 p2"
+'
+
+test_expect_success 'Failed exec stops the rebase' '
+    write_script fake-editor <<-\EOF &&
+	printf "keep q1\nexec test -f exec-ok\nkeep p2\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    command_error stg rebase --interactive 2>err &&
+    grep -e "exec .test -f exec-ok. failed" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "q1" &&
+    command_error stg rebase --continue 2>err &&
+    grep -e "exec .test -f exec-ok. failed" err &&
+    test "$(echo $(stg series --applied --noprefix))" = "q1" &&
+    touch exec-ok &&
+    stg rebase --continue &&
+    test "$(echo $(stg series --applied --noprefix))" = "q1 p2"
+'
+
+test_expect_success 'Continue without a stopped rebase' '
+    command_error stg rebase --continue 2>err &&
+    grep -e "no interactive rebase in progress" err
+'
+
+test_expect_success 'Break and continue' '
+    write_script fake-editor <<-\EOF &&
+	printf "keep q1\nbreak\nkeep p2\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(echo $(stg series --applied --noprefix))" = "q1" &&
+    stg new -m extra &&
+    stg rebase --continue &&
+    test "$(echo $(stg series --applied --noprefix))" = "q1 extra p2"
+'
+
+test_expect_success 'Split a patch' '
+    stg delete $(stg series --all --noprefix --no-description) &&
+    echo a >a.txt &&
+    echo b >b.txt &&
+    stg add a.txt b.txt &&
+    stg new -m both &&
+    stg refresh &&
+    stg new -m top &&
+    write_script fake-editor <<-\EOF &&
+	printf "split both\nkeep top\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(echo $(stg series --applied --noprefix))" = "both" &&
+    test "$(stg files both)" = "" &&
+    stg refresh -- a.txt &&
+    stg new -m b-only &&
+    stg refresh &&
+    stg rebase --continue &&
+    test "$(echo $(stg series --applied --noprefix))" = "both b-only top" &&
+    test "$(stg files both)" = "A a.txt" &&
+    test "$(stg files b-only)" = "A b.txt" &&
+    git diff-index --quiet HEAD
+'
+
+test_expect_success 'Delete merged patches after break and continue' '
+    stg delete $(stg series --all --noprefix --no-description) &&
+    git branch merged-upstream &&
+    for p in m1 m2
+    do
+        echo $p >$p.txt &&
+        stg add $p.txt &&
+        stg new -m $p &&
+        stg refresh || return 1
+    done &&
+    git checkout merged-upstream &&
+    echo m2 >m2.txt &&
+    git add m2.txt &&
+    git commit -m "m2 upstream" &&
+    git checkout master &&
+    write_script fake-editor <<-\EOF &&
+	printf "keep m1\nbreak\nkeep m2\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive --delete-merged merged-upstream &&
+    test "$(echo $(stg series --applied --noprefix))" = "m1" &&
+    stg rebase --continue &&
+    test "$(echo $(stg series --all --noprefix))" = "m1" &&
+    git log -1 --format=%B refs/stacks/master >log &&
+    grep -e "^m2: merged as $(git rev-parse merged-upstream)" log &&
+    test_path_is_missing .git/stgit-rebase-upstream
+'
+
 test_done
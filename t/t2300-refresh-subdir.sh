@@ -13,7 +13,7 @@ test_expect_success 'Refresh from a subdirectory' '
     cd bar &&
     stg refresh &&
     cd .. &&
-    [ "$(stg status)" = "" ]
+    [ "$(stg status --short)" = "" ]
 '
 
 test_expect_success 'Refresh again' '
@@ -22,7 +22,7 @@ test_expect_success 'Refresh again' '
     cd bar &&
     stg refresh &&
     cd .. &&
-    [ "$(stg status)" = "" ]
+    [ "$(stg status --short)" = "" ]
 '
 
 test_expect_success 'Refresh file in subdirectory' '
@@ -31,19 +31,19 @@ test_expect_success 'Refresh file in subdirectory' '
     cd bar &&
     stg refresh bar.txt &&
     cd .. &&
-    [ "$(stg status)" = " M foo.txt" ]
+    [ "$(stg status --short)" = " M foo.txt" ]
 '
 
 test_expect_success 'Refresh whole subdirectory' '
     echo bar4 >>bar/bar.txt &&
     stg refresh bar &&
-    [ "$(stg status)" = " M foo.txt" ]
+    [ "$(stg status --short)" = " M foo.txt" ]
 '
 
 test_expect_success 'Refresh subdirectories recursively' '
     echo bar5 >>bar/bar.txt &&
     stg refresh . &&
-    [ "$(stg status)" = "" ]
+    [ "$(stg status --short)" = "" ]
 '
 
 test_expect_success 'refresh -u' '
@@ -55,7 +55,7 @@ test_expect_success 'refresh -u' '
     echo xyzzy >>bar/bar.txt &&
     echo xyzzy >>bar/baz.txt &&
     stg refresh -u &&
-    test "$(echo $(stg status))" = "M bar/bar.txt M foo.txt" &&
+    test "$(echo $(stg status --short))" = "M bar/bar.txt M foo.txt" &&
     test "$(echo $(stg files p0))" = "A bar/bar.txt A foo.txt" &&
     test "$(echo $(stg files p1))" = "A bar/baz.txt"
 '
@@ -63,7 +63,7 @@ test_expect_success 'refresh -u' '
 test_expect_success 'refresh -u -p <subdir>' '
     echo xyzzy >>bar/baz.txt &&
     stg refresh -p p0 -u bar &&
-    test "$(echo $(stg status))" = "M bar/baz.txt M foo.txt" &&
+    test "$(echo $(stg status --short))" = "M bar/baz.txt M foo.txt" &&
     test "$(echo $(stg files p0))" = "A bar/bar.txt A foo.txt" &&
     test "$(echo $(stg files p1))" = "A bar/baz.txt"
 '
@@ -71,9 +71,9 @@ test_expect_success 'refresh -u -p <subdir>' '
 test_expect_success 'refresh an unapplied patch' '
     stg refresh -u &&
     stg goto --keep p0 &&
-    test "$(stg status)" = " M foo.txt" &&
+    test "$(stg status --short)" = " M foo.txt" &&
     stg refresh -p p1 &&
-    test "$(stg status)" = "" &&
+    test "$(stg status --short)" = "" &&
     test "$(echo $(stg files p1))" = "A bar/baz.txt M foo.txt"
 '
 
@@ -0,0 +1,104 @@
+#!/bin/sh
+
+test_description='Run "stg clean --merged" and "--delete-merged"'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    test_commit_bulk --message="base %s" 2 &&
+    stg init &&
+    stg new p0 -m p0 &&
+    echo p0 >p0.txt &&
+    stg add p0.txt &&
+    stg refresh &&
+    stg new p1 -m "p1
+
+Change-Id: I0123456789abcdef" &&
+    echo p1 >p1.txt &&
+    stg add p1.txt &&
+    stg refresh &&
+    stg new p2 -m p2 &&
+    echo p2 >p2.txt &&
+    stg add p2.txt &&
+    stg refresh
+'
+
+test_expect_success 'Clean merged requires an upstream' '
+    command_error stg clean --merged 2>err &&
+    grep -e "branch \`master\` has no upstream" err
+'
+
+test_expect_success 'Clean without merged patches' '
+    git branch upstream "$(stg id {base})" &&
+    git branch --set-upstream-to=upstream &&
+    stg clean --merged &&
+    [ "$(echo $(stg series --noprefix))" = "p0 p1 p2" ]
+'
+
+test_expect_success 'Merge reworked patches upstream' '
+    git checkout upstream &&
+    echo "p1 reworked" >p1-reworked.txt &&
+    git add p1-reworked.txt &&
+    git commit -m "p1 reworked
+
+Change-Id: I0123456789abcdef" &&
+    echo "p0 reworked" >p0-reworked.txt &&
+    git add p0-reworked.txt &&
+    git commit -m p0 &&
+    git checkout master
+'
+
+test_expect_success 'Clean merged patches' '
+    stg clean --merged &&
+    [ "$(echo $(stg series --noprefix))" = "p0 p2" ] &&
+    git log -1 --format=%B refs/stacks/master >log &&
+    grep -e "^p1: merged as $(git rev-parse upstream~1) (matched by Change-Id trailer)" log
+'
+
+test_expect_success 'Clean merged patches matching subject and author' '
+    test_config stgit.merged.match-subject true &&
+    stg clean --merged &&
+    [ "$(echo $(stg series --noprefix))" = "p2" ] &&
+    git log -1 --format=%B refs/stacks/master >log &&
+    grep -e "^p0: merged as $(git rev-parse upstream) (matched by subject and author)" log
+'
+
+test_expect_success 'Delete merged patches when rebasing' '
+    stg undo --hard &&
+    [ "$(echo $(stg series --noprefix))" = "p0 p2" ] &&
+    git checkout upstream &&
+    echo p2 >p2.txt &&
+    git add p2.txt &&
+    git commit -m p2 &&
+    git checkout master &&
+    stg rebase --delete-merged upstream &&
+    [ "$(echo $(stg series --noprefix))" = "p0" ] &&
+    git log -1 --format=%B refs/stacks/master >log &&
+    grep -e "^p2: merged as $(git rev-parse upstream)" log
+'
+
+test_expect_success 'Shared Link trailer alone does not mark a patch merged' '
+    for p in l1 l2
+    do
+        stg new $p -m "$p
+
+Link: https://example.com/series" &&
+        echo $p >$p.txt &&
+        stg add $p.txt &&
+        stg refresh || return 1
+    done &&
+    git checkout upstream &&
+    echo "l1 reworked" >l1-reworked.txt &&
+    git add l1-reworked.txt &&
+    git commit -m "l1
+
+Link: https://example.com/series" &&
+    git checkout master &&
+    stg clean --merged &&
+    [ "$(echo $(stg series --noprefix))" = "p0 l2" ] &&
+    git log -1 --format=%B refs/stacks/master >log &&
+    grep -e "^l1: merged as $(git rev-parse upstream) (matched by Link trailer and subject)" log &&
+    ! grep -e "^l2:" log
+'
+
+test_done
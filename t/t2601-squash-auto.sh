@@ -0,0 +1,56 @@
+#!/bin/sh
+
+test_description='Test fixup! and squash! patches with "stg squash --auto"'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    test_commit_bulk --start=0 --filename=foo%s.txt --contents="foo %s" --message="p%s" 3 &&
+    stg uncommit -n 3
+'
+
+test_expect_success 'Create fixup patch with new' '
+    echo "fixed" >>foo0.txt &&
+    stg new --refresh --fixup p0 fix0 &&
+    [ "$(git log -1 --format=%s)" = "fixup! p0" ] &&
+    [ "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2 fix0" ]
+'
+
+test_expect_success 'Create fixup patch with refresh' '
+    echo "fixed" >>foo1.txt &&
+    stg refresh --fixup p1 &&
+    [ "$(git log -1 --format=%s)" = "fixup! p1" ] &&
+    [ "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2 fix0 fixup-p1" ]
+'
+
+test_expect_success 'Fixup target must exist' '
+    command_error stg new --fixup not-a-patch 2>err &&
+    grep "patch \`not-a-patch\` does not exist" err
+'
+
+test_expect_success 'Squash fixup patches into their targets' '
+    stg squash --auto &&
+    [ "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2" ] &&
+    [ "$(git log -1 --format=%s $(stg id p0))" = "p0" ] &&
+    [ "$(git log -1 --format=%s $(stg id p1))" = "p1" ] &&
+    test "$(git show $(stg id p0):foo0.txt)" = "$(printf "foo 0\nfixed")" &&
+    test "$(git show $(stg id p1):foo1.txt)" = "$(printf "foo 1\nfixed")"
+'
+
+test_expect_success 'Nothing to squash' '
+    stg squash --auto 2>&1 | grep "no fixup! or squash! patches" &&
+    [ "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2" ]
+'
+
+test_expect_success 'Autosquash unapplied fixup patch during rebase' '
+    echo "fixed again" >>foo0.txt &&
+    stg new --refresh --fixup p0 fix0 &&
+    stg new --fixup p2 fix2 &&
+    stg pop fix2 &&
+    stg rebase --autosquash $(git rev-parse $(stg id p0)^) &&
+    [ "$(echo $(stg series --applied --noprefix))" = "p0 p1 p2" ] &&
+    [ "$(echo $(stg series --unapplied --noprefix))" = "fix2" ] &&
+    test "$(git show $(stg id p0):foo0.txt)" = "$(printf "foo 0\nfixed\nfixed again")"
+'
+
+test_done
@@ -34,8 +34,8 @@ test_expect_success 'Refresh top patch' '
     echo bar 3 >>foo3.txt &&
     stg refresh &&
     test "$(git notes show)" = "note3" &&
-    stg status &&
-    test -z "$(stg status)" &&
+    stg status --short &&
+    test -z "$(stg status --short)" &&
     stg patches foo3.txt >patches.txt &&
     cat >expected.txt <<-\EOF &&
 	p0
@@ -45,13 +45,13 @@ test_expect_success 'Refresh top patch' '
 '
 
 test_expect_success 'Refresh middle patch' '
-    stg status &&
+    stg status --short &&
     echo bar 2 >>foo2.txt &&
     stg refresh -p p2 &&
     test "$(git notes show $(stg id p2))" = "note2" &&
     test "$(git notes show)" = "note3" &&
-    stg status &&
-    test -z "$(stg status)" &&
+    stg status --short &&
+    test -z "$(stg status --short)" &&
     stg patches foo2.txt >patches.txt &&
     cat >expected.txt <<-\EOF &&
 	p0
@@ -61,14 +61,14 @@ test_expect_success 'Refresh middle patch' '
 '
 
 test_expect_success 'Refresh bottom patch' '
-    stg status &&
+    stg status --short &&
     echo bar 1 >>foo1.txt &&
     stg refresh -p p1 &&
     test "$(git notes show $(stg id p1))" = "note1" &&
     test "$(git notes show $(stg id p2))" = "note2" &&
     test "$(git notes show)" = "note3" &&
-    stg status &&
-    test -z "$(stg status)" &&
+    stg status --short &&
+    test -z "$(stg status --short)" &&
     stg patches foo1.txt >patches.txt &&
     cat >expected.txt <<-\EOF &&
 	p0
@@ -78,7 +78,7 @@ test_expect_success 'Refresh bottom patch' '
 '
 
 test_expect_success 'Refresh --index' '
-    stg status &&
+    stg status --short &&
     stg new p4 -m "refresh_index" &&
     git notes add -m note4 &&
     echo baz 1 >>foo1.txt &&
@@ -20,12 +20,12 @@ test_expect_success 'Initialize StGit stack' '
 '
 
 test_expect_success 'Add new file to non-top patch' '
-    stg status >status1.txt &&
+    stg status --short >status1.txt &&
     test_must_be_empty status1.txt &&
     echo y >new.txt &&
     stg add new.txt &&
     stg refresh -p p1 &&
-    stg status >status2.txt &&
+    stg status --short >status2.txt &&
     test_must_be_empty status2.txt &&
     stg files p1 >files1.txt &&
     cat >expected.txt <<-\EOF &&
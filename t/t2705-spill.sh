@@ -27,7 +27,7 @@ test_expect_success 'Create a patch' '
 '
 
 test_expect_success 'Check file status' '
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected.txt <<-\EOF &&
 	A  patch0.txt
 	EOF
@@ -38,7 +38,7 @@ test_expect_success 'Check file status' '
 
 test_expect_success 'Refresh patch' '
     stg refresh &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     test_must_be_empty status.txt &&
     stg patches patch0.txt >patches.txt &&
     cat >expected.txt <<-\EOF &&
@@ -58,7 +58,7 @@ test_expect_success 'Patch description did not change' '
 '
 
 test_expect_success 'Changes are now in index' '
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected.txt <<-\EOF &&
 	A  patch0.txt
 	EOF
@@ -82,7 +82,7 @@ test_expect_success 'Spill with annotation' '
 test_expect_success 'Spill with --reset' '
     stg refresh &&
     stg spill --reset &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected.txt <<-\EOF &&
 	?? patch0.txt
 	EOF
@@ -104,7 +104,7 @@ test_expect_success 'Setup nested files' '
     echo h >dir0/dir2/h.txt &&
     echo i >dir0/dir2/i.txt &&
     stg add dir0 &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected.txt <<-\EOF &&
 	A  dir0/a.txt
 	A  dir0/b.txt
@@ -125,7 +125,7 @@ test_expect_success 'Create patch over subset of files' '
     echo A >dir0/a.txt &&
     echo E >dir0/dir1/e.txt &&
     echo I >dir0/dir2/i.txt &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected.txt <<-\EOF &&
 	 M dir0/a.txt
 	 M dir0/dir1/e.txt
@@ -136,7 +136,7 @@ test_expect_success 'Create patch over subset of files' '
 '
 test_expect_success 'Spill subsets of files' '
     stg spill dir0/dir1 &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected-status.txt <<-\EOF &&
 	M  dir0/dir1/e.txt
 	EOF
@@ -155,7 +155,7 @@ test_expect_success 'Spill while in subdir' '
         cd dir0 &&
         stg spill dir1
     ) &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     test_cmp expected-status.txt status.txt &&
     stg files >files.txt &&
     test_cmp expected-files.txt files.txt &&
@@ -167,7 +167,7 @@ test_expect_success 'Spill uptree pathspec' '
         cd dir0/dir1 &&
         stg spill -r ../a.txt ../dir2
     ) &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected-status.txt <<-\EOF &&
 	 M dir0/a.txt
 	 M dir0/dir2/i.txt
@@ -184,7 +184,7 @@ test_expect_success 'Spill uptree pathspec' '
 test_expect_success 'Spill with modified worktree' '
     echo "modification" >>dir0/a.txt &&
     stg spill dir0/dir1 &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected-status.txt <<-\EOF &&
 	 M dir0/a.txt
 	M  dir0/dir1/e.txt
@@ -203,7 +203,7 @@ test_expect_success 'Spill with modified worktree' '
 test_expect_success 'Spill and reset with modified worktree' '
     echo "modification" >>dir0/a.txt &&
     stg spill --reset dir0/dir1 &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected-status.txt <<-\EOF &&
 	 M dir0/a.txt
 	 M dir0/dir1/e.txt
@@ -223,7 +223,7 @@ test_expect_success 'Spill with modified spillable file' '
     echo "modification" >>dir0/a.txt &&
     echo "modification" >>dir0/dir1/e.txt &&
     stg spill "dir0/dir1/e*" &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected-status.txt <<-\EOF &&
 	 M dir0/a.txt
 	MM dir0/dir1/e.txt
@@ -251,7 +251,7 @@ test_expect_success 'Spill patch with just-added files' '
         cd dir0 &&
         stg spill dir1/new.txt
     ) &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected-status.txt <<-\EOF &&
 	A  dir0/dir1/new.txt
 	EOF
@@ -279,7 +279,7 @@ test_expect_success 'Spill and reset patch with just-added files' '
         cd dir0 &&
         stg spill --reset dir1/new.txt
     ) &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected-status.txt <<-\EOF &&
 	?? dir0/dir1/new.txt
 	EOF
@@ -307,7 +307,7 @@ test_expect_success 'Spill patch with just-added files in wildcard dir' '
         cd dir0 &&
         stg spill dir1
     ) &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected-status.txt <<-\EOF &&
 	M  dir0/dir1/e.txt
 	A  dir0/dir1/new.txt
@@ -330,7 +330,7 @@ test_expect_success 'Spill removed file' '
     stg rm dir0/dir1/e.txt &&
     stg new -rm rm-file &&
     stg spill dir0/dir1 &&
-    stg status >status.txt &&
+    stg status --short >status.txt &&
     cat >expected-status.txt <<-\EOF &&
 	D  dir0/dir1/e.txt
 	EOF
@@ -30,7 +30,7 @@ test_expect_success 'Invalid arguments' '
 
 test_expect_success 'Pop middle patch, creating a conflict' '
     conflict stg pop p2 &&
-    stg status a >actual.txt &&
+    stg status --short a >actual.txt &&
     cat >expected.txt <<-\EOF &&
 	UU a
 	EOF
@@ -40,14 +40,14 @@ test_expect_success 'Pop middle patch, creating a conflict' '
 
 test_expect_success 'Try to reset without --hard' '
     command_error stg reset refs/stacks/master^~1 &&
-    stg status a >actual.txt &&
+    stg status --short a >actual.txt &&
     test_cmp expected.txt actual.txt &&
     test "$(echo $(stg series))" = "+ p1 > p3 - p2"
 '
 
 test_expect_success 'Try to reset with --hard' '
     stg reset --hard refs/stacks/master^~1 &&
-    stg status a >actual.txt &&
+    stg status --short a >actual.txt &&
     test_must_be_empty actual.txt &&
     test "$(echo $(stg series))" = "+ p1 + p2 > p3"
 '
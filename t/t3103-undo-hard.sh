@@ -25,7 +25,7 @@ test_expect_success 'Initialize StGit stack with three patches' '
 
 test_expect_success 'Pop middle patch, creating a conflict' '
     conflict stg pop p2 &&
-    stg status a >actual.txt &&
+    stg status --short a >actual.txt &&
     cat >expected.txt <<-\EOF &&
 	UU a
 	EOF
@@ -36,7 +36,7 @@ test_expect_success 'Pop middle patch, creating a conflict' '
 
 test_expect_success 'Try to undo without --hard' '
     command_error stg undo &&
-    stg status a >actual.txt &&
+    stg status --short a >actual.txt &&
     test_cmp expected.txt actual.txt &&
     test "$(echo $(stg series))" = "+ p1 > p3 - p2" &&
     test "$(stg id)" = "$(stg id $(stg top))"
@@ -44,7 +44,7 @@ test_expect_success 'Try to undo without --hard' '
 
 test_expect_success 'Try to undo with --hard' '
     stg undo --hard &&
-    stg status a >actual.txt &&
+    stg status --short a >actual.txt &&
     test_must_be_empty actual.txt &&
     test "$(echo $(stg series))" = "+ p1 + p2 > p3" &&
     test "$(stg id)" = "$(stg id $(stg top))"
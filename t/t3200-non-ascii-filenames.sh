@@ -38,7 +38,7 @@ test_expect_success 'Setup' '
 '
 
 test_expect_success 'Status of modified non-ASCII file' '
-    stg status >output.txt &&
+    stg status --short >output.txt &&
     cat >expected.txt <<-\EOF &&
 	 M "sk\303\244rg\303\245rds\303\266.txt"
 	EOF
@@ -50,7 +50,7 @@ test_expect_success 'Refresh changes to non-ASCII file' '
 '
 
 test_expect_success 'Status after refresh' '
-    stg status >output.txt &&
+    stg status --short >output.txt &&
     test_must_be_empty output.txt
 '
 
@@ -108,7 +108,7 @@ test_expect_success 'Pick --fold without applied patches' '
     stg pop --all &&
     stg pick --fold D &&
     test "$(echo $(stg series --unapplied --noprefix))" = "A B C D" &&
-    test "$(echo $(stg status))" = "A d" &&
+    test "$(echo $(stg status --short))" = "A d" &&
     stg reset --hard
 '
 
@@ -136,18 +136,18 @@ test_expect_success 'Pick with empty result' '
 
 test_expect_success 'Pick --fold with empty result' '
     stg pick --fold -B foo A &&
-    test -z "$(stg status)"
+    test -z "$(stg status --short)"
 '
 
 test_expect_success 'Pick --fold --files empty result' '
     stg pick --fold -B foo A --file c &&
-    test -z "$(stg status)"
+    test -z "$(stg status --short)"
 '
 
 test_expect_success 'Pick --update' '
     stg goto C &&
     stg pick --update -B foo E &&
-    test "$(stg status)" = "M  c" &&
+    test "$(stg status --short)" = "M  c" &&
     test "$(echo $(cat c))" = "C CC" &&
     stg reset --hard
 '
@@ -192,7 +192,7 @@ test_expect_success 'Pick with conflict' '
     rm err &&
     test "$(stg top)" = "AAA" &&
     test "$(echo $(stg series -A --noprefix))" = "C2 A AAA" &&
-    test "$(echo $(stg status))" = "UU a" &&
+    test "$(echo $(stg status --short))" = "UU a" &&
     stg reset --hard &&
     stg undo
 '
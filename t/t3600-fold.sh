@@ -57,21 +57,21 @@ test_expect_success 'Fold a patch file' '
     stg fold fold1.diff &&
     test_when_finished "stg reset --hard" &&
     test "hello from p1 and fold1" = "$(echo $(cat foo.txt))" &&
-    stg status --porcelain foo.txt | grep -e "M  foo.txt"
+    stg status --short foo.txt | grep -e "M  foo.txt"
 '
 
 test_expect_success 'Fold a patch from stdin' '
     cat fold1.diff | stg fold &&
     test_when_finished "stg reset --hard" &&
     test "hello from p1 and fold1" = "$(echo $(cat foo.txt))" &&
-    stg status --porcelain foo.txt | grep -e "M  foo.txt"
+    stg status --short foo.txt | grep -e "M  foo.txt"
 '
 
 test_expect_success 'Threeway fold' '
     stg fold --threeway threeway.diff &&
     test_when_finished "stg reset --hard" &&
     test "preface hello from p1" = "$(echo $(cat foo.txt))" &&
-    stg status --porcelain foo.txt | grep -e "M  foo.txt"
+    stg status --short foo.txt | grep -e "M  foo.txt"
 '
 
 test_expect_success 'Attempt to fold conflicting patch' '
@@ -82,7 +82,7 @@ test_expect_success 'Attempt to fold conflicting patch' '
     stg refresh &&
     command_error stg fold fold1.diff 2>err &&
     grep "patch does not apply" err &&
-    test -z "$(echo $(stg status --porcelain foo.txt))" &&
+    test -z "$(echo $(stg status --short foo.txt))" &&
     test ! -e foo.txt.rej
 '
 
@@ -93,14 +93,14 @@ test_expect_success 'Attempt to fold conflicting patch with rejects' '
     stg refresh &&
     command_error stg fold --reject fold1.diff 2>err &&
     grep "patch failed" err &&
-    test -z "$(echo $(stg status --porcelain foo.txt))" &&
+    test -z "$(echo $(stg status --short foo.txt))" &&
     test -e foo.txt.rej &&
     rm foo.txt.rej
 '
 
 test_expect_success 'Attempt to fold conflicting patch with -C0' '
     stg fold -C0 --reject fold1.diff &&
-    stg status --porcelain foo.txt | grep -e "M  foo.txt" &&
+    stg status --short foo.txt | grep -e "M  foo.txt" &&
     test "$(tail -n 1 foo.txt)" = "and fold1" &&
     git reset -- foo.txt &&
     git checkout foo.txt
@@ -109,7 +109,7 @@ test_expect_success 'Attempt to fold conflicting patch with -C0' '
 test_expect_success 'Fold with base' '
     stg fold --base p1 threeway.diff &&
     test "preface hello from p2" = "$(echo $(cat foo.txt))" &&
-    stg status --porcelain foo.txt | grep -e "M  foo.txt"
+    stg status --short foo.txt | grep -e "M  foo.txt"
 '
 
 test_done
@@ -87,4 +87,101 @@ test_expect_success 'Export series with empty patch' '
     stg import -S export6/series
 '
 
+test_expect_success 'Export to mbox' '
+    stg export --mbox series.mbox patch-1 patch-2 &&
+    test "$(grep -c "^From [0-9a-f]\{40\} " series.mbox)" = "2" &&
+    grep "^Subject: \[PATCH 1/2\] patch-1$" series.mbox &&
+    grep "^Subject: \[PATCH 2/2\] patch-2$" series.mbox &&
+    grep "^From: =?UTF-8?q?A=20=C3=9A=20Thor?= <author@example.com>$" series.mbox &&
+    grep "^Date: " series.mbox
+'
+
+test_expect_success 'Reimport mbox export' '
+    stg delete $(stg series --noprefix) &&
+    stg import --mbox series.mbox &&
+    test "$(echo $(stg series --noprefix))" = "patch-1 patch-2" &&
+    test "$(git log -2 --format=%an | uniq)" = "A Ú Thor" &&
+    test "$(stg show patch-2 | grep "^+line")" = "+line 2"
+'
+
+test_expect_success 'Export to tar archives' '
+    stg new -m patch-3 &&
+    echo "line 3" >>foo.txt &&
+    stg refresh &&
+    for ext in tar tar.gz tgz tar.bz2; do
+      stg export --archive series.$ext || return 1
+    done &&
+    tar -tf series.tar >listing &&
+    cat >expected <<-\EOF &&
+	patches-master/series
+	patches-master/patch-1
+	patches-master/patch-2
+	patches-master/patch-3
+	EOF
+    test_cmp expected listing &&
+    tar -tzf series.tgz >listing &&
+    test_cmp expected listing
+'
+
+test_expect_success 'Reimport archive exports' '
+    for ext in tar tar.gz tgz tar.bz2; do
+      stg delete $(stg series --noprefix) &&
+      stg import --series series.$ext &&
+      test "$(echo $(stg series --noprefix))" = "patch-1 patch-2 patch-3" &&
+      test "$(tail -n 1 foo.txt)" = "line 3" || return 1
+    done
+'
+
+test_expect_success 'Export to archive with unknown extension' '
+    command_error stg export --archive series.zip 2>err &&
+    grep "does not have a .tar, .tar.gz, .tgz, or .tar.bz2 extension" err &&
+    test_path_is_missing series.zip
+'
+
+test_expect_success 'Export with template language' '
+    cat >.git/sig.tmpl <<-\EOF &&
+	-- 
+	{{ authemail }}
+	EOF
+    cat >template <<-\EOF &&
+	{# Export template #}
+	{{ index }}/{{ total }} {{ name }}: {{ shortdescr | upper }}
+	{% if trailers %}
+	has trailers
+	{% else %}
+	no trailers
+	{% endif %}
+	{% for file in files %}
+	{{ file.path }} +{{ file.added }} -{{ file.deleted }}
+	{% endfor %}
+	Date: {{ authdate | date("unix") }}
+	{% include "sig.tmpl" %}
+	EOF
+    stg export -t template -d export7 &&
+    head -n 1 export7/patch-2 >out &&
+    echo "2/3 patch-2: PATCH-2" >expected &&
+    test_cmp expected out &&
+    grep -e "^no trailers$" export7/patch-2 &&
+    grep -e "^foo.txt +1 -0$" export7/patch-2 &&
+    grep -e "^Date: [0-9][0-9]*$" export7/patch-2 &&
+    grep -e "^author@example.com$" export7/patch-2 &&
+    grep -e "^+line 2$" export7/patch-2
+'
+
+test_expect_success 'Export with invalid template' '
+    printf "{# Bad template #}\n{%% if name %%}\n{{ name }}\n" >bad-template &&
+    command_error stg export -t bad-template -d export8 2>err &&
+    grep -e "line 2: .* is not closed" err
+'
+
+test_expect_success 'Export with legacy template containing braces' '
+    cat >legacy-template <<-\EOF &&
+	%(shortdescr)s: {{ not a tag }} {% if {# %(authemail)s
+	EOF
+    stg export -t legacy-template -d export9 &&
+    head -n 1 export9/patch-2 >out &&
+    echo "patch-2: {{ not a tag }} {% if {# author@example.com" >expected &&
+    test_cmp expected out
+'
+
 test_done
@@ -0,0 +1,127 @@
+#!/bin/sh
+
+test_description='Test stg bundle'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup stack with applied, unapplied, and hidden patches' '
+    test_commit base &&
+    git checkout -b stack &&
+    stg init &&
+    for i in 0 1 2 3; do
+        stg new -m "patch $i" p$i &&
+        echo "line $i" >>file.txt &&
+        stg add file.txt &&
+        stg refresh || return 1
+    done &&
+    stg pop -n 2 &&
+    stg hide p3 &&
+    git checkout -b other master &&
+    stg init &&
+    stg new -m "other patch" o0 &&
+    git checkout stack
+'
+
+test_expect_success 'Create bundle of current stack' '
+    stg bundle create stack.bundle &&
+    git bundle list-heads stack.bundle >heads &&
+    test_line_count = 6 heads &&
+    grep -e " refs/heads/stack$" heads &&
+    grep -e " refs/stacks/stack$" heads &&
+    for p in p0 p1 p2 p3; do
+        grep -e "^$(git rev-parse refs/patches/stack/$p) refs/patches/stack/$p$" heads ||
+        return 1
+    done &&
+    test_create_repo verify &&
+    git -C verify bundle verify ../stack.bundle
+'
+
+test_expect_success 'Import bundle into another repository' '
+    test_create_repo imported &&
+    (
+        cd imported &&
+        stg bundle import ../stack.bundle &&
+        test "$(git rev-parse refs/heads/stack)" = "$(git -C .. rev-parse refs/heads/stack)" &&
+        stg series --all -b stack >series &&
+        stg -C .. series --all -b stack >expected &&
+        test_cmp expected series &&
+        for p in p0 p1 p2 p3; do
+            test "$(git rev-parse refs/patches/stack/$p)" = \
+                 "$(git -C .. rev-parse refs/patches/stack/$p)" ||
+            return 1
+        done &&
+        stg log -b stack >log &&
+        test_line_count = 1 log
+    )
+'
+
+test_expect_success 'Import refuses to replace existing stack' '
+    (
+        cd imported &&
+        command_error stg bundle import ../stack.bundle 2>err &&
+        grep -e "StGit stack already initialized for branch .stack.; use .--force." err
+    )
+'
+
+test_expect_success 'Import refuses to replace existing branch' '
+    (
+        cd imported &&
+        stg branch --cleanup --force stack &&
+        command_error stg bundle import ../stack.bundle 2>err &&
+        grep -e "branch .stack. already exists; use .--force." err
+    )
+'
+
+test_expect_success 'Import with force replaces existing stack' '
+    stg -C imported bundle import --force ../stack.bundle &&
+    stg new -m "new patch" p4 &&
+    stg bundle create --log stack.bundle &&
+    (
+        cd imported &&
+        stg bundle import --force ../stack.bundle &&
+        stg series --all -b stack >series &&
+        stg -C .. series --all -b stack >expected &&
+        test_cmp expected series &&
+        stg log -b stack >log &&
+        stg -C .. log -b stack >expected &&
+        test_cmp expected log
+    )
+'
+
+test_expect_success 'Import refuses to replace current branch' '
+    (
+        cd imported &&
+        git checkout stack &&
+        command_error stg bundle import --force ../stack.bundle 2>err &&
+        grep -e "cannot import stack over the current branch .stack." err
+    )
+'
+
+test_expect_success 'Bundle multiple stacks' '
+    stg bundle create both.bundle stack other &&
+    test_create_repo both &&
+    (
+        cd both &&
+        command_error stg bundle import ../both.bundle nope 2>err &&
+        grep -e "no stack for branch .nope. in bundle" err &&
+        stg bundle import ../both.bundle other &&
+        test "$(stg series --noprefix -b other)" = "o0" &&
+        test_must_fail git rev-parse --verify -q refs/stacks/stack &&
+        stg bundle import ../both.bundle stack &&
+        test "$(stg series --noprefix --applied -b stack)" = "$(stg -C .. series --noprefix --applied -b stack)"
+    )
+'
+
+test_expect_success 'Bundle without stacks' '
+    git bundle create plain.bundle master &&
+    command_error stg bundle import plain.bundle 2>err &&
+    grep -e "bundle .plain.bundle. does not contain any StGit stacks" err
+'
+
+test_expect_success 'Bundle with inconsistent stack' '
+    git commit --allow-empty -m "external" &&
+    command_error stg bundle create bad.bundle 2>err &&
+    grep -e "HEAD and stack top are not the same" err
+'
+
+test_done
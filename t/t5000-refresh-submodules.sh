@@ -21,7 +21,7 @@ test_expect_success 'setup submodule'  '
 test_expect_success 'refresh with a submodule does not include by default' '
     stg new -m p1 &&
     stg refresh &&
-    [ "$(stg status)" = " M submodules/foo" ]
+    [ "$(stg status --short)" = " M submodules/foo" ]
 '
 
 test_expect_success 'refresh includes non-submodule changes' '
@@ -31,9 +31,9 @@ test_expect_success 'refresh includes non-submodule changes' '
     (
         cd dir2 &&
         stg refresh &&
-        [ "$(stg status)" = " M submodules/foo" ]
+        [ "$(stg status --short)" = " M submodules/foo" ]
     ) &&
-    [ "$(stg status)" = " M submodules/foo" ]
+    [ "$(stg status --short)" = " M submodules/foo" ]
 '
 
 test_expect_success 'refresh with --submodules' '
@@ -41,7 +41,7 @@ test_expect_success 'refresh with --submodules' '
         cd dir2 &&
         stg refresh --submodules
     ) &&
-    [ "$(stg status)" = "" ]
+    [ "$(stg status --short)" = "" ]
 '
 
 test_expect_success 'refresh --no-submodules overrides config' '
@@ -49,12 +49,12 @@ test_expect_success 'refresh --no-submodules overrides config' '
     stg undo &&
     git config stgit.refreshsubmodules yes &&
     stg refresh --no-submodules &&
-    [ "$(stg status)" = " M submodules/foo" ]
+    [ "$(stg status --short)" = " M submodules/foo" ]
 '
 
 test_expect_success 'refresh with config' '
     stg refresh &&
-    [ "$(stg status)" = "" ]
+    [ "$(stg status --short)" = "" ]
 '
 
 test_done
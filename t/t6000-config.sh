@@ -12,7 +12,7 @@ test_expect_success 'stg accepts keys without values' '
     echo "[stgit]" >>.git/config &&
     echo "	aboolean" >>.git/config &&
     stg init &&
-    stg status
+    stg status --short
 '
 
 test_done
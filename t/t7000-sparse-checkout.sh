@@ -38,7 +38,7 @@ outside_cone_files_absent() {
 }
 
 clean_status() {
-    stg status >status-out &&
+    stg status --short >status-out &&
     test_line_count = 0 status-out
 }
 
@@ -123,7 +123,7 @@ test_expect_success 'Create merge conflict' '
     cone_intact &&
     clean_status &&
     conflict stg push patch1 &&
-    stg status >status-out &&
+    stg status --short >status-out &&
     cat >status-expected <<-\EOF &&
 	UU b/1/beta.txt
 	EOF